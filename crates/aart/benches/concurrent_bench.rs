@@ -0,0 +1,207 @@
+use aart::{olc::pin, Aart, ConcurrentAart, OlcArt, RowexArt, ShardedAart};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::SeedableRng;
+use rand::{rngs::StdRng, Rng};
+use std::{thread, time::Instant};
+
+const SIZE: usize = 100_000;
+const THREADS: u64 = 4;
+
+fn seeded_rng(alter: u64) -> impl Rng {
+    StdRng::seed_from_u64(0xEA3C47920F94A980 ^ alter)
+}
+
+fn gen_keys() -> Vec<u64> {
+    let mut rng = seeded_rng(0x2C5A1D0F93E7B846);
+    (0..SIZE).map(|_| rng.gen()).collect()
+}
+
+pub fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_insert");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    let keys = gen_keys();
+
+    group.bench_with_input(BenchmarkId::new("aart", SIZE), &keys, |b, keys| {
+        b.iter(|| {
+            let mut tree = Aart::<u64, u64>::new();
+            for k in keys {
+                tree.insert(k, *k);
+            }
+            tree
+        })
+    });
+    group.bench_with_input(
+        BenchmarkId::new("concurrent_aart", SIZE),
+        &keys,
+        |b, keys| {
+            b.iter(|| {
+                let tree = ConcurrentAart::<u64, u64>::new();
+                for k in keys {
+                    tree.insert(k, *k);
+                }
+                tree
+            })
+        },
+    );
+    group.bench_with_input(BenchmarkId::new("olc", SIZE), &keys, |b, keys| {
+        b.iter(|| {
+            let tree = OlcArt::<u64, u64>::new();
+            for k in keys {
+                tree.insert(k, *k).unwrap();
+            }
+            tree
+        })
+    });
+    group.bench_with_input(BenchmarkId::new("rowex", SIZE), &keys, |b, keys| {
+        b.iter(|| {
+            let tree = RowexArt::<u64, u64>::new();
+            for k in keys {
+                tree.insert(k, *k).unwrap();
+            }
+            tree
+        })
+    });
+
+    group.finish();
+}
+
+pub fn get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_get");
+    group.throughput(Throughput::Elements(1));
+    let keys = gen_keys();
+
+    let mut aart = Aart::<u64, u64>::new();
+    let olc = OlcArt::<u64, u64>::new();
+    let rowex = RowexArt::<u64, u64>::new();
+    for k in keys.iter() {
+        aart.insert(k, *k);
+        olc.insert(k, *k).unwrap();
+        rowex.insert(k, *k).unwrap();
+    }
+
+    group.bench_function("aart", |b| {
+        let mut rng = seeded_rng(0x73B0A5E1C94D28F6);
+        b.iter(|| *aart.get(&keys[rng.gen_range(0..keys.len())]).unwrap())
+    });
+    group.bench_function("olc", |b| {
+        let mut rng = seeded_rng(0x73B0A5E1C94D28F6);
+        let guard = pin();
+        b.iter(|| {
+            *olc.get(&keys[rng.gen_range(0..keys.len())], &guard)
+                .unwrap()
+        })
+    });
+    group.bench_function("rowex", |b| {
+        let mut rng = seeded_rng(0x73B0A5E1C94D28F6);
+        let guard = pin();
+        b.iter(|| {
+            *rowex
+                .get(&keys[rng.gen_range(0..keys.len())], &guard)
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+/// Run `iters` operations on every thread, one in ten of which is an insert of a new key.
+fn run_mixed(
+    iters: u64,
+    keys: &[u64],
+    get: impl Fn(u64) -> bool + Sync,
+    insert: impl Fn(u64) + Sync,
+) -> std::time::Duration {
+    let start = Instant::now();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let (get, insert) = (&get, &insert);
+            s.spawn(move || {
+                let mut rng = seeded_rng(t);
+                for i in 0..iters {
+                    if i % 10 == 0 {
+                        insert(rng.gen());
+                    } else {
+                        assert!(get(keys[rng.gen_range(0..keys.len())]));
+                    }
+                }
+            });
+        }
+    });
+    start.elapsed()
+}
+
+pub fn mixed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_mixed");
+    group.throughput(Throughput::Elements(THREADS));
+    let keys = gen_keys();
+
+    group.bench_function("concurrent_aart", |b| {
+        let tree = ConcurrentAart::<u64, u64>::new();
+        for k in keys.iter() {
+            tree.insert(k, *k);
+        }
+        b.iter_custom(|iters| {
+            run_mixed(
+                iters,
+                &keys,
+                |k| tree.contains_key(&k),
+                |k| {
+                    tree.insert(&k, k);
+                },
+            )
+        })
+    });
+    group.bench_function("sharded_aart", |b| {
+        let tree = ShardedAart::<u64, u64, 16>::new();
+        for k in keys.iter() {
+            tree.insert(k, *k);
+        }
+        b.iter_custom(|iters| {
+            run_mixed(
+                iters,
+                &keys,
+                |k| tree.contains_key(&k),
+                |k| {
+                    tree.insert(&k, k);
+                },
+            )
+        })
+    });
+    group.bench_function("olc", |b| {
+        let tree = OlcArt::<u64, u64>::new();
+        for k in keys.iter() {
+            tree.insert(k, *k).unwrap();
+        }
+        b.iter_custom(|iters| {
+            run_mixed(
+                iters,
+                &keys,
+                |k| tree.contains_key(&k),
+                |k| {
+                    tree.insert(&k, k).unwrap();
+                },
+            )
+        })
+    });
+    group.bench_function("rowex", |b| {
+        let tree = RowexArt::<u64, u64>::new();
+        for k in keys.iter() {
+            tree.insert(k, *k).unwrap();
+        }
+        b.iter_custom(|iters| {
+            run_mixed(
+                iters,
+                &keys,
+                |k| tree.contains_key(&k),
+                |k| {
+                    tree.insert(&k, k).unwrap();
+                },
+            )
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(concurrent_benches, insert, get, mixed);
+criterion_main!(concurrent_benches);
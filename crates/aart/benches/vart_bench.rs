@@ -1,8 +1,9 @@
-use aart::Aart;
+use aart::{Aart, Auto, Inline};
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use rand::prelude::SliceRandom;
 use rand::SeedableRng;
 use rand::{rngs::StdRng, Rng};
+use std::sync::Arc;
 
 fn seeded_rng(alter: u64) -> impl Rng {
     StdRng::seed_from_u64(0xEA3C47920F94A980 ^ alter)
@@ -11,8 +12,24 @@ fn seeded_rng(alter: u64) -> impl Rng {
 pub fn seq_insert(c: &mut Criterion) {
     let mut group = c.benchmark_group("seq_insert");
     group.throughput(Throughput::Elements(1));
-    group.bench_function("seq_insert", |b| {
-        let mut tree = Aart::<u64, _>::new();
+    group.bench_function("arc", |b| {
+        let mut tree = Aart::<u64, u64, Arc<u64>>::with_storage();
+        let mut key = 0u64;
+        b.iter(|| {
+            tree.insert(&key, key);
+            key += 1;
+        })
+    });
+    group.bench_function("inline", |b| {
+        let mut tree = Aart::<u64, u64, Inline<u64>>::with_storage();
+        let mut key = 0u64;
+        b.iter(|| {
+            tree.insert(&key, key);
+            key += 1;
+        })
+    });
+    group.bench_function("auto", |b| {
+        let mut tree = Aart::<u64, u64, Auto<u64>>::with_storage();
         let mut key = 0u64;
         b.iter(|| {
             tree.insert(&key, key);
@@ -38,6 +55,60 @@ pub fn rand_insert(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("art_u64_arc", |b| {
+        let mut tree = Aart::<str, u64, Arc<u64>>::with_storage();
+        let mut rng = seeded_rng(0xE080D1A42C207DAF);
+        b.iter(|| {
+            let idx = rng.gen_range(0..keys.len());
+            tree.insert(&keys[idx], idx as u64);
+        })
+    });
+
+    group.bench_function("art_u64_inline", |b| {
+        let mut tree = Aart::<str, u64, Inline<u64>>::with_storage();
+        let mut rng = seeded_rng(0xE080D1A42C207DAF);
+        b.iter(|| {
+            let idx = rng.gen_range(0..keys.len());
+            tree.insert(&keys[idx], idx as u64);
+        })
+    });
+
+    group.bench_function("art_u64_auto", |b| {
+        let mut tree = Aart::<str, u64, Auto<u64>>::with_storage();
+        let mut rng = seeded_rng(0xE080D1A42C207DAF);
+        b.iter(|| {
+            let idx = rng.gen_range(0..keys.len());
+            tree.insert(&keys[idx], idx as u64);
+        })
+    });
+
+    group.finish();
+}
+
+pub fn bulk_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bulk_load");
+
+    let size = 100_000u64;
+    group.throughput(Throughput::Elements(size));
+    // keys in little endian byte order so they are sorted for `from_sorted_iter`.
+    let keys: Vec<u64> = (0..size).map(|x| x.swap_bytes() >> 32).collect();
+
+    group.bench_with_input(BenchmarkId::new("insert", size), &keys, |b, keys| {
+        b.iter(|| {
+            let mut tree = Aart::<u64, _>::new();
+            for k in keys {
+                tree.insert(k, *k);
+            }
+            tree
+        })
+    });
+
+    group.bench_with_input(
+        BenchmarkId::new("from_sorted_iter", size),
+        &keys,
+        |b, keys| b.iter(|| Aart::<u64, _>::from_sorted_iter(keys.iter().map(|k| (k, *k)))),
+    );
+
     group.finish();
 }
 
@@ -173,6 +244,6 @@ fn gen_keys(l1_prefix: usize, l2_prefix: usize, suffix: usize) -> Vec<String> {
 }
 
 //criterion_group!(delete_benches, seq_delete, rand_delete);
-criterion_group!(insert_benches, seq_insert, rand_insert);
+criterion_group!(insert_benches, seq_insert, rand_insert, bulk_load);
 criterion_group!(read_benches, seq_get, rand_get, rand_get_str);
 criterion_main!(insert_benches, read_benches);
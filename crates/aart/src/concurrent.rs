@@ -0,0 +1,128 @@
+//! A map which can be read from many threads without locking.
+
+use crate::{
+    key::Key,
+    prim::sync::{Mutex, MutexGuard},
+    watch::Watchers,
+    Aart,
+};
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+use std::{
+    marker::PhantomData,
+    sync::{atomic::Ordering, Arc, PoisonError},
+};
+
+/// A concurrent map built on top of the persistent [`Aart`].
+///
+/// The map holds a pointer to the current version of the tree. Readers load the current version
+/// without taking any locks and are never blocked by writers. Writers are serialized by a lock,
+/// each write path copies the current version and then atomically replaces it. Old versions are
+/// freed once no reader can still be looking at them.
+///
+/// Writes which change the keys starting with a prefix can be watched with
+/// [`ConcurrentAart::watch`].
+pub struct ConcurrentAart<K: Key + ?Sized, V> {
+    current: Atomic<Aart<K, V, Arc<V>>>,
+    /// Serializes the writers, holds the watchers which are notified after every write.
+    writer: Mutex<Watchers<K, V>>,
+    _marker: PhantomData<Arc<V>>,
+}
+
+impl<K: Key + ?Sized, V> ConcurrentAart<K, V> {
+    pub fn new() -> Self {
+        Self::from(Aart::with_storage())
+    }
+
+    /// Returns the current version of the tree.
+    ///
+    /// The returned tree is a cheap copy which is not affected by later writes to the map.
+    pub fn snapshot(&self) -> Aart<K, V, Arc<V>> {
+        let guard = epoch::pin();
+        let current = self.current.load(Ordering::Acquire, &guard);
+        unsafe { current.deref() }.clone()
+    }
+
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        let guard = epoch::pin();
+        let current = unsafe { self.current.load(Ordering::Acquire, &guard).deref() };
+        current
+            .inner
+            .get(key.as_key_bytes())
+            .map(|x| x.value.clone())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        let guard = epoch::pin();
+        let current = unsafe { self.current.load(Ordering::Acquire, &guard).deref() };
+        current.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        let guard = epoch::pin();
+        unsafe { self.current.load(Ordering::Acquire, &guard).deref() }.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    pub fn insert(&self, key: &K, value: V) -> Option<Arc<V>> {
+        self.update(|tree| tree.insert(key, value))
+    }
+
+    /// Remove a value, returns the removed value if the key was present.
+    pub fn remove(&self, key: &K) -> Option<Arc<V>> {
+        self.update(|tree| tree.remove(key))
+    }
+
+    /// Apply a modification to a copy of the current version and make the copy the new current
+    /// version.
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Aart<K, V, Arc<V>>) -> R,
+    {
+        let mut watchers = self.writer();
+        let guard = epoch::pin();
+        let current = self.current.load(Ordering::Acquire, &guard);
+        let mut new = unsafe { current.deref() }.clone();
+        let res = f(&mut new);
+        let new = Owned::new(new).into_shared(&guard);
+        let old = self.current.swap(new, Ordering::AcqRel, &guard);
+        // Watchers are notified after the swap so they see the new version when reading the map.
+        unsafe {
+            watchers.notify(old.deref(), new.deref());
+            guard.defer_destroy(old);
+        }
+        res
+    }
+
+    pub(crate) fn writer(&self) -> MutexGuard<'_, Watchers<K, V>> {
+        self.writer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Key + ?Sized, V> From<Aart<K, V, Arc<V>>> for ConcurrentAart<K, V> {
+    fn from(tree: Aart<K, V, Arc<V>>) -> Self {
+        ConcurrentAart {
+            current: Atomic::new(tree),
+            writer: Mutex::new(Watchers::new()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> Default for ConcurrentAart<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Key + ?Sized, V> Drop for ConcurrentAart<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let current = self.current.load(Ordering::Relaxed, epoch::unprotected());
+            drop(current.into_owned());
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Cursors which can move freely through the entries of a tree.
+
+use crate::{
+    iter::key_bytes,
+    key::{BorrowedKey, Key, KeyBytes},
+    raw::nodes::{NodeLeaf, NodeRef},
+    value::ValueStorage,
+    Aart,
+};
+use std::{cmp::Ordering, marker::PhantomData};
+
+/// A position in a tree.
+///
+/// Nodes don't point to their parents, so the cursor keeps an explicit stack of the branch nodes
+/// on the path to the current leaf together with the byte of the child taken in each of them.
+/// When the cursor is not at a leaf it is at the "ghost" position, which lies after the last and
+/// before the first entry of the tree.
+pub(crate) struct RawCursor<'a, K: KeyBytes + ?Sized, V> {
+    root: Option<NodeRef<'a, K, V>>,
+    key: Vec<u8>,
+    stack: Vec<(NodeRef<'a, K, V>, u8)>,
+    leaf: Option<NodeRef<'a, K, V>>,
+}
+
+impl<'a, K: KeyBytes + ?Sized, V> RawCursor<'a, K, V> {
+    pub fn new(root: Option<NodeRef<'a, K, V>>) -> Self {
+        RawCursor {
+            root,
+            key: Vec::new(),
+            stack: Vec::new(),
+            leaf: None,
+        }
+    }
+
+    /// Returns the key bytes and leaf of the current entry.
+    pub fn current(&self) -> Option<(&[u8], &'a NodeLeaf<K, V>)> {
+        let leaf = self.leaf?.cast::<NodeLeaf<K, V>>()?;
+        Some((self.key.as_slice(), leaf))
+    }
+
+    /// Move to the ghost position.
+    pub fn reset(&mut self) {
+        self.key.clear();
+        self.stack.clear();
+        self.leaf = None;
+    }
+
+    /// Move to the first or last leaf below a node, the key must contain the bytes up to the node.
+    fn descend(&mut self, mut node: NodeRef<'a, K, V>, last: bool) {
+        loop {
+            self.key.extend_from_slice(node.prefix());
+            if node.is::<NodeLeaf<K, V>>() {
+                self.leaf = Some(node);
+                return;
+            }
+            let (branch, child) = if last {
+                node.prev_node(u8::MAX)
+            } else {
+                node.next_node(0)
+            }
+            .expect("branch node without children");
+            self.stack.push((node, branch));
+            self.key.push(branch);
+            node = child;
+        }
+    }
+
+    /// Move to the first leaf after, or if `last` before, the subtree of the child at the top of
+    /// the stack.
+    fn ascend(&mut self, last: bool) {
+        while let Some((node, branch)) = self.stack.pop() {
+            self.key.pop();
+            let sibling = if last {
+                branch.checked_sub(1).and_then(|x| node.prev_node(x))
+            } else {
+                branch.checked_add(1).and_then(|x| node.next_node(x))
+            };
+            if let Some((branch, child)) = sibling {
+                self.stack.push((node, branch));
+                self.key.push(branch);
+                self.descend(child, last);
+                return;
+            }
+            let len = self.key.len() - node.prefix().len();
+            self.key.truncate(len);
+        }
+        self.reset();
+    }
+
+    pub fn seek_first(&mut self) {
+        self.reset();
+        if let Some(root) = self.root {
+            self.descend(root, false);
+        }
+    }
+
+    pub fn seek_last(&mut self) {
+        self.reset();
+        if let Some(root) = self.root {
+            self.descend(root, true);
+        }
+    }
+
+    /// Move to the first leaf with key bytes larger or equal to `key`.
+    pub fn seek(&mut self, key: &[u8]) {
+        self.reset();
+        let Some(mut node) = self.root else {
+            return;
+        };
+
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            let rest = &key[depth..];
+            let len = prefix.len().min(rest.len());
+            match prefix[..len].cmp(&rest[..len]) {
+                // Every key in the node is smaller than the key.
+                Ordering::Less => return self.ascend(false),
+                // Every key in the node is larger than the key.
+                Ordering::Greater => return self.descend(node, false),
+                Ordering::Equal if len < prefix.len() => return self.descend(node, false),
+                Ordering::Equal => {}
+            }
+            depth += len;
+
+            if node.is::<NodeLeaf<K, V>>() {
+                if depth < key.len() {
+                    return self.ascend(false);
+                }
+                self.key.extend_from_slice(prefix);
+                self.leaf = Some(node);
+                return;
+            }
+            if depth == key.len() {
+                return self.descend(node, false);
+            }
+
+            let Some((branch, child)) = node.next_node(key[depth]) else {
+                return self.ascend(false);
+            };
+            self.key.extend_from_slice(prefix);
+            self.stack.push((node, branch));
+            self.key.push(branch);
+            if branch != key[depth] {
+                return self.descend(child, false);
+            }
+            node = child;
+            depth += 1;
+        }
+    }
+
+    /// Move to the next leaf, or the first leaf if at the ghost position.
+    pub fn next(&mut self) {
+        self.step(false)
+    }
+
+    /// Move to the previous leaf, or the last leaf if at the ghost position.
+    pub fn prev(&mut self) {
+        self.step(true)
+    }
+
+    fn step(&mut self, back: bool) {
+        let Some(leaf) = self.leaf.take() else {
+            if back {
+                self.seek_last()
+            } else {
+                self.seek_first()
+            }
+            return;
+        };
+        let len = self.key.len() - leaf.prefix().len();
+        self.key.truncate(len);
+        self.ascend(back);
+    }
+}
+
+impl<K: KeyBytes + ?Sized, V> Clone for RawCursor<'_, K, V> {
+    fn clone(&self) -> Self {
+        RawCursor {
+            root: self.root,
+            key: self.key.clone(),
+            stack: self.stack.clone(),
+            leaf: self.leaf,
+        }
+    }
+}
+
+/// A cursor over the entries of an [`Aart`].
+///
+/// Created at the ghost position between the last and the first entry, moving with
+/// [`Cursor::next`] from there goes to the first entry and with [`Cursor::prev`] to the last.
+pub struct Cursor<'a, K: Key + ?Sized, V, S = std::sync::Arc<V>> {
+    raw: RawCursor<'a, K::Bytes, S>,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K: Key + ?Sized, V: 'a, S: ValueStorage<Value = V>> Cursor<'a, K, V, S> {
+    /// Move to the first entry with a key larger or equal to the given key, or to the ghost
+    /// position if there is no such entry.
+    pub fn seek(&mut self, key: &K) {
+        self.raw.seek(&key_bytes(key))
+    }
+
+    pub fn seek_first(&mut self) {
+        self.raw.seek_first()
+    }
+
+    pub fn seek_last(&mut self) {
+        self.raw.seek_last()
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) {
+        self.raw.next()
+    }
+
+    pub fn prev(&mut self) {
+        self.raw.prev()
+    }
+
+    /// Returns the bytes of the current key.
+    pub fn key_bytes(&self) -> Option<&[u8]> {
+        self.raw.current().map(|x| x.0)
+    }
+
+    pub fn value(&self) -> Option<&'a V> {
+        self.raw.current().map(|x| x.1.value.get())
+    }
+}
+
+impl<K: Key + BorrowedKey + ?Sized, V, S: ValueStorage<Value = V>> Cursor<'_, K, V, S> {
+    pub fn key(&self) -> Option<&K> {
+        self.key_bytes().map(|x| unsafe { K::from_key_bytes(x) })
+    }
+}
+
+impl<K: Key + ?Sized, V, S> Clone for Cursor<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        Cursor {
+            raw: self.raw.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V, S> Aart<K, V, S> {
+    /// Returns a cursor at the ghost position of the tree.
+    pub fn cursor(&self) -> Cursor<'_, K, V, S> {
+        Cursor {
+            raw: RawCursor::new(self.inner.root()),
+            _marker: PhantomData,
+        }
+    }
+}
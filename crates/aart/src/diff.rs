@@ -0,0 +1,71 @@
+//! Changes between two versions of a tree.
+
+use crate::{
+    key::{FromKeyBytes, Key},
+    raw::{RawChange, RawDiff},
+    Aart,
+};
+use std::{marker::PhantomData, sync::Arc};
+
+/// A difference in a single entry between two versions of a tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<K: ToOwned + ?Sized, V> {
+    /// The key is only present in the new tree.
+    Added { key: K::Owned, value: Arc<V> },
+    /// The key is only present in the old tree.
+    Removed { key: K::Owned, value: Arc<V> },
+    /// The key is present in both trees with a different value.
+    Modified {
+        key: K::Owned,
+        old: Arc<V>,
+        new: Arc<V>,
+    },
+}
+
+impl<K: Key + ?Sized, V> Aart<K, V, Arc<V>> {
+    /// Returns the changes from `old` to `new` in key order.
+    ///
+    /// Subtrees shared between the trees are skipped, so the changes between a tree and an earlier
+    /// clone of it are found in time proportional to the amount of changes and not the size of the
+    /// trees. Values are compared by identity: a key which was inserted again is modified even if
+    /// its new value is equal to the old one.
+    pub fn diff<'a>(old: &'a Self, new: &'a Self) -> Diff<'a, K, V> {
+        Diff {
+            inner: old.inner.diff(&new.inner),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the changes between two versions of a tree, created by [`Aart::diff`].
+///
+/// The changes are found while iterating, the trees are only compared as far as needed to find the
+/// next change.
+pub struct Diff<'a, K: Key + ?Sized, V> {
+    inner: RawDiff<'a, K::Bytes, V>,
+    _marker: PhantomData<fn(&K)>,
+}
+
+impl<K: FromKeyBytes + ?Sized, V> Iterator for Diff<'_, K, V> {
+    type Item = Change<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, change) = self.inner.next()?;
+        let key = K::owned_from_key_bytes(key);
+        Some(match change {
+            RawChange::Added(value) => Change::Added {
+                key,
+                value: value.clone(),
+            },
+            RawChange::Removed(value) => Change::Removed {
+                key,
+                value: value.clone(),
+            },
+            RawChange::Modified(old, new) => Change::Modified {
+                key,
+                old: old.clone(),
+                new: new.clone(),
+            },
+        })
+    }
+}
@@ -0,0 +1,144 @@
+//! Printing trees.
+//!
+//! The [`Display`](fmt::Display) implementation of [`Aart`] prints the nodes of the tree, one per
+//! line, with its kind, `len`, prefix and the amount of parents and trees sharing the node.
+//! Children are indented below their parent and preceded by their branch key. [`Aart::display`]
+//! prints the tree with [`DisplayOptions`] to leave out parts of large trees.
+//!
+//! The [`Debug`](fmt::Debug) implementation prints the entries of the tree as a map.
+
+use crate::{
+    key::{BorrowedKey, Key, KeyBytes},
+    raw::nodes::{NodeLeaf, NodeRef},
+    value::ValueStorage,
+    Aart,
+};
+use std::{fmt, sync::atomic::Ordering};
+
+/// Options limiting how much of a tree is printed.
+///
+/// Children which are left out are summarized by a line with their amount.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplayOptions {
+    /// The amount of levels below the root to print the children of, `Some(0)` only prints the
+    /// root.
+    pub max_depth: Option<usize>,
+    /// The amount of children to print per node.
+    pub max_children: Option<usize>,
+}
+
+/// A tree printed with [`DisplayOptions`], returned by [`Aart::display`].
+pub struct TreeDisplay<'a, K: Key + ?Sized, V, S> {
+    tree: &'a Aart<K, V, S>,
+    options: DisplayOptions,
+}
+
+impl<K, V, S> fmt::Display for TreeDisplay<'_, K, V, S>
+where
+    K: Key + ?Sized,
+    V: fmt::Debug,
+    S: ValueStorage<Value = V>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tree.inner.root() {
+            Some(root) => {
+                write!(f, "TREE = ")?;
+                display_node(f, root, 1, &self.options)
+            }
+            None => writeln!(f, "TREE = EMPTY"),
+        }
+    }
+}
+
+fn display_node<K: KeyBytes + ?Sized, S: ValueStorage<Value: fmt::Debug>>(
+    f: &mut fmt::Formatter,
+    node: NodeRef<'_, K, S>,
+    depth: usize,
+    options: &DisplayOptions,
+) -> fmt::Result {
+    let kind = format!("{:?}", node.kind()).to_uppercase();
+    let ref_count = node.ref_count().load(Ordering::Relaxed);
+    if let Some(leaf) = node.cast::<NodeLeaf<K, S>>() {
+        return writeln!(
+            f,
+            "{kind}: prefix={:?},ref_count={ref_count} | {:?}",
+            node.prefix(),
+            leaf.value.get()
+        );
+    }
+
+    let mut children = Vec::new();
+    let mut next = 0u16;
+    while next <= u8::MAX as u16 {
+        let Some((key, child)) = node.next_node(next as u8) else {
+            break;
+        };
+        children.push((key, child));
+        next = key as u16 + 1;
+    }
+    writeln!(
+        f,
+        "{kind}: len={},prefix={:?},ref_count={ref_count}",
+        children.len(),
+        node.prefix()
+    )?;
+
+    let indent = |f: &mut fmt::Formatter| (0..depth).try_for_each(|_| f.write_str("  "));
+    let shown = if options.max_depth.is_some_and(|x| depth > x) {
+        0
+    } else {
+        options.max_children.unwrap_or(usize::MAX)
+    };
+    for (key, child) in children.iter().take(shown) {
+        indent(f)?;
+        write!(f, "[{key}] = ")?;
+        display_node(f, *child, depth + 1, options)?;
+    }
+    let rest = children.len().saturating_sub(shown);
+    if rest > 0 {
+        indent(f)?;
+        writeln!(f, "... {rest} more")?;
+    }
+    Ok(())
+}
+
+impl<K, V, S> Aart<K, V, S>
+where
+    K: Key + ?Sized,
+    V: fmt::Debug,
+    S: ValueStorage<Value = V>,
+{
+    pub fn display(&self, options: DisplayOptions) -> TreeDisplay<'_, K, V, S> {
+        TreeDisplay {
+            tree: self,
+            options,
+        }
+    }
+}
+
+impl<K, V, S> fmt::Display for Aart<K, V, S>
+where
+    K: Key + ?Sized,
+    V: fmt::Debug,
+    S: ValueStorage<Value = V>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(DisplayOptions::default()).fmt(f)
+    }
+}
+
+impl<K, V, S> fmt::Debug for Aart<K, V, S>
+where
+    K: Key + BorrowedKey + fmt::Debug + ?Sized,
+    V: fmt::Debug,
+    S: ValueStorage<Value = V>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut iter = self.iter();
+        while let Some((k, v)) = iter.next() {
+            map.entry(&k, v);
+        }
+        map.finish()
+    }
+}
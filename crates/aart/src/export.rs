@@ -0,0 +1,172 @@
+//! Exporting the structure of a tree for inspection.
+//!
+//! [`Aart::to_dot`] renders the tree as a Graphviz graph and [`Aart::to_json`] as a nested JSON
+//! document. Prefixes are shown with printable ASCII as is and other bytes as `\xNN`.
+
+use crate::{
+    key::{Key, KeyBytes},
+    raw::nodes::{NodeLeaf, NodeRef},
+    value::ValueStorage,
+    Aart,
+};
+use std::{
+    fmt::{self, Write},
+    sync::atomic::Ordering,
+};
+
+/// A description of a single node, decoupled from the node types.
+struct ExportNode {
+    kind: String,
+    prefix: Vec<u8>,
+    ref_count: usize,
+    value: Option<String>,
+    children: Vec<(u8, ExportNode)>,
+}
+
+impl ExportNode {
+    fn new<K, S>(node: NodeRef<'_, K, S>) -> Self
+    where
+        K: KeyBytes + ?Sized,
+        S: ValueStorage<Value: fmt::Debug>,
+    {
+        let mut res = ExportNode {
+            kind: format!("{:?}", node.kind()),
+            prefix: node.prefix().to_vec(),
+            ref_count: node.ref_count().load(Ordering::Relaxed),
+            value: None,
+            children: Vec::new(),
+        };
+        if let Some(leaf) = node.cast::<NodeLeaf<K, S>>() {
+            res.value = Some(format!("{:?}", leaf.value.get()));
+            return res;
+        }
+
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, child)) = node.next_node(next as u8) else {
+                break;
+            };
+            res.children.push((key, ExportNode::new(child)));
+            next = key as u16 + 1;
+        }
+        res
+    }
+
+    fn write_dot(&self, out: &mut String, id: &mut usize) -> usize {
+        let this = *id;
+        *id += 1;
+
+        let mut label = format!(
+            "{}\\nprefix: {}\\nref_count: {}",
+            self.kind,
+            dot_escape(&escape_bytes(&self.prefix)),
+            self.ref_count
+        );
+        if let Some(value) = &self.value {
+            write!(label, "\\nvalue: {}", dot_escape(value)).unwrap();
+        } else {
+            write!(label, "\\nchildren: {}", self.children.len()).unwrap();
+        }
+        writeln!(out, "  n{this} [label=\"{label}\"];").unwrap();
+
+        for (key, child) in self.children.iter() {
+            let child_id = child.write_dot(out, id);
+            let key = dot_escape(&escape_bytes(&[*key]));
+            writeln!(out, "  n{this} -> n{child_id} [label=\"{key}\"];").unwrap();
+        }
+        this
+    }
+
+    fn write_json(&self, out: &mut String) {
+        write!(
+            out,
+            "{{\"kind\":\"{}\",\"prefix\":\"{}\",\"ref_count\":{}",
+            self.kind,
+            json_escape(&escape_bytes(&self.prefix)),
+            self.ref_count
+        )
+        .unwrap();
+        if let Some(value) = &self.value {
+            write!(out, ",\"value\":\"{}\"", json_escape(value)).unwrap();
+        } else {
+            out.push_str(",\"children\":[");
+            for (idx, (key, child)) in self.children.iter().enumerate() {
+                if idx != 0 {
+                    out.push(',');
+                }
+                write!(out, "{{\"key\":{key},\"node\":").unwrap();
+                child.write_json(out);
+                out.push('}');
+            }
+            out.push(']');
+        }
+        out.push('}');
+    }
+}
+
+/// Escape bytes to a string with printable ASCII as is and other bytes as `\xNN`.
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut res = String::new();
+    for b in bytes {
+        match b {
+            b'\\' => res.push_str("\\\\"),
+            0x20..=0x7e => res.push(*b as char),
+            x => write!(res, "\\x{x:02x}").unwrap(),
+        }
+    }
+    res
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn json_escape(s: &str) -> String {
+    let mut res = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            x if (x as u32) < 0x20 => write!(res, "\\u{:04x}", x as u32).unwrap(),
+            x => res.push(x),
+        }
+    }
+    res
+}
+
+impl<K, V, S> Aart<K, V, S>
+where
+    K: Key + ?Sized,
+    V: fmt::Debug,
+    S: ValueStorage<Value = V>,
+{
+    /// Returns a Graphviz graph of the nodes in the tree.
+    ///
+    /// Every node is labeled with its kind, prefix and reference count and leafs with their value. Edges are
+    /// labeled with the key of the branch.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph aart {\n  node [shape=box, fontname=\"monospace\"];\n");
+        if let Some(root) = self.inner.root() {
+            ExportNode::new(root).write_dot(&mut out, &mut 0);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Returns a JSON document describing the nodes in the tree.
+    ///
+    /// The document is an object with the amount of keys in `len` and the root node in `root`,
+    /// which is `null` for an empty tree. Every node has a `kind`, a `prefix` and the amount of
+    /// parents and trees sharing the node in `ref_count`. Leafs have the debug representation of
+    /// their value in `value` and other nodes a list of `children`, each with the branch `key` and
+    /// the child `node`.
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"len\":{},\"root\":", self.len());
+        match self.inner.root() {
+            Some(root) => ExportNode::new(root).write_json(&mut out),
+            None => out.push_str("null"),
+        }
+        out.push('}');
+        out
+    }
+}
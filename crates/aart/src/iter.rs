@@ -0,0 +1,195 @@
+use crate::{
+    key::{BorrowedKey, Key, KeyBytes},
+    raw::nodes::{NodeLeaf, NodeRef},
+    value::ValueStorage,
+};
+use std::{cmp::Ordering, marker::PhantomData, ops::Bound};
+
+/// An iterator over the leafs of tree in key order.
+///
+/// Keeps an explicit stack of the branch nodes currently being iterated, together with the next
+/// key to look at in the node and the length of the key before the prefix of the node.
+pub struct RawIterator<'a, K: KeyBytes + ?Sized, V> {
+    key: Vec<u8>,
+    start: Option<NodeRef<'a, K, V>>,
+    stack: Vec<(NodeRef<'a, K, V>, u16, usize)>,
+}
+
+impl<'a, K: KeyBytes + ?Sized, V> RawIterator<'a, K, V> {
+    pub fn new(root: Option<NodeRef<'a, K, V>>) -> Self {
+        RawIterator {
+            key: Vec::new(),
+            start: root,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Create an iterator positioned at the first key larger than, or if `inclusive` equal to,
+    /// `start`.
+    pub fn seek(root: Option<NodeRef<'a, K, V>>, start: &[u8], inclusive: bool) -> Self {
+        let mut this = Self::new(None);
+        let Some(mut node) = root else {
+            return this;
+        };
+
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            let rest = &start[depth..];
+            let len = prefix.len().min(rest.len());
+            match prefix[..len].cmp(&rest[..len]) {
+                // every key in the node is smaller than start.
+                Ordering::Less => return this,
+                // every key in the node is larger than start.
+                Ordering::Greater => {
+                    this.start = Some(node);
+                    return this;
+                }
+                Ordering::Equal if len < prefix.len() => {
+                    this.start = Some(node);
+                    return this;
+                }
+                Ordering::Equal => {}
+            }
+            depth += len;
+
+            if node.is::<NodeLeaf<K, V>>() {
+                if depth == start.len() && inclusive {
+                    this.start = Some(node);
+                }
+                return this;
+            }
+
+            let base = this.key.len();
+            this.key.extend_from_slice(prefix);
+            if depth == start.len() {
+                this.stack.push((node, 0, base));
+                return this;
+            }
+
+            let branch = start[depth];
+            match node.get(branch) {
+                Some(child) => {
+                    this.stack.push((node, branch as u16 + 1, base));
+                    this.key.push(branch);
+                    node = child;
+                    depth += 1;
+                }
+                None => {
+                    this.stack.push((node, branch as u16, base));
+                    return this;
+                }
+            }
+        }
+    }
+
+    /// Enter a node, returns the node if it is a leaf, otherwise pushes the node onto the stack.
+    fn enter(&mut self, node: NodeRef<'a, K, V>) -> Option<&'a NodeLeaf<K, V>> {
+        let base = self.key.len();
+        self.key.extend_from_slice(node.prefix());
+        if let Some(leaf) = node.cast::<NodeLeaf<K, V>>() {
+            return Some(leaf);
+        }
+        self.stack.push((node, 0, base));
+        None
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&[u8], &'a NodeLeaf<K, V>)> {
+        if let Some(root) = self.start.take() {
+            if let Some(leaf) = self.enter(root) {
+                return Some((self.key.as_slice(), leaf));
+            }
+        }
+
+        loop {
+            let (node, next, base) = self.stack.last_mut()?;
+            let found = if *next > u8::MAX as u16 {
+                None
+            } else {
+                node.next_node(*next as u8)
+            };
+
+            let Some((branch, child)) = found else {
+                self.key.truncate(*base);
+                self.stack.pop();
+                continue;
+            };
+
+            *next = branch as u16 + 1;
+            let len = *base + node.prefix().len();
+            self.key.truncate(len);
+            self.key.push(branch);
+
+            if let Some(leaf) = self.enter(child) {
+                return Some((self.key.as_slice(), leaf));
+            }
+        }
+    }
+}
+
+/// The end bound of a range iteration, in key bytes.
+pub(crate) enum End {
+    Unbounded,
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+    Prefix(Vec<u8>),
+}
+
+impl End {
+    pub(crate) fn from_bound<K: Key + ?Sized>(bound: Bound<&&K>) -> Self {
+        match bound {
+            Bound::Included(x) => End::Included(key_bytes(*x)),
+            Bound::Excluded(x) => End::Excluded(key_bytes(*x)),
+            Bound::Unbounded => End::Unbounded,
+        }
+    }
+
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        match self {
+            End::Unbounded => true,
+            End::Included(x) => key <= x.as_slice(),
+            End::Excluded(x) => key < x.as_slice(),
+            End::Prefix(x) => key.starts_with(x),
+        }
+    }
+}
+
+pub(crate) fn key_bytes<K: Key + ?Sized>(key: &K) -> Vec<u8> {
+    let bytes = key.as_key_bytes();
+    (0..bytes.len()).map(|i| bytes.at(i).unwrap()).collect()
+}
+
+/// An iterator over the entries of a tree in key order.
+pub struct Iter<'a, K: Key + ?Sized, V, S = std::sync::Arc<V>> {
+    raw: RawIterator<'a, K::Bytes, S>,
+    end: End,
+    done: bool,
+    _marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K: Key + ?Sized, V, S> Iter<'a, K, V, S> {
+    pub(crate) fn new(raw: RawIterator<'a, K::Bytes, S>, end: End) -> Self {
+        Iter {
+            raw,
+            end,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K: Key + BorrowedKey + ?Sized, V: 'a, S: ValueStorage<Value = V>> Iter<'a, K, V, S> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a V)> {
+        if self.done {
+            return None;
+        }
+        let (key, leaf) = self.raw.next()?;
+        if !self.end.contains(key) {
+            self.done = true;
+            return None;
+        }
+        unsafe { Some((K::from_key_bytes(key), leaf.value.get())) }
+    }
+}
@@ -132,6 +132,13 @@ unsafe impl<K: KeyBytes + ?Sized> KeyStorage<K> for InlineStorage {
         this
     }
 
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self {
+        let mut this = unsafe { InlineStorage::new(prefix.len(), data) };
+        let dst = this.buffer_ptr().as_ptr();
+        unsafe { std::ptr::copy_nonoverlapping(prefix.as_ptr(), dst, prefix.len()) };
+        this
+    }
+
     fn data(&self) -> NodeData {
         self.data
     }
@@ -155,7 +162,7 @@ unsafe impl<K: KeyBytes + ?Sized> KeyStorage<K> for InlineStorage {
     }
 
     fn prepend_prefix(&mut self, prefix: &[u8], key: u8) {
-        let len = if self.len < INLINE_MAX {
+        let len = if self.len < INLINE_FULL {
             self.len as usize
         } else {
             unsafe { self.buffer.ptr.as_ref().len }
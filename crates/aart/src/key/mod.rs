@@ -2,13 +2,14 @@ use bytemuck::{Pod, TransparentWrapper, Zeroable};
 use core::fmt;
 use std::{cmp::Ordering, marker::PhantomData};
 
-use crate::raw::nodes::NodeHeader;
-
 use self::{inline_buffer::InlineStorage, pod::PodStorageU8};
 
 mod inline_buffer;
+mod net;
 mod pod;
 
+pub use net::{Ipv4Net, Ipv6Net};
+
 #[derive(Clone, Copy, Pod, Zeroable)]
 #[repr(C)]
 pub struct NodeData(pub(crate) [u8; 3]);
@@ -20,9 +21,9 @@ pub struct NodeData(pub(crate) [u8; 3]);
 /// rules will result in undefined behaviour.
 ///
 /// - The implementation of this trait must ensure that any call to [`KeyStorage::data`] or
-/// [`KeyStorage::data_mut`] returns an object with the same value as the one given by
-/// [`KeyStorage::store`]. Further more the value object must not changed unless changed externally
-/// by using [`KeyStorage::data_mut`].
+///   [`KeyStorage::data_mut`] returns an object with the same value as the one given by
+///   [`KeyStorage::store`]. Further more the value object must not changed unless changed
+///   externally by using [`KeyStorage::data_mut`].
 ///
 /// In short the caller of this trait must be able to trust that the storage won't suddenly change
 /// the value of NodeData.
@@ -32,6 +33,9 @@ pub unsafe trait KeyStorage<K: KeyBytes + ?Sized>: Sized {
 
     fn new_from(existing: &Self, data: NodeData) -> Self;
 
+    /// Create the storage for a prefix given as bytes.
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self;
+
     /// Return a reference to NodeData.
     ///
     /// The implementation of this trait must ensure that the reference to this node data is to the
@@ -79,6 +83,52 @@ pub trait Key {
     fn as_key_bytes(&self) -> &Self::Bytes;
 }
 
+pub trait BorrowedKey {
+    /// # Safety
+    /// The bytes must be the full key bytes of a key of this type.
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self;
+}
+
+impl BorrowedKey for str {
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        std::str::from_utf8(&bytes[..bytes.len() - 1]).unwrap()
+    }
+}
+
+/// A key which can be recreated from its key bytes as an owned value, this includes keys which
+/// can't be borrowed from their bytes.
+pub trait FromKeyBytes: Key + ToOwned {
+    /// Recreate a key from its full key bytes.
+    fn owned_from_key_bytes(bytes: &[u8]) -> Self::Owned;
+}
+
+impl FromKeyBytes for str {
+    fn owned_from_key_bytes(bytes: &[u8]) -> String {
+        unsafe { Self::from_key_bytes(bytes) }.to_owned()
+    }
+}
+
+/// A key which can be a prefix of another key.
+///
+/// The bytes of these keys end with a terminator byte which never directly follows the bytes of a
+/// shorter key within a longer key. The tree thus never contains a key whose bytes are a prefix of
+/// another key, while the key a terminator follows can still be found as the prefix of a longer
+/// key.
+pub trait PrefixKey: Key + ToOwned {
+    const TERMINATOR: u8;
+
+    /// Recreate a key from its full key bytes, including the terminator.
+    fn from_prefix_bytes(bytes: &[u8]) -> Self::Owned;
+}
+
+impl PrefixKey for str {
+    const TERMINATOR: u8 = INVALID_STR_BYTE;
+
+    fn from_prefix_bytes(bytes: &[u8]) -> String {
+        unsafe { Self::from_key_bytes(bytes) }.to_owned()
+    }
+}
+
 pub trait KeyBytes {
     type Storage: KeyStorage<Self>;
 
@@ -226,6 +276,12 @@ macro_rules! impl_pod {
                     PodBytesU8::wrap_ref(bytemuck::bytes_of(self))
                 }
             }
+
+            impl FromKeyBytes for $t {
+                fn owned_from_key_bytes(bytes: &[u8]) -> Self {
+                    bytemuck::pod_read_unaligned(bytes)
+                }
+            }
         )*
     }
 }
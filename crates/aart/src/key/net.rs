@@ -0,0 +1,117 @@
+//! Network address keys for routing tables.
+//!
+//! A network is stored as one byte for every significant bit of its address followed by a
+//! terminator, so a network is a prefix of all the networks and addresses it contains and
+//! [`Aart::longest_prefix_match`](crate::Aart::longest_prefix_match) finds the most specific
+//! network containing an address. As keys are borrowed as bytes the networks keep their address
+//! in this form.
+
+use super::{FromKeyBytes, Key, PostfixedBytes, PrefixKey};
+use core::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The byte following the bits of a network, bits are stored as the bytes 0 and 1.
+const NET_TERMINATOR: u8 = 2;
+
+macro_rules! impl_net {
+    ($name:ident, $addr:ident, $bits:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name {
+            bits: [u8; <$bits>::BITS as usize],
+            len: u8,
+        }
+
+        impl $name {
+            const MAX_LEN: u8 = <$bits>::BITS as u8;
+
+            /// Create a network from an address and the amount of significant bits, the other
+            /// bits of the address are cleared.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `len` is larger than the amount of bits in the address.
+            pub fn new(addr: $addr, len: u8) -> Self {
+                assert!(len <= Self::MAX_LEN, "network length out of range");
+                let addr = <$bits>::from(addr);
+                let mut bits = [0; <$bits>::BITS as usize];
+                for (i, b) in bits[..len as usize].iter_mut().enumerate() {
+                    *b = (addr >> (Self::MAX_LEN as usize - 1 - i)) as u8 & 1;
+                }
+                $name { bits, len }
+            }
+
+            pub fn addr(&self) -> $addr {
+                let bits = self
+                    .bits
+                    .iter()
+                    .fold(0, |acc: $bits, b| acc << 1 | *b as $bits);
+                $addr::from(bits)
+            }
+
+            /// Returns the amount of significant bits of the address.
+            pub fn prefix_len(&self) -> u8 {
+                self.len
+            }
+
+            /// Returns whether the network contains the given address.
+            pub fn contains(&self, addr: $addr) -> bool {
+                Self::new(addr, self.len) == *self
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}/{}", self.addr(), self.len)
+            }
+        }
+
+        impl From<$addr> for $name {
+            /// Returns the network containing only the given address.
+            fn from(addr: $addr) -> Self {
+                Self::new(addr, Self::MAX_LEN)
+            }
+        }
+
+        impl Key for $name {
+            type Bytes = PostfixedBytes<NET_TERMINATOR>;
+
+            fn as_key_bytes(&self) -> &Self::Bytes {
+                PostfixedBytes::from_bytes(&self.bits[..self.len as usize])
+            }
+        }
+
+        impl FromKeyBytes for $name {
+            fn owned_from_key_bytes(bytes: &[u8]) -> Self {
+                Self::from_prefix_bytes(bytes)
+            }
+        }
+
+        impl PrefixKey for $name {
+            const TERMINATOR: u8 = NET_TERMINATOR;
+
+            fn from_prefix_bytes(bytes: &[u8]) -> Self {
+                let len = bytes.len() - 1;
+                let mut bits = [0; <$bits>::BITS as usize];
+                bits[..len].copy_from_slice(&bytes[..len]);
+                $name {
+                    bits,
+                    len: len as u8,
+                }
+            }
+        }
+    };
+}
+
+impl_net!(
+    Ipv4Net,
+    Ipv4Addr,
+    u32,
+    "An IPv4 network, an address of which only the first bits are significant."
+);
+impl_net!(
+    Ipv6Net,
+    Ipv6Addr,
+    u128,
+    "An IPv6 network, an address of which only the first bits are significant."
+);
@@ -35,6 +35,19 @@ unsafe impl<P: Pod> KeyStorage<PodBytesU8<P>> for PodStorageU8<P> {
         }
     }
 
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self {
+        assert!(prefix.len() <= std::mem::size_of::<P>());
+
+        let mut value: MaybeUninit<P> = MaybeUninit::uninit();
+        let dst = value.as_mut_ptr().cast::<u8>();
+        unsafe { std::ptr::copy_nonoverlapping(prefix.as_ptr(), dst, prefix.len()) };
+        PodStorageU8 {
+            value,
+            len: prefix.len() as u8,
+            data,
+        }
+    }
+
     fn data(&self) -> NodeData {
         self.data
     }
@@ -1,34 +1,176 @@
 #![allow(dead_code)]
 
+use iter::{key_bytes, End, Iter, RawIterator};
 use key::Key;
 use raw::RawAart;
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
 
+pub mod concurrent;
+pub mod cursor;
+pub mod diff;
+pub mod display;
+pub mod export;
+pub mod fuzzy;
+pub mod iter;
 pub mod key;
+pub mod olc;
+#[cfg(feature = "order-statistics")]
+pub mod order;
+pub mod pattern;
+pub mod prefix;
 mod prim;
 pub mod raw;
+pub mod rowex;
+pub mod set;
+pub mod sharded;
+pub mod value;
+pub mod watch;
 
-pub struct Aart<K: Key + ?Sized, V> {
-    inner: RawAart<K::Bytes, V>,
+pub use concurrent::ConcurrentAart;
+pub use olc::OlcArt;
+pub use rowex::RowexArt;
+pub use sharded::ShardedAart;
+pub use value::{Auto, Inline, ValueStorage};
+
+/// A persistent adaptive radix tree.
+///
+/// Modifying the tree copies the path to the modified leaf instead of changing nodes in place, so
+/// cloning a tree is cheap and the clone is not affected by later modifications to the original.
+///
+/// Values are stored in the leafs with `S`, see [`ValueStorage`]. By default values no larger than
+/// a pointer are stored in the leafs and larger values behind an
+/// [`Arc`](std::sync::Arc), see [`Auto`].
+pub struct Aart<K: Key + ?Sized, V, S = Auto<V>> {
+    inner: RawAart<K::Bytes, S>,
+    len: usize,
+    _marker: PhantomData<V>,
 }
 
-impl<K: Key + ?Sized, V> Aart<K, V> {
+impl<K: Key + ?Sized, V: Clone> Aart<K, V> {
     pub fn new() -> Self {
+        Self::with_storage()
+    }
+
+    /// Create a tree from an iterator of unique keys sorted in ascending byte order.
+    ///
+    /// Builds the tree bottom-up, allocating every node once at the size it needs to be instead of
+    /// path copying the tree for every key.
+    ///
+    /// Keys must be in the order of their key bytes: integer keys are ordered by their little
+    /// endian byte representation and strings are ordered as if they were terminated by the byte
+    /// [`key::INVALID_STR_BYTE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not sorted or contain duplicates.
+    pub fn from_sorted_iter<'a, I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a K, V)>,
+        K: 'a,
+    {
+        Self::from_sorted_iter_with_storage(iter)
+    }
+}
+
+impl<K: Key + ?Sized, V, S: ValueStorage<Value = V>> Aart<K, V, S> {
+    /// Create an empty tree storing its values with `S`.
+    pub fn with_storage() -> Self {
         Aart {
             inner: RawAart::new(),
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a tree storing its values with `S` from an iterator of unique keys sorted in
+    /// ascending byte order, see [`Aart::from_sorted_iter`].
+    pub fn from_sorted_iter_with_storage<'a, I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a K, V)>,
+        K: 'a,
+    {
+        let (keys, values): (Vec<&K::Bytes>, Vec<V>) =
+            iter.into_iter().map(|(k, v)| (k.as_key_bytes(), v)).unzip();
+        Aart {
+            inner: RawAart::from_sorted(&keys, &mut values.into_iter().map(S::new)),
+            len: keys.len(),
+            _marker: PhantomData,
         }
     }
 
-    pub fn insert(&mut self, key: &K, value: V) {
-        self.inner.insert(key.as_key_bytes(), value);
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    pub fn insert(&mut self, key: &K, value: V) -> Option<S::Owned> {
+        let res = self.inner.insert(key.as_key_bytes(), S::new(value));
+        self.len += res.is_none() as usize;
+        res.map(S::into_owned)
     }
 
-    pub fn get(&mut self, key: &K) -> Option<&V> {
-        self.inner.get(key.as_key_bytes()).map(|x| &*x.value)
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.inner.get(key.as_key_bytes()).map(|x| x.value.get())
+    }
+
+    /// Remove a value, returns the removed value if the key was present.
+    pub fn remove(&mut self, key: &K) -> Option<S::Owned> {
+        let res = self.inner.remove(key.as_key_bytes());
+        self.len -= res.is_some() as usize;
+        res.map(S::into_owned)
+    }
+
+    /// Returns an iterator over all the entries in the tree in key order.
+    pub fn iter(&self) -> Iter<'_, K, V, S> {
+        Iter::new(RawIterator::new(self.inner.root()), End::Unbounded)
+    }
+
+    /// Returns an iterator over the entries with keys within the range in key order.
+    pub fn range<'r, R>(&self, range: R) -> Iter<'_, K, V, S>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let end = End::from_bound(range.end_bound());
+        let root = self.inner.root();
+        let raw = match range.start_bound() {
+            Bound::Included(x) => RawIterator::seek(root, &key_bytes(*x), true),
+            Bound::Excluded(x) => RawIterator::seek(root, &key_bytes(*x), false),
+            Bound::Unbounded => RawIterator::new(root),
+        };
+        Iter::new(raw, end)
+    }
+}
+
+impl<V, S: ValueStorage<Value = V>> Aart<str, V, S> {
+    /// Returns an iterator over the entries with keys starting with the given prefix.
+    pub fn scan_prefix(&self, prefix: &str) -> Iter<'_, str, V, S> {
+        let prefix = prefix.as_bytes();
+        let raw = RawIterator::seek(self.inner.root(), prefix, true);
+        Iter::new(raw, End::Prefix(prefix.to_vec()))
+    }
+}
+
+impl<K: Key + ?Sized, V, S> Clone for Aart<K, V, S> {
+    /// Returns a copy of the tree, this does not copy any nodes.
+    fn clone(&self) -> Self {
+        Aart {
+            inner: self.inner.clone(),
+            len: self.len,
+            _marker: PhantomData,
+        }
     }
 }
 
-impl<K: Key + ?Sized, V> Default for Aart<K, V> {
+impl<K: Key + ?Sized, V, S: ValueStorage<Value = V>> Default for Aart<K, V, S> {
     fn default() -> Self {
-        Self::new()
+        Self::with_storage()
     }
 }
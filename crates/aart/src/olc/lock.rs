@@ -0,0 +1,72 @@
+use crate::prim::{
+    hint,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+const OBSOLETE: u64 = 0b01;
+const LOCKED: u64 = 0b10;
+
+/// A lock which readers validate instead of acquire.
+///
+/// The lock word holds a version which is incremented every time a writer releases the lock. A
+/// reader remembers the version before reading a node and checks that it is unchanged afterwards,
+/// a different version means a writer changed the node in between and the reader has to restart.
+/// A node which is removed from the tree is marked obsolete when it is unlocked so that readers
+/// which still hold a pointer to it restart as well.
+///
+/// Writers of the [`OlcArt`](crate::OlcArt) only try to take the lock and restart if they fail.
+/// Readers of the [`RowexArt`](crate::RowexArt) ignore the lock, so its writers simply wait for it.
+pub struct VersionLock(AtomicU64);
+
+impl VersionLock {
+    pub fn new() -> Self {
+        VersionLock(AtomicU64::new(0))
+    }
+
+    /// Returns the current version, or `None` if the lock is held or the node is obsolete.
+    pub fn read(&self) -> Option<u64> {
+        let version = self.0.load(Ordering::Acquire);
+        (version & (LOCKED | OBSOLETE) == 0).then_some(version)
+    }
+
+    /// Returns whether the version is unchanged since it was read.
+    pub fn check(&self, version: u64) -> bool {
+        self.0.load(Ordering::Acquire) == version
+    }
+
+    /// Take the lock if the version is unchanged since it was read.
+    pub fn upgrade(&self, version: u64) -> bool {
+        self.0
+            .compare_exchange(
+                version,
+                version + LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+
+    /// Wait for the lock to be released and take it, returns false if the node is obsolete.
+    pub fn lock(&self) -> bool {
+        loop {
+            let version = self.0.load(Ordering::Relaxed);
+            if version & OBSOLETE != 0 {
+                return false;
+            }
+            if version & LOCKED == 0 && self.upgrade(version) {
+                return true;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// Release the lock, incrementing the version.
+    pub fn unlock(&self) {
+        self.0.fetch_add(LOCKED, Ordering::Release);
+    }
+
+    /// Release the lock of a node which is no longer part of the tree.
+    pub fn unlock_obsolete(&self) {
+        self.0.fetch_add(LOCKED | OBSOLETE, Ordering::Release);
+    }
+}
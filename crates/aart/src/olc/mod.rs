@@ -0,0 +1,452 @@
+//! An adaptive radix tree which is modified in place by many threads at once.
+//!
+//! Unlike [`ConcurrentAart`](crate::ConcurrentAart), which copies the path to every modified leaf,
+//! [`OlcArt`] changes its nodes in place and uses optimistic lock coupling to synchronize threads.
+//! Every branch node carries a version lock. Readers never write to shared memory, they remember
+//! the version of a node before reading it and check that the version is unchanged after.
+//! Writers lock only the nodes they change, which is the node the key is inserted into or removed
+//! from, and its parent when that node has to be replaced. Removed nodes are freed once every
+//! thread which could still be reading them is done, using `crossbeam_epoch`.
+
+pub(crate) mod lock;
+pub(crate) mod nodes;
+
+use crate::{
+    iter::key_bytes,
+    key::{Key, KeyBytes, KeyPrefixError},
+    prim::{
+        hint,
+        sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    },
+};
+pub use crossbeam_epoch::{pin, Guard};
+use lock::VersionLock;
+use nodes::{NodeHeader, NodeKind, NodeRef};
+use std::{marker::PhantomData, ptr};
+
+/// A concurrent adaptive radix tree using optimistic lock coupling.
+///
+/// Values are only ever dropped once no reader can observe them, references to values returned
+/// by [`OlcArt::get`] are therefore valid for as long as the [`Guard`] they were read with.
+pub struct OlcArt<K: Key + ?Sized, V> {
+    root: AtomicPtr<NodeHeader>,
+    /// Protects the root pointer, acting as the lock of the parent of the root node.
+    lock: VersionLock,
+    len: AtomicUsize,
+    _marker: PhantomData<(*const K, *const V)>,
+}
+
+unsafe impl<K: Key + ?Sized, V: Send + Sync> Send for OlcArt<K, V> {}
+unsafe impl<K: Key + ?Sized, V: Send + Sync> Sync for OlcArt<K, V> {}
+
+/// The slot containing a pointer to a node, along with the lock and version of the node the slot
+/// is part of.
+#[derive(Clone, Copy)]
+struct Slot<'a> {
+    lock: &'a VersionLock,
+    version: u64,
+    ptr: &'a AtomicPtr<NodeHeader>,
+}
+
+impl Slot<'_> {
+    /// Load the node in the slot, returns `None` if the version changed.
+    fn load(&self) -> Option<Option<NodeRef>> {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        self.lock
+            .check(self.version)
+            .then(|| unsafe { NodeRef::new(ptr) })
+    }
+
+    fn lock(&self) -> bool {
+        self.lock.upgrade(self.version)
+    }
+
+    fn replace(&self, node: Option<NodeRef>) {
+        self.ptr.store(
+            node.map(NodeRef::as_ptr).unwrap_or_else(ptr::null_mut),
+            Ordering::Release,
+        );
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Take the lock of a node, returns `None` if it is obsolete or changed since the version was
+/// read.
+fn lock_node(node: NodeRef) -> Option<()> {
+    let lock = &node.header().lock;
+    lock.upgrade(lock.read()?).then_some(())
+}
+
+impl<K: Key + ?Sized, V> OlcArt<K, V> {
+    pub fn new() -> Self {
+        OlcArt {
+            root: AtomicPtr::new(ptr::null_mut()),
+            lock: VersionLock::new(),
+            len: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn root_slot(&self) -> Option<Slot<'_>> {
+        Some(Slot {
+            lock: &self.lock,
+            version: self.lock.read()?,
+            ptr: &self.root,
+        })
+    }
+
+    /// Returns a reference to the value of the key, which stays valid while the guard is alive.
+    pub fn get<'g>(&self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let _ = guard;
+        let key = key.as_key_bytes();
+        'restart: loop {
+            let Some(mut slot) = self.root_slot() else {
+                hint::spin_loop();
+                continue;
+            };
+            let mut depth = 0;
+            loop {
+                let Some(node) = slot.load() else {
+                    hint::spin_loop();
+                    continue 'restart;
+                };
+                let node = node?;
+                if node.is_leaf() {
+                    let leaf = unsafe { node.as_leaf::<V>() };
+                    let matches = leaf.key.len() == key.len()
+                        && (0..key.len()).all(|i| key.at(i) == Some(leaf.key[i]));
+                    return matches.then_some(&leaf.value);
+                }
+                let header = node.header();
+                let Some(version) = header.lock.read() else {
+                    hint::spin_loop();
+                    continue 'restart;
+                };
+                let prefix_matches =
+                    (0..header.prefix.len()).all(|i| key.at(depth + i) == Some(header.prefix[i]));
+                depth += header.prefix.len();
+                let next = prefix_matches
+                    .then(|| key.at(depth))
+                    .flatten()
+                    .and_then(|byte| node.find(byte));
+                match next {
+                    Some(ptr) => {
+                        slot = Slot {
+                            lock: &header.lock,
+                            version,
+                            ptr,
+                        };
+                        depth += 1;
+                    }
+                    None if header.lock.check(version) => return None,
+                    None => {
+                        hint::spin_loop();
+                        continue 'restart;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key, &pin()).is_some()
+    }
+
+    /// Insert a value, returns true if the key was not yet present.
+    ///
+    /// The value previously stored under the key is dropped once no reader can observe it.
+    ///
+    /// # Errors
+    /// Returns [`KeyPrefixError`] if the bytes of the key are a prefix of the bytes of a stored key
+    /// or the other way around, which only happens for keys whose bytes aren't prefix free.
+    pub fn insert(&self, key: &K, value: V) -> Result<bool, KeyPrefixError> {
+        let guard = pin();
+        let key = key_bytes(key);
+        let leaf = NodeRef::new_leaf(key.clone().into_boxed_slice(), value);
+        loop {
+            match self.try_insert(&key, leaf, &guard) {
+                Some(Ok(inserted)) => {
+                    self.len.fetch_add(inserted as usize, Ordering::Relaxed);
+                    return Ok(inserted);
+                }
+                Some(Err(e)) => {
+                    // The leaf was never published so no reader can observe it.
+                    unsafe { leaf.free::<V>() };
+                    return Err(e);
+                }
+                None => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// A single attempt at inserting the leaf, returns `None` if the attempt has to be restarted.
+    fn try_insert(
+        &self,
+        key: &[u8],
+        leaf: NodeRef,
+        guard: &Guard,
+    ) -> Option<Result<bool, KeyPrefixError>> {
+        let mut slot = self.root_slot()?;
+        let mut depth = 0;
+        loop {
+            let Some(node) = slot.load()? else {
+                // Only the root slot is empty, the slots of branch nodes are found by their key.
+                debug_assert!(ptr::eq(slot.ptr, &self.root));
+                slot.lock().then_some(())?;
+                slot.replace(Some(leaf));
+                slot.lock.unlock();
+                return Some(Ok(true));
+            };
+
+            if node.is_leaf() {
+                let existing = unsafe { node.as_leaf::<V>() };
+                if *existing.key == *key {
+                    slot.lock().then_some(())?;
+                    slot.replace(Some(leaf));
+                    slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                    return Some(Ok(false));
+                }
+                // Split the leaf into a node with both leafs as branches.
+                let len = common_prefix(&existing.key[depth..], &key[depth..]);
+                if depth + len == existing.key.len().min(key.len()) {
+                    return Some(Err(KeyPrefixError));
+                }
+                let (old_key, new_key) = (existing.key[depth + len], key[depth + len]);
+                let branch = NodeRef::new_branch(
+                    NodeKind::Node4,
+                    key[depth..depth + len].into(),
+                    &[(old_key, node.as_ptr()), (new_key, leaf.as_ptr())],
+                );
+                if !slot.lock() {
+                    unsafe { branch.free::<V>() };
+                    return None;
+                }
+                slot.replace(Some(branch));
+                slot.lock.unlock();
+                return Some(Ok(true));
+            }
+
+            let header = node.header();
+            let version = header.lock.read()?;
+            let prefix = &header.prefix;
+            let len = common_prefix(prefix, &key[depth..]);
+            if len < prefix.len() {
+                if depth + len == key.len() {
+                    header.lock.check(version).then_some(())?;
+                    return Some(Err(KeyPrefixError));
+                }
+                // The key diverges within the prefix, replace the node by a new node with the
+                // common part of the prefix with a copy of the node and the leaf as branches.
+                slot.lock().then_some(())?;
+                if !header.lock.upgrade(version) {
+                    slot.lock.unlock();
+                    return None;
+                }
+                let copy =
+                    NodeRef::new_branch(header.kind(), prefix[len + 1..].into(), &node.branches());
+                let branch = NodeRef::new_branch(
+                    NodeKind::Node4,
+                    prefix[..len].into(),
+                    &[
+                        (prefix[len], copy.as_ptr()),
+                        (key[depth + len], leaf.as_ptr()),
+                    ],
+                );
+                slot.replace(Some(branch));
+                header.lock.unlock_obsolete();
+                slot.lock.unlock();
+                unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                return Some(Ok(true));
+            }
+            depth += prefix.len();
+
+            let Some(&byte) = key.get(depth) else {
+                header.lock.check(version).then_some(())?;
+                return Some(Err(KeyPrefixError));
+            };
+            if let Some(ptr) = node.find(byte) {
+                slot = Slot {
+                    lock: &header.lock,
+                    version,
+                    ptr,
+                };
+                depth += 1;
+                continue;
+            }
+
+            if !node.is_full() {
+                header.lock.upgrade(version).then_some(())?;
+                node.insert(byte, leaf);
+                header.lock.unlock();
+                return Some(Ok(true));
+            }
+
+            // Replace the full node by a compacted or larger copy containing the leaf.
+            slot.lock().then_some(())?;
+            if !header.lock.upgrade(version) {
+                slot.lock.unlock();
+                return None;
+            }
+            let mut branches = node.branches();
+            branches.push((byte, leaf.as_ptr()));
+            let grown =
+                NodeRef::new_branch(NodeKind::for_len(branches.len()), prefix.clone(), &branches);
+            slot.replace(Some(grown));
+            header.lock.unlock_obsolete();
+            slot.lock.unlock();
+            unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+            return Some(Ok(true));
+        }
+    }
+
+    /// Remove a value, returns true if the key was present.
+    ///
+    /// The removed value is dropped once no reader can observe it.
+    pub fn remove(&self, key: &K) -> bool {
+        let guard = pin();
+        let key = key_bytes(key);
+        loop {
+            if let Some(removed) = self.try_remove(&key, &guard) {
+                self.len.fetch_sub(removed as usize, Ordering::Relaxed);
+                return removed;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// A single attempt at removing the key, returns `None` if the attempt has to be restarted.
+    fn try_remove(&self, key: &[u8], guard: &Guard) -> Option<bool> {
+        let mut slot = self.root_slot()?;
+        // The slot of the node containing `slot` along with that node and the key of `slot`.
+        let mut parent: Option<(Slot, NodeRef, u8)> = None;
+        let mut depth = 0;
+        loop {
+            let Some(node) = slot.load()? else {
+                return Some(false);
+            };
+
+            if node.is_leaf() {
+                if *unsafe { node.as_leaf::<V>() }.key != *key {
+                    return Some(false);
+                }
+                let Some((parent_slot, owner, byte)) = parent else {
+                    slot.lock().then_some(())?;
+                    slot.replace(None);
+                    slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                    return Some(true);
+                };
+
+                let header = owner.header();
+                if header.len() == 2 {
+                    // Only a single branch remains, replace the owner by that branch.
+                    parent_slot.lock().then_some(())?;
+                    if !slot.lock() {
+                        parent_slot.lock.unlock();
+                        return None;
+                    }
+                    let (other_key, other) = owner
+                        .branches()
+                        .into_iter()
+                        .find(|&(k, _)| k != byte)
+                        .unwrap();
+                    let other = unsafe { NodeRef::new(other) }.unwrap();
+                    if other.is_leaf() {
+                        parent_slot.replace(Some(other));
+                    } else {
+                        // The prefix of the owner and the key of the branch move into the
+                        // prefix of the remaining node, which thus has to be copied.
+                        if lock_node(other).is_none() {
+                            slot.lock.unlock();
+                            parent_slot.lock.unlock();
+                            return None;
+                        }
+                        let other_header = other.header();
+                        let mut prefix = header.prefix.to_vec();
+                        prefix.push(other_key);
+                        prefix.extend_from_slice(&other_header.prefix);
+                        let copy = NodeRef::new_branch(
+                            other_header.kind(),
+                            prefix.into_boxed_slice(),
+                            &other.branches(),
+                        );
+                        parent_slot.replace(Some(copy));
+                        other_header.lock.unlock_obsolete();
+                        unsafe { guard.defer_unchecked(move || other.free::<V>()) };
+                    }
+                    header.lock.unlock_obsolete();
+                    parent_slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || owner.free::<V>()) };
+                } else if owner.should_shrink_after_remove() {
+                    parent_slot.lock().then_some(())?;
+                    if !slot.lock() {
+                        parent_slot.lock.unlock();
+                        return None;
+                    }
+                    let mut branches = owner.branches();
+                    branches.retain(|&(k, _)| k != byte);
+                    let shrunk = NodeRef::new_branch(
+                        NodeKind::for_len(branches.len()),
+                        header.prefix.clone(),
+                        &branches,
+                    );
+                    parent_slot.replace(Some(shrunk));
+                    header.lock.unlock_obsolete();
+                    parent_slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || owner.free::<V>()) };
+                } else {
+                    slot.lock().then_some(())?;
+                    owner.remove(byte);
+                    slot.lock.unlock();
+                }
+                unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                return Some(true);
+            }
+
+            let header = node.header();
+            let version = header.lock.read()?;
+            let prefix = &header.prefix;
+            if key.get(depth..depth + prefix.len()) != Some(&**prefix) {
+                return header.lock.check(version).then_some(false);
+            }
+            depth += prefix.len();
+            let Some(ptr) = key.get(depth).and_then(|&byte| node.find(byte)) else {
+                return header.lock.check(version).then_some(false);
+            };
+            parent = Some((slot, node, key[depth]));
+            slot = Slot {
+                lock: &header.lock,
+                version,
+                ptr,
+            };
+            depth += 1;
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> Default for OlcArt<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Key + ?Sized, V> Drop for OlcArt<K, V> {
+    fn drop(&mut self) {
+        let root = self.root.load(Ordering::Relaxed);
+        if let Some(root) = unsafe { NodeRef::new(root) } {
+            unsafe { root.free_tree::<V>() }
+        }
+    }
+}
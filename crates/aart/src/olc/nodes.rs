@@ -0,0 +1,428 @@
+//! The nodes shared by [`OlcArt`](crate::OlcArt) and [`RowexArt`](crate::RowexArt).
+//!
+//! They keep the kinds and shrink thresholds of the nodes of the art crate, but not their layout.
+//! Node4 and Node16 don't keep their keys sorted and, like Node48, never reuse a slot. A branch is
+//! added in the next unused slot and a removed branch leaves its slot empty, so a node can be full
+//! with fewer branches than slots. Such a node is replaced by a compacted copy, which sorts the
+//! branches. Node256 has a slot for every key.
+
+use super::lock::VersionLock;
+use crate::prim::sync::atomic::{AtomicPtr, AtomicU16, AtomicU8, Ordering};
+use std::ptr::{self, NonNull};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum NodeKind {
+    Leaf,
+    Node4,
+    Node16,
+    Node48,
+    Node256,
+}
+
+impl NodeKind {
+    /// Returns the smallest kind of branch node which can hold the amount of branches.
+    pub fn for_len(len: usize) -> Self {
+        match len {
+            0..=4 => NodeKind::Node4,
+            5..=16 => NodeKind::Node16,
+            17..=48 => NodeKind::Node48,
+            _ => NodeKind::Node256,
+        }
+    }
+}
+
+/// The header at the start of every node.
+///
+/// The kind and prefix of a node never change once it is part of the tree, a node which needs a
+/// different size or prefix is replaced by a copy. Only the branches of a node change in place
+/// while its lock is held.
+///
+/// Every change to the branches is a single atomic store which makes it visible, so a reader sees
+/// a consistent node even without validating its version. To that end the nodes with up to 48
+/// branches never reuse a slot: a new branch is put in the next unused slot and a removed branch
+/// leaves its slot empty, a node without unused slots is replaced by a compacted copy.
+#[repr(C)]
+pub struct NodeHeader {
+    kind: NodeKind,
+    pub lock: VersionLock,
+    len: AtomicU16,
+    /// The amount of slots which have been used.
+    used: AtomicU8,
+    pub prefix: Box<[u8]>,
+}
+
+impl NodeHeader {
+    fn new(kind: NodeKind, prefix: Box<[u8]>) -> Self {
+        NodeHeader {
+            kind,
+            lock: VersionLock::new(),
+            len: AtomicU16::new(0),
+            used: AtomicU8::new(0),
+            prefix,
+        }
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// Returns the amount of branches of the node.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire) as usize
+    }
+
+    fn set_len(&self, len: usize) {
+        self.len.store(len as u16, Ordering::Release)
+    }
+
+    fn used(&self) -> usize {
+        self.used.load(Ordering::Acquire) as usize
+    }
+
+    /// Take the next unused slot.
+    fn next_slot(&self) -> usize {
+        self.used.load(Ordering::Relaxed) as usize
+    }
+
+    /// Publish a branch stored in the next unused slot.
+    fn publish_slot(&self) {
+        self.used.fetch_add(1, Ordering::Release);
+        self.set_len(self.len() + 1);
+    }
+}
+
+/// A leaf storing the full key of its entry.
+///
+/// Leafs are never changed, replacing the value of a key replaces the leaf.
+#[repr(C)]
+pub struct Leaf<V> {
+    header: NodeHeader,
+    pub key: Box<[u8]>,
+    pub value: V,
+}
+
+/// A node with up to `N` branches.
+///
+/// Keys are not sorted, as moving branches around could make a reader miss a branch.
+#[repr(C)]
+pub struct SmallNode<const N: usize> {
+    header: NodeHeader,
+    keys: [AtomicU8; N],
+    ptr: [AtomicPtr<NodeHeader>; N],
+}
+
+pub type Node4 = SmallNode<4>;
+pub type Node16 = SmallNode<16>;
+
+/// A node with up to 48 branches.
+///
+/// The idx array contains the position of the branch for a key in the ptr array, or `u8::MAX` if
+/// the node has no branch for the key. Empty slots in the ptr array are null.
+#[repr(C)]
+pub struct Node48 {
+    header: NodeHeader,
+    idx: [AtomicU8; 256],
+    ptr: [AtomicPtr<NodeHeader>; 48],
+}
+
+/// A node with a slot for every possible branch.
+#[repr(C)]
+pub struct Node256 {
+    header: NodeHeader,
+    ptr: [AtomicPtr<NodeHeader>; 256],
+}
+
+fn null_slots<const N: usize>() -> [AtomicPtr<NodeHeader>; N] {
+    std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut()))
+}
+
+impl<const N: usize> SmallNode<N> {
+    fn new(kind: NodeKind, prefix: Box<[u8]>) -> Self {
+        SmallNode {
+            header: NodeHeader::new(kind, prefix),
+            keys: std::array::from_fn(|_| AtomicU8::new(0)),
+            ptr: null_slots(),
+        }
+    }
+
+    fn find(&self, key: u8) -> Option<&AtomicPtr<NodeHeader>> {
+        let used = self.header.used().min(N);
+        (0..used)
+            .filter(|&i| self.keys[i].load(Ordering::Acquire) == key)
+            .map(|i| &self.ptr[i])
+            .find(|p| !p.load(Ordering::Acquire).is_null())
+    }
+
+    fn insert(&self, key: u8, child: *mut NodeHeader) {
+        let at = self.header.next_slot();
+        self.keys[at].store(key, Ordering::Release);
+        self.ptr[at].store(child, Ordering::Release);
+        self.header.publish_slot();
+    }
+
+    fn remove(&self, key: u8) {
+        self.find(key)
+            .expect("node has no branch for the key")
+            .store(ptr::null_mut(), Ordering::Release);
+        self.header.set_len(self.header.len() - 1);
+    }
+
+    fn branches(&self) -> Vec<(u8, *mut NodeHeader)> {
+        let mut branches: Vec<_> = (0..self.header.used())
+            .map(|i| {
+                (
+                    self.keys[i].load(Ordering::Relaxed),
+                    self.ptr[i].load(Ordering::Relaxed),
+                )
+            })
+            .filter(|(_, p)| !p.is_null())
+            .collect();
+        branches.sort_unstable_by_key(|&(k, _)| k);
+        branches
+    }
+}
+
+impl Node48 {
+    fn new(prefix: Box<[u8]>) -> Self {
+        Node48 {
+            header: NodeHeader::new(NodeKind::Node48, prefix),
+            idx: std::array::from_fn(|_| AtomicU8::new(u8::MAX)),
+            ptr: null_slots(),
+        }
+    }
+
+    fn find(&self, key: u8) -> Option<&AtomicPtr<NodeHeader>> {
+        self.ptr
+            .get(self.idx[key as usize].load(Ordering::Acquire) as usize)
+            .filter(|p| !p.load(Ordering::Acquire).is_null())
+    }
+
+    fn insert(&self, key: u8, child: *mut NodeHeader) {
+        let at = self.header.next_slot();
+        self.ptr[at].store(child, Ordering::Release);
+        self.idx[key as usize].store(at as u8, Ordering::Release);
+        self.header.publish_slot();
+    }
+
+    fn remove(&self, key: u8) {
+        let at = self.idx[key as usize].load(Ordering::Relaxed);
+        self.idx[key as usize].store(u8::MAX, Ordering::Release);
+        self.ptr[at as usize].store(ptr::null_mut(), Ordering::Release);
+        self.header.set_len(self.header.len() - 1);
+    }
+
+    fn branches(&self) -> Vec<(u8, *mut NodeHeader)> {
+        (0..=u8::MAX)
+            .filter_map(|key| {
+                let at = self.idx[key as usize].load(Ordering::Relaxed);
+                let p = self.ptr.get(at as usize)?;
+                Some((key, p.load(Ordering::Relaxed)))
+            })
+            .collect()
+    }
+}
+
+impl Node256 {
+    fn new(prefix: Box<[u8]>) -> Self {
+        Node256 {
+            header: NodeHeader::new(NodeKind::Node256, prefix),
+            ptr: null_slots(),
+        }
+    }
+
+    fn find(&self, key: u8) -> Option<&AtomicPtr<NodeHeader>> {
+        let slot = &self.ptr[key as usize];
+        (!slot.load(Ordering::Acquire).is_null()).then_some(slot)
+    }
+
+    fn insert(&self, key: u8, child: *mut NodeHeader) {
+        self.ptr[key as usize].store(child, Ordering::Release);
+        self.header.set_len(self.header.len() + 1);
+    }
+
+    fn remove(&self, key: u8) {
+        self.ptr[key as usize].store(ptr::null_mut(), Ordering::Release);
+        self.header.set_len(self.header.len() - 1);
+    }
+
+    fn branches(&self) -> Vec<(u8, *mut NodeHeader)> {
+        (0..=u8::MAX)
+            .filter_map(|key| {
+                let p = self.ptr[key as usize].load(Ordering::Relaxed);
+                (!p.is_null()).then_some((key, p))
+            })
+            .collect()
+    }
+}
+
+/// A pointer to a node in the tree.
+///
+/// Nodes are only freed once no thread can hold a pointer to them anymore, so dereferencing a
+/// `NodeRef` is valid as long as the thread stays pinned in the epoch in which it loaded the
+/// pointer from a node it validated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct NodeRef(NonNull<NodeHeader>);
+
+impl NodeRef {
+    /// Create a reference from a pointer loaded from the tree.
+    ///
+    /// # Safety
+    /// The pointer must be null or point to a node which is not freed while the reference is used.
+    pub unsafe fn new(ptr: *mut NodeHeader) -> Option<Self> {
+        NonNull::new(ptr).map(NodeRef)
+    }
+
+    fn alloc<T>(node: T) -> Self {
+        NodeRef(NonNull::from(Box::leak(Box::new(node))).cast())
+    }
+
+    /// Allocate a new leaf.
+    pub fn new_leaf<V>(key: Box<[u8]>, value: V) -> Self {
+        Self::alloc(Leaf {
+            header: NodeHeader::new(NodeKind::Leaf, Box::default()),
+            key,
+            value,
+        })
+    }
+
+    /// Allocate a new branch node of the given kind with the branches.
+    pub fn new_branch(
+        kind: NodeKind,
+        prefix: Box<[u8]>,
+        branches: &[(u8, *mut NodeHeader)],
+    ) -> Self {
+        let node = match kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => Self::alloc(Node4::new(kind, prefix)),
+            NodeKind::Node16 => Self::alloc(Node16::new(kind, prefix)),
+            NodeKind::Node48 => Self::alloc(Node48::new(prefix)),
+            NodeKind::Node256 => Self::alloc(Node256::new(prefix)),
+        };
+        for &(key, child) in branches {
+            node.insert(key, NodeRef(NonNull::new(child).unwrap()));
+        }
+        node
+    }
+
+    pub fn as_ptr(self) -> *mut NodeHeader {
+        self.0.as_ptr()
+    }
+
+    pub fn header<'a>(self) -> &'a NodeHeader {
+        unsafe { self.0.as_ref() }
+    }
+
+    pub fn is_leaf(self) -> bool {
+        self.header().kind == NodeKind::Leaf
+    }
+
+    /// # Safety
+    /// The node must be a leaf with values of type `V`.
+    pub unsafe fn as_leaf<'a, V>(self) -> &'a Leaf<V> {
+        debug_assert!(self.is_leaf());
+        self.0.cast().as_ref()
+    }
+
+    fn cast<'a, T>(self) -> &'a T {
+        unsafe { self.0.cast().as_ref() }
+    }
+
+    /// Returns the slot of the branch for the key if the node has one.
+    ///
+    /// A branch is only found once its slot is published, so the result is always a branch the
+    /// node had at some point. An optimistic reader still has to validate the version of the node
+    /// afterwards to know the branch is current.
+    pub fn find<'a>(self, key: u8) -> Option<&'a AtomicPtr<NodeHeader>> {
+        match self.header().kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => self.cast::<Node4>().find(key),
+            NodeKind::Node16 => self.cast::<Node16>().find(key),
+            NodeKind::Node48 => self.cast::<Node48>().find(key),
+            NodeKind::Node256 => self.cast::<Node256>().find(key),
+        }
+    }
+
+    /// Returns whether the node has no unused slot left for a new branch.
+    pub fn is_full(self) -> bool {
+        let used = self.header().used();
+        match self.header().kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => used == 4,
+            NodeKind::Node16 => used == 16,
+            NodeKind::Node48 => used == 48,
+            NodeKind::Node256 => false,
+        }
+    }
+
+    /// Returns whether the node should be replaced by a smaller node after removing a branch.
+    pub fn should_shrink_after_remove(self) -> bool {
+        let len = self.header().len() - 1;
+        match self.header().kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => false,
+            NodeKind::Node16 => len < 5,
+            NodeKind::Node48 => len < 16,
+            NodeKind::Node256 => len < 48,
+        }
+    }
+
+    /// Insert a branch into a locked node which is not full and has no branch for the key.
+    pub fn insert(self, key: u8, child: NodeRef) {
+        match self.header().kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => self.cast::<Node4>().insert(key, child.as_ptr()),
+            NodeKind::Node16 => self.cast::<Node16>().insert(key, child.as_ptr()),
+            NodeKind::Node48 => self.cast::<Node48>().insert(key, child.as_ptr()),
+            NodeKind::Node256 => self.cast::<Node256>().insert(key, child.as_ptr()),
+        }
+    }
+
+    /// Remove the branch for the key from a locked node.
+    pub fn remove(self, key: u8) {
+        match self.header().kind {
+            NodeKind::Leaf => unreachable!("a leaf has no branches"),
+            NodeKind::Node4 => self.cast::<Node4>().remove(key),
+            NodeKind::Node16 => self.cast::<Node16>().remove(key),
+            NodeKind::Node48 => self.cast::<Node48>().remove(key),
+            NodeKind::Node256 => self.cast::<Node256>().remove(key),
+        }
+    }
+
+    /// Returns the branches of a locked node in key order.
+    pub fn branches(self) -> Vec<(u8, *mut NodeHeader)> {
+        match self.header().kind {
+            NodeKind::Leaf => Vec::new(),
+            NodeKind::Node4 => self.cast::<Node4>().branches(),
+            NodeKind::Node16 => self.cast::<Node16>().branches(),
+            NodeKind::Node48 => self.cast::<Node48>().branches(),
+            NodeKind::Node256 => self.cast::<Node256>().branches(),
+        }
+    }
+
+    /// Free the node without the nodes below it.
+    ///
+    /// # Safety
+    /// The node must not be reachable by any thread and its leafs must have values of type `V`.
+    pub unsafe fn free<V>(self) {
+        let ptr = self.0.as_ptr();
+        match self.header().kind {
+            NodeKind::Leaf => drop(Box::from_raw(ptr.cast::<Leaf<V>>())),
+            NodeKind::Node4 => drop(Box::from_raw(ptr.cast::<Node4>())),
+            NodeKind::Node16 => drop(Box::from_raw(ptr.cast::<Node16>())),
+            NodeKind::Node48 => drop(Box::from_raw(ptr.cast::<Node48>())),
+            NodeKind::Node256 => drop(Box::from_raw(ptr.cast::<Node256>())),
+        }
+    }
+
+    /// Free the node and all the nodes below it.
+    ///
+    /// # Safety
+    /// See [`NodeRef::free`].
+    pub unsafe fn free_tree<V>(self) {
+        for (_, child) in self.branches() {
+            NodeRef::new(child).unwrap().free_tree::<V>();
+        }
+        self.free::<V>();
+    }
+}
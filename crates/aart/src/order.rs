@@ -0,0 +1,49 @@
+//! Order statistics on trees.
+//!
+//! With the `order-statistics` feature every branch node keeps the amount of leafs below it,
+//! which allows finding the position of a key and the key at a position by walking a single path
+//! through the tree instead of iterating over all smaller keys. The counts are kept up to date by
+//! the path copying of modifications, so shared subtrees keep a single count.
+
+use crate::{
+    iter::key_bytes,
+    key::{BorrowedKey, Key},
+    value::ValueStorage,
+    Aart,
+};
+use std::ops::{Bound, RangeBounds};
+
+impl<K: Key + ?Sized, V, S> Aart<K, V, S> {
+    /// Returns the amount of keys in the tree which are smaller than the given key.
+    pub fn rank(&self, key: &K) -> usize {
+        self.inner.rank(&key_bytes(key), false)
+    }
+
+    /// Returns the amount of keys within the range.
+    pub fn range_count<'r, R>(&self, range: R) -> usize
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(x) => self.inner.rank(&key_bytes(*x), false),
+            Bound::Excluded(x) => self.inner.rank(&key_bytes(*x), true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(x) => self.inner.rank(&key_bytes(*x), true),
+            Bound::Excluded(x) => self.inner.rank(&key_bytes(*x), false),
+            Bound::Unbounded => self.len,
+        };
+        end.saturating_sub(start)
+    }
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V, S: ValueStorage<Value = V>> Aart<K, V, S> {
+    /// Returns the key and stored value at the given position in key order.
+    pub fn nth(&self, idx: usize) -> Option<(K::Owned, &V)> {
+        let (key, value) = self.inner.nth(idx)?;
+        let key = unsafe { K::from_key_bytes(&key) }.to_owned();
+        Some((key, value.get()))
+    }
+}
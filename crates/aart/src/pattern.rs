@@ -0,0 +1,41 @@
+//! Scanning for keys matching a glob pattern or a regular expression.
+
+use crate::{key::BorrowedKey, value::ValueStorage, Aart};
+use key_automata::{Automaton, Glob};
+
+#[cfg(feature = "regex")]
+pub use key_automata::BuildError;
+
+impl<V, S: ValueStorage<Value = V>> Aart<str, V, S> {
+    /// Returns the keys matching a glob pattern in key order, together with their values.
+    ///
+    /// Patterns follow the redis `KEYS` syntax: `*` matches any sequence of characters, `?` any
+    /// single character, `[abc]`, `[a-z]` and `[^abc]` a character from, or not from, a set and
+    /// `\` escapes the next character. Subtrees are skipped as soon as their prefix can no longer
+    /// match, so patterns starting with a literal only visit the keys with that prefix.
+    pub fn scan_glob(&self, pattern: &str) -> impl Iterator<Item = (String, &V)> {
+        self.scan(Glob::new(pattern))
+    }
+
+    /// Returns the keys fully matched by a regular expression in key order, together with their
+    /// values.
+    ///
+    /// The tree is walked with a DFA of the expression, skipping subtrees as soon as the DFA can
+    /// no longer reach a match.
+    #[cfg(feature = "regex")]
+    pub fn scan_regex(
+        &self,
+        pattern: &str,
+    ) -> Result<impl Iterator<Item = (String, &V)>, Box<BuildError>> {
+        Ok(self.scan(key_automata::Regex::new(pattern)?))
+    }
+
+    fn scan<A: Automaton<Match = ()>>(&self, automaton: A) -> impl Iterator<Item = (String, &V)> {
+        let mut search = self.inner.search(automaton);
+        std::iter::from_fn(move || {
+            let (key, value, ()) = search.next()?;
+            let key = unsafe { <str as BorrowedKey>::from_key_bytes(key) }.to_owned();
+            Some((key, value.get()))
+        })
+    }
+}
@@ -1,8 +1,9 @@
 #[cfg(crossbeam_loom)]
 mod loom {
     #[cfg(test)]
+    #[allow(unused_imports)]
     pub use loom::thread;
-    pub use loom::{alloc, sync};
+    pub use loom::{alloc, hint, sync};
 }
 
 #[cfg(crossbeam_loom)]
@@ -11,8 +12,9 @@ pub use loom::*;
 #[cfg(not(crossbeam_loom))]
 mod std {
     #[cfg(test)]
+    #[allow(unused_imports)]
     pub use std::thread;
-    pub use std::{alloc, sync};
+    pub use std::{alloc, hint, sync};
 }
 #[cfg(not(crossbeam_loom))]
 pub use std::*;
@@ -0,0 +1,156 @@
+//! Finding the changes between two versions of a tree.
+//!
+//! Versions of a tree created by path copying share every node which did not change between them.
+//! Shared subtrees are skipped without looking at their keys, so the cost of a diff depends on the
+//! size of the change and not on the size of the trees.
+
+use super::{
+    merge::{zip_children, Side},
+    nodes::NodeLeaf,
+    RawAart,
+};
+use crate::key::KeyBytes;
+use std::sync::Arc;
+
+impl<K: KeyBytes + ?Sized, V> RawAart<K, Arc<V>> {
+    /// Returns an iterator over the keys which differ between the trees in key order.
+    ///
+    /// Values are compared by identity, not by their contents.
+    pub(crate) fn diff<'a>(&'a self, new: &'a Self) -> RawDiff<'a, K, V> {
+        RawDiff::new(self.root().map(Side::new), new.root().map(Side::new))
+    }
+
+    /// Returns whether a key starting with `prefix` differs between the trees.
+    pub(crate) fn prefix_changed(&self, new: &Self, prefix: &[u8]) -> bool {
+        // The node can be copied without its keys changing, e.g. when a node above it is split.
+        RawDiff::new(self.prefix_node(prefix), new.prefix_node(prefix))
+            .next()
+            .is_some()
+    }
+}
+
+/// The values of a key which differs between two trees.
+pub(crate) enum RawChange<'a, V> {
+    Added(&'a Arc<V>),
+    Removed(&'a Arc<V>),
+    Modified(&'a Arc<V>, &'a Arc<V>),
+}
+
+/// A pair of nodes to compare, below the first `base` bytes of the key and the branch key.
+struct Pending<'a, K: KeyBytes + ?Sized, V> {
+    base: usize,
+    branch: Option<u8>,
+    old: Option<Side<'a, K, Arc<V>>>,
+    new: Option<Side<'a, K, Arc<V>>>,
+}
+
+/// An iterator over the changes between two trees in key order.
+///
+/// Keeps an explicit stack of the node pairs which still have to be compared, the children of a
+/// pair are pushed in reverse order so they are compared in key order.
+pub(crate) struct RawDiff<'a, K: KeyBytes + ?Sized, V> {
+    key: Vec<u8>,
+    stack: Vec<Pending<'a, K, V>>,
+}
+
+impl<'a, K: KeyBytes + ?Sized, V> RawDiff<'a, K, V> {
+    pub(super) fn new(old: Option<Side<'a, K, Arc<V>>>, new: Option<Side<'a, K, Arc<V>>>) -> Self {
+        RawDiff {
+            key: Vec::new(),
+            stack: vec![Pending {
+                base: 0,
+                branch: None,
+                old,
+                new,
+            }],
+        }
+    }
+
+    /// Returns the key bytes and values of the next key which differs between the trees.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&[u8], RawChange<'a, V>)> {
+        loop {
+            let Pending {
+                base,
+                branch,
+                old,
+                new,
+            } = self.stack.pop()?;
+            self.key.truncate(base);
+            self.key.extend(branch);
+
+            let change = match (old, new) {
+                (None, None) => None,
+                (Some(x), None) => self.one_side(x, false).map(RawChange::Removed),
+                (None, Some(x)) => self.one_side(x, true).map(RawChange::Added),
+                (Some(old), Some(new)) => self.compare(old, new),
+            };
+            if let Some(change) = change {
+                return Some((&self.key, change));
+            }
+        }
+    }
+
+    /// Returns the value if the node is a leaf, otherwise pushes its children to be compared
+    /// against nothing. The node is only present in the new tree if `added`, else in the old one.
+    fn one_side(&mut self, side: Side<'a, K, Arc<V>>, added: bool) -> Option<&'a Arc<V>> {
+        let prefix = side.prefix();
+        self.key.extend_from_slice(prefix);
+        if let Some(leaf) = side.node.cast::<NodeLeaf<K, Arc<V>>>() {
+            return Some(&leaf.value);
+        }
+
+        let base = self.key.len();
+        for (branch, child) in side.children(prefix.len()).into_iter().rev() {
+            let (old, new) = match added {
+                true => (None, Some(child)),
+                false => (Some(child), None),
+            };
+            self.stack.push(Pending {
+                base,
+                branch: Some(branch),
+                old,
+                new,
+            });
+        }
+        None
+    }
+
+    /// Returns the change if both nodes are leafs of the same key, otherwise pushes the pairs of
+    /// their children to be compared.
+    fn compare(
+        &mut self,
+        old: Side<'a, K, Arc<V>>,
+        new: Side<'a, K, Arc<V>>,
+    ) -> Option<RawChange<'a, V>> {
+        if old.node.as_ptr() == new.node.as_ptr() && old.skip == new.skip {
+            // The subtree is shared by both trees.
+            return None;
+        }
+
+        let (pa, pb) = (old.prefix(), new.prefix());
+        let common = pa.iter().zip(pb).take_while(|(x, y)| x == y).count();
+        self.key.extend_from_slice(&pa[..common]);
+        if common == pa.len() && common == pb.len() {
+            let leafs = (
+                old.node.cast::<NodeLeaf<K, Arc<V>>>(),
+                new.node.cast::<NodeLeaf<K, Arc<V>>>(),
+            );
+            if let (Some(x), Some(y)) = leafs {
+                return (!Arc::ptr_eq(&x.value, &y.value))
+                    .then_some(RawChange::Modified(&x.value, &y.value));
+            }
+        }
+
+        let base = self.key.len();
+        for (branch, old, new) in zip_children(old, new, common).into_iter().rev() {
+            self.stack.push(Pending {
+                base,
+                branch: Some(branch),
+                old,
+                new,
+            });
+        }
+        None
+    }
+}
@@ -0,0 +1,229 @@
+//! Merging two trees by walking them together.
+//!
+//! Nodes are compared by their prefix, a subtree which is only present in one of the trees is
+//! shared with the new tree or dropped as a whole without looking at its keys. The same goes for
+//! a subtree which both trees share, e.g. when merging a tree with an earlier version of itself.
+
+use super::{
+    new_branch,
+    nodes::{NodeBox, NodeHeader, NodeLeaf, NodeRef},
+    RawAart,
+};
+use crate::key::{KeyBytes, KeyPrefixError};
+
+/// Which entries are kept when merging two trees.
+pub(crate) struct Merge<F> {
+    /// Keep the entries only present in the left tree.
+    pub left: bool,
+    /// Keep the entries only present in the right tree.
+    pub right: bool,
+    /// Keep the entries of a subtree shared by both trees, `both` is not called for them.
+    pub shared: bool,
+    /// Merge the values of a key present in both trees, the entry is dropped if this returns
+    /// `None`.
+    pub both: F,
+    /// The amount of keys found in both trees.
+    pub found: usize,
+    /// The amount of keys found in both trees which were kept.
+    pub kept: usize,
+}
+
+/// A node with the first `skip` bytes of its prefix already matched.
+pub(super) struct Side<'a, K: KeyBytes + ?Sized, V> {
+    pub node: NodeRef<'a, K, V>,
+    pub skip: usize,
+}
+
+impl<'a, K: KeyBytes + ?Sized, V> Side<'a, K, V> {
+    pub fn new(node: NodeRef<'a, K, V>) -> Self {
+        Side { node, skip: 0 }
+    }
+
+    pub fn prefix(&self) -> &'a [u8] {
+        &self.node.prefix()[self.skip..]
+    }
+
+    /// Returns the node as a subtree of the new tree, sharing it if its prefix is unchanged.
+    fn into_box(self) -> NodeBox<K, V>
+    where
+        V: Clone,
+    {
+        if self.skip == 0 {
+            self.node.to_box()
+        } else {
+            self.node.copy_drop_prefix(self.skip)
+        }
+    }
+
+    /// Returns the branches of the node as if its prefix ended after `at` bytes.
+    pub fn children(self, at: usize) -> Vec<(u8, Side<'a, K, V>)> {
+        let prefix = self.prefix();
+        if at < prefix.len() {
+            let side = Side {
+                node: self.node,
+                skip: self.skip + at + 1,
+            };
+            return vec![(prefix[at], side)];
+        }
+
+        assert!(!self.node.is::<NodeLeaf<K, V>>(), "{:?}", KeyPrefixError);
+        let mut res = Vec::new();
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, child)) = self.node.next_node(next as u8) else {
+                break;
+            };
+            res.push((key, Side::new(child)));
+            next = key as u16 + 1;
+        }
+        res
+    }
+}
+
+impl<K: KeyBytes + ?Sized, V: Clone> RawAart<K, V> {
+    /// Merge two trees into a new tree.
+    pub(crate) fn merge<F>(&self, other: &Self, merge: &mut Merge<F>) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        let a = self.root().map(Side::new);
+        let b = other.root().map(Side::new);
+        RawAart {
+            root: merge_node(a, b, merge),
+        }
+    }
+}
+
+fn merge_node<K, V, F>(
+    a: Option<Side<'_, K, V>>,
+    b: Option<Side<'_, K, V>>,
+    merge: &mut Merge<F>,
+) -> Option<NodeBox<K, V>>
+where
+    K: KeyBytes + ?Sized,
+    V: Clone,
+    F: FnMut(&V, &V) -> Option<V>,
+{
+    let (a, b) = match (a, b) {
+        (None, None) => return None,
+        (Some(a), None) => return merge.left.then(|| a.into_box()),
+        (None, Some(b)) => return merge.right.then(|| b.into_box()),
+        (Some(a), Some(b)) => (a, b),
+    };
+
+    if a.node.as_ptr() == b.node.as_ptr() && a.skip == b.skip {
+        let count = leaf_count(a.node);
+        merge.found += count;
+        if !merge.shared {
+            return None;
+        }
+        merge.kept += count;
+        return Some(a.into_box());
+    }
+
+    let (pa, pb) = (a.prefix(), b.prefix());
+    let common = pa.iter().zip(pb).take_while(|(x, y)| x == y).count();
+    let prefix = &pa[..common];
+    if common == pa.len() && common == pb.len() {
+        let leafs = (
+            a.node.cast::<NodeLeaf<K, V>>(),
+            b.node.cast::<NodeLeaf<K, V>>(),
+        );
+        if let (Some(x), Some(y)) = leafs {
+            merge.found += 1;
+            let value = (merge.both)(&x.value, &y.value)?;
+            merge.kept += 1;
+            return Some(x.with_value(prefix.len(), value));
+        }
+    }
+
+    // Both nodes now branch at the end of the common prefix.
+    let mut children = Vec::new();
+    for (key, a, b) in zip_children(a, b, common) {
+        if let Some(x) = merge_node(a, b, merge) {
+            children.push((key, x));
+        }
+    }
+
+    match children.len() {
+        0 => None,
+        // A branch with a single child is merged into that child.
+        1 => {
+            let (key, child) = children.pop().unwrap();
+            Some(child.as_ref().copy_prepend_prefix(prefix, key))
+        }
+        count => {
+            let header = |data| NodeHeader::from_prefix(prefix, data);
+            Some(new_branch(header, count, children.into_iter()))
+        }
+    }
+}
+
+/// Returns the amount of leafs below a node.
+fn leaf_count<K: KeyBytes + ?Sized, V>(node: NodeRef<'_, K, V>) -> usize {
+    #[cfg(feature = "order-statistics")]
+    return node.leaf_count();
+
+    // Without the counts kept in the branch nodes only the branch nodes have to be visited, the
+    // leafs are counted from the kinds of the children.
+    #[cfg(not(feature = "order-statistics"))]
+    {
+        let mut count = 0;
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if node.is::<NodeLeaf<K, V>>() {
+                count += 1;
+                continue;
+            }
+            let mut next = 0u16;
+            while next <= u8::MAX as u16 {
+                let Some((key, child)) = node.next_node(next as u8) else {
+                    break;
+                };
+                if child.is::<NodeLeaf<K, V>>() {
+                    count += 1;
+                } else {
+                    stack.push(child);
+                }
+                next = key as u16 + 1;
+            }
+        }
+        count
+    }
+}
+
+/// A branch key with the child of each node under that key.
+pub(super) type ChildPair<'a, K, V> = (u8, Option<Side<'a, K, V>>, Option<Side<'a, K, V>>);
+
+/// Returns the branches of both nodes in order as if their prefixes ended after `at` bytes,
+/// pairing the branches with the same key.
+pub(super) fn zip_children<'a, K, V>(
+    a: Side<'a, K, V>,
+    b: Side<'a, K, V>,
+    at: usize,
+) -> Vec<ChildPair<'a, K, V>>
+where
+    K: KeyBytes + ?Sized,
+{
+    let mut ca = a.children(at).into_iter().peekable();
+    let mut cb = b.children(at).into_iter().peekable();
+    let mut res = Vec::new();
+    loop {
+        let next = match (ca.peek(), cb.peek()) {
+            (None, None) => return res,
+            (Some(x), Some(y)) if x.0 == y.0 => {
+                let (key, a) = ca.next().unwrap();
+                (key, Some(a), cb.next().map(|x| x.1))
+            }
+            (Some(x), y) if y.is_none_or(|y| x.0 < y.0) => {
+                let (key, a) = ca.next().unwrap();
+                (key, Some(a), None)
+            }
+            _ => {
+                let (key, b) = cb.next().unwrap();
+                (key, None, Some(b))
+            }
+        };
+        res.push(next);
+    }
+}
@@ -1,9 +1,20 @@
 use crate::{key::KeyBytes, raw::nodes::Node4};
+use bytemuck::Zeroable;
+use std::ops::Range;
 
+mod automaton;
+mod diff;
+mod merge;
 pub mod nodes;
-use nodes::{NodeBox, NodeLeaf};
+#[cfg(feature = "order-statistics")]
+mod order;
+mod prefix;
+use nodes::{Node16, Node256, Node48, NodeBox, NodeKind, NodeLeaf};
 pub mod root;
 
+pub(crate) use diff::{RawChange, RawDiff};
+pub(crate) use merge::Merge;
+
 #[cfg(test)]
 mod test;
 
@@ -21,86 +32,123 @@ impl<K: KeyBytes + ?Sized, V> RawAart<K, V> {
         Self { root: None }
     }
 
+    /// Returns the leaf of a key.
+    ///
+    /// The prefixes of branch nodes are skipped without comparing them, the full key stored in the
+    /// leaf at the end of the path is compared with the key instead.
     pub fn get(&self, b: &K) -> Option<&NodeLeaf<K, V>> {
-        let root = self.root.as_ref()?;
+        let mut node = self.root()?;
+        let mut depth = 0;
+        loop {
+            if let Some(leaf) = node.cast::<NodeLeaf<K, V>>() {
+                return leaf.matches(b).then_some(leaf);
+            }
+            depth += node.prefix().len();
+            node = node.get(b.at(depth)?)?;
+            depth += 1;
+        }
+    }
 
-        let common_prefix_len = b.common_prefix_length(root.prefix()).unwrap();
-        if common_prefix_len == b.len() {
-            return Some(root.as_ref().cast::<NodeLeaf<K, V>>().unwrap());
+    /// Build a tree bottom-up from keys sorted in ascending byte order.
+    ///
+    /// `values` must yield the value for each key in the same order as the keys.
+    pub fn from_sorted(keys: &[&K], values: &mut impl Iterator<Item = V>) -> Self {
+        if keys.is_empty() {
+            return Self::new();
         }
+        Self {
+            root: Some(build_node(keys, values, 0)),
+        }
+    }
+
+    /// Create a tree from its root node.
+    pub fn from_root(root: Option<NodeBox<K, V>>) -> Self {
+        Self { root }
+    }
+
+    /// Returns the root node of the tree, consuming the tree.
+    pub fn into_root(self) -> Option<NodeBox<K, V>> {
+        self.root
+    }
 
-        unsafe { get_node(root.as_ref(), b) }
+    /// Returns the root node of the tree.
+    pub fn root(&self) -> Option<NodeRef<'_, K, V>> {
+        self.root.as_ref().map(|x| x.as_ref())
     }
 
-    pub fn insert(&mut self, b: &K, value: V) {
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    pub fn insert(&mut self, b: &K, value: V) -> Option<V>
+    where
+        V: Clone,
+    {
         let Some(root) = self.root.as_ref() else {
-            self.root = Some(NodeBox::new(NodeLeaf::new(b, b.len(), value)));
-            return;
+            self.root = Some(NodeLeaf::new_box(b, 0, value));
+            return None;
         };
 
-        let leaf = NodeBox::new(NodeLeaf::new(b, 0, value));
-        self.root = Some(unsafe { insert_node(root.as_ref(), b, leaf) });
+        let (new, old) = unsafe { insert_node(root.as_ref(), b, 0, value) };
+        self.root = Some(new);
+        old
     }
-}
 
-unsafe fn get_node<'a, K, V>(node: NodeRef<'a, K, V>, k: &K) -> Option<&'a NodeLeaf<K, V>>
-where
-    K: KeyBytes + ?Sized,
-{
-    let common_len = k.common_prefix_length(node.prefix()).unwrap();
-    if common_len == k.len() {
-        // exact match, return the leaf node.
-        assert_eq!(k.len(), node.prefix().len());
-        assert!(node.is::<NodeLeaf<_, _>>());
-        return Some(node.cast_unchecked());
+    /// Remove a value, returns the removed value if the key was present.
+    pub fn remove(&mut self, b: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        let root = self.root.as_ref()?;
+        let (new, value) = unsafe { remove_node(root.as_ref(), b)? };
+        self.root = new;
+        Some(value)
     }
+}
 
-    if common_len != node.prefix().len() {
-        // diverges in prefix, node not in tree
-        return None;
+impl<K: KeyBytes + ?Sized, V> Clone for RawAart<K, V> {
+    /// Returns a copy of the tree, this only increments the reference count of the root.
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
     }
-
-    let branch_key = k.at(common_len).unwrap();
-    let next = node.get(branch_key)?;
-    get_node(next, k.drop_prefix(common_len + 1))
 }
 
-unsafe fn insert_node<K, V>(root: NodeRef<K, V>, b: &K, leaf: NodeBox<K, V>) -> NodeBox<K, V>
+/// The node replacing a node a key was inserted into, together with the replaced value.
+type Inserted<K, V> = (NodeBox<K, V>, Option<V>);
+
+/// Insert the value into the node at `depth` in the full key, returning the node which should
+/// replace it together with the value previously stored under the key.
+unsafe fn insert_node<K, V>(root: NodeRef<K, V>, full: &K, depth: usize, value: V) -> Inserted<K, V>
 where
     K: KeyBytes + ?Sized,
+    V: Clone,
 {
-    debug_assert!(leaf.as_ref().is::<NodeLeaf<_, _>>());
     let curr = root;
+    let b = full.drop_prefix(depth);
     let pref_common_len = b.common_prefix_length(curr.prefix()).unwrap();
     if pref_common_len == b.len() {
         // exact match, return the leaf node.
         assert_eq!(b.len(), curr.prefix().len());
-        assert!(curr.is::<NodeLeaf<_, _>>());
-        unsafe {
-            leaf.as_ref()
-                .as_ptr()
-                .replace(NodeHeader::new(b, b.len(), NodeHeaderData::leaf()));
-        }
-        return leaf;
+        let old = curr.cast::<NodeLeaf<K, V>>().unwrap().value.clone();
+        return (NodeLeaf::new_box(full, depth, value), Some(old));
     }
 
     if pref_common_len == curr.prefix().len() {
         // prefixed matched uses remaining key to insert node.
         let key = b.at(pref_common_len).unwrap();
-        let new_b = b.drop_prefix(pref_common_len + 1);
-
-        if let Some(x) = curr.get(key) {
-            let branch = insert_node(x, new_b, leaf);
-            return copy_insert(curr, key, branch);
-        } else {
-            unsafe {
-                leaf.as_ref().as_ptr().replace(NodeHeader::new(
-                    new_b,
-                    new_b.len(),
-                    NodeHeaderData::leaf(),
-                ));
+
+        match curr.get(key) {
+            Some(x) if pref_common_len + 1 < b.len() => {
+                let (branch, old) = insert_node(x, full, depth + pref_common_len + 1, value);
+                return (copy_insert(curr, key, branch), old);
+            }
+            next => {
+                // The key ends at the branch, so a node under it is the leaf of the key.
+                let old = next
+                    .and_then(|x| x.cast::<NodeLeaf<K, V>>())
+                    .map(|x| x.value.clone());
+                let leaf = NodeLeaf::new_box(full, depth + pref_common_len + 1, value);
+                return (copy_insert(curr, key, leaf), old);
             }
-            return copy_insert(curr, key, leaf);
         }
     }
 
@@ -109,23 +157,199 @@ where
     assert!(pref_common_len < curr.prefix().len());
 
     let new_key = b.at(pref_common_len).unwrap();
-    let new_b = b.drop_prefix(pref_common_len + 1);
-
     let old_key = curr.prefix()[pref_common_len];
 
-    unsafe {
-        leaf.as_ref()
-            .as_ptr()
-            .replace(NodeHeader::new(new_b, new_b.len(), NodeHeaderData::leaf()));
+    let leaf = NodeLeaf::new_box(full, depth + pref_common_len + 1, value);
+    let old_node = curr.copy_drop_prefix(pref_common_len + 1);
+    #[allow(unused_mut)]
+    let mut node = Node4::new_split(b, pref_common_len, (new_key, leaf), (old_key, old_node));
+    #[cfg(feature = "order-statistics")]
+    {
+        node.header.leafs = curr.leaf_count() + 1;
+    }
+    (NodeBox::new(node), None)
+}
+
+/// The node replacing a node a key was removed from, together with the removed value.
+type Removed<K, V> = (Option<NodeBox<K, V>>, V);
+
+/// Remove the key from the node, returning the node which should replace it or `None` if the node
+/// should be removed entirely, together with the removed value.
+///
+/// Returns `None` if the key was not present, in which case the tree remains unchanged.
+unsafe fn remove_node<K, V>(node: NodeRef<K, V>, k: &K) -> Option<Removed<K, V>>
+where
+    K: KeyBytes + ?Sized,
+    V: Clone,
+{
+    let common_len = k.common_prefix_length(node.prefix()).ok()?;
+    if common_len == k.len() {
+        let leaf = node.cast::<NodeLeaf<K, V>>()?;
+        return Some((None, leaf.value.clone()));
     }
 
-    let old_node = curr.copy_drop_prefix(pref_common_len + 1);
-    NodeBox::new(Node4::new_split(
-        b,
-        pref_common_len,
-        (new_key, leaf),
-        (old_key, old_node),
-    ))
+    if common_len != node.prefix().len() || node.is::<NodeLeaf<_, _>>() {
+        return None;
+    }
+
+    let branch_key = k.at(common_len).unwrap();
+    let next = node.get(branch_key)?;
+    let (new, value) = if common_len + 1 == k.len() {
+        // key is consumed by the branch, only a leaf without a prefix can match.
+        let leaf = next
+            .cast::<NodeLeaf<_, _>>()
+            .filter(|x| x.prefix().is_empty())?;
+        (None, leaf.value.clone())
+    } else {
+        remove_node(next, k.drop_prefix(common_len + 1))?
+    };
+
+    let new = match new {
+        Some(x) => node.copy_insert(branch_key, x),
+        None => node.copy_remove(branch_key).unwrap(),
+    };
+    Some((Some(new), value))
+}
+
+/// Create a header with the given range of the key as its prefix.
+fn prefix_header<K, V>(key: &K, range: Range<usize>, data: NodeHeaderData) -> NodeHeader<K, V>
+where
+    K: KeyBytes + ?Sized,
+{
+    if range.is_empty() {
+        // The key might not be able to represent a view starting at its end.
+        return NodeHeader::new(key, 0, data);
+    }
+    NodeHeader::new(key.drop_prefix(range.start), range.len(), data)
+}
+
+/// Build the node containing all the given keys, all of which share the first `depth` bytes.
+///
+/// The common prefix of the keys is computed once and each branch node is allocated at the size
+/// required for the amount of branches it has.
+fn build_node<K, V>(
+    keys: &[&K],
+    values: &mut impl Iterator<Item = V>,
+    depth: usize,
+) -> NodeBox<K, V>
+where
+    K: KeyBytes + ?Sized,
+{
+    let first = keys[0];
+    if keys.len() == 1 {
+        let value = values.next().expect("missing value for key");
+        return NodeLeaf::new_box(first, depth, value);
+    }
+
+    let mut split = first.len();
+    for k in &keys[1..] {
+        let end = split.min(k.len());
+        split = (depth..end)
+            .find(|&i| first.at(i) != k.at(i))
+            .unwrap_or(end);
+        assert!(
+            split < k.len() && split < first.len(),
+            "sorted input contained a duplicate key"
+        );
+    }
+
+    let range = depth..split;
+    let count = branches(keys, split).count();
+    let mut last = None;
+    let children = branches(keys, split).map(|(branch, run)| {
+        assert!(
+            last.map(|x| x < branch).unwrap_or(true),
+            "input keys were not sorted"
+        );
+        last = Some(branch);
+        (branch, build_node(run, values, split + 1))
+    });
+
+    let header = |data| prefix_header(first, range, data);
+    new_branch(header, count, children)
+}
+
+/// Create a branch node of the smallest kind which fits `count` branches.
+///
+/// `header` is called with the node data of the created node.
+fn new_branch<K, V>(
+    header: impl FnOnce(NodeHeaderData) -> NodeHeader<K, V>,
+    count: usize,
+    children: impl Iterator<Item = (u8, NodeBox<K, V>)>,
+) -> NodeBox<K, V>
+where
+    K: KeyBytes + ?Sized,
+{
+    let node = match count {
+        0..=4 => {
+            let data = NodeHeaderData::new(count as u8, NodeKind::Node4, 0);
+            let header = header(data);
+            let mut ptr = <[Option<NodeBox<K, V>>; 4] as Zeroable>::zeroed();
+            let mut keys = [0u8; 4];
+            for (idx, (k, c)) in children.enumerate() {
+                keys[idx] = k;
+                ptr[idx] = Some(c);
+            }
+            NodeBox::new(Node4 { header, ptr, keys })
+        }
+        5..=16 => {
+            let data = NodeHeaderData::new(count as u8, NodeKind::Node16, 0);
+            let header = header(data);
+            let mut ptr = <[Option<NodeBox<K, V>>; 16] as Zeroable>::zeroed();
+            let mut keys = [0u8; 16];
+            for (idx, (k, c)) in children.enumerate() {
+                keys[idx] = k;
+                ptr[idx] = Some(c);
+            }
+            NodeBox::new(Node16 { header, ptr, keys })
+        }
+        17..=48 => {
+            let data = NodeHeaderData::new(count as u8, NodeKind::Node48, 0);
+            let header = header(data);
+            let mut ptr = <[Option<NodeBox<K, V>>; 48] as Zeroable>::zeroed();
+            let mut idxs = [u8::MAX; 256];
+            for (idx, (k, c)) in children.enumerate() {
+                idxs[k as usize] = idx as u8;
+                ptr[idx] = Some(c);
+            }
+            NodeBox::new(Node48 { header, ptr, idxs })
+        }
+        _ => {
+            // HACK: In order to fit the full capacity of node256 into a single byte we subtract 1
+            // from the length when we store it.
+            let data = NodeHeaderData::new((count - 1) as u8, NodeKind::Node256, 0);
+            let header = header(data);
+            let mut ptr = <[Option<NodeBox<K, V>>; 256] as Zeroable>::zeroed();
+            for (k, c) in children {
+                ptr[k as usize] = Some(c);
+            }
+            NodeBox::new(Node256 { header, ptr })
+        }
+    };
+    #[cfg(feature = "order-statistics")]
+    {
+        // The node is not yet shared so it is fine to mutate it.
+        unsafe { (*node.as_ptr()).leafs = order::child_leafs(node.as_ref()) };
+    }
+    node
+}
+
+/// Returns the runs of keys which have the same byte at the given offset.
+fn branches<'k, 'a, K>(keys: &'k [&'a K], at: usize) -> impl Iterator<Item = (u8, &'k [&'a K])> + 'k
+where
+    K: KeyBytes + ?Sized,
+{
+    let mut rest = keys;
+    std::iter::from_fn(move || {
+        let branch = rest.first()?.at(at).unwrap();
+        let len = rest
+            .iter()
+            .position(|k| k.at(at) != Some(branch))
+            .unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(len);
+        rest = tail;
+        Some((branch, run))
+    })
 }
 
 fn copy_insert<K, V>(target: NodeRef<K, V>, key: u8, node: NodeBox<K, V>) -> NodeBox<K, V>
@@ -1,13 +1,29 @@
-use std::sync::Arc;
+use crate::{
+    key::KeyBytes,
+    prim::{
+        alloc::{self, Layout},
+        sync::atomic::AtomicUsize,
+    },
+};
+use std::{marker::PhantomData, ptr::NonNull};
 
-use crate::key::KeyBytes;
-
-use super::{Node, NodeBox, NodeHeader, NodeHeaderData, NodeKind};
+use super::{Node, NodeBox, NodeHeader, NodeKind};
 
+/// A leaf node storing the full key of its entry.
+///
+/// Leafs have no node header, the pointer to a leaf is tagged instead, see [`NodeBox`]. The bytes
+/// of the key directly follow the leaf in its allocation, so a leaf takes a single allocation
+/// however long its key is. The prefix of the leaf is the part of the key below its parent, so a
+/// leaf moving up or down the tree only changes where its prefix starts. Lookups compare the
+/// searched key with the full key once at the leaf.
 #[repr(C)]
 pub struct NodeLeaf<K: KeyBytes + ?Sized, V> {
-    pub(crate) header: NodeHeader<K, V>,
-    pub(crate) value: Arc<V>,
+    pub(crate) ref_count: AtomicUsize,
+    len: u32,
+    /// The offset in the key where the prefix of the leaf starts.
+    depth: u32,
+    pub(crate) value: V,
+    _marker: PhantomData<K>,
 }
 
 unsafe impl<K: KeyBytes + ?Sized, V> Node for NodeLeaf<K, V> {
@@ -16,21 +32,106 @@ unsafe impl<K: KeyBytes + ?Sized, V> Node for NodeLeaf<K, V> {
     type Key = K;
 
     type Value = V;
+
+    fn header(&self) -> &NodeHeader<K, V> {
+        unreachable!("leafs have no node header")
+    }
 }
 
 impl<K: KeyBytes + ?Sized, V> NodeLeaf<K, V> {
-    pub fn new(key: &K, until: usize, value: V) -> Self {
-        let header = NodeHeader::new(key, until, NodeHeaderData::leaf());
-        NodeLeaf {
-            header,
-            value: Arc::new(value),
+    /// Create a leaf for the full key, with the key from `depth` onwards as its prefix.
+    pub fn new_box(key: &K, depth: usize, value: V) -> NodeBox<K, V> {
+        Self::alloc(key.len(), depth, value, |dst| {
+            for (i, x) in dst.iter_mut().enumerate() {
+                *x = key.at(i).unwrap();
+            }
+        })
+    }
+
+    /// Returns the layout of a leaf with a key of the given length, and the offset of the key.
+    fn layout(len: usize) -> (Layout, usize) {
+        let (layout, offset) = Layout::new::<Self>()
+            .extend(Layout::array::<u8>(len).unwrap())
+            .unwrap();
+        (layout.pad_to_align(), offset)
+    }
+
+    fn alloc(len: usize, depth: usize, value: V, init: impl FnOnce(&mut [u8])) -> NodeBox<K, V> {
+        assert!(len <= u32::MAX as usize, "key too long");
+        assert!(depth <= len);
+        let (layout, offset) = Self::layout(len);
+        unsafe {
+            let ptr = NonNull::new(alloc::alloc(layout)).unwrap();
+            ptr.cast::<Self>().as_ptr().write(NodeLeaf {
+                ref_count: AtomicUsize::new(1),
+                len: len as u32,
+                depth: depth as u32,
+                value,
+                _marker: PhantomData,
+            });
+            init(std::slice::from_raw_parts_mut(
+                ptr.as_ptr().add(offset),
+                len,
+            ));
+            NodeBox::from_leaf(ptr.cast())
         }
     }
 
-    pub fn copy_drop_prefix(&self, drop: usize) -> NodeBox<K, V> {
-        NodeBox::new(NodeLeaf {
-            header: self.header.copy_drop_prefix(drop),
-            value: self.value.clone(),
+    /// Drop the leaf and free its allocation.
+    ///
+    /// # Safety
+    /// The pointer must point to a leaf which is no longer referenced.
+    pub(crate) unsafe fn drop_in_place(ptr: NonNull<Self>) {
+        let (layout, _) = Self::layout(ptr.as_ref().len as usize);
+        std::ptr::drop_in_place(ptr.as_ptr());
+        alloc::dealloc(ptr.as_ptr().cast(), layout)
+    }
+
+    /// Returns the full key of the leaf.
+    pub fn key(&self) -> &[u8] {
+        unsafe {
+            let ptr = (self as *const Self).add(1).cast::<u8>();
+            std::slice::from_raw_parts(ptr, self.len as usize)
+        }
+    }
+
+    /// Returns the part of the key below the parent of the leaf.
+    pub fn prefix(&self) -> &[u8] {
+        &self.key()[self.depth as usize..]
+    }
+
+    /// Returns whether the leaf is the leaf of the given full key.
+    pub fn matches(&self, key: &K) -> bool {
+        key.len() == self.len as usize
+            && key.common_prefix_length(self.key()).ok() == Some(key.len())
+    }
+
+    /// Copy the leaf with the same key and a prefix of the given length, but a different value.
+    pub fn with_value(&self, prefix_len: usize, value: V) -> NodeBox<K, V> {
+        let key = self.key();
+        Self::alloc(key.len(), key.len() - prefix_len, value, |dst| {
+            dst.copy_from_slice(key)
         })
     }
+
+    pub fn copy_drop_prefix(&self, drop: usize) -> NodeBox<K, V>
+    where
+        V: Clone,
+    {
+        self.with_value(self.prefix().len() - drop, self.value.clone())
+    }
+
+    /// Copy the leaf with the prefix followed by the key prepended to its prefix.
+    ///
+    /// These bytes are already part of the full key, so only where the prefix starts changes.
+    pub fn copy_prepend_prefix(&self, prefix: &[u8], key: u8) -> NodeBox<K, V>
+    where
+        V: Clone,
+    {
+        let len = self.prefix().len() + prefix.len() + 1;
+        let start = self.key().len() - len;
+        debug_assert!(self.key()[start..].starts_with(prefix));
+        debug_assert_eq!(self.key()[start + prefix.len()], key);
+        self.with_value(len, self.value.clone())
+    }
 }
@@ -14,6 +14,7 @@ mod node48;
 mod ptr;
 pub use leaf::NodeLeaf;
 pub use node16::Node16;
+pub use node256::Node256;
 pub use node4::Node4;
 pub use node48::Node48;
 pub use ptr::*;
@@ -27,14 +28,6 @@ pub struct NodeHeaderData {
 }
 
 impl NodeHeaderData {
-    pub fn leaf() -> Self {
-        NodeHeaderData {
-            len: 0,
-            kind: NodeKind::Leaf as u8,
-            free: 0,
-        }
-    }
-
     pub fn new(len: u8, kind: NodeKind, free: u8) -> Self {
         NodeHeaderData {
             len,
@@ -78,6 +71,9 @@ impl NodeKind {
 pub struct NodeHeader<K: KeyBytes + ?Sized, V> {
     pub(crate) ref_count: AtomicUsize,
     storage: K::Storage,
+    /// The amount of leafs below the node, only maintained for branch nodes.
+    #[cfg(feature = "order-statistics")]
+    pub(crate) leafs: usize,
     _marker: PhantomData<V>,
 }
 
@@ -88,6 +84,8 @@ impl<K: KeyBytes + ?Sized, V> NodeHeader<K, V> {
         NodeHeader {
             ref_count,
             storage,
+            #[cfg(feature = "order-statistics")]
+            leafs: 0,
             _marker: PhantomData,
         }
     }
@@ -96,6 +94,18 @@ impl<K: KeyBytes + ?Sized, V> NodeHeader<K, V> {
         Self {
             ref_count: AtomicUsize::new(1),
             storage: K::Storage::new_from(&existing.storage, bytemuck::cast(data)),
+            #[cfg(feature = "order-statistics")]
+            leafs: existing.leafs,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn from_prefix(prefix: &[u8], data: NodeHeaderData) -> Self {
+        Self {
+            ref_count: AtomicUsize::new(1),
+            storage: K::Storage::from_prefix(prefix, bytemuck::cast(data)),
+            #[cfg(feature = "order-statistics")]
+            leafs: 0,
             _marker: PhantomData,
         }
     }
@@ -104,6 +114,8 @@ impl<K: KeyBytes + ?Sized, V> NodeHeader<K, V> {
         Self {
             ref_count: AtomicUsize::new(1),
             storage: self.storage.copy_drop_prefix(until),
+            #[cfg(feature = "order-statistics")]
+            leafs: self.leafs,
             _marker: PhantomData,
         }
     }
@@ -119,6 +131,11 @@ impl<K: KeyBytes + ?Sized, V> NodeHeader<K, V> {
     pub fn prefix(&self) -> &[u8] {
         self.storage.prefix()
     }
+
+    /// Prepend the prefix followed by the key to the current prefix.
+    pub fn prepend_prefix(&mut self, prefix: &[u8], key: u8) {
+        self.storage.prepend_prefix(prefix, key)
+    }
 }
 
 /// A trait implemented by ART node types.
@@ -1,4 +1,4 @@
-use std::{cmp::Ordering, usize};
+use std::cmp::Ordering;
 
 use bytemuck::Zeroable;
 
@@ -30,7 +30,7 @@ impl<K: KeyBytes + ?Sized, V> Node16<K, V> {
         self.header.data().len == 5
     }
 
-    pub fn get(&self, key: u8) -> Option<NodeRef<K, V>> {
+    pub fn get(&self, key: u8) -> Option<NodeRef<'_, K, V>> {
         let position = self.keys.iter().copied().position(|x| x == key)?;
         self.ptr[position].as_ref().map(|x| x.as_ref())
     }
@@ -46,6 +46,20 @@ impl<K: KeyBytes + ?Sized, V> Node16<K, V> {
         Err(self.header.data().len)
     }
 
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        let len = self.header.data().len as usize;
+        let idx = self.keys[..len].iter().position(|x| *x >= from)?;
+        Some((self.keys[idx], self.ptr[idx].as_ref()?.as_ref()))
+    }
+
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        let len = self.header.data().len as usize;
+        let idx = self.keys[..len].iter().rposition(|x| *x <= from)?;
+        Some((self.keys[idx], self.ptr[idx].as_ref()?.as_ref()))
+    }
+
     pub fn copy_drop_prefix(&self, until: usize) -> NodeBox<K, V> {
         let header = self.header.copy_drop_prefix(until);
         let ptr = self.ptr.clone();
@@ -122,7 +136,10 @@ impl<K: KeyBytes + ?Sized, V> Node16<K, V> {
         NodeBox::new(Node48 { header, ptr, idxs })
     }
 
-    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>> {
+    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>>
+    where
+        V: Clone,
+    {
         let data = self.header.data();
         if !self.keys[..data.len as usize].contains(&key) {
             return None;
@@ -140,8 +157,7 @@ impl<K: KeyBytes + ?Sized, V> Node16<K, V> {
             let mut ptr = <[Option<NodeBox<K, V>>; 16] as Zeroable>::zeroed();
             let mut keys: [u8; 16] = Zeroable::zeroed();
 
-            for (idx, (k, v)) in self
-                .keys
+            for (idx, (k, v)) in self.keys[..data.len as usize]
                 .iter()
                 .zip(self.ptr.iter())
                 .filter(|x| *x.0 != key)
@@ -157,8 +173,7 @@ impl<K: KeyBytes + ?Sized, V> Node16<K, V> {
         let mut ptr = <[Option<NodeBox<K, V>>; 4] as Zeroable>::zeroed();
         let mut keys: [u8; 4] = Zeroable::zeroed();
 
-        for (idx, (k, v)) in self
-            .keys
+        for (idx, (k, v)) in self.keys[..data.len as usize]
             .iter()
             .zip(self.ptr.iter())
             .filter(|x| *x.0 != key)
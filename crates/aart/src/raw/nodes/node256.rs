@@ -1,5 +1,3 @@
-use std::usize;
-
 use bytemuck::Zeroable;
 
 use super::{ptr::NodeBox, Node, Node48, NodeHeader, NodeHeaderData, NodeKind, NodeRef};
@@ -24,10 +22,20 @@ impl<K: KeyBytes + ?Sized, V> Node256<K, V> {
         self.header.data().len == 48
     }
 
-    pub fn get(&self, key: u8) -> Option<NodeRef<K, V>> {
+    pub fn get(&self, key: u8) -> Option<NodeRef<'_, K, V>> {
         self.ptr[key as usize].as_ref().map(|x| x.as_ref())
     }
 
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        (from..=u8::MAX).find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        (0..=from).rev().find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
     pub fn copy_drop_prefix(&self, until: usize) -> NodeBox<K, V> {
         let header = self.header.copy_drop_prefix(until);
         let ptr = self.ptr.clone();
@@ -52,7 +60,10 @@ impl<K: KeyBytes + ?Sized, V> Node256<K, V> {
         NodeBox::new(Self { ptr, header })
     }
 
-    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>> {
+    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>>
+    where
+        V: Clone,
+    {
         self.ptr[key as usize].as_ref()?;
 
         let data = self.header.data();
@@ -2,10 +2,7 @@ use std::cmp::Ordering;
 
 use bytemuck::Zeroable;
 
-use crate::{
-    key::{KeyBytes, KeyStorage},
-    raw::nodes::node16::Node16,
-};
+use crate::{key::KeyBytes, raw::nodes::node16::Node16};
 
 use super::{Node, NodeBox, NodeHeader, NodeHeaderData, NodeKind, NodeRef};
 
@@ -33,7 +30,7 @@ impl<K: KeyBytes + ?Sized, V> Node4<K, V> {
         self.header.data().len == 2
     }
 
-    pub fn get(&self, key: u8) -> Option<NodeRef<K, V>> {
+    pub fn get(&self, key: u8) -> Option<NodeRef<'_, K, V>> {
         let position = self.find_key(key).ok()?;
         self.ptr[position as usize].as_ref().map(|x| x.as_ref())
     }
@@ -128,12 +125,28 @@ impl<K: KeyBytes + ?Sized, V> Node4<K, V> {
         NodeBox::new(Node16 { header, ptr, keys })
     }
 
-    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>> {
-        if !self.keys.contains(&key) {
-            return None;
-        }
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        let len = self.header.data().len as usize;
+        let idx = self.keys[..len].iter().position(|x| *x >= from)?;
+        Some((self.keys[idx], self.ptr[idx].as_ref()?.as_ref()))
+    }
 
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        let len = self.header.data().len as usize;
+        let idx = self.keys[..len].iter().rposition(|x| *x <= from)?;
+        Some((self.keys[idx], self.ptr[idx].as_ref()?.as_ref()))
+    }
+
+    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>>
+    where
+        V: Clone,
+    {
         let data = self.header.data();
+        if !self.keys[..data.len as usize].contains(&key) {
+            return None;
+        }
 
         if !self.should_shrink() {
             let header = NodeHeader::new_from(
@@ -147,8 +160,7 @@ impl<K: KeyBytes + ?Sized, V> Node4<K, V> {
             let mut ptr = <[Option<NodeBox<K, V>>; 4] as Zeroable>::zeroed();
             let mut keys: [u8; 4] = Zeroable::zeroed();
 
-            for (idx, (k, v)) in self
-                .keys
+            for (idx, (k, v)) in self.keys[..data.len as usize]
                 .iter()
                 .zip(self.ptr.iter())
                 .filter(|x| *x.0 != key)
@@ -160,8 +172,11 @@ impl<K: KeyBytes + ?Sized, V> Node4<K, V> {
             return Some(NodeBox::new(Self { header, ptr, keys }));
         }
 
-        todo!()
-        // Node has only one node left after removing, fold into a single node.
+        // Node has only one node left after removing, fold into a single node by prepending the
+        // prefix of this node and the key of the remaining branch to its prefix.
+        let idx = (self.keys[0] == key) as usize;
+        let remaining = self.ptr[idx].as_ref().unwrap().as_ref();
+        Some(remaining.copy_prepend_prefix(self.header.prefix(), self.keys[idx]))
     }
 
     pub fn new_split(
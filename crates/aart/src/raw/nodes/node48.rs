@@ -28,7 +28,7 @@ impl<K: KeyBytes + ?Sized, V> Node48<K, V> {
         self.header.data().len == 17
     }
 
-    pub fn get(&self, key: u8) -> Option<NodeRef<K, V>> {
+    pub fn get(&self, key: u8) -> Option<NodeRef<'_, K, V>> {
         let idx = self.idxs[key as usize];
         if idx != u8::MAX {
             return self.ptr[idx as usize].as_ref().map(|x| x.as_ref());
@@ -36,6 +36,16 @@ impl<K: KeyBytes + ?Sized, V> Node48<K, V> {
         None
     }
 
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        (from..=u8::MAX).find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodeRef<'_, K, V>)> {
+        (0..=from).rev().find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
     pub fn copy_drop_prefix(&self, until: usize) -> NodeBox<K, V> {
         let header = self.header.copy_drop_prefix(until);
         let ptr = self.ptr.clone();
@@ -105,7 +115,10 @@ impl<K: KeyBytes + ?Sized, V> Node48<K, V> {
         NodeBox::new(Node256 { header, ptr })
     }
 
-    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>> {
+    pub fn copy_remove(&self, key: u8) -> Option<NodeBox<K, V>>
+    where
+        V: Clone,
+    {
         let key_idx = self.idxs[key as usize];
         if key_idx == u8::MAX {
             return None;
@@ -117,7 +130,7 @@ impl<K: KeyBytes + ?Sized, V> Node48<K, V> {
             let header = NodeHeader::new_from(
                 &self.header,
                 NodeHeaderData {
-                    len: data.len + 1,
+                    len: data.len - 1,
                     ..data
                 },
             );
@@ -151,7 +164,7 @@ impl<K: KeyBytes + ?Sized, V> Node48<K, V> {
         let mut write = 0;
         for i in 0..256 {
             let idx = self.idxs[i];
-            if idx == u8::MAX {
+            if idx == u8::MAX || i == key as usize {
                 continue;
             }
             keys[write] = i as u8;
@@ -3,12 +3,31 @@ use super::{
 };
 use crate::{
     key::KeyBytes,
-    prim::alloc::{self, Layout},
+    prim::{
+        alloc::{self, Layout},
+        sync::atomic::AtomicUsize,
+    },
     raw::nodes::Node4,
 };
 use bytemuck::ZeroableInOption;
 use std::{marker::PhantomData, ops::Deref, ptr::NonNull, sync::atomic::Ordering};
 
+/// The bit set in a pointer to a leaf.
+///
+/// Leafs have no node header to read their kind from, see [`NodeLeaf`]. Every node is aligned to
+/// at least the alignment of its reference count so this bit is never set in a node pointer.
+const LEAF_TAG: usize = 1;
+
+/// Returns whether the node pointer is a tagged pointer to a leaf.
+fn is_leaf<K: KeyBytes + ?Sized, V>(ptr: NonNull<NodeHeader<K, V>>) -> bool {
+    ptr.addr().get() & LEAF_TAG != 0
+}
+
+/// Returns the node pointer without the leaf tag.
+fn untag<K: KeyBytes + ?Sized, V>(ptr: NonNull<NodeHeader<K, V>>) -> NonNull<NodeHeader<K, V>> {
+    unsafe { NonNull::new_unchecked(ptr.as_ptr().map_addr(|x| x & !LEAF_TAG)) }
+}
+
 pub struct NodeRef<'a, K: KeyBytes + ?Sized, V> {
     ptr: NonNull<NodeHeader<K, V>>,
     _marker: PhantomData<&'a NodeBox<K, V>>,
@@ -22,6 +41,16 @@ impl<K: KeyBytes + ?Sized, V> Clone for NodeRef<'_, K, V> {
 impl<K: KeyBytes + ?Sized, V> Copy for NodeRef<'_, K, V> {}
 
 impl<'a, K: KeyBytes + ?Sized, V> NodeRef<'a, K, V> {
+    /// # Safety
+    /// The pointer must point to a valid node, tagged if it is a leaf, which outlives the
+    /// reference.
+    pub unsafe fn from_nonnull(ptr: NonNull<NodeHeader<K, V>>) -> Self {
+        NodeRef {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
     pub fn is<N>(self) -> bool
     where
         N: Node<Key = K, Value = V>,
@@ -35,7 +64,7 @@ impl<'a, K: KeyBytes + ?Sized, V> NodeRef<'a, K, V> {
     where
         N: Node<Key = K, Value = V>,
     {
-        unsafe { self.ptr.cast().as_ref() }
+        unsafe { untag(self.ptr).cast().as_ref() }
     }
 
     pub fn cast<N>(self) -> Option<&'a N>
@@ -45,17 +74,41 @@ impl<'a, K: KeyBytes + ?Sized, V> NodeRef<'a, K, V> {
         self.is::<N>().then(|| unsafe { self.cast_unchecked() })
     }
 
+    /// Returns the pointer to the node, which is tagged for a leaf.
     pub fn as_ptr(self) -> *mut NodeHeader<K, V> {
         self.ptr.as_ptr()
     }
 
+    pub fn kind(self) -> NodeKind {
+        if is_leaf(self.ptr) {
+            return NodeKind::Leaf;
+        }
+        self.header().kind()
+    }
+
+    /// Returns the prefix of the node.
+    pub fn prefix(self) -> &'a [u8] {
+        match self.cast::<NodeLeaf<K, V>>() {
+            Some(leaf) => leaf.prefix(),
+            None => self.header().prefix(),
+        }
+    }
+
+    /// Returns the reference count of the node.
+    pub fn ref_count(self) -> &'a AtomicUsize {
+        match self.cast::<NodeLeaf<K, V>>() {
+            Some(leaf) => &leaf.ref_count,
+            None => &self.header().ref_count,
+        }
+    }
+
     pub fn as_nonnull(self) -> NonNull<NodeHeader<K, V>> {
         self.ptr
     }
 
     pub fn get(self, key: u8) -> Option<NodeRef<'a, K, V>> {
         unsafe {
-            match self.data().kind() {
+            match self.kind() {
                 NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
                 NodeKind::Node4 => self.cast_unchecked::<Node4<_, _>>().get(key),
                 NodeKind::Node16 => self.cast_unchecked::<Node16<_, _>>().get(key),
@@ -65,9 +118,38 @@ impl<'a, K: KeyBytes + ?Sized, V> NodeRef<'a, K, V> {
         }
     }
 
-    pub fn copy_insert(self, key: u8, value: NodeBox<K, V>) -> NodeBox<K, V> {
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(self, from: u8) -> Option<(u8, NodeRef<'a, K, V>)> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self.cast_unchecked::<Node4<_, _>>().next_node(from),
+                NodeKind::Node16 => self.cast_unchecked::<Node16<_, _>>().next_node(from),
+                NodeKind::Node48 => self.cast_unchecked::<Node48<_, _>>().next_node(from),
+                NodeKind::Node256 => self.cast_unchecked::<Node256<_, _>>().next_node(from),
+            }
+        }
+    }
+
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(self, from: u8) -> Option<(u8, NodeRef<'a, K, V>)> {
         unsafe {
-            match self.data().kind() {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self.cast_unchecked::<Node4<_, _>>().prev_node(from),
+                NodeKind::Node16 => self.cast_unchecked::<Node16<_, _>>().prev_node(from),
+                NodeKind::Node48 => self.cast_unchecked::<Node48<_, _>>().prev_node(from),
+                NodeKind::Node256 => self.cast_unchecked::<Node256<_, _>>().prev_node(from),
+            }
+        }
+    }
+
+    pub fn copy_insert(self, key: u8, value: NodeBox<K, V>) -> NodeBox<K, V> {
+        #[cfg(feature = "order-statistics")]
+        let leafs =
+            self.leafs - self.get(key).map_or(0, |x| x.leaf_count()) + value.as_ref().leaf_count();
+        let new = unsafe {
+            match self.kind() {
                 NodeKind::Leaf => panic!("tried to insert a branch in a leaf node"),
                 NodeKind::Node4 => self.cast_unchecked::<Node4<_, _>>().copy_insert(key, value),
                 NodeKind::Node16 => self
@@ -80,24 +162,80 @@ impl<'a, K: KeyBytes + ?Sized, V> NodeRef<'a, K, V> {
                     .cast_unchecked::<Node256<_, _>>()
                     .copy_insert(key, value),
             }
+        };
+        #[cfg(feature = "order-statistics")]
+        {
+            // The copy is not yet shared so it is fine to mutate it.
+            unsafe { (*new.as_ptr()).leafs = leafs };
         }
+        new
     }
 
-    pub fn copy_remove(self, key: u8) -> Option<NodeBox<K, V>> {
-        unsafe {
-            match self.data().kind() {
+    pub fn copy_remove(self, key: u8) -> Option<NodeBox<K, V>>
+    where
+        V: Clone,
+    {
+        // A node4 folded into its remaining child also ends up with the remaining leafs.
+        #[cfg(feature = "order-statistics")]
+        let leafs = self.leafs - self.get(key)?.leaf_count();
+        let new = unsafe {
+            match self.kind() {
                 NodeKind::Leaf => panic!("tried to remove a branch from a leaf node"),
                 NodeKind::Node4 => self.cast_unchecked::<Node4<_, _>>().copy_remove(key),
                 NodeKind::Node16 => self.cast_unchecked::<Node16<_, _>>().copy_remove(key),
                 NodeKind::Node48 => self.cast_unchecked::<Node48<_, _>>().copy_remove(key),
                 NodeKind::Node256 => self.cast_unchecked::<Node256<_, _>>().copy_remove(key),
             }
+        };
+        #[cfg(feature = "order-statistics")]
+        if let Some(new) = new.as_ref().filter(|x| !x.as_ref().is::<NodeLeaf<K, V>>()) {
+            unsafe { (*new.as_ptr()).leafs = leafs };
+        }
+        new
+    }
+
+    /// Returns the amount of leafs below the node.
+    #[cfg(feature = "order-statistics")]
+    pub fn leaf_count(self) -> usize {
+        if self.is::<NodeLeaf<K, V>>() {
+            1
+        } else {
+            self.leafs
+        }
+    }
+
+    /// Returns the header of the node for the lifetime of the reference.
+    pub fn header(self) -> &'a NodeHeader<K, V> {
+        debug_assert!(!is_leaf(self.ptr), "leafs have no node header");
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Returns a new reference to the node.
+    pub fn to_box(self) -> NodeBox<K, V> {
+        self.ref_count().fetch_add(1, Ordering::AcqRel);
+        unsafe { NodeBox::from_nonnull(self.ptr) }
+    }
+
+    /// Copy the node with the prefix followed by the key prepended to its prefix.
+    pub fn copy_prepend_prefix(self, prefix: &[u8], key: u8) -> NodeBox<K, V>
+    where
+        V: Clone,
+    {
+        if let Some(leaf) = self.cast::<NodeLeaf<K, V>>() {
+            return leaf.copy_prepend_prefix(prefix, key);
         }
+        let new = self.copy_drop_prefix(0);
+        // The copy is not yet shared so it is fine to mutate it.
+        unsafe { (*new.as_ptr()).prepend_prefix(prefix, key) };
+        new
     }
 
-    pub fn copy_drop_prefix(self, drop: usize) -> NodeBox<K, V> {
+    pub fn copy_drop_prefix(self, drop: usize) -> NodeBox<K, V>
+    where
+        V: Clone,
+    {
         unsafe {
-            match self.data().kind() {
+            match self.kind() {
                 NodeKind::Leaf => self
                     .cast_unchecked::<NodeLeaf<_, _>>()
                     .copy_drop_prefix(drop),
@@ -116,10 +254,13 @@ impl<K: KeyBytes + ?Sized, V> Deref for NodeRef<'_, K, V> {
     type Target = NodeHeader<K, V>;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { self.ptr.as_ref() }
+        self.header()
     }
 }
 
+/// An owned reference to a node, nodes are shared by counting their references.
+///
+/// A pointer to a leaf is tagged with [`LEAF_TAG`].
 #[repr(transparent)]
 pub struct NodeBox<K: KeyBytes + ?Sized, V>(NonNull<NodeHeader<K, V>>);
 unsafe impl<K: KeyBytes + ?Sized, V> ZeroableInOption for NodeBox<K, V> {}
@@ -131,6 +272,8 @@ impl<K: KeyBytes + ?Sized, V> NodeBox<K, V> {
     where
         N: Node<Key = K, Value = V>,
     {
+        // Leafs are followed by their prefix, see `NodeLeaf::new_box`.
+        debug_assert_ne!(N::KIND, NodeKind::Leaf);
         debug_assert_eq!(node.header().kind(), N::KIND);
         unsafe {
             let ptr = NonNull::new(alloc::alloc(Layout::new::<N>()))
@@ -141,19 +284,29 @@ impl<K: KeyBytes + ?Sized, V> NodeBox<K, V> {
         }
     }
 
+    /// # Safety
+    /// The pointer must point to a valid leaf and the caller must own one of its reference counts.
+    pub unsafe fn from_leaf(ptr: NonNull<NodeLeaf<K, V>>) -> Self {
+        Self(ptr.cast().map_addr(|x| x | LEAF_TAG))
+    }
+
+    /// # Safety
+    /// The pointer must point to a valid node, tagged if it is a leaf, and the caller must own one
+    /// of its reference counts.
     pub unsafe fn from_nonnull(ptr: NonNull<NodeHeader<K, V>>) -> Self {
         Self(ptr)
     }
 
+    /// # Safety
+    /// The pointer must point to a valid node, tagged if it is a leaf, which is no longer
+    /// referenced.
     pub unsafe fn drop_in_place(ptr: NonNull<NodeHeader<K, V>>) {
+        if is_leaf(ptr) {
+            let ptr = untag(ptr).cast::<NodeLeaf<K, V>>();
+            return unsafe { NodeLeaf::drop_in_place(ptr) };
+        }
         match ptr.as_ref().kind() {
-            NodeKind::Leaf => {
-                let ptr = ptr.cast::<NodeLeaf<K, V>>();
-                unsafe {
-                    std::ptr::drop_in_place(ptr.as_ptr());
-                    alloc::dealloc(ptr.as_ptr().cast(), Layout::new::<NodeLeaf<K, V>>())
-                }
-            }
+            NodeKind::Leaf => unreachable!("untagged pointer to a leaf"),
             NodeKind::Node4 => {
                 let ptr = ptr.cast::<Node4<K, V>>();
                 unsafe {
@@ -185,17 +338,23 @@ impl<K: KeyBytes + ?Sized, V> NodeBox<K, V> {
         }
     }
 
-    pub fn as_ref(&self) -> NodeRef<K, V> {
+    pub fn as_ref(&self) -> NodeRef<'_, K, V> {
         NodeRef {
             ptr: self.0,
             _marker: PhantomData,
         }
     }
 
+    /// Returns the pointer to the node, which is tagged for a leaf.
     pub fn as_ptr(&self) -> *mut NodeHeader<K, V> {
         self.0.as_ptr()
     }
 
+    /// Returns the prefix of the node.
+    pub fn prefix(&self) -> &[u8] {
+        self.as_ref().prefix()
+    }
+
     pub fn into_nonnull(self) -> NonNull<NodeHeader<K, V>> {
         let res = self.0;
         std::mem::forget(self);
@@ -205,7 +364,7 @@ impl<K: KeyBytes + ?Sized, V> NodeBox<K, V> {
 
 impl<K: KeyBytes + ?Sized, V> Clone for NodeBox<K, V> {
     fn clone(&self) -> Self {
-        self.ref_count.fetch_add(1, Ordering::AcqRel);
+        self.as_ref().ref_count().fetch_add(1, Ordering::AcqRel);
         NodeBox(self.0)
     }
 }
@@ -214,13 +373,13 @@ impl<K: KeyBytes + ?Sized, V> Deref for NodeBox<K, V> {
     type Target = NodeHeader<K, V>;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { self.0.as_ref() }
+        self.as_ref().header()
     }
 }
 
 impl<K: KeyBytes + ?Sized, V> Drop for NodeBox<K, V> {
     fn drop(&mut self) {
-        let count = self.ref_count.fetch_sub(1, Ordering::AcqRel);
+        let count = self.as_ref().ref_count().fetch_sub(1, Ordering::AcqRel);
 
         if count == 1 {
             unsafe { Self::drop_in_place(self.0) }
@@ -0,0 +1,69 @@
+//! Finding the longest stored key which is a prefix of a key, and the keys which start with a
+//! prefix.
+
+use super::{merge::Side, nodes::NodeLeaf, RawAart};
+use crate::key::KeyBytes;
+
+impl<K: KeyBytes + ?Sized, V> RawAart<K, V> {
+    /// Returns the node below which exactly the keys starting with `prefix` are stored, with the
+    /// part of its prefix within `prefix` skipped, or `None` if no key starts with `prefix`.
+    pub(super) fn prefix_node(&self, prefix: &[u8]) -> Option<Side<'_, K, V>> {
+        let mut node = self.root()?;
+        let mut rest = prefix;
+        loop {
+            let node_prefix = node.prefix();
+            let len = node_prefix.len().min(rest.len());
+            if node_prefix[..len] != rest[..len] {
+                return None;
+            }
+            if len == rest.len() {
+                return Some(Side { node, skip: len });
+            }
+            if node.is::<NodeLeaf<K, V>>() {
+                return None;
+            }
+            node = node.get(rest[len])?;
+            rest = &rest[len + 1..];
+        }
+    }
+
+    /// Returns the longest stored key consisting of a prefix of `key` followed by `terminator`,
+    /// as the length of that prefix and the value of the key.
+    pub(crate) fn longest_prefix(&self, key: &[u8], terminator: u8) -> Option<(usize, &V)> {
+        let mut node = self.root()?;
+        let mut depth = 0;
+        let mut best = None;
+        loop {
+            let prefix = node.prefix();
+            let rest = &key[depth..];
+            if let Some(leaf) = node.cast::<NodeLeaf<K, V>>() {
+                if let Some((last, prefix)) = prefix.split_last() {
+                    if *last == terminator && rest.starts_with(prefix) {
+                        best = Some((depth + prefix.len(), &leaf.value));
+                    }
+                }
+                return best;
+            }
+
+            if !rest.starts_with(prefix) {
+                return best;
+            }
+            depth += prefix.len();
+
+            // A key ending here is stored as a leaf below the terminator branch.
+            if let Some(leaf) = node
+                .get(terminator)
+                .and_then(|x| x.cast::<NodeLeaf<K, V>>())
+                .filter(|x| x.prefix().is_empty())
+            {
+                best = Some((depth, &leaf.value));
+            }
+
+            let Some(next) = key.get(depth).and_then(|x| node.get(*x)) else {
+                return best;
+            };
+            node = next;
+            depth += 1;
+        }
+    }
+}
@@ -27,15 +27,15 @@ impl<K: KeyBytes + ?Sized, V> RootPtr<K, V> {
             loop {
                 let ptr = self.ptr.load_consume();
                 let ptr = NonNull::new(ptr)?;
-                let node: &NodeHeader<K, V> = ptr.as_ref();
-                let mut count = node.ref_count.load_consume();
+                let node = NodeRef::<K, V>::from_nonnull(ptr);
+                let mut count = node.ref_count().load_consume();
                 loop {
                     if count == 0 {
                         break;
                     }
 
                     let new_count = count.checked_add(1).unwrap();
-                    match node.ref_count.compare_exchange_weak(
+                    match node.ref_count().compare_exchange_weak(
                         count,
                         new_count,
                         Ordering::AcqRel,
@@ -69,12 +69,11 @@ impl<K: KeyBytes + ?Sized, V> RootPtr<K, V> {
             Ok(x) => {
                 std::mem::forget(new);
                 if let Some(x) = NonNull::new(x) {
-                    unsafe {
-                        let count = x.as_ref().ref_count.fetch_sub(1, Ordering::AcqRel);
-                        if count == 1 {
-                            guard.defer_unchecked(move || NodeBox::drop_in_place(x))
-                        }
-                    }
+                    // Readers which loaded the old root before the exchange might not have taken
+                    // their reference yet, so the reference of the root is only released once
+                    // they are unpinned. Otherwise a reader dropping its copy could free the node
+                    // from under them.
+                    unsafe { guard.defer_unchecked(move || drop(NodeBox::<K, V>::from_nonnull(x))) }
                 }
                 Ok(())
             }
@@ -1,6 +1,10 @@
 use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::{collections::BTreeMap, ops::Bound, sync::Arc, thread};
 
-use crate::Aart;
+use crate::{
+    diff::Change, display::DisplayOptions, iter::RawIterator, key::INVALID_STR_BYTE, Aart,
+    ConcurrentAart, Inline, OlcArt, RowexArt, ShardedAart,
+};
 
 #[test]
 fn basic_insert_str() {
@@ -39,3 +43,1535 @@ fn seq_insert_pod() {
         tree.insert(&i, i);
     }
 }
+
+#[test]
+fn from_sorted_iter_pod() {
+    // integer keys are ordered by their little endian bytes.
+    let keys: Vec<u64> = (0..100_000u64).map(|x| x.swap_bytes() >> 40).collect();
+    let tree = Aart::from_sorted_iter(keys.iter().map(|x| (x, *x)));
+
+    for k in keys.iter() {
+        assert_eq!(tree.get(k), Some(k));
+    }
+    assert_eq!(tree.get(&u64::MAX), None);
+}
+
+#[test]
+fn from_sorted_iter_str() {
+    let mut keys: Vec<String> = (0..10_000u64)
+        .map(|_| {
+            let k: u64 = thread_rng().gen();
+            // vary the length so that there are nodes of every size.
+            format!("{:x}", k >> (k % 48))
+        })
+        .collect();
+    keys.sort_by(|a, b| {
+        let a = a.bytes().chain([INVALID_STR_BYTE]);
+        let b = b.bytes().chain([INVALID_STR_BYTE]);
+        a.cmp(b)
+    });
+    keys.dedup();
+
+    let tree = Aart::<str, usize>::from_sorted_iter(keys.iter().map(|x| x.as_str()).zip(0..));
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k), Some(&i));
+    }
+}
+
+#[test]
+fn insert_str_prefix() {
+    let mut tree = Aart::<str, usize>::new();
+    tree.insert("ab", 0);
+    tree.insert("a", 1);
+    tree.insert("abc", 2);
+    assert_eq!(tree.get("ab"), Some(&0));
+    assert_eq!(tree.get("a"), Some(&1));
+    assert_eq!(tree.get("abc"), Some(&2));
+    assert_eq!(tree.get("abcd"), None);
+}
+
+#[test]
+#[should_panic]
+fn from_sorted_iter_unsorted() {
+    Aart::<str, usize>::from_sorted_iter([("b", 0), ("a", 1)]);
+}
+
+fn sort_str(keys: &mut [String]) {
+    keys.sort_by(|a, b| {
+        let a = a.bytes().chain([INVALID_STR_BYTE]);
+        let b = b.bytes().chain([INVALID_STR_BYTE]);
+        a.cmp(b)
+    });
+}
+
+#[test]
+fn remove_pod() {
+    let mut tree = Aart::<u64, u64>::new();
+    let mut model = BTreeMap::new();
+    for _ in 0..100_000 {
+        // a small key space so that nodes both grow and shrink.
+        let k: u64 = thread_rng().gen_range(0..4096) * 0x0101;
+        if thread_rng().gen_bool(0.5) {
+            assert_eq!(tree.insert(&k, k).is_some(), model.insert(k, k).is_some());
+        } else {
+            assert_eq!(tree.remove(&k), model.remove(&k));
+        }
+        assert_eq!(tree.len(), model.len());
+    }
+    for (k, v) in model.iter() {
+        assert_eq!(tree.get(k), Some(v));
+    }
+    for k in model.keys() {
+        assert_eq!(tree.remove(k), Some(*k));
+    }
+    assert!(tree.is_empty());
+    assert_eq!(tree.remove(&0), None);
+}
+
+#[test]
+fn remove_str() {
+    let mut tree = Aart::<str, usize>::new();
+    let mut model = BTreeMap::new();
+    for i in 0..20_000 {
+        let k: u64 = thread_rng().gen_range(0..2048);
+        let key = format!("{:x}", k >> (k % 12));
+        if thread_rng().gen_bool(0.6) {
+            assert_eq!(
+                tree.insert(&key, i).is_some(),
+                model.insert(key, i).is_some()
+            );
+        } else {
+            assert_eq!(tree.remove(&key), model.remove(&key));
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+
+    let mut keys: Vec<String> = model.keys().cloned().collect();
+    sort_str(&mut keys);
+    let mut iter = tree.iter();
+    for k in keys.iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), &model[k])));
+    }
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn range_str() {
+    let mut keys: Vec<String> = (0..10_000u64)
+        .map(|_| {
+            let k: u64 = thread_rng().gen();
+            format!("{:x}", k >> (k % 48))
+        })
+        .collect();
+    sort_str(&mut keys);
+    keys.dedup();
+
+    let mut tree = Aart::<str, usize>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+
+    let mut iter = tree.range(keys[100].as_str()..keys[200].as_str());
+    for (i, k) in keys.iter().enumerate().take(200).skip(100) {
+        assert_eq!(iter.next(), Some((k.as_str(), &i)));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = tree.range((
+        Bound::Excluded(keys[100].as_str()),
+        Bound::Included(keys[200].as_str()),
+    ));
+    for (i, k) in keys.iter().enumerate().take(201).skip(101) {
+        assert_eq!(iter.next(), Some((k.as_str(), &i)));
+    }
+    assert_eq!(iter.next(), None);
+
+    for prefix in ["", "ab", "1f", "not hex"] {
+        let mut iter = tree.scan_prefix(prefix);
+        for (i, k) in keys.iter().enumerate().filter(|x| x.1.starts_with(prefix)) {
+            assert_eq!(iter.next(), Some((k.as_str(), &i)));
+        }
+        assert_eq!(iter.next(), None);
+    }
+}
+
+#[test]
+fn clone_is_snapshot() {
+    let mut tree = Aart::<u64, u64>::new();
+    for i in 0..1000 {
+        tree.insert(&i, i);
+    }
+    let snapshot = tree.clone();
+    for i in 0..1000 {
+        if i % 2 == 0 {
+            tree.remove(&i);
+        } else {
+            tree.insert(&i, i + 1);
+        }
+    }
+    assert_eq!(snapshot.len(), 1000);
+    assert_eq!(tree.len(), 500);
+    for i in 0..1000 {
+        assert_eq!(snapshot.get(&i), Some(&i));
+    }
+}
+
+#[test]
+fn concurrent() {
+    let map = Arc::new(ConcurrentAart::<u64, u64>::new());
+    let writers: Vec<_> = (0..4u64)
+        .map(|t| {
+            let map = map.clone();
+            thread::spawn(move || {
+                for i in 0..2000 {
+                    map.insert(&(i * 4 + t), i);
+                }
+                for i in (0..2000).step_by(2) {
+                    assert_eq!(map.remove(&(i * 4 + t)).as_deref(), Some(&i));
+                }
+            })
+        })
+        .collect();
+    let readers: Vec<_> = (0..4u64)
+        .map(|_| {
+            let map = map.clone();
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    // a snapshot is never affected by concurrent writes.
+                    let snapshot = map.snapshot();
+                    let len = snapshot.len();
+                    let mut count = 0;
+                    for k in 0..8000 {
+                        count += snapshot.get(&k).is_some() as usize;
+                    }
+                    assert_eq!(count, len);
+                }
+            })
+        })
+        .collect();
+    for t in writers.into_iter().chain(readers) {
+        t.join().unwrap();
+    }
+
+    assert_eq!(map.len(), 4000);
+    for k in 0..8000u64 {
+        let i = k / 4;
+        assert_eq!(map.get(&k).as_deref(), (i % 2 == 1).then_some(&i));
+    }
+}
+
+#[test]
+fn export() {
+    let mut tree = Aart::<str, usize>::new();
+    assert_eq!(tree.to_json(), r#"{"len":0,"root":null}"#);
+    tree.insert("a", 1);
+    tree.insert("b", 2);
+    let snapshot = tree.clone();
+    tree.insert("c", 3);
+
+    // The leafs are shared with the snapshot.
+    assert_eq!(
+        tree.to_json(),
+        r#"{"len":3,"root":{"kind":"Node4","prefix":"","ref_count":1,"children":[{"key":97,"node":{"kind":"Leaf","prefix":"\\xbf","ref_count":2,"value":"1"}},{"key":98,"node":{"kind":"Leaf","prefix":"\\xbf","ref_count":2,"value":"2"}},{"key":99,"node":{"kind":"Leaf","prefix":"\\xbf","ref_count":1,"value":"3"}}]}}"#
+    );
+    drop(snapshot);
+
+    let dot = tree.to_dot();
+    assert!(dot.starts_with("digraph aart {\n"));
+    assert!(dot.contains("n0 [label=\"Node4\\nprefix: \\nref_count: 1\\nchildren: 3\"];"));
+    assert!(dot.contains("n3 [label=\"Leaf\\nprefix: \\\\xbf\\nref_count: 1\\nvalue: 3\"];"));
+    assert!(dot.contains("n0 -> n3 [label=\"c\"];"));
+}
+
+#[test]
+fn display() {
+    let mut tree = Aart::<str, usize>::new();
+    assert_eq!(tree.to_string(), "TREE = EMPTY\n");
+    assert_eq!(format!("{tree:?}"), "{}");
+    for (idx, key) in ["aa", "ab", "ac", "b"].iter().enumerate() {
+        tree.insert(key, idx);
+    }
+    let snapshot = tree.clone();
+    // The root is shared with the snapshot.
+    assert_eq!(
+        tree.to_string(),
+        "TREE = NODE4: len=2,prefix=[],ref_count=2
+  [97] = NODE4: len=3,prefix=[],ref_count=1
+    [97] = LEAF: prefix=[191],ref_count=1 | 0
+    [98] = LEAF: prefix=[191],ref_count=1 | 1
+    [99] = LEAF: prefix=[191],ref_count=1 | 2
+  [98] = LEAF: prefix=[191],ref_count=1 | 3
+"
+    );
+    let options = DisplayOptions {
+        max_depth: Some(0),
+        max_children: None,
+    };
+    assert_eq!(
+        tree.display(options).to_string(),
+        "TREE = NODE4: len=2,prefix=[],ref_count=2
+  ... 2 more
+"
+    );
+    let options = DisplayOptions {
+        max_depth: None,
+        max_children: Some(2),
+    };
+    assert_eq!(
+        tree.display(options).to_string(),
+        "TREE = NODE4: len=2,prefix=[],ref_count=2
+  [97] = NODE4: len=3,prefix=[],ref_count=1
+    [97] = LEAF: prefix=[191],ref_count=1 | 0
+    [98] = LEAF: prefix=[191],ref_count=1 | 1
+    ... 1 more
+  [98] = LEAF: prefix=[191],ref_count=1 | 3
+"
+    );
+    assert_eq!(
+        format!("{tree:?}"),
+        r#"{"aa": 0, "ab": 1, "ac": 2, "b": 3}"#
+    );
+    drop(snapshot);
+}
+
+#[test]
+fn set_operations() {
+    // Values are shared, so copies of a leaf hold the same value.
+    type Tree = Aart<u64, u64, Arc<u64>>;
+    fn check(tree: &Tree, model: &BTreeMap<u64, u64>, keys: &[u64]) {
+        assert_eq!(tree.len(), model.len());
+        for k in keys {
+            assert_eq!(tree.get(k), model.get(k), "key {k:x}");
+        }
+    }
+
+    let (mut a, mut b) = (Tree::with_storage(), Tree::with_storage());
+    let (mut model_a, mut model_b) = (BTreeMap::new(), BTreeMap::new());
+    let mut keys = Vec::new();
+    for i in 0..20_000 {
+        // Mix dense and sparse keys so the trees share some subtrees and not others.
+        let k = if i % 3 == 0 {
+            thread_rng().gen_range(0..4096)
+        } else {
+            thread_rng().gen()
+        };
+        keys.push(k);
+        if thread_rng().gen_bool(0.5) {
+            a.insert(&k, k);
+            model_a.insert(k, k);
+        } else {
+            b.insert(&k, k / 2);
+            model_b.insert(k, k / 2);
+        }
+    }
+
+    let mut model = model_a.clone();
+    for (k, v) in model_b.iter() {
+        model.entry(*k).and_modify(|x| *x ^= v).or_insert(*v);
+    }
+    check(&a.union_with(&b, |x, y| x ^ y), &model, &keys);
+
+    let model = model_a
+        .iter()
+        .filter_map(|(k, v)| Some((*k, v ^ model_b.get(k)?)))
+        .collect();
+    check(&a.intersection(&b, |x, y| x ^ y), &model, &keys);
+
+    let model = model_a
+        .iter()
+        .filter(|(k, _)| !model_b.contains_key(k))
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    check(&a.difference(&b, |_, _| None), &model, &keys);
+
+    let model = model_a
+        .iter()
+        .chain(model_b.iter())
+        .filter(|(k, _)| model_a.contains_key(k) != model_b.contains_key(k))
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    check(&a.symmetric_difference(&b), &model, &keys);
+
+    // Merging with a later version only visits the subtrees which changed, the values of the
+    // shared leafs are the same and never passed to the closure.
+    let mut c = a.clone();
+    let mut model_c = model_a.clone();
+    for k in keys.iter().step_by(7) {
+        c.insert(k, k + 1);
+        model_c.insert(*k, k + 1);
+    }
+    let changed = model_a.iter().filter(|(k, v)| model_c[k] != **v).count();
+    let mut calls = 0;
+    let union = a.union_with(&c, |_, y| {
+        calls += 1;
+        *y
+    });
+    check(&union, &model_c, &keys);
+    assert_eq!(calls, changed);
+    assert!(a.symmetric_difference(&a.clone()).is_empty());
+    check(
+        &a.intersection(&a.clone(), |_, _| unreachable!()),
+        &model_a,
+        &keys,
+    );
+
+    let empty = Tree::with_storage();
+    check(&a.union_with(&empty, |x, _| *x), &model_a, &keys);
+    check(&empty.union_with(&b, |x, _| *x), &model_b, &keys);
+    assert!(a.intersection(&empty, |x, _| *x).is_empty());
+}
+
+#[test]
+fn set_operations_share_subtrees() {
+    let mut a = Aart::<str, usize>::new();
+    let mut b = Aart::<str, usize>::new();
+    a.insert("apple", 0);
+    a.insert("apricot", 1);
+    b.insert("banana", 2);
+    b.insert("apple", 3);
+
+    let union = a.union_with(&b, |x, y| x + y);
+    let mut entries = Vec::new();
+    let mut iter = union.iter();
+    while let Some((k, v)) = iter.next() {
+        entries.push((k.to_owned(), *v));
+    }
+    assert_eq!(
+        entries,
+        [
+            ("apple".to_owned(), 3),
+            ("apricot".to_owned(), 1),
+            ("banana".to_owned(), 2)
+        ]
+    );
+
+    // The leafs of "apricot" and "banana" are used by the union as is.
+    let json = union.to_json();
+    assert!(
+        json.contains(r#""prefix":"icot\\xbf","ref_count":2"#),
+        "{json}"
+    );
+    assert!(
+        json.contains(r#""prefix":"anana\\xbf","ref_count":2"#),
+        "{json}"
+    );
+    assert!(
+        json.contains(r#""prefix":"le\\xbf","ref_count":1,"value":"3""#),
+        "{json}"
+    );
+    drop(union);
+    assert!(a
+        .to_json()
+        .contains(r#""prefix":"icot\\xbf","ref_count":1"#));
+}
+
+#[test]
+fn diff() {
+    fn entry(change: Change<str, usize>) -> (String, Option<usize>, Option<usize>) {
+        match change {
+            Change::Added { key, value } => (key, None, Some(*value)),
+            Change::Removed { key, value } => (key, Some(*value), None),
+            Change::Modified { key, old, new } => (key, Some(*old), Some(*new)),
+        }
+    }
+
+    let mut keys: Vec<String> = (0..2000)
+        .map(|_| format!("{:x}", thread_rng().gen::<u32>()))
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut old = Aart::<str, usize, Arc<usize>>::with_storage();
+    for (i, k) in keys.iter().enumerate() {
+        old.insert(k, i);
+    }
+    assert_eq!(Aart::diff(&old, &old.clone()).count(), 0);
+
+    let mut new = old.clone();
+    let mut expected = Vec::new();
+    for (i, k) in keys.iter().enumerate() {
+        match i % 7 {
+            0 => {
+                new.remove(k);
+                expected.push((k.clone(), Some(i), None));
+            }
+            1 => {
+                new.insert(k, i + 1);
+                expected.push((k.clone(), Some(i), Some(i + 1)));
+            }
+            // Inserting an equal value still counts as a modification.
+            2 => {
+                new.insert(k, i);
+                expected.push((k.clone(), Some(i), Some(i)));
+            }
+            _ => {}
+        }
+    }
+    for i in 0..100 {
+        let key = format!("{i:x}g");
+        new.insert(&key, i);
+        expected.push((key, None, Some(i)));
+    }
+
+    // Keys are yielded in the order of the tree, which places a key after its extensions.
+    expected.sort_by_key(|x| {
+        let mut key = x.0.clone().into_bytes();
+        key.push(INVALID_STR_BYTE);
+        key
+    });
+    assert_eq!(
+        Aart::diff(&old, &new).map(entry).collect::<Vec<_>>(),
+        expected
+    );
+    let reverse = Aart::diff(&new, &old).map(entry);
+    assert!(reverse.eq(expected.into_iter().map(|(k, a, b)| (k, b, a))));
+
+    // Keys which can't be borrowed from their bytes are recreated.
+    let mut old = Aart::<u64, u64, Arc<u64>>::with_storage();
+    for k in 0..1000 {
+        old.insert(&k, k);
+    }
+    let mut new = old.clone();
+    new.remove(&10);
+    new.insert(&20, 0);
+    new.insert(&5000, 0);
+    let mut changes = Aart::diff(&old, &new).collect::<Vec<_>>();
+    changes.sort_by_key(|x| match x {
+        Change::Added { key, .. } | Change::Removed { key, .. } | Change::Modified { key, .. } => {
+            *key
+        }
+    });
+    assert_eq!(
+        changes,
+        [
+            Change::Removed {
+                key: 10,
+                value: Arc::new(10)
+            },
+            Change::Modified {
+                key: 20,
+                old: Arc::new(20),
+                new: Arc::new(0)
+            },
+            Change::Added {
+                key: 5000,
+                value: Arc::new(0)
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "order-statistics")]
+#[test]
+fn order_statistics() {
+    use std::collections::BTreeSet;
+
+    // The tree orders integer keys by their little endian bytes.
+    fn check(tree: &Aart<u64, u64>, expect: &BTreeSet<[u8; 8]>, keys: &[u64]) {
+        for k in keys {
+            let bytes = k.to_le_bytes();
+            assert_eq!(tree.rank(k), expect.range(..bytes).count(), "key {k:x}");
+            let within = expect.range(bytes..).take(100).count();
+            let end = expect
+                .range(bytes..)
+                .nth(99)
+                .map(|x| u64::from_le_bytes(*x));
+            match end {
+                Some(end) => assert_eq!(tree.range_count(k..=&end), within),
+                None => assert_eq!(tree.range_count(k..), within),
+            }
+        }
+        assert_eq!(tree.range_count(..), expect.len());
+    }
+
+    let mut tree = Aart::<u64, u64>::new();
+    let mut expect = BTreeSet::new();
+    let mut keys = Vec::new();
+    for i in 0..20_000 {
+        let k = match i % 3 {
+            0 => thread_rng().gen::<u64>() % 4096,
+            _ => thread_rng().gen(),
+        };
+        keys.push(k);
+        tree.insert(&k, k);
+        expect.insert(k.to_le_bytes());
+    }
+    let snapshot = tree.clone();
+    let snapshot_expect = expect.clone();
+    check(&tree, &expect, &keys[..1000]);
+
+    for k in keys.iter().step_by(3) {
+        tree.remove(k);
+        expect.remove(&k.to_le_bytes());
+    }
+    check(&tree, &expect, &keys[..1000]);
+    // Counts in the nodes shared with the snapshot are unaffected by the removals.
+    check(&snapshot, &snapshot_expect, &keys[..1000]);
+
+    let union = tree.union_with(&Aart::new(), |x, _| *x);
+    check(&union, &expect, &keys[..1000]);
+    let mut sorted: Vec<_> = expect.iter().map(|x| u64::from_le_bytes(*x)).collect();
+    sorted.truncate(1000);
+    let built = Aart::from_sorted_iter(sorted.iter().map(|k| (k, *k)));
+    let built_expect = expect.iter().take(1000).copied().collect();
+    check(&built, &built_expect, &keys[..1000]);
+
+    let mut tree = Aart::<str, usize>::new();
+    for (i, k) in ["apple", "apricot", "banana", "band", "ban", "cherry"]
+        .iter()
+        .enumerate()
+    {
+        tree.insert(k, i);
+    }
+    let mut i = 0;
+    let mut iter = tree.iter();
+    while let Some((k, v)) = iter.next() {
+        let (key, value) = tree.nth(i).unwrap();
+        assert_eq!((key.as_str(), value), (k, v));
+        assert_eq!(tree.rank(k), i);
+        i += 1;
+    }
+    assert!(tree.nth(i).is_none());
+    // "b" sorts after all keys starting with it, only "cherry" lies before "c".
+    assert_eq!(tree.range_count(&"b"..&"c"), 1);
+    assert_eq!(tree.range_count(&"apricot"..=&"ban"), 4);
+}
+
+#[test]
+fn longest_prefix_match() {
+    use crate::key::{Ipv4Net, Ipv6Net};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let net = |a, b, c, d, len| Ipv4Net::new(Ipv4Addr::new(a, b, c, d), len);
+    let lookup = |routes: &Aart<Ipv4Net, &'static str>, a, b, c, d| {
+        let addr = Ipv4Net::from(Ipv4Addr::new(a, b, c, d));
+        routes.longest_prefix_match(&addr).map(|(k, v)| (k, *v))
+    };
+    let mut routes = Aart::<Ipv4Net, &str>::new();
+    routes.insert(&net(0, 0, 0, 0, 0), "default");
+    routes.insert(&net(10, 0, 0, 0, 8), "ten");
+    routes.insert(&net(10, 1, 0, 0, 16), "ten-one");
+    routes.insert(&net(10, 1, 2, 3, 32), "host");
+    let snapshot = routes.clone();
+
+    assert_eq!(
+        lookup(&routes, 10, 1, 2, 3),
+        Some((net(10, 1, 2, 3, 32), "host"))
+    );
+    assert_eq!(
+        lookup(&routes, 10, 1, 2, 4),
+        Some((net(10, 1, 0, 0, 16), "ten-one"))
+    );
+    assert_eq!(
+        lookup(&routes, 10, 2, 3, 4),
+        Some((net(10, 0, 0, 0, 8), "ten"))
+    );
+    assert_eq!(
+        lookup(&routes, 8, 8, 8, 8),
+        Some((net(0, 0, 0, 0, 0), "default"))
+    );
+    assert_eq!(format!("{:?}", net(10, 1, 2, 3, 16)), "10.1.0.0/16");
+
+    routes.remove(&net(10, 1, 0, 0, 16));
+    assert_eq!(
+        lookup(&routes, 10, 1, 2, 4),
+        Some((net(10, 0, 0, 0, 8), "ten"))
+    );
+    assert_eq!(
+        lookup(&snapshot, 10, 1, 2, 4),
+        Some((net(10, 1, 0, 0, 16), "ten-one"))
+    );
+
+    let mut routes = Aart::<Ipv6Net, u32>::new();
+    let v6 = |s: &str, len| Ipv6Net::new(s.parse::<Ipv6Addr>().unwrap(), len);
+    routes.insert(&v6("2001:db8::", 32), 1);
+    routes.insert(&v6("2001:db8:1::", 48), 2);
+    let addr = Ipv6Net::from("2001:db8:1::7".parse::<Ipv6Addr>().unwrap());
+    let found = routes.longest_prefix_match(&addr).map(|(k, v)| (k, *v));
+    assert_eq!(found, Some((v6("2001:db8:1::", 48), 2)));
+
+    let mut urls = Aart::<str, u32>::new();
+    urls.insert("/", 0);
+    urls.insert("/api", 1);
+    urls.insert("/api/users", 2);
+    let lookup = |x| urls.longest_prefix_match(x).map(|(k, v)| (k, *v));
+    assert_eq!(lookup("/api/users/7"), Some(("/api/users".to_owned(), 2)));
+    assert_eq!(lookup("/api/user"), Some(("/api".to_owned(), 1)));
+    assert_eq!(lookup("/index.html"), Some(("/".to_owned(), 0)));
+    assert_eq!(lookup("api"), None);
+}
+
+#[test]
+fn fuzzy() {
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, x) in a.chars().enumerate() {
+            let mut next = vec![i + 1];
+            for (j, y) in b.iter().enumerate() {
+                next.push(
+                    (row[j] + (x != *y) as usize)
+                        .min(row[j + 1] + 1)
+                        .min(next[j] + 1),
+                );
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    let words = [
+        "apple", "apply", "ample", "maple", "applet", "app", "banana", "bandana", "cafe", "café",
+        "caffe", "naïve", "native", "", "a",
+    ];
+    let mut tree = Aart::<str, usize>::new();
+    for (i, w) in words.iter().enumerate() {
+        tree.insert(w, i);
+    }
+    // Searching a snapshot must not see later changes.
+    let snapshot = tree.clone();
+    tree.remove("apply");
+    tree.remove("café");
+
+    for tree in [&tree, &snapshot] {
+        for query in ["apple", "aple", "cafe", "naive", "banan", "", "zzzz", "ä"] {
+            for max in 0..3 {
+                let found: Vec<_> = tree.fuzzy(query, max).map(|(k, v, d)| (k, *v, d)).collect();
+                // Matches are returned in the order of the tree.
+                let mut expect = Vec::new();
+                let mut iter = tree.iter();
+                while let Some((k, v)) = iter.next() {
+                    let d = distance(k, query);
+                    if d <= max {
+                        expect.push((k.to_owned(), *v, d));
+                    }
+                }
+                assert_eq!(found, expect, "query {query:?} max {max}");
+            }
+        }
+    }
+    assert_eq!(snapshot.fuzzy("apply", 0).count(), 1);
+    assert_eq!(tree.fuzzy("apply", 0).count(), 0);
+}
+
+#[test]
+fn scan_glob() {
+    let keys = [
+        "user:1:session",
+        "user:2:session",
+        "user:2:profile",
+        "user:12:session",
+        "user::session",
+        "user:é:session",
+        "admin:1:session",
+        "user",
+        "[user]",
+        "a*b",
+        "",
+    ];
+    let mut tree = Aart::<str, usize>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+
+    let scan = |tree: &Aart<str, usize>, pattern| {
+        let mut found: Vec<_> = tree.scan_glob(pattern).map(|(k, _)| k).collect();
+        found.sort();
+        found
+    };
+    let expect = |x: &[&str]| {
+        let mut x: Vec<_> = x.iter().map(|x| x.to_string()).collect();
+        x.sort();
+        x
+    };
+
+    assert_eq!(
+        scan(&tree, "user:*:session"),
+        expect(&[
+            "user:1:session",
+            "user:2:session",
+            "user:12:session",
+            "user::session",
+            "user:é:session"
+        ])
+    );
+    assert_eq!(
+        scan(&tree, "user:?:session"),
+        expect(&["user:1:session", "user:2:session", "user:é:session"])
+    );
+    assert_eq!(
+        scan(&tree, "user:[1-2]:*"),
+        expect(&["user:1:session", "user:2:session", "user:2:profile"])
+    );
+    assert_eq!(
+        scan(&tree, "user:[^1]:s*"),
+        expect(&["user:2:session", "user:é:session"])
+    );
+    assert_eq!(
+        scan(&tree, "*:1:*"),
+        expect(&["user:1:session", "admin:1:session"])
+    );
+    assert_eq!(scan(&tree, "user"), expect(&["user"]));
+    assert_eq!(scan(&tree, "a\\*b"), expect(&["a*b"]));
+    assert_eq!(scan(&tree, "\\[user]"), expect(&["[user]"]));
+    assert_eq!(scan(&tree, "[user"), expect(&[]));
+    assert_eq!(scan(&tree, ""), expect(&[""]));
+    assert_eq!(scan(&tree, "*").len(), keys.len());
+
+    // Matches are returned in key order.
+    let found: Vec<_> = tree.scan_glob("*").map(|(k, v)| (k, *v)).collect();
+    let mut iter = tree.iter();
+    let mut all = Vec::new();
+    while let Some((k, v)) = iter.next() {
+        all.push((k.to_owned(), *v));
+    }
+    assert_eq!(found, all);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn scan_regex() {
+    let keys = [
+        "user:1:session",
+        "user:2:session",
+        "user:12:session",
+        "user:é:session",
+        "admin:1:session",
+        "user",
+        "",
+    ];
+    let mut tree = Aart::<str, usize>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+
+    let scan = |tree: &Aart<str, usize>, pattern| {
+        let mut found: Vec<_> = tree.scan_regex(pattern).unwrap().map(|(k, _)| k).collect();
+        found.sort();
+        found
+    };
+    assert_eq!(
+        scan(&tree, r"user:\d+:session"),
+        ["user:12:session", "user:1:session", "user:2:session"]
+    );
+    assert_eq!(scan(&tree, r"user:.:session").len(), 3);
+    // The expression has to match the whole key.
+    assert_eq!(scan(&tree, "user"), ["user"]);
+    assert_eq!(scan(&tree, "use"), Vec::<String>::new());
+    assert_eq!(scan(&tree, "user|use|u"), ["user"]);
+    assert_eq!(
+        scan(&tree, "(admin|user):1:.*"),
+        ["admin:1:session", "user:1:session"]
+    );
+    assert_eq!(scan(&tree, ".*").len(), keys.len());
+    assert!(tree.scan_regex("(").is_err());
+}
+
+#[test]
+fn cursor() {
+    let mut rng = thread_rng();
+    let mut tree = Aart::<str, u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..2_000 {
+        let k: u64 = rng.gen();
+        let key = format!("{:x}", k >> (k % 48));
+        tree.insert(&key, k);
+        keys.push(key);
+    }
+    sort_str(&mut keys);
+    keys.dedup();
+
+    // The cursor of a snapshot does not see later changes.
+    let snapshot = tree.clone();
+    for k in keys.iter().step_by(2) {
+        tree.remove(k);
+    }
+
+    let mut cursor = snapshot.cursor();
+    assert_eq!(cursor.key(), None);
+    cursor.next();
+    for k in keys.iter() {
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        assert_eq!(cursor.value(), snapshot.get(k));
+        cursor.next();
+    }
+    // Moving past the end goes to the ghost position and from there around to the start.
+    assert_eq!(cursor.key(), None);
+    cursor.next();
+    assert_eq!(cursor.key(), Some(keys[0].as_str()));
+    cursor.prev();
+    assert_eq!(cursor.key(), None);
+    cursor.prev();
+    for k in keys.iter().rev() {
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        cursor.prev();
+    }
+    assert_eq!(cursor.key(), None);
+
+    let bytes = |x: &str| x.bytes().chain([INVALID_STR_BYTE]).collect::<Vec<_>>();
+    let mut cursor = tree.cursor();
+    let remaining: Vec<_> = keys.iter().skip(1).step_by(2).collect();
+    for _ in 0..1_000 {
+        let k: u64 = rng.gen();
+        let probe = format!("{:x}", k >> (k % 64));
+        let expect = remaining.iter().find(|x| bytes(x) >= bytes(&probe));
+        cursor.seek(&probe);
+        assert_eq!(cursor.key(), expect.map(|x| x.as_str()), "seek {probe:?}");
+    }
+    for (i, k) in remaining.iter().enumerate().step_by(7) {
+        cursor.seek(k);
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        cursor.prev();
+        assert_eq!(
+            cursor.key(),
+            i.checked_sub(1).map(|x| remaining[x].as_str())
+        );
+    }
+    cursor.seek_last();
+    assert_eq!(cursor.key(), remaining.last().map(|x| x.as_str()));
+    cursor.seek_first();
+    assert_eq!(cursor.key(), remaining.first().map(|x| x.as_str()));
+}
+
+#[test]
+fn inline_values() {
+    let mut keys: Vec<u64> = (0..10_000).map(|_| thread_rng().gen()).collect();
+    keys.sort_by_key(|x| x.to_le_bytes());
+    keys.dedup();
+
+    let mut tree = Aart::<u64, u64, Inline<u64>>::with_storage();
+    for k in keys.iter() {
+        assert_eq!(tree.insert(k, !k), None);
+    }
+    let snapshot = tree.clone();
+    for k in keys.iter() {
+        assert_eq!(tree.insert(k, *k), Some(!k));
+    }
+    assert_eq!(tree.len(), keys.len());
+
+    let mut cursor = tree.cursor();
+    for k in keys.iter() {
+        cursor.next();
+        assert_eq!(cursor.value(), Some(k));
+    }
+    cursor.next();
+    assert_eq!(cursor.value(), None);
+    // Copies of leafs carry their own copy of the value.
+    for k in keys.iter().step_by(2) {
+        assert_eq!(tree.remove(k), Some(*k));
+    }
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k), (i % 2 == 1).then_some(k));
+        assert_eq!(snapshot.get(k), Some(&!k));
+    }
+
+    let sorted =
+        Aart::<u64, u64, Inline<u64>>::from_sorted_iter_with_storage(keys.iter().map(|x| (x, *x)));
+    let odd = sorted.difference(&tree, |_, _| None);
+    assert_eq!(odd.len(), keys.len().div_ceil(2));
+    let mut cursor = odd.cursor();
+    cursor.seek_last();
+    assert_eq!(cursor.value(), keys.iter().step_by(2).next_back());
+}
+
+#[test]
+fn auto_values() {
+    // Values up to the size of a pointer are stored in the leaf, larger values are shared.
+    let mut small = Aart::<u64, u64>::new();
+    let mut large = Aart::<u64, String>::new();
+    for k in 0..1000u64 {
+        assert_eq!(small.insert(&k, k), None);
+        assert_eq!(large.insert(&k, k.to_string()), None);
+    }
+    let (small_snapshot, large_snapshot) = (small.clone(), large.clone());
+    for k in 0..1000u64 {
+        assert_eq!(small.insert(&k, k + 1), Some(k));
+        assert_eq!(large.remove(&k), Some(k.to_string()));
+    }
+    assert!(large.is_empty());
+    assert_eq!(small.get(&7), Some(&8));
+    assert_eq!(small_snapshot.get(&7), Some(&7));
+    assert_eq!(large_snapshot.get(&7).map(String::as_str), Some("7"));
+
+    // Shared values are found in both trees without calling the closure.
+    let same = large_snapshot.intersection(&large_snapshot.clone(), |_, _| unreachable!());
+    assert_eq!(same.len(), 1000);
+    let merged = small.intersection(&small_snapshot, |a, b| a.min(b) * 2);
+    assert_eq!(merged.get(&7), Some(&14));
+}
+
+#[test]
+fn leaf_prefixes() {
+    // Long keys sharing prefixes of different lengths, so leafs are moved up and down the tree.
+    let mut tree = Aart::<str, usize>::new();
+    let mut model = BTreeMap::new();
+    for i in 0..10_000 {
+        let k: u64 = thread_rng().gen_range(0..4096);
+        let key = format!("{}{:x}", "prefix/".repeat((k % 3) as usize), k >> (k % 12));
+        if thread_rng().gen_bool(0.6) {
+            assert_eq!(tree.insert(&key, i), model.insert(key, i));
+        } else {
+            assert_eq!(tree.remove(&key), model.remove(&key));
+        }
+        if i % 1000 == 0 {
+            let merged = tree.union_with(&Aart::from_sorted_iter([("prefix/", 0)]), |a, _| *a);
+            assert_eq!(merged.get("prefix/"), model.get("prefix/").or(Some(&0)));
+        }
+    }
+
+    let mut iter = RawIterator::new(tree.inner.root());
+    let mut count = 0;
+    while let Some((key, leaf)) = iter.next() {
+        assert_eq!(leaf.key(), key);
+        assert!(key.ends_with(leaf.prefix()));
+        count += 1;
+    }
+    assert_eq!(count, model.len());
+    for (k, v) in model.iter() {
+        assert_eq!(tree.get(k), Some(v));
+        assert_eq!(tree.get(&k[..k.len() - 1]), model.get(&k[..k.len() - 1]));
+        // Branch prefixes are skipped by get, so keys differing only there must not match.
+        let changed = k.replacen('p', "q", 1);
+        assert_eq!(tree.get(&changed), model.get(&changed));
+    }
+}
+
+#[test]
+fn olc_model() {
+    // Keys of different lengths sharing prefixes, so nodes grow, shrink, split and collapse.
+    let tree = OlcArt::<str, usize>::new();
+    let mut model = BTreeMap::new();
+    for i in 0..20_000 {
+        let k: u64 = thread_rng().gen_range(0..4096);
+        let key = format!("{}{:x}", "prefix/".repeat((k % 3) as usize), k >> (k % 12));
+        if thread_rng().gen_bool(0.6) {
+            assert_eq!(
+                tree.insert(&key, i).unwrap(),
+                model.insert(key, i).is_none()
+            );
+        } else {
+            assert_eq!(tree.remove(&key), model.remove(&key).is_some());
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+    let guard = crate::olc::pin();
+    for (k, v) in model.iter() {
+        assert_eq!(tree.get(k, &guard), Some(v));
+        assert_eq!(
+            tree.get(&k[..k.len() - 1], &guard),
+            model.get(&k[..k.len() - 1])
+        );
+    }
+    for k in model.keys() {
+        assert!(tree.remove(k));
+    }
+    assert!(tree.is_empty());
+    assert_eq!(tree.get("prefix/", &guard), None);
+}
+
+#[test]
+fn olc_concurrent() {
+    let tree = Arc::new(OlcArt::<u64, u64>::new());
+    let writers: Vec<_> = (0..4u64)
+        .map(|t| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for i in 0..4000 {
+                    assert!(tree.insert(&(i * 4 + t), i).unwrap());
+                }
+                for i in (0..4000).step_by(2) {
+                    assert!(tree.remove(&(i * 4 + t)));
+                }
+                for i in (1..4000).step_by(4) {
+                    assert!(!tree.insert(&(i * 4 + t), i).unwrap());
+                }
+            })
+        })
+        .collect();
+    let readers: Vec<_> = (0..4u64)
+        .map(|_| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    let guard = crate::olc::pin();
+                    for k in 0..16_000u64 {
+                        // a key is either missing or has its value.
+                        if let Some(v) = tree.get(&k, &guard) {
+                            assert_eq!(*v, k / 4);
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in writers.into_iter().chain(readers) {
+        t.join().unwrap();
+    }
+
+    assert_eq!(tree.len(), 8000);
+    let guard = crate::olc::pin();
+    for k in 0..16_000u64 {
+        let i = k / 4;
+        assert_eq!(tree.get(&k, &guard), (i % 2 == 1).then_some(&i));
+    }
+}
+
+/// A key whose bytes are not prefix free.
+struct RawKey(Vec<u8>);
+
+impl crate::key::Key for RawKey {
+    type Bytes = [u8];
+
+    fn as_key_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[test]
+fn olc_prefix_keys() {
+    let tree = OlcArt::<RawKey, u8>::new();
+    let key = |k: &str| RawKey(k.as_bytes().to_vec());
+    assert!(tree.insert(&key("ab"), 0).unwrap());
+    // A prefix of a leaf and a leaf with a prefix.
+    assert!(tree.insert(&key("a"), 1).is_err());
+    assert!(tree.insert(&key("abc"), 1).is_err());
+    assert!(tree.remove(&key("ab")));
+    tree.insert(&key("abc"), 2).unwrap();
+    tree.insert(&key("abd"), 3).unwrap();
+    // Keys ending within the prefix of a node and right after it.
+    assert!(tree.insert(&key("a"), 4).is_err());
+    assert!(tree.insert(&key("ab"), 4).is_err());
+    assert_eq!(tree.len(), 2);
+    let guard = crate::olc::pin();
+    assert_eq!(tree.get(&key("abc"), &guard), Some(&2));
+    assert_eq!(tree.get(&key("ab"), &guard), None);
+}
+
+#[test]
+fn rowex_prefix_keys() {
+    let tree = RowexArt::<RawKey, u8>::new();
+    let key = |k: &str| RawKey(k.as_bytes().to_vec());
+    assert!(tree.insert(&key("ab"), 0).unwrap());
+    // A prefix of a leaf and a leaf with a prefix.
+    assert!(tree.insert(&key("a"), 1).is_err());
+    assert!(tree.insert(&key("abc"), 1).is_err());
+    assert!(tree.remove(&key("ab")));
+    tree.insert(&key("abc"), 2).unwrap();
+    tree.insert(&key("abd"), 3).unwrap();
+    // Keys ending within the prefix of a node and right after it.
+    assert!(tree.insert(&key("a"), 4).is_err());
+    assert!(tree.insert(&key("ab"), 4).is_err());
+    assert_eq!(tree.len(), 2);
+    let guard = crate::olc::pin();
+    assert_eq!(tree.get(&key("abc"), &guard), Some(&2));
+    assert_eq!(tree.get(&key("ab"), &guard), None);
+}
+
+#[test]
+fn rowex_model() {
+    let tree = RowexArt::<str, usize>::new();
+    let mut model = BTreeMap::new();
+    for i in 0..20_000 {
+        let k: u64 = thread_rng().gen_range(0..4096);
+        let key = format!("{}{:x}", "prefix/".repeat((k % 3) as usize), k >> (k % 12));
+        if thread_rng().gen_bool(0.6) {
+            assert_eq!(
+                tree.insert(&key, i).unwrap(),
+                model.insert(key, i).is_none()
+            );
+        } else {
+            assert_eq!(tree.remove(&key), model.remove(&key).is_some());
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+    let guard = crate::olc::pin();
+    for (k, v) in model.iter() {
+        assert_eq!(tree.get(k, &guard), Some(v));
+        assert_eq!(
+            tree.get(&k[..k.len() - 1], &guard),
+            model.get(&k[..k.len() - 1])
+        );
+    }
+    for k in model.keys() {
+        assert!(tree.remove(k));
+    }
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn rowex_concurrent() {
+    let tree = Arc::new(RowexArt::<u64, u64>::new());
+    let writers: Vec<_> = (0..4u64)
+        .map(|t| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for i in 0..4000 {
+                    assert!(tree.insert(&(i * 4 + t), i).unwrap());
+                }
+                for i in (0..4000).step_by(2) {
+                    assert!(tree.remove(&(i * 4 + t)));
+                }
+            })
+        })
+        .collect();
+    let readers: Vec<_> = (0..4u64)
+        .map(|_| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    let guard = crate::olc::pin();
+                    for k in 0..16_000u64 {
+                        if let Some(v) = tree.get(&k, &guard) {
+                            assert_eq!(*v, k / 4);
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in writers.into_iter().chain(readers) {
+        t.join().unwrap();
+    }
+
+    assert_eq!(tree.len(), 8000);
+    let guard = crate::olc::pin();
+    for k in 0..16_000u64 {
+        let i = k / 4;
+        assert_eq!(tree.get(&k, &guard), (i % 2 == 1).then_some(&i));
+    }
+}
+
+#[test]
+fn sharded_model() {
+    fn bytes(key: &str) -> Vec<u8> {
+        let mut bytes = key.as_bytes().to_vec();
+        bytes.push(INVALID_STR_BYTE);
+        bytes
+    }
+
+    let tree = ShardedAart::<str, usize, 7>::new();
+    // keyed by the key bytes as that is the order the tree iterates in.
+    let mut model = BTreeMap::new();
+    for i in 0..20_000 {
+        let k: u32 = thread_rng().gen_range(0..1 << 14);
+        let key = format!("{}{:x}", char::from(b' ' + (k % 95) as u8), k >> (k % 7));
+        if thread_rng().gen_bool(0.6) {
+            let prev = model.insert(bytes(&key), (key.clone(), i));
+            assert_eq!(tree.insert(&key, i).map(|x| *x), prev.map(|x| x.1));
+        } else {
+            let prev = model.remove(&bytes(&key));
+            assert_eq!(tree.remove(&key).map(|x| *x), prev.map(|x| x.1));
+        }
+    }
+    assert_eq!(tree.len(), model.len());
+
+    let snapshot = tree.snapshot();
+    let mut iter = snapshot.iter();
+    for (k, v) in model.values() {
+        assert_eq!(iter.next(), Some((k.as_str(), v)));
+        assert_eq!(tree.get(k).map(|x| *x), Some(*v));
+        assert_eq!(snapshot.get(k), Some(v));
+    }
+    assert_eq!(iter.next(), None);
+
+    let keys: Vec<_> = model.values().map(|x| x.0.clone()).collect();
+    for _ in 0..100 {
+        let mut a = keys.choose(&mut thread_rng()).unwrap();
+        let mut b = keys.choose(&mut thread_rng()).unwrap();
+        if bytes(a) > bytes(b) {
+            std::mem::swap(&mut a, &mut b);
+        }
+        let mut iter =
+            snapshot.range::<(Bound<&str>, Bound<&str>)>((Bound::Excluded(a), Bound::Included(b)));
+        let range = (Bound::Excluded(bytes(a)), Bound::Included(bytes(b)));
+        for (k, v) in model.range(range).map(|x| x.1) {
+            assert_eq!(iter.next(), Some((k.as_str(), v)));
+        }
+        assert_eq!(iter.next(), None);
+    }
+
+    for k in keys.iter() {
+        assert!(tree.remove(k).is_some());
+    }
+    assert!(tree.is_empty());
+    // the snapshot is not affected by the removals.
+    assert_eq!(
+        snapshot.iter().next().map(|x| x.0),
+        keys.first().map(|x| x.as_str())
+    );
+}
+
+#[test]
+fn sharded_concurrent() {
+    let tree = Arc::new(ShardedAart::<u64, u64, 4>::new());
+    let threads: Vec<_> = (0..4u64)
+        .map(|t| {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for i in 0..4000 {
+                    assert!(tree.insert(&(i * 4 + t), i).is_none());
+                }
+                for i in (0..4000).step_by(2) {
+                    assert_eq!(tree.remove(&(i * 4 + t)).map(|x| *x), Some(i));
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    assert_eq!(tree.len(), 8000);
+    for k in 0..16_000u64 {
+        let i = k / 4;
+        assert_eq!(tree.get(&k).map(|x| *x), (i % 2 == 1).then_some(i));
+    }
+}
+
+#[test]
+fn watch_prefix() {
+    let map = ConcurrentAart::<str, usize>::new();
+    map.insert("users/bob", 0);
+    let mut users = map.watch(b"users/");
+    let mut bob = map.watch(b"users/bob");
+    assert!(users.try_recv().is_none());
+
+    map.insert("groups/admin", 1);
+    map.insert("users/alice", 2);
+    assert!(bob.try_recv().is_none());
+    let version = users.try_recv().unwrap();
+    assert_eq!(version.get("users/alice"), Some(&2));
+    assert!(users.try_recv().is_none());
+
+    // changes are coalesced into the latest version.
+    map.insert("users/bob", 3);
+    map.remove("users/alice");
+    map.remove("users/carol");
+    let version = users.try_recv().unwrap();
+    assert_eq!(version.get("users/alice"), None);
+    assert_eq!(version.get("users/bob"), Some(&3));
+    assert!(users.try_recv().is_none());
+    assert_eq!(bob.try_recv().unwrap().get("users/bob"), Some(&3));
+
+    map.remove("groups/admin");
+    assert!(users.try_recv().is_none());
+    assert!(bob.try_recv().is_none());
+
+    let waiter = thread::spawn(move || {
+        let version = users.recv().unwrap();
+        assert_eq!(version.get("users/dave"), Some(&4));
+        assert!(users.recv().is_none());
+    });
+    map.insert("users/dave", 4);
+    drop(map);
+    waiter.join().unwrap();
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn watch_stream() {
+    use futures_core::Stream;
+    use std::{
+        pin::Pin,
+        task::{Context, Poll, Waker},
+    };
+
+    let map = ConcurrentAart::<u64, u64>::new();
+    let mut watcher = map.watch(&[]);
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(Pin::new(&mut watcher).poll_next(&mut cx).is_pending());
+    map.insert(&1, 1);
+    match Pin::new(&mut watcher).poll_next(&mut cx) {
+        Poll::Ready(Some(version)) => assert_eq!(version.get(&1), Some(&1)),
+        _ => panic!("expected a version"),
+    }
+    drop(map);
+    assert!(matches!(
+        Pin::new(&mut watcher).poll_next(&mut cx),
+        Poll::Ready(None)
+    ));
+}
+
+/// Exhaustively checks interleavings of concurrent operations on the [`OlcArt`] and [`RowexArt`].
+///
+/// Run with `RUSTFLAGS="--cfg crossbeam_loom" cargo test -p aart --features loom --release loom_models`.
+#[cfg(crossbeam_loom)]
+mod loom_models {
+    use crate::{
+        olc::{pin, OlcArt},
+        prim::thread,
+        RowexArt,
+    };
+    use std::sync::Arc;
+
+    /// Every operation touches many atomics, bound the preemptions to keep the amount of
+    /// interleavings which are checked manageable.
+    fn model(f: impl Fn() + Sync + Send + 'static) {
+        let mut builder = loom::model::Builder::new();
+        builder.preemption_bound = Some(3);
+        builder.check(f);
+    }
+
+    #[test]
+    fn olc_loom_insert_split() {
+        model(|| {
+            let tree = Arc::new(OlcArt::<str, u8>::new());
+            tree.insert("ab", 0).unwrap();
+            tree.insert("ac", 1).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    // Splits the prefix of the root.
+                    assert!(tree.insert("b", 2).unwrap());
+                })
+            };
+            // Adds a branch to the root or to the node it was split into.
+            assert!(tree.insert("ad", 3).unwrap());
+            t.join().unwrap();
+
+            let guard = pin();
+            for (key, value) in [("ab", 0), ("ac", 1), ("b", 2), ("ad", 3)] {
+                assert_eq!(tree.get(key, &guard), Some(&value));
+            }
+            assert_eq!(tree.len(), 4);
+        });
+    }
+
+    #[test]
+    fn olc_loom_insert_same_key() {
+        model(|| {
+            let tree = Arc::new(OlcArt::<str, u8>::new());
+            tree.insert("ab", 0).unwrap();
+            tree.insert("ac", 1).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || tree.insert("ad", 2).unwrap())
+            };
+            let inserted = tree.insert("ad", 3).unwrap();
+            // Exactly one of the inserts adds the key.
+            assert_ne!(t.join().unwrap(), inserted);
+
+            let guard = pin();
+            assert!(matches!(tree.get("ad", &guard), Some(&2) | Some(&3)));
+            assert_eq!(tree.len(), 3);
+            assert!(tree.remove("ad"));
+            assert_eq!(tree.get("ad", &guard), None);
+        });
+    }
+
+    #[test]
+    fn olc_loom_grow_while_reading() {
+        model(|| {
+            let tree = Arc::new(OlcArt::<u8, u8>::new());
+            for k in 0..4 {
+                tree.insert(&k, k).unwrap();
+            }
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    // The root is full and replaced by a larger node.
+                    assert!(tree.insert(&4, 4).unwrap());
+                })
+            };
+            let guard = pin();
+            assert_eq!(tree.get(&3, &guard), Some(&3));
+            assert!(matches!(tree.get(&4, &guard), None | Some(&4)));
+            t.join().unwrap();
+            assert_eq!(tree.get(&4, &guard), Some(&4));
+        });
+    }
+
+    #[test]
+    fn olc_loom_remove_collapse() {
+        model(|| {
+            let tree = Arc::new(OlcArt::<str, u8>::new());
+            tree.insert("aa", 0).unwrap();
+            tree.insert("ab", 1).unwrap();
+            tree.insert("b", 2).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    // Collapses the node below the root into the remaining leaf.
+                    assert!(tree.remove("aa"));
+                })
+            };
+            let guard = pin();
+            assert!(matches!(tree.get("aa", &guard), None | Some(&0)));
+            assert_eq!(tree.get("ab", &guard), Some(&1));
+            assert!(tree.insert("ac", 3).unwrap());
+            t.join().unwrap();
+
+            assert_eq!(tree.get("aa", &guard), None);
+            assert_eq!(tree.get("ab", &guard), Some(&1));
+            assert_eq!(tree.get("ac", &guard), Some(&3));
+            assert_eq!(tree.len(), 3);
+        });
+    }
+
+    #[test]
+    fn rowex_loom_insert_same_key() {
+        model(|| {
+            let tree = Arc::new(RowexArt::<str, u8>::new());
+            tree.insert("ab", 0).unwrap();
+            tree.insert("ac", 1).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || tree.insert("ad", 2).unwrap())
+            };
+            let inserted = tree.insert("ad", 3).unwrap();
+            assert_ne!(t.join().unwrap(), inserted);
+
+            let guard = pin();
+            assert!(matches!(tree.get("ad", &guard), Some(&2) | Some(&3)));
+            assert_eq!(tree.len(), 3);
+        });
+    }
+
+    #[test]
+    fn rowex_loom_split_while_reading() {
+        model(|| {
+            let tree = Arc::new(RowexArt::<str, u8>::new());
+            tree.insert("ab", 0).unwrap();
+            tree.insert("ac", 1).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    // Splits the prefix of the root.
+                    assert!(tree.insert("b", 2).unwrap());
+                })
+            };
+            let guard = pin();
+            // Readers never miss an entry, even in a node which is being replaced.
+            assert_eq!(tree.get("ab", &guard), Some(&0));
+            assert_eq!(tree.get("ac", &guard), Some(&1));
+            assert!(matches!(tree.get("b", &guard), None | Some(&2)));
+            t.join().unwrap();
+            assert_eq!(tree.get("b", &guard), Some(&2));
+        });
+    }
+
+    #[test]
+    fn rowex_loom_remove_collapse() {
+        model(|| {
+            let tree = Arc::new(RowexArt::<str, u8>::new());
+            tree.insert("aa", 0).unwrap();
+            tree.insert("ab", 1).unwrap();
+            tree.insert("b", 2).unwrap();
+            let t = {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    assert!(tree.remove("aa"));
+                })
+            };
+            let guard = pin();
+            assert_eq!(tree.get("ab", &guard), Some(&1));
+            assert!(tree.insert("ac", 3).unwrap());
+            t.join().unwrap();
+
+            assert_eq!(tree.get("aa", &guard), None);
+            assert_eq!(tree.get("ab", &guard), Some(&1));
+            assert_eq!(tree.get("ac", &guard), Some(&3));
+            assert_eq!(tree.len(), 3);
+        });
+    }
+}
@@ -0,0 +1,428 @@
+//! An adaptive radix tree using read-optimized write exclusion (ROWEX).
+//!
+//! [`RowexArt`] uses the same nodes as [`OlcArt`](crate::OlcArt) but synchronizes differently.
+//! Writers lock the nodes they change and wait for each other, while readers ignore the locks
+//! entirely and never restart. This works because every change to a node is made visible with a
+//! single atomic store, so a reader always sees a consistent node. A node which needs a different
+//! size or prefix is replaced by a copy in the same way, a reader which is still in the replaced
+//! node sees the node as it was right before it was replaced.
+
+use crate::{
+    iter::key_bytes,
+    key::{Key, KeyBytes, KeyPrefixError},
+    olc::{
+        lock::VersionLock,
+        nodes::{NodeHeader, NodeKind, NodeRef},
+        pin, Guard,
+    },
+    prim::{
+        hint,
+        sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    },
+};
+use std::{marker::PhantomData, ptr};
+
+/// A concurrent adaptive radix tree where readers never wait or retry.
+///
+/// Values are only ever dropped once no reader can observe them, references to values returned
+/// by [`RowexArt::get`] are therefore valid for as long as the [`Guard`] they were read with.
+pub struct RowexArt<K: Key + ?Sized, V> {
+    root: AtomicPtr<NodeHeader>,
+    /// Protects the root pointer, acting as the lock of the parent of the root node.
+    lock: VersionLock,
+    len: AtomicUsize,
+    _marker: PhantomData<(*const K, *const V)>,
+}
+
+unsafe impl<K: Key + ?Sized, V: Send + Sync> Send for RowexArt<K, V> {}
+unsafe impl<K: Key + ?Sized, V: Send + Sync> Sync for RowexArt<K, V> {}
+
+/// The slot containing a pointer to a node, along with the lock of the node the slot is part of.
+#[derive(Clone, Copy)]
+struct Slot<'a> {
+    lock: &'a VersionLock,
+    ptr: &'a AtomicPtr<NodeHeader>,
+}
+
+impl Slot<'_> {
+    fn load(&self) -> Option<NodeRef> {
+        unsafe { NodeRef::new(self.ptr.load(Ordering::Acquire)) }
+    }
+
+    /// Lock the node the slot is part of, returns `None` if that node is obsolete or the slot no
+    /// longer contains the expected node.
+    fn lock(&self, expected: Option<NodeRef>) -> Option<()> {
+        if !self.lock.lock() {
+            return None;
+        }
+        if self.load() != expected {
+            self.lock.unlock();
+            return None;
+        }
+        Some(())
+    }
+
+    fn replace(&self, node: Option<NodeRef>) {
+        self.ptr.store(
+            node.map(NodeRef::as_ptr).unwrap_or_else(ptr::null_mut),
+            Ordering::Release,
+        );
+    }
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+impl<K: Key + ?Sized, V> RowexArt<K, V> {
+    pub fn new() -> Self {
+        RowexArt {
+            root: AtomicPtr::new(ptr::null_mut()),
+            lock: VersionLock::new(),
+            len: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn root_slot(&self) -> Slot<'_> {
+        Slot {
+            lock: &self.lock,
+            ptr: &self.root,
+        }
+    }
+
+    /// Returns a reference to the value of the key, which stays valid while the guard is alive.
+    pub fn get<'g>(&self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        let _ = guard;
+        let key = key.as_key_bytes();
+        let mut node = self.root_slot().load()?;
+        let mut depth = 0;
+        while !node.is_leaf() {
+            let prefix = &node.header().prefix;
+            if !(0..prefix.len()).all(|i| key.at(depth + i) == Some(prefix[i])) {
+                return None;
+            }
+            depth += prefix.len();
+            let slot = node.find(key.at(depth)?)?;
+            node = unsafe { NodeRef::new(slot.load(Ordering::Acquire)) }?;
+            depth += 1;
+        }
+        let leaf = unsafe { node.as_leaf::<V>() };
+        let matches =
+            leaf.key.len() == key.len() && (0..key.len()).all(|i| key.at(i) == Some(leaf.key[i]));
+        matches.then_some(&leaf.value)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key, &pin()).is_some()
+    }
+
+    /// Insert a value, returns true if the key was not yet present.
+    ///
+    /// The value previously stored under the key is dropped once no reader can observe it.
+    ///
+    /// # Errors
+    /// Returns [`KeyPrefixError`] if the bytes of the key are a prefix of the bytes of a stored key
+    /// or the other way around, which only happens for keys whose bytes aren't prefix free.
+    pub fn insert(&self, key: &K, value: V) -> Result<bool, KeyPrefixError> {
+        let guard = pin();
+        let key = key_bytes(key);
+        let leaf = NodeRef::new_leaf(key.clone().into_boxed_slice(), value);
+        loop {
+            match self.try_insert(&key, leaf, &guard) {
+                Some(Ok(inserted)) => {
+                    self.len.fetch_add(inserted as usize, Ordering::Relaxed);
+                    return Ok(inserted);
+                }
+                Some(Err(e)) => {
+                    // The leaf was never published so no reader can observe it.
+                    unsafe { leaf.free::<V>() };
+                    return Err(e);
+                }
+                None => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// A single attempt at inserting the leaf, returns `None` if the tree changed in a way which
+    /// requires the attempt to be restarted.
+    fn try_insert(
+        &self,
+        key: &[u8],
+        leaf: NodeRef,
+        guard: &Guard,
+    ) -> Option<Result<bool, KeyPrefixError>> {
+        let mut slot = self.root_slot();
+        let mut depth = 0;
+        loop {
+            let Some(node) = slot.load() else {
+                // Only the root slot is empty, the slots of branch nodes are found by their key.
+                slot.lock(None)?;
+                slot.replace(Some(leaf));
+                slot.lock.unlock();
+                return Some(Ok(true));
+            };
+
+            if node.is_leaf() {
+                let existing = unsafe { node.as_leaf::<V>() };
+                if *existing.key == *key {
+                    slot.lock(Some(node))?;
+                    slot.replace(Some(leaf));
+                    slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                    return Some(Ok(false));
+                }
+                // Split the leaf into a node with both leafs as branches.
+                let len = common_prefix(&existing.key[depth..], &key[depth..]);
+                if depth + len == existing.key.len().min(key.len()) {
+                    return Some(Err(KeyPrefixError));
+                }
+                let branch = NodeRef::new_branch(
+                    NodeKind::Node4,
+                    key[depth..depth + len].into(),
+                    &[
+                        (existing.key[depth + len], node.as_ptr()),
+                        (key[depth + len], leaf.as_ptr()),
+                    ],
+                );
+                if slot.lock(Some(node)).is_none() {
+                    unsafe { branch.free::<V>() };
+                    return None;
+                }
+                slot.replace(Some(branch));
+                slot.lock.unlock();
+                return Some(Ok(true));
+            }
+
+            let header = node.header();
+            let prefix = &header.prefix;
+            let len = common_prefix(prefix, &key[depth..]);
+            if len < prefix.len() {
+                if depth + len == key.len() {
+                    return Some(Err(KeyPrefixError));
+                }
+                // The key diverges within the prefix, replace the node by a new node with the
+                // common part of the prefix with a copy of the node and the leaf as branches.
+                slot.lock(Some(node))?;
+                if !header.lock.lock() {
+                    slot.lock.unlock();
+                    return None;
+                }
+                let copy =
+                    NodeRef::new_branch(header.kind(), prefix[len + 1..].into(), &node.branches());
+                let branch = NodeRef::new_branch(
+                    NodeKind::Node4,
+                    prefix[..len].into(),
+                    &[
+                        (prefix[len], copy.as_ptr()),
+                        (key[depth + len], leaf.as_ptr()),
+                    ],
+                );
+                slot.replace(Some(branch));
+                header.lock.unlock_obsolete();
+                slot.lock.unlock();
+                unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                return Some(Ok(true));
+            }
+            depth += prefix.len();
+
+            let Some(&byte) = key.get(depth) else {
+                return Some(Err(KeyPrefixError));
+            };
+            if let Some(ptr) = node.find(byte) {
+                slot = Slot {
+                    lock: &header.lock,
+                    ptr,
+                };
+                depth += 1;
+                continue;
+            }
+
+            // Slots are never reused, so a full node stays full until it is replaced.
+            if !node.is_full() {
+                if !header.lock.lock() {
+                    return None;
+                }
+                if node.find(byte).is_some() || node.is_full() {
+                    header.lock.unlock();
+                    return None;
+                }
+                node.insert(byte, leaf);
+                header.lock.unlock();
+                return Some(Ok(true));
+            }
+
+            // Replace the full node by a compacted or larger copy containing the leaf.
+            slot.lock(Some(node))?;
+            if !header.lock.lock() {
+                slot.lock.unlock();
+                return None;
+            }
+            if node.find(byte).is_some() {
+                header.lock.unlock();
+                slot.lock.unlock();
+                return None;
+            }
+            let mut branches = node.branches();
+            branches.push((byte, leaf.as_ptr()));
+            let grown =
+                NodeRef::new_branch(NodeKind::for_len(branches.len()), prefix.clone(), &branches);
+            slot.replace(Some(grown));
+            header.lock.unlock_obsolete();
+            slot.lock.unlock();
+            unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+            return Some(Ok(true));
+        }
+    }
+
+    /// Remove a value, returns true if the key was present.
+    ///
+    /// The removed value is dropped once no reader can observe it.
+    pub fn remove(&self, key: &K) -> bool {
+        let guard = pin();
+        let key = key_bytes(key);
+        loop {
+            if let Some(removed) = self.try_remove(&key, &guard) {
+                self.len.fetch_sub(removed as usize, Ordering::Relaxed);
+                return removed;
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// A single attempt at removing the key, returns `None` if the tree changed in a way which
+    /// requires the attempt to be restarted.
+    fn try_remove(&self, key: &[u8], guard: &Guard) -> Option<bool> {
+        let mut slot = self.root_slot();
+        // The slot of the node containing `slot` along with that node and the key of `slot`.
+        let mut parent: Option<(Slot, NodeRef, u8)> = None;
+        let mut depth = 0;
+        loop {
+            let Some(node) = slot.load() else {
+                return Some(false);
+            };
+
+            if node.is_leaf() {
+                if *unsafe { node.as_leaf::<V>() }.key != *key {
+                    return Some(false);
+                }
+                let Some((parent_slot, owner, byte)) = parent else {
+                    slot.lock(Some(node))?;
+                    slot.replace(None);
+                    slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                    return Some(true);
+                };
+
+                let header = owner.header();
+                let replace = |owner: NodeRef| {
+                    owner.header().len() == 2 || owner.should_shrink_after_remove()
+                };
+                if !replace(owner) {
+                    slot.lock(Some(node))?;
+                    if replace(owner) {
+                        slot.lock.unlock();
+                        return None;
+                    }
+                    owner.remove(byte);
+                    slot.lock.unlock();
+                    unsafe { guard.defer_unchecked(move || node.free::<V>()) };
+                    return Some(true);
+                }
+
+                parent_slot.lock(Some(owner))?;
+                if slot.lock(Some(node)).is_none() {
+                    parent_slot.lock.unlock();
+                    return None;
+                }
+                if !replace(owner) {
+                    slot.lock.unlock();
+                    parent_slot.lock.unlock();
+                    return None;
+                }
+                let mut branches = owner.branches();
+                branches.retain(|&(k, _)| k != byte);
+                let replacement = match *branches {
+                    [(other_key, other)] => {
+                        // Only a single branch remains, replace the owner by that branch.
+                        let other = unsafe { NodeRef::new(other) }.unwrap();
+                        if other.is_leaf() {
+                            other
+                        } else {
+                            // The prefix of the owner and the key of the branch move into the
+                            // prefix of the remaining node, which thus has to be copied.
+                            let other_header = other.header();
+                            if !other_header.lock.lock() {
+                                slot.lock.unlock();
+                                parent_slot.lock.unlock();
+                                return None;
+                            }
+                            let mut prefix = header.prefix.to_vec();
+                            prefix.push(other_key);
+                            prefix.extend_from_slice(&other_header.prefix);
+                            let copy = NodeRef::new_branch(
+                                other_header.kind(),
+                                prefix.into_boxed_slice(),
+                                &other.branches(),
+                            );
+                            other_header.lock.unlock_obsolete();
+                            unsafe { guard.defer_unchecked(move || other.free::<V>()) };
+                            copy
+                        }
+                    }
+                    _ => NodeRef::new_branch(
+                        NodeKind::for_len(branches.len()),
+                        header.prefix.clone(),
+                        &branches,
+                    ),
+                };
+                parent_slot.replace(Some(replacement));
+                header.lock.unlock_obsolete();
+                parent_slot.lock.unlock();
+                unsafe {
+                    guard.defer_unchecked(move || owner.free::<V>());
+                    guard.defer_unchecked(move || node.free::<V>());
+                }
+                return Some(true);
+            }
+
+            let header = node.header();
+            let prefix = &header.prefix;
+            if key.get(depth..depth + prefix.len()) != Some(&**prefix) {
+                return Some(false);
+            }
+            depth += prefix.len();
+            let Some(ptr) = key.get(depth).and_then(|&byte| node.find(byte)) else {
+                return Some(false);
+            };
+            parent = Some((slot, node, key[depth]));
+            slot = Slot {
+                lock: &header.lock,
+                ptr,
+            };
+            depth += 1;
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> Default for RowexArt<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Key + ?Sized, V> Drop for RowexArt<K, V> {
+    fn drop(&mut self) {
+        if let Some(root) = self.root_slot().load() {
+            unsafe { root.free_tree::<V>() }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//! Set operations on trees.
+//!
+//! The operations walk both trees together instead of inserting the entries of one tree into the
+//! other. Subtrees which are only present in one of the trees are shared with the new tree or
+//! dropped as a whole, so disjoint parts of the trees are never searched key by key.
+//!
+//! All operations return a new tree. Keys present in both trees with the same value, a leaf or
+//! value storage shared by both trees, are kept by the union and intersection and dropped by the
+//! differences without comparing the values. The other keys present in both trees are passed to a
+//! closure which decides their value, it is called with the value from `self` first.
+
+use crate::{key::Key, raw::Merge, value::ValueStorage, Aart};
+use std::marker::PhantomData;
+
+impl<K: Key + ?Sized, V, S: ValueStorage<Value = V>> Aart<K, V, S> {
+    /// Returns a tree with the entries of both trees, the values of keys present in both trees
+    /// are merged with `f`.
+    pub fn union_with<F>(&self, other: &Self, mut f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> V,
+    {
+        self.merge(other, true, true, true, |a, b| Some(f(a, b)))
+    }
+
+    /// Returns a tree with the keys present in both trees, their values are merged with `f`.
+    pub fn intersection<F>(&self, other: &Self, mut f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> V,
+    {
+        self.merge(other, false, false, true, |a, b| Some(f(a, b)))
+    }
+
+    /// Returns a tree with the entries of `self` which are not present in `other`.
+    ///
+    /// Keys present in both trees with different values are kept with the value returned by `f`,
+    /// if it returns one.
+    pub fn difference<F>(&self, other: &Self, f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        self.merge(other, true, false, false, f)
+    }
+
+    /// Returns a tree with the entries which are present in only one of the trees.
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.merge(other, true, true, false, |_, _| None)
+    }
+
+    fn merge<F>(&self, other: &Self, left: bool, right: bool, shared: bool, mut both: F) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        let mut merge = Merge {
+            left,
+            right,
+            shared,
+            both: |a: &S, b: &S| match a.same(b) {
+                true => shared.then(|| a.clone()),
+                false => both(a.get(), b.get()).map(S::new),
+            },
+            found: 0,
+            kept: 0,
+        };
+        let inner = self.inner.merge(&other.inner, &mut merge);
+        // Subtrees present in only one tree are kept whole, so only the keys found in both trees
+        // have to be accounted for.
+        let mut len = merge.kept;
+        if left {
+            len += self.len - merge.found;
+        }
+        if right {
+            len += other.len - merge.found;
+        }
+        Aart {
+            inner,
+            len,
+            _marker: PhantomData,
+        }
+    }
+}
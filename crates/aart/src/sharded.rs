@@ -0,0 +1,251 @@
+//! A concurrent map which spreads its keys over several independent trees.
+
+use crate::{
+    iter::{self, key_bytes, End, RawIterator},
+    key::{BorrowedKey, Key, KeyBytes},
+    prim::sync::atomic::{AtomicIsize, Ordering},
+    raw::{nodes::NodeRef, root::RootPtr, RawAart},
+};
+use crossbeam_epoch as epoch;
+use crossbeam_utils::{Backoff, CachePadded};
+use std::{
+    array,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+/// A concurrent map which routes every key by its first byte to one of `N` independent trees.
+///
+/// Every shard holds the root of a persistent tree in a [`RootPtr`]. A writer path copies the
+/// current version of its shard and installs the copy by exchanging the root, retrying if another
+/// writer replaced the root in the mean time. Writes to different shards never touch the same root
+/// so spreading the keys over more shards reduces the contention on the exchange.
+///
+/// Shard `i` holds the keys with a first byte `b` for which `b * N / 256 == i`, every shard owns a
+/// contiguous range of keys. Iterating the shards one after another thus yields the entries in key
+/// order.
+///
+/// `N` must be at least 1 and at most 256.
+pub struct ShardedAart<K: Key + ?Sized, V, const N: usize> {
+    shards: [CachePadded<Shard<K, V>>; N],
+    _marker: PhantomData<Arc<V>>,
+}
+
+struct Shard<K: Key + ?Sized, V> {
+    root: RootPtr<K::Bytes, Arc<V>>,
+    /// The amount of entries in the shard, updated after a write is installed so it can briefly be
+    /// negative when a removal is counted before the insertion of the same key.
+    len: AtomicIsize,
+}
+
+impl<K: Key + ?Sized, V> Shard<K, V> {
+    /// Apply a modification to a copy of the current version of the shard and install the copy,
+    /// the modification is retried on a new copy if another writer installed a version first.
+    fn update<R>(&self, mut f: impl FnMut(&mut RawAart<K::Bytes, Arc<V>>) -> R) -> R {
+        let guard = epoch::pin();
+        let backoff = Backoff::new();
+        loop {
+            // The current version is kept alive until the exchange so its root can't be freed and
+            // reused by another version.
+            let current = RawAart::from_root(self.root.clone(&guard));
+            let mut new = current.clone();
+            let res = f(&mut new);
+            if new.root().map(NodeRef::as_ptr) == current.root().map(NodeRef::as_ptr) {
+                return res;
+            }
+            if self
+                .root
+                .exchange(current.root(), new.into_root(), &guard)
+                .is_ok()
+            {
+                return res;
+            }
+            backoff.spin();
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V, const N: usize> ShardedAart<K, V, N> {
+    pub fn new() -> Self {
+        const {
+            assert!(
+                N > 0 && N <= 256,
+                "the amount of shards must be within 1..=256"
+            )
+        };
+        ShardedAart {
+            shards: array::from_fn(|_| {
+                CachePadded::new(Shard {
+                    root: RootPtr::null(),
+                    len: AtomicIsize::new(0),
+                })
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the shard which holds the key.
+    fn shard_index(key: &K) -> usize {
+        key.as_key_bytes().at(0).map_or(0, |b| b as usize * N / 256)
+    }
+
+    /// Returns the current version of every shard.
+    ///
+    /// The shards are loaded one after another, a write which happens while the snapshot is taken
+    /// can be missing from the snapshot while a later write to another shard is included.
+    pub fn snapshot(&self) -> Snapshot<K, V, N> {
+        let guard = epoch::pin();
+        Snapshot {
+            shards: array::from_fn(|i| RawAart::from_root(self.shards[i].root.clone(&guard))),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        let guard = epoch::pin();
+        let shard = &self.shards[Self::shard_index(key)];
+        RawAart::from_root(shard.root.clone(&guard))
+            .get(key.as_key_bytes())
+            .map(|x| x.value.clone())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the amount of entries in the map, writes which are in progress may or may not be
+    /// counted.
+    pub fn len(&self) -> usize {
+        let len: isize = self
+            .shards
+            .iter()
+            .map(|x| x.len.load(Ordering::Relaxed))
+            .sum();
+        len.max(0) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    pub fn insert(&self, key: &K, value: V) -> Option<Arc<V>> {
+        let value = Arc::new(value);
+        let shard = &self.shards[Self::shard_index(key)];
+        let res = shard.update(|tree| tree.insert(key.as_key_bytes(), value.clone()));
+        if res.is_none() {
+            shard.len.fetch_add(1, Ordering::Relaxed);
+        }
+        res
+    }
+
+    /// Remove a value, returns the removed value if the key was present.
+    pub fn remove(&self, key: &K) -> Option<Arc<V>> {
+        let shard = &self.shards[Self::shard_index(key)];
+        let res = shard.update(|tree| tree.remove(key.as_key_bytes()));
+        if res.is_some() {
+            shard.len.fetch_sub(1, Ordering::Relaxed);
+        }
+        res
+    }
+}
+
+impl<K: Key + ?Sized, V, const N: usize> Default for ShardedAart<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The versions of the shards of a [`ShardedAart`] at the time the snapshot was taken.
+///
+/// The snapshot is not affected by later writes to the map.
+pub struct Snapshot<K: Key + ?Sized, V, const N: usize> {
+    shards: [RawAart<K::Bytes, Arc<V>>; N],
+    _marker: PhantomData<Arc<V>>,
+}
+
+impl<K: Key + ?Sized, V, const N: usize> Snapshot<K, V, N> {
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.shards[ShardedAart::<K, V, N>::shard_index(key)]
+            .get(key.as_key_bytes())
+            .map(|x| &*x.value)
+    }
+
+    /// Returns an iterator over all the entries in the snapshot in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(
+            self.shards
+                .iter()
+                .map(|x| iter::Iter::new(RawIterator::new(x.root()), End::Unbounded))
+                .collect(),
+        )
+    }
+
+    /// Returns an iterator over the entries with keys within the range in key order.
+    ///
+    /// Only the shards which can hold keys within the range are iterated.
+    pub fn range<'r, R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let first = match range.start_bound() {
+            Bound::Included(x) | Bound::Excluded(x) => ShardedAart::<K, V, N>::shard_index(x),
+            Bound::Unbounded => 0,
+        };
+        let last = match range.end_bound() {
+            Bound::Included(x) | Bound::Excluded(x) => ShardedAart::<K, V, N>::shard_index(x),
+            Bound::Unbounded => N - 1,
+        };
+        let start = match range.start_bound() {
+            Bound::Included(x) => Some((key_bytes(*x), true)),
+            Bound::Excluded(x) => Some((key_bytes(*x), false)),
+            Bound::Unbounded => None,
+        };
+        let shards = self.shards.get(first..=last).unwrap_or_default();
+        Iter::new(
+            shards
+                .iter()
+                .map(|x| {
+                    let raw = match &start {
+                        Some((start, inclusive)) => RawIterator::seek(x.root(), start, *inclusive),
+                        None => RawIterator::new(x.root()),
+                    };
+                    iter::Iter::new(raw, End::from_bound(range.end_bound()))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// An iterator over the entries of the shards of a [`Snapshot`] in key order.
+pub struct Iter<'a, K: Key + ?Sized, V> {
+    shards: std::vec::IntoIter<iter::Iter<'a, K, V>>,
+    current: Option<iter::Iter<'a, K, V>>,
+}
+
+impl<'a, K: Key + ?Sized, V> Iter<'a, K, V> {
+    fn new(shards: Vec<iter::Iter<'a, K, V>>) -> Self {
+        let mut shards = shards.into_iter();
+        Iter {
+            current: shards.next(),
+            shards,
+        }
+    }
+}
+
+impl<'a, K: Key + BorrowedKey + ?Sized, V: 'a> Iter<'a, K, V> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a V)> {
+        loop {
+            let current: *mut iter::Iter<'a, K, V> = self.current.as_mut()?;
+            // The entry borrows the shard iterator, returning it directly would keep the iterator
+            // borrowed when moving on to the next shard.
+            if let Some(entry) = unsafe { (*current).next() } {
+                return Some(entry);
+            }
+            self.current = self.shards.next();
+        }
+    }
+}
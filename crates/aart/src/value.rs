@@ -0,0 +1,180 @@
+//! How values are stored in the leafs of a tree.
+//!
+//! Leafs are shared between versions of a tree and copied when the prefix of a leaf changes, so
+//! the storage of a value is cloned along with its leaf. By default values are stored behind an
+//! [`Arc`], which costs an allocation on insert and keeps every value at a single place no matter
+//! how many versions of the tree contain it. Small plain values can instead be stored [`Inline`]
+//! in the leaf and copied along with it. [`Auto`] makes the choice from the size of the value and
+//! is the storage used unless another one is given.
+
+use bytemuck::Pod;
+use std::{mem::ManuallyDrop, ptr, sync::Arc};
+
+/// The storage of a value in a leaf.
+pub trait ValueStorage: Clone {
+    type Value;
+
+    /// The value returned when it is replaced or removed from a tree.
+    ///
+    /// This is the value itself if it can be moved out of the storage, a storage which shares the
+    /// value with other versions of the tree returns the shared value instead.
+    type Owned;
+
+    fn new(value: Self::Value) -> Self;
+
+    fn get(&self) -> &Self::Value;
+
+    fn into_owned(self) -> Self::Owned;
+
+    /// Returns whether both storages hold the same value without comparing the values, e.g.
+    /// because the value is shared.
+    fn same(&self, other: &Self) -> bool {
+        let _ = other;
+        false
+    }
+}
+
+/// Values are shared between copies of a leaf.
+impl<V> ValueStorage for Arc<V> {
+    type Value = V;
+    /// Other versions of the tree can still hold the value.
+    type Owned = Arc<V>;
+
+    fn new(value: V) -> Self {
+        Arc::new(value)
+    }
+
+    fn get(&self) -> &V {
+        self
+    }
+
+    fn into_owned(self) -> Arc<V> {
+        self
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(self, other)
+    }
+}
+
+/// A value stored directly in the leaf.
+///
+/// Inserting does not allocate anything besides the leaf itself and reading does not touch a
+/// reference count, but the value is copied every time its leaf is copied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Inline<V>(pub V);
+
+impl<V: Pod> ValueStorage for Inline<V> {
+    type Value = V;
+    type Owned = V;
+
+    fn new(value: V) -> Self {
+        Inline(value)
+    }
+
+    fn get(&self) -> &V {
+        &self.0
+    }
+
+    fn into_owned(self) -> V {
+        self.0
+    }
+
+    /// Inline values are the same if their bytes are equal.
+    fn same(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(&self.0) == bytemuck::bytes_of(&other.0)
+    }
+}
+
+/// A value stored directly in the leaf if it is not larger than a pointer, otherwise behind an
+/// [`Arc`].
+///
+/// The choice is made from the size of the value at compile time. Small values like integers are
+/// cloned along with their leaf instead of costing an allocation, larger values are shared.
+///
+/// Values stored in the leaf can not be compared, so set operations pass every key present in
+/// both trees to their closure unless the leaf itself is shared.
+pub struct Auto<V> {
+    repr: Repr<V>,
+}
+
+union Repr<V> {
+    inline: ManuallyDrop<V>,
+    shared: ManuallyDrop<Arc<V>>,
+}
+
+impl<V> Auto<V> {
+    /// Whether the value is stored in the leaf.
+    const INLINE: bool = std::mem::size_of::<V>() <= std::mem::size_of::<Arc<V>>();
+}
+
+impl<V: Clone> ValueStorage for Auto<V> {
+    type Value = V;
+    type Owned = V;
+
+    fn new(value: V) -> Self {
+        let repr = match Self::INLINE {
+            true => Repr {
+                inline: ManuallyDrop::new(value),
+            },
+            false => Repr {
+                shared: ManuallyDrop::new(Arc::new(value)),
+            },
+        };
+        Auto { repr }
+    }
+
+    fn get(&self) -> &V {
+        unsafe {
+            match Self::INLINE {
+                true => &self.repr.inline,
+                false => &self.repr.shared,
+            }
+        }
+    }
+
+    /// A shared value is cloned if other versions of the tree still hold it.
+    fn into_owned(self) -> V {
+        let this = ManuallyDrop::new(self);
+        unsafe {
+            match Self::INLINE {
+                true => ManuallyDrop::into_inner(ptr::read(&this.repr.inline)),
+                false => {
+                    Arc::unwrap_or_clone(ManuallyDrop::into_inner(ptr::read(&this.repr.shared)))
+                }
+            }
+        }
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        !Self::INLINE && unsafe { Arc::ptr_eq(&self.repr.shared, &other.repr.shared) }
+    }
+}
+
+impl<V: Clone> Clone for Auto<V> {
+    fn clone(&self) -> Self {
+        let repr = unsafe {
+            match Self::INLINE {
+                true => Repr {
+                    inline: self.repr.inline.clone(),
+                },
+                false => Repr {
+                    shared: self.repr.shared.clone(),
+                },
+            }
+        };
+        Auto { repr }
+    }
+}
+
+impl<V> Drop for Auto<V> {
+    fn drop(&mut self) {
+        unsafe {
+            match Self::INLINE {
+                true => ManuallyDrop::drop(&mut self.repr.inline),
+                false => ManuallyDrop::drop(&mut self.repr.shared),
+            }
+        }
+    }
+}
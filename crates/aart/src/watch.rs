@@ -0,0 +1,164 @@
+//! Watching a [`ConcurrentAart`] for changes to the keys starting with a prefix.
+
+use crate::{
+    key::Key,
+    prim::sync::{Condvar, Mutex, MutexGuard},
+    Aart, ConcurrentAart,
+};
+use std::sync::{Arc, PoisonError, Weak};
+#[cfg(feature = "stream")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// The watchers of a map, held by the writer lock of the map.
+pub(crate) struct Watchers<K: Key + ?Sized, V>(Vec<Weak<Watch<K, V>>>);
+
+impl<K: Key + ?Sized, V> Watchers<K, V> {
+    pub(crate) fn new() -> Self {
+        Watchers(Vec::new())
+    }
+
+    /// Notify every watcher whose keys differ between the versions, dropping the watchers which
+    /// are no longer used.
+    pub(crate) fn notify(&mut self, old: &Aart<K, V, Arc<V>>, new: &Aart<K, V, Arc<V>>) {
+        self.0.retain(|watch| {
+            let Some(watch) = watch.upgrade() else {
+                return false;
+            };
+            if old.inner.prefix_changed(&new.inner, &watch.prefix) {
+                watch.update(|state| state.version = Some(new.clone()));
+            }
+            true
+        });
+    }
+}
+
+impl<K: Key + ?Sized, V> Drop for Watchers<K, V> {
+    fn drop(&mut self) {
+        for watch in self.0.iter().filter_map(Weak::upgrade) {
+            watch.update(|state| state.closed = true);
+        }
+    }
+}
+
+struct Watch<K: Key + ?Sized, V> {
+    prefix: Box<[u8]>,
+    state: Mutex<State<K, V>>,
+    changed: Condvar,
+}
+
+struct State<K: Key + ?Sized, V> {
+    /// The version after the latest change which the watcher did not receive yet.
+    version: Option<Aart<K, V, Arc<V>>>,
+    /// Whether the map was dropped.
+    closed: bool,
+    #[cfg(feature = "stream")]
+    waker: Option<Waker>,
+}
+
+impl<K: Key + ?Sized, V> Watch<K, V> {
+    fn lock(&self) -> MutexGuard<'_, State<K, V>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Change the state and wake up the watcher.
+    fn update(&self, f: impl FnOnce(&mut State<K, V>)) {
+        let mut state = self.lock();
+        f(&mut state);
+        #[cfg(feature = "stream")]
+        let waker = state.waker.take();
+        drop(state);
+        self.changed.notify_all();
+        #[cfg(feature = "stream")]
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Receives the versions of a [`ConcurrentAart`] which changed the keys starting with a prefix,
+/// created by [`ConcurrentAart::watch`].
+///
+/// Changes which happen before the watcher receives a version are coalesced, the watcher only
+/// receives the latest version. Changes are found by comparing the subtrees holding the watched
+/// keys, which are shared between the versions unless a write copied them, so writes to other
+/// keys are skipped quickly. Values are compared by identity, inserting a key again is a change.
+///
+/// With the `stream` feature the watcher is a [`Stream`](futures_core::Stream) of the versions.
+pub struct Watcher<K: Key + ?Sized, V> {
+    watch: Arc<Watch<K, V>>,
+}
+
+impl<K: Key + ?Sized, V> Watcher<K, V> {
+    /// Returns the prefix of the key bytes the watcher is notified for.
+    pub fn prefix(&self) -> &[u8] {
+        &self.watch.prefix
+    }
+
+    /// Returns the version after the latest change if a change happened since the last received
+    /// version, without blocking.
+    pub fn try_recv(&mut self) -> Option<Aart<K, V, Arc<V>>> {
+        self.watch.lock().version.take()
+    }
+
+    /// Wait for a change and return the version after the latest change.
+    ///
+    /// Returns `None` once the map is dropped.
+    pub fn recv(&mut self) -> Option<Aart<K, V, Arc<V>>> {
+        let mut state = self.watch.lock();
+        loop {
+            if let Some(version) = state.version.take() {
+                return Some(version);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .watch
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<K: Key + ?Sized, V> futures_core::Stream for Watcher<K, V> {
+    type Item = Aart<K, V, Arc<V>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.watch.lock();
+        if let Some(version) = state.version.take() {
+            return Poll::Ready(Some(version));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<K: Key + ?Sized, V> ConcurrentAart<K, V> {
+    /// Returns a watcher which receives the versions of the map written after this call which
+    /// changed a key starting with `prefix`.
+    ///
+    /// The prefix is matched against the key bytes, for string keys these are the bytes of the
+    /// string.
+    pub fn watch(&self, prefix: &[u8]) -> Watcher<K, V> {
+        let watch = Arc::new(Watch {
+            prefix: prefix.into(),
+            state: Mutex::new(State {
+                version: None,
+                closed: false,
+                #[cfg(feature = "stream")]
+                waker: None,
+            }),
+            changed: Condvar::new(),
+        });
+        self.writer().0.push(Arc::downgrade(&watch));
+        Watcher { watch }
+    }
+}
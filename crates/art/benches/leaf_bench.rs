@@ -0,0 +1,59 @@
+//! Compares trees of `u64` values with and without the `inline-leafs` feature, run once with
+//! `cargo bench -p art --bench leaf_bench` and once more with `--features inline-leafs` to compare
+//! against the first run.
+
+use art::Art;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::SeedableRng;
+use rand::{rngs::StdRng, Rng};
+
+const SIZE: usize = 100_000;
+
+fn seeded_rng(alter: u64) -> impl Rng {
+    StdRng::seed_from_u64(0xEA3C47920F94A980 ^ alter)
+}
+
+/// Random keys, whose leafs keep most of their key as prefix.
+fn random_keys() -> Vec<u64> {
+    let mut rng = seeded_rng(0x2C5A1D0F93E7B846);
+    (0..SIZE).map(|_| rng.gen()).collect()
+}
+
+/// Dense row ids, whose leafs have no prefix left below the last branch.
+fn sequential_keys() -> Vec<u64> {
+    (0..SIZE as u64).map(u64::to_be).collect()
+}
+
+fn build(keys: &[u64]) -> Art<u64, u64> {
+    let mut tree = Art::new();
+    for k in keys {
+        tree.insert(k, *k);
+    }
+    tree
+}
+
+pub fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("leaf_insert");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    for (name, keys) in [("random", random_keys()), ("sequential", sequential_keys())] {
+        group.bench_with_input(BenchmarkId::new(name, SIZE), &keys, |b, keys| {
+            b.iter(|| build(keys))
+        });
+    }
+    group.finish();
+}
+
+pub fn get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("leaf_get");
+    group.throughput(Throughput::Elements(SIZE as u64));
+    for (name, keys) in [("random", random_keys()), ("sequential", sequential_keys())] {
+        let tree = build(&keys);
+        group.bench_with_input(BenchmarkId::new(name, SIZE), &keys, |b, keys| {
+            b.iter(|| keys.iter().filter_map(|k| tree.get(k)).sum::<u64>())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(leaf_benches, insert, get);
+criterion_main!(leaf_benches);
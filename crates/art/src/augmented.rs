@@ -0,0 +1,175 @@
+//! A tree caching an aggregate of the values below every node.
+//!
+//! [`AugmentedArt`] keeps the aggregate of all values in a subtree next to the pointer to the
+//! subtree in its parent node. Modifying a value recomputes the aggregates on the path to it, and
+//! the aggregate of a range of keys only has to visit the nodes on the paths to the start and end
+//! of the range: every other subtree is either fully inside the range, in which case its cached
+//! aggregate is used, or fully outside of it.
+
+use crate::{iter::key_bytes, key::Key, raw::RawArt};
+use std::ops::{Add, RangeBounds};
+
+/// A value which can be computed for a set of values by combining the values of subsets.
+///
+/// `combine` must be associative and `identity` must not change a value it is combined with.
+/// Aggregates are combined in key order so `combine` does not need to be commutative. They are
+/// kept next to the child pointers in the nodes and moved along with them, hence `Copy`.
+pub trait Aggregate<V>: Copy {
+    /// The aggregate of an empty set of values.
+    fn identity() -> Self;
+
+    /// The aggregate of a single value.
+    fn from_value(value: &V) -> Self;
+
+    /// The aggregate of two consecutive sets of values.
+    fn combine(&self, other: &Self) -> Self;
+}
+
+/// No aggregate, for trees which don't keep one.
+impl<V> Aggregate<V> for () {
+    fn identity() -> Self {}
+
+    fn from_value(_: &V) -> Self {}
+
+    fn combine(&self, _: &Self) -> Self {}
+}
+
+/// The amount of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Count(pub usize);
+
+impl<V> Aggregate<V> for Count {
+    fn identity() -> Self {
+        Count(0)
+    }
+
+    fn from_value(_: &V) -> Self {
+        Count(1)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Count(self.0 + other.0)
+    }
+}
+
+/// The sum of the values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sum<T>(pub T);
+
+impl<T: Copy + Default + Add<Output = T>> Aggregate<T> for Sum<T> {
+    fn identity() -> Self {
+        Sum(T::default())
+    }
+
+    fn from_value(value: &T) -> Self {
+        Sum(*value)
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        Sum(self.0 + other.0)
+    }
+}
+
+/// The smallest value, `None` if there are no values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Min<T>(pub Option<T>);
+
+impl<T: Copy + Ord> Aggregate<T> for Min<T> {
+    fn identity() -> Self {
+        Min(None)
+    }
+
+    fn from_value(value: &T) -> Self {
+        Min(Some(*value))
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Min(Some(a.min(b))),
+            (a, b) => Min(a.or(b)),
+        }
+    }
+}
+
+/// The largest value, `None` if there are no values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Max<T>(pub Option<T>);
+
+impl<T: Copy + Ord> Aggregate<T> for Max<T> {
+    fn identity() -> Self {
+        Max(None)
+    }
+
+    fn from_value(value: &T) -> Self {
+        Max(Some(*value))
+    }
+
+    fn combine(&self, other: &Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Max(Some(a.max(b))),
+            (a, b) => Max(a.or(b)),
+        }
+    }
+}
+
+/// A tree which caches an aggregate of type `A` over the values below every node.
+pub struct AugmentedArt<K: Key + ?Sized, V, A: Aggregate<V>> {
+    tree: RawArt<K, V, A>,
+    len: usize,
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> AugmentedArt<K, V, A> {
+    pub fn new() -> Self {
+        AugmentedArt {
+            tree: RawArt::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    pub fn insert(&mut self, key: &K, value: V) -> Option<V> {
+        let res = self.tree.insert(key, value);
+        self.tree.update_aggregates(key);
+        self.len += res.is_none() as usize;
+        res
+    }
+
+    /// Remove a value, returns the removed value if the key was present.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let res = self.tree.remove(key)?;
+        self.tree.update_aggregates(key);
+        self.len -= 1;
+        Some(res)
+    }
+
+    /// Returns the aggregate of the values with keys within the range.
+    pub fn aggregate<'r, R>(&self, range: R) -> A
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let start = range.start_bound().map(|x| key_bytes(*x));
+        let end = range.end_bound().map(|x| key_bytes(*x));
+        let (start, end) = (start.as_ref(), end.as_ref());
+        self.tree
+            .aggregate(start.map(Vec::as_slice), end.map(Vec::as_slice))
+    }
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> Default for AugmentedArt<K, V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,800 @@
+//! A copy-on-write variant of [`Art`](crate::Art).
+//!
+//! [`CowArt`] keeps its nodes behind reference counts, so cloning a tree only copies the pointer
+//! to the root. Modifying a tree copies the nodes on the path to the modified leaf which are
+//! shared with another tree, nodes which are not shared are modified in place.
+//!
+//! The tree uses the node kinds of `Art`: leafs storing the remaining bytes of their key, and
+//! branches with up to 4, 16, 48 or 256 children which grow and shrink as branches are inserted
+//! and removed. Every kind is allocated at its own size, a child is a reference counted pointer
+//! tagged with the kind of the node it points to.
+
+use crate::{
+    iter::{key_bytes, End},
+    key::{BorrowedKey, Key},
+};
+use std::{
+    cmp::Ordering,
+    marker::PhantomData,
+    mem,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
+
+#[derive(Clone)]
+struct Leaf<V> {
+    prefix: Box<[u8]>,
+    value: V,
+}
+
+/// A branch node with at most `N` children, the keys are kept sorted.
+#[derive(Clone)]
+struct SmallNode<V, const N: usize> {
+    prefix: Box<[u8]>,
+    len: u8,
+    keys: [u8; N],
+    children: [Option<Child<V>>; N],
+}
+
+type Node4<V> = SmallNode<V, 4>;
+type Node16<V> = SmallNode<V, 16>;
+
+/// A branch node with at most 48 children and an index from key to child.
+#[derive(Clone)]
+struct Node48<V> {
+    prefix: Box<[u8]>,
+    len: u8,
+    /// The index of the child for every key, `u8::MAX` if the node has no branch for the key.
+    idx: [u8; 256],
+    children: [Option<Child<V>>; 48],
+}
+
+/// A branch node with a child slot for every key.
+#[derive(Clone)]
+struct Node256<V> {
+    prefix: Box<[u8]>,
+    len: u16,
+    children: [Option<Child<V>>; 256],
+}
+
+/// The children of a branch node with their keys, in key order.
+type Children<V> = Vec<(u8, Child<V>)>;
+
+/// A reference counted pointer to a node.
+enum Child<V> {
+    Leaf(Arc<Leaf<V>>),
+    Node4(Arc<Node4<V>>),
+    Node16(Arc<Node16<V>>),
+    Node48(Arc<Node48<V>>),
+    Node256(Arc<Node256<V>>),
+}
+
+enum NodeRef<'a, V> {
+    Leaf(&'a Leaf<V>),
+    Branch(&'a dyn Branch<V>),
+}
+
+enum NodeMut<'a, V> {
+    Leaf(&'a mut Leaf<V>),
+    Branch(&'a mut dyn Branch<V>),
+}
+
+/// The operations shared by the branch node kinds.
+trait Branch<V> {
+    fn prefix(&self) -> &[u8];
+
+    fn set_prefix(&mut self, prefix: Box<[u8]>);
+
+    fn len(&self) -> usize;
+
+    /// Returns whether a branch can't be inserted without growing the node.
+    fn is_full(&self) -> bool;
+
+    /// Returns whether the children should be moved to a smaller node kind.
+    fn should_shrink(&self) -> bool;
+
+    fn get(&self, key: u8) -> Option<&Child<V>>;
+
+    fn get_mut(&mut self, key: u8) -> Option<&mut Child<V>>;
+
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    fn next_node(&self, from: u8) -> Option<(u8, &Child<V>)>;
+
+    /// Insert a branch for a key the node has no branch for into a node which is not full.
+    fn insert(&mut self, key: u8, child: Child<V>);
+
+    fn remove(&mut self, key: u8) -> Option<Child<V>>;
+
+    /// Move the prefix and the children in key order out of the node.
+    fn take(&mut self) -> (Box<[u8]>, Children<V>);
+}
+
+impl<V, const N: usize> SmallNode<V, N> {
+    fn new(prefix: Box<[u8]>) -> Self {
+        SmallNode {
+            prefix,
+            len: 0,
+            keys: [0; N],
+            children: std::array::from_fn(|_| None),
+        }
+    }
+
+    fn find(&self, key: u8) -> Result<usize, usize> {
+        self.keys[..self.len as usize].binary_search(&key)
+    }
+}
+
+impl<V, const N: usize> Branch<V> for SmallNode<V, N> {
+    fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    fn set_prefix(&mut self, prefix: Box<[u8]>) {
+        self.prefix = prefix;
+    }
+
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    fn is_full(&self) -> bool {
+        self.len as usize == N
+    }
+
+    fn should_shrink(&self) -> bool {
+        N > 4 && self.len < 5
+    }
+
+    fn get(&self, key: u8) -> Option<&Child<V>> {
+        let idx = self.find(key).ok()?;
+        self.children[idx].as_ref()
+    }
+
+    fn get_mut(&mut self, key: u8) -> Option<&mut Child<V>> {
+        let idx = self.find(key).ok()?;
+        self.children[idx].as_mut()
+    }
+
+    fn next_node(&self, from: u8) -> Option<(u8, &Child<V>)> {
+        let idx = self.find(from).unwrap_or_else(|x| x);
+        Some((*self.keys.get(idx)?, self.children[idx].as_ref()?))
+    }
+
+    fn insert(&mut self, key: u8, child: Child<V>) {
+        let len = self.len as usize;
+        let idx = self.find(key).unwrap_err();
+        self.keys.copy_within(idx..len, idx + 1);
+        self.children[idx..=len].rotate_right(1);
+        self.keys[idx] = key;
+        self.children[idx] = Some(child);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, key: u8) -> Option<Child<V>> {
+        let len = self.len as usize;
+        let idx = self.find(key).ok()?;
+        let res = self.children[idx].take();
+        self.keys.copy_within(idx + 1..len, idx);
+        self.children[idx..len].rotate_left(1);
+        self.len -= 1;
+        res
+    }
+
+    fn take(&mut self) -> (Box<[u8]>, Children<V>) {
+        let len = mem::take(&mut self.len) as usize;
+        let children = (0..len)
+            .map(|i| (self.keys[i], self.children[i].take().unwrap()))
+            .collect();
+        (mem::take(&mut self.prefix), children)
+    }
+}
+
+impl<V> Node48<V> {
+    fn new(prefix: Box<[u8]>) -> Self {
+        Node48 {
+            prefix,
+            len: 0,
+            idx: [u8::MAX; 256],
+            children: std::array::from_fn(|_| None),
+        }
+    }
+}
+
+impl<V> Branch<V> for Node48<V> {
+    fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    fn set_prefix(&mut self, prefix: Box<[u8]>) {
+        self.prefix = prefix;
+    }
+
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == 48
+    }
+
+    fn should_shrink(&self) -> bool {
+        self.len < 16
+    }
+
+    fn get(&self, key: u8) -> Option<&Child<V>> {
+        self.children.get(self.idx[key as usize] as usize)?.as_ref()
+    }
+
+    fn get_mut(&mut self, key: u8) -> Option<&mut Child<V>> {
+        self.children
+            .get_mut(self.idx[key as usize] as usize)?
+            .as_mut()
+    }
+
+    fn next_node(&self, from: u8) -> Option<(u8, &Child<V>)> {
+        (from..=u8::MAX).find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
+    fn insert(&mut self, key: u8, child: Child<V>) {
+        let slot = self.children.iter().position(Option::is_none).unwrap();
+        self.children[slot] = Some(child);
+        self.idx[key as usize] = slot as u8;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, key: u8) -> Option<Child<V>> {
+        let slot = mem::replace(&mut self.idx[key as usize], u8::MAX);
+        let res = self.children.get_mut(slot as usize)?.take();
+        self.len -= 1;
+        res
+    }
+
+    fn take(&mut self) -> (Box<[u8]>, Children<V>) {
+        self.len = 0;
+        let children = (0..=u8::MAX)
+            .filter_map(|k| {
+                let slot = mem::replace(&mut self.idx[k as usize], u8::MAX);
+                Some((k, self.children.get_mut(slot as usize)?.take().unwrap()))
+            })
+            .collect();
+        (mem::take(&mut self.prefix), children)
+    }
+}
+
+impl<V> Node256<V> {
+    fn new(prefix: Box<[u8]>) -> Self {
+        Node256 {
+            prefix,
+            len: 0,
+            children: std::array::from_fn(|_| None),
+        }
+    }
+}
+
+impl<V> Branch<V> for Node256<V> {
+    fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    fn set_prefix(&mut self, prefix: Box<[u8]>) {
+        self.prefix = prefix;
+    }
+
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    fn is_full(&self) -> bool {
+        false
+    }
+
+    fn should_shrink(&self) -> bool {
+        self.len < 48
+    }
+
+    fn get(&self, key: u8) -> Option<&Child<V>> {
+        self.children[key as usize].as_ref()
+    }
+
+    fn get_mut(&mut self, key: u8) -> Option<&mut Child<V>> {
+        self.children[key as usize].as_mut()
+    }
+
+    fn next_node(&self, from: u8) -> Option<(u8, &Child<V>)> {
+        (from..=u8::MAX).find_map(|k| self.get(k).map(|x| (k, x)))
+    }
+
+    fn insert(&mut self, key: u8, child: Child<V>) {
+        self.children[key as usize] = Some(child);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, key: u8) -> Option<Child<V>> {
+        let res = self.children[key as usize].take()?;
+        self.len -= 1;
+        Some(res)
+    }
+
+    fn take(&mut self) -> (Box<[u8]>, Children<V>) {
+        self.len = 0;
+        let children = (0..=u8::MAX)
+            .filter_map(|k| Some((k, self.children[k as usize].take()?)))
+            .collect();
+        (mem::take(&mut self.prefix), children)
+    }
+}
+
+impl<V> Child<V> {
+    /// Create a leaf with the bytes of the key from `depth` as its prefix.
+    fn leaf<K: Key + ?Sized>(key: &K, depth: usize, value: V) -> Self {
+        Child::Leaf(Arc::new(Leaf {
+            prefix: (depth..key.len()).map(|i| key.at(i)).collect(),
+            value,
+        }))
+    }
+
+    /// Create the smallest kind of branch node which fits the children, which must be sorted by
+    /// their key.
+    fn new_branch(prefix: Box<[u8]>, children: Children<V>) -> Self {
+        fn fill<V, B: Branch<V>>(mut node: B, children: Children<V>) -> Arc<B> {
+            for (key, child) in children {
+                node.insert(key, child);
+            }
+            Arc::new(node)
+        }
+        match children.len() {
+            0..=4 => Child::Node4(fill(SmallNode::new(prefix), children)),
+            5..=16 => Child::Node16(fill(SmallNode::new(prefix), children)),
+            17..=48 => Child::Node48(fill(Node48::new(prefix), children)),
+            _ => Child::Node256(fill(Node256::new(prefix), children)),
+        }
+    }
+
+    fn as_ref(&self) -> NodeRef<'_, V> {
+        match self {
+            Child::Leaf(x) => NodeRef::Leaf(x),
+            Child::Node4(x) => NodeRef::Branch(&**x),
+            Child::Node16(x) => NodeRef::Branch(&**x),
+            Child::Node48(x) => NodeRef::Branch(&**x),
+            Child::Node256(x) => NodeRef::Branch(&**x),
+        }
+    }
+
+    fn prefix(&self) -> &[u8] {
+        match self.as_ref() {
+            NodeRef::Leaf(x) => &x.prefix,
+            NodeRef::Branch(x) => x.prefix(),
+        }
+    }
+}
+
+impl<V: Clone> Child<V> {
+    /// Returns a mutable reference to the node, copying the node if it is shared with another
+    /// tree.
+    fn as_mut(&mut self) -> NodeMut<'_, V> {
+        match self {
+            Child::Leaf(x) => NodeMut::Leaf(Arc::make_mut(x)),
+            Child::Node4(x) => NodeMut::Branch(&mut *Arc::make_mut(x)),
+            Child::Node16(x) => NodeMut::Branch(&mut *Arc::make_mut(x)),
+            Child::Node48(x) => NodeMut::Branch(&mut *Arc::make_mut(x)),
+            Child::Node256(x) => NodeMut::Branch(&mut *Arc::make_mut(x)),
+        }
+    }
+
+    fn set_prefix(&mut self, prefix: Box<[u8]>) {
+        match self.as_mut() {
+            NodeMut::Leaf(x) => x.prefix = prefix,
+            NodeMut::Branch(x) => x.set_prefix(prefix),
+        }
+    }
+
+    fn into_value(self) -> V {
+        match self {
+            Child::Leaf(x) => Arc::unwrap_or_clone(x).value,
+            _ => unreachable!("expected a leaf node"),
+        }
+    }
+}
+
+impl<V> Clone for Child<V> {
+    /// Returns a new reference to the node.
+    fn clone(&self) -> Self {
+        match self {
+            Child::Leaf(x) => Child::Leaf(x.clone()),
+            Child::Node4(x) => Child::Node4(x.clone()),
+            Child::Node16(x) => Child::Node16(x.clone()),
+            Child::Node48(x) => Child::Node48(x.clone()),
+            Child::Node256(x) => Child::Node256(x.clone()),
+        }
+    }
+}
+
+/// A tree sharing its nodes with its clones.
+pub struct CowArt<K: Key + ?Sized, V> {
+    root: Option<Child<V>>,
+    len: usize,
+    _marker: PhantomData<fn(&K)>,
+}
+
+impl<K: Key + ?Sized, V> CowArt<K, V> {
+    const PREFIX_PANIC: &'static str = "the key was a prefix of an existing key";
+
+    pub fn new() -> Self {
+        CowArt {
+            root: None,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut node = self.root.as_ref()?;
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            if depth + prefix.len() > key.len()
+                || prefix
+                    .iter()
+                    .enumerate()
+                    .any(|(i, p)| key.at(depth + i) != *p)
+            {
+                return None;
+            }
+            depth += prefix.len();
+
+            match node.as_ref() {
+                NodeRef::Leaf(x) => return (depth == key.len()).then_some(&x.value),
+                NodeRef::Branch(x) => {
+                    if depth >= key.len() {
+                        return None;
+                    }
+                    node = x.get(key.at(depth))?;
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns an iterator over all the entries in the tree in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter::new(End::Unbounded);
+        if let Some(root) = self.root.as_ref() {
+            iter.pending = iter.enter(root);
+        }
+        iter
+    }
+
+    /// Returns an iterator over the entries with keys within the range in key order.
+    pub fn range<'r, R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let end = End::from_bound(range.end_bound());
+        let root = self.root.as_ref();
+        match range.start_bound() {
+            Bound::Included(x) => Iter::seek(root, &key_bytes(*x), true, end),
+            Bound::Excluded(x) => Iter::seek(root, &key_bytes(*x), false, end),
+            Bound::Unbounded => Iter::seek(root, &[], true, end),
+        }
+    }
+}
+
+impl<V> CowArt<str, V> {
+    /// Returns an iterator over the entries with keys starting with the given prefix.
+    pub fn scan_prefix(&self, prefix: &str) -> Iter<'_, str, V> {
+        let prefix = prefix.as_bytes();
+        Iter::seek(
+            self.root.as_ref(),
+            prefix,
+            true,
+            End::Prefix(prefix.to_vec()),
+        )
+    }
+}
+
+impl<K: Key + ?Sized, V: Clone> CowArt<K, V> {
+    /// Returns a mutable reference to a value, copying the nodes on the path to it which are
+    /// shared with other trees.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        // Don't copy any nodes if the key isn't present.
+        self.get(key)?;
+
+        let mut node = self.root.as_mut()?;
+        let mut depth = 0;
+        loop {
+            depth += node.prefix().len();
+            match node.as_mut() {
+                NodeMut::Leaf(x) => return Some(&mut x.value),
+                NodeMut::Branch(x) => {
+                    node = x.get_mut(key.at(depth))?;
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Insert a value, returns the value previously stored under the key if there was one.
+    ///
+    /// If the leaf of the key is shared with another tree the returned value is a clone.
+    pub fn insert(&mut self, key: &K, value: V) -> Option<V> {
+        let res = match self.root.as_mut() {
+            Some(root) => Self::insert_node(root, key, 0, value),
+            None => {
+                self.root = Some(Child::leaf(key, 0, value));
+                None
+            }
+        };
+        self.len += res.is_none() as usize;
+        res
+    }
+
+    fn insert_node(node: &mut Child<V>, key: &K, depth: usize, value: V) -> Option<V> {
+        let prefix = node.prefix();
+        let mismatch = prefix.iter().enumerate().position(|(i, p)| {
+            assert!(depth + i < key.len(), "{}", Self::PREFIX_PANIC);
+            key.at(depth + i) != *p
+        });
+        if let Some(mismatch) = mismatch {
+            // Split the prefix, moving the existing node below a new node4.
+            let old_key = prefix[mismatch];
+            let old_prefix = prefix[mismatch + 1..].into();
+            let branch = Child::Node4(Arc::new(SmallNode::new(prefix[..mismatch].into())));
+            let mut old = mem::replace(node, branch);
+            old.set_prefix(old_prefix);
+            let leaf = Child::leaf(key, depth + mismatch + 1, value);
+
+            let NodeMut::Branch(branch) = node.as_mut() else {
+                unreachable!()
+            };
+            branch.insert(old_key, old);
+            branch.insert(key.at(depth + mismatch), leaf);
+            return None;
+        }
+        let depth = depth + prefix.len();
+
+        match node.as_mut() {
+            NodeMut::Leaf(x) => {
+                assert_eq!(depth, key.len(), "{}", Self::PREFIX_PANIC);
+                Some(mem::replace(&mut x.value, value))
+            }
+            NodeMut::Branch(branch) => {
+                assert!(depth < key.len(), "{}", Self::PREFIX_PANIC);
+                let branch_key = key.at(depth);
+                if let Some(child) = branch.get_mut(branch_key) {
+                    return Self::insert_node(child, key, depth + 1, value);
+                }
+
+                let leaf = Child::leaf(key, depth + 1, value);
+                if !branch.is_full() {
+                    branch.insert(branch_key, leaf);
+                    return None;
+                }
+                // Grow the node into the next larger kind.
+                let (prefix, mut children) = branch.take();
+                let idx = children.partition_point(|x| x.0 < branch_key);
+                children.insert(idx, (branch_key, leaf));
+                *node = Child::new_branch(prefix, children);
+                None
+            }
+        }
+    }
+
+    /// Remove a value, returns the removed value if the key was present.
+    ///
+    /// If the leaf of the key is shared with another tree the returned value is a clone.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        // Don't copy any nodes if the key isn't present.
+        self.get(key)?;
+        self.len -= 1;
+
+        let root = self.root.as_mut().unwrap();
+        if let Child::Leaf(_) = root {
+            return self.root.take().map(Child::into_value);
+        }
+        Some(Self::remove_node(root, key, 0))
+    }
+
+    /// Remove a key which is present below the given branch node.
+    fn remove_node(node: &mut Child<V>, key: &K, depth: usize) -> V {
+        let depth = depth + node.prefix().len();
+        let NodeMut::Branch(branch) = node.as_mut() else {
+            unreachable!("expected a branch node")
+        };
+
+        let branch_key = key.at(depth);
+        let child = branch.get_mut(branch_key).unwrap();
+        let value = if let Child::Leaf(_) = child {
+            branch.remove(branch_key).unwrap().into_value()
+        } else {
+            Self::remove_node(child, key, depth + 1)
+        };
+
+        if branch.len() == 1 {
+            // A branch with a single child is merged into that child.
+            let (prefix, children) = branch.take();
+            let (child_key, mut child) = children.into_iter().next().unwrap();
+            let mut prefix = prefix.into_vec();
+            prefix.push(child_key);
+            prefix.extend_from_slice(child.prefix());
+            child.set_prefix(prefix.into());
+            *node = child;
+        } else if branch.should_shrink() {
+            let (prefix, children) = branch.take();
+            *node = Child::new_branch(prefix, children);
+        }
+        value
+    }
+}
+
+impl<K: Key + ?Sized, V> Clone for CowArt<K, V> {
+    /// Returns a copy of the tree, this does not copy any nodes.
+    fn clone(&self) -> Self {
+        CowArt {
+            root: self.root.clone(),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> Default for CowArt<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the entries of a [`CowArt`] in key order.
+///
+/// Keeps an explicit stack of the branch nodes currently being iterated, together with the next
+/// key to look at in the node and the length of the key before the prefix of the node.
+pub struct Iter<'a, K: Key + ?Sized, V> {
+    key: Vec<u8>,
+    pending: Option<&'a Leaf<V>>,
+    stack: Vec<(&'a dyn Branch<V>, u16, usize)>,
+    end: End,
+    _marker: PhantomData<fn(&K)>,
+}
+
+impl<'a, K: Key + ?Sized, V> Iter<'a, K, V> {
+    fn new(end: End) -> Self {
+        Iter {
+            key: Vec::new(),
+            pending: None,
+            stack: Vec::new(),
+            end,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create an iterator positioned at the first key larger than, or if `inclusive` equal to,
+    /// `start`.
+    fn seek(root: Option<&'a Child<V>>, start: &[u8], inclusive: bool, end: End) -> Self {
+        let mut this = Self::new(end);
+        let Some(mut node) = root else {
+            return this;
+        };
+
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            let rest = &start[depth..];
+            let len = prefix.len().min(rest.len());
+            match prefix[..len].cmp(&rest[..len]) {
+                // every key in the node is smaller than start.
+                Ordering::Less => return this,
+                // every key in the node is larger than start.
+                Ordering::Greater => {
+                    this.pending = this.enter(node);
+                    return this;
+                }
+                Ordering::Equal if len < prefix.len() => {
+                    this.pending = this.enter(node);
+                    return this;
+                }
+                Ordering::Equal => {}
+            }
+
+            let base = this.key.len();
+            this.key.extend_from_slice(prefix);
+            depth += len;
+
+            let branch = match node.as_ref() {
+                NodeRef::Leaf(leaf) => {
+                    if depth == start.len() && inclusive {
+                        this.pending = Some(leaf);
+                    }
+                    return this;
+                }
+                NodeRef::Branch(x) => x,
+            };
+
+            if depth == start.len() {
+                this.stack.push((branch, 0, base));
+                return this;
+            }
+
+            let branch_key = start[depth];
+            match branch.get(branch_key) {
+                Some(child) => {
+                    this.stack.push((branch, branch_key as u16 + 1, base));
+                    this.key.push(branch_key);
+                    node = child;
+                    depth += 1;
+                }
+                None => {
+                    this.stack.push((branch, branch_key as u16, base));
+                    return this;
+                }
+            }
+        }
+    }
+
+    /// Enter a node, returns the node if it is a leaf, otherwise pushes the node onto the stack.
+    fn enter(&mut self, node: &'a Child<V>) -> Option<&'a Leaf<V>> {
+        let base = self.key.len();
+        self.key.extend_from_slice(node.prefix());
+        match node.as_ref() {
+            NodeRef::Leaf(leaf) => Some(leaf),
+            NodeRef::Branch(branch) => {
+                self.stack.push((branch, 0, base));
+                None
+            }
+        }
+    }
+
+    fn next_leaf(&mut self) -> Option<&'a Leaf<V>> {
+        if let Some(x) = self.pending.take() {
+            return Some(x);
+        }
+
+        loop {
+            let (node, next, base) = self.stack.last_mut()?;
+            let node = *node;
+            let found = if *next > u8::MAX as u16 {
+                None
+            } else {
+                node.next_node(*next as u8)
+            };
+
+            let Some((branch, child)) = found else {
+                self.key.truncate(*base);
+                self.stack.pop();
+                continue;
+            };
+
+            *next = branch as u16 + 1;
+            let len = *base + node.prefix().len();
+            self.key.truncate(len);
+            self.key.push(branch);
+
+            if let Some(leaf) = self.enter(child) {
+                return Some(leaf);
+            }
+        }
+    }
+}
+
+impl<'a, K: Key + BorrowedKey + ?Sized, V> Iter<'a, K, V> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a V)> {
+        let leaf = self.next_leaf()?;
+        if !self.end.contains(&self.key) {
+            self.stack.clear();
+            return None;
+        }
+        unsafe { Some((K::from_key_bytes(&self.key), &leaf.value)) }
+    }
+}
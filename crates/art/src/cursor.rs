@@ -0,0 +1,357 @@
+//! Cursors which can move freely through the entries of a tree.
+
+use crate::{
+    iter::{key_bytes, Item},
+    key::{BorrowedKey, Key},
+    raw::{Borrow, LeafNode, MutValue, NodePtr, TypedNodePtr, ValidPtr},
+    Art,
+};
+use std::cmp::Ordering;
+
+/// A position in a tree.
+///
+/// Nodes don't point to their parents, so the cursor keeps an explicit stack of the branch nodes
+/// on the path to the current leaf together with the byte of the child taken in each of them.
+/// When the cursor is not at a leaf it is at the "ghost" position, which lies after the last and
+/// before the first entry of the tree.
+pub(crate) struct RawCursor<O, K: Key + ?Sized, V> {
+    root: Option<NodePtr<O, K, V>>,
+    key: Vec<u8>,
+    stack: Vec<(NodePtr<O, K, V>, u8)>,
+    leaf: Option<TypedNodePtr<O, LeafNode<K, V>>>,
+}
+
+impl<O: ValidPtr + Copy, K: Key + ?Sized, V> RawCursor<O, K, V> {
+    pub fn new(root: Option<NodePtr<O, K, V>>) -> Self {
+        RawCursor {
+            root,
+            key: Vec::new(),
+            stack: Vec::new(),
+            leaf: None,
+        }
+    }
+
+    /// Returns the key bytes and leaf of the current entry.
+    pub fn current(&self) -> Option<Item<'_, O, K, V>> {
+        self.leaf.map(|x| (self.key.as_slice(), x))
+    }
+
+    /// Move to the ghost position.
+    pub fn reset(&mut self) {
+        self.key.clear();
+        self.stack.clear();
+        self.leaf = None;
+    }
+
+    /// Move to the first or last leaf below a node, the key must contain the bytes up to the node.
+    fn descend(&mut self, mut node: NodePtr<O, K, V>, last: bool) {
+        loop {
+            self.key.extend_from_slice(node.prefix());
+            if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+                self.leaf = Some(leaf);
+                return;
+            }
+            let (branch, child) = if last {
+                node.prev_node(u8::MAX)
+            } else {
+                node.next_node(0)
+            }
+            .expect("branch node without children");
+            self.stack.push((node, branch));
+            self.key.push(branch);
+            node = child;
+        }
+    }
+
+    /// Move to the first leaf after, or if `last` before, the subtree of the child at the top of
+    /// the stack.
+    fn ascend(&mut self, last: bool) {
+        while let Some((node, branch)) = self.stack.pop() {
+            self.key.pop();
+            let sibling = if last {
+                branch.checked_sub(1).and_then(|x| node.prev_node(x))
+            } else {
+                branch.checked_add(1).and_then(|x| node.next_node(x))
+            };
+            if let Some((branch, child)) = sibling {
+                self.stack.push((node, branch));
+                self.key.push(branch);
+                self.descend(child, last);
+                return;
+            }
+            let len = self.key.len() - node.prefix().len();
+            self.key.truncate(len);
+        }
+        self.reset();
+    }
+
+    pub fn seek_first(&mut self) {
+        self.reset();
+        if let Some(root) = self.root {
+            self.descend(root, false);
+        }
+    }
+
+    pub fn seek_last(&mut self) {
+        self.reset();
+        if let Some(root) = self.root {
+            self.descend(root, true);
+        }
+    }
+
+    /// Move to the first leaf with key bytes larger or equal to `key`.
+    pub fn seek(&mut self, key: &[u8]) {
+        self.reset();
+        let Some(mut node) = self.root else {
+            return;
+        };
+
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            let rest = &key[depth..];
+            let len = prefix.len().min(rest.len());
+            match prefix[..len].cmp(&rest[..len]) {
+                // Every key in the node is smaller than the key.
+                Ordering::Less => return self.ascend(false),
+                // Every key in the node is larger than the key.
+                Ordering::Greater => return self.descend(node, false),
+                Ordering::Equal if len < prefix.len() => return self.descend(node, false),
+                Ordering::Equal => {}
+            }
+            depth += len;
+
+            if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+                if depth < key.len() {
+                    return self.ascend(false);
+                }
+                self.key.extend_from_slice(prefix);
+                self.leaf = Some(leaf);
+                return;
+            }
+            if depth == key.len() {
+                return self.descend(node, false);
+            }
+
+            self.key.extend_from_slice(prefix);
+            let Some((branch, child)) = node.next_node(key[depth]) else {
+                let len = self.key.len() - prefix.len();
+                self.key.truncate(len);
+                return self.ascend(false);
+            };
+            self.stack.push((node, branch));
+            self.key.push(branch);
+            if branch != key[depth] {
+                return self.descend(child, false);
+            }
+            node = child;
+            depth += 1;
+        }
+    }
+
+    /// Move to the next leaf, or the first leaf if at the ghost position.
+    pub fn next(&mut self) {
+        self.step(false)
+    }
+
+    /// Move to the previous leaf, or the last leaf if at the ghost position.
+    pub fn prev(&mut self) {
+        self.step(true)
+    }
+
+    fn step(&mut self, back: bool) {
+        let Some(leaf) = self.leaf.take() else {
+            if back {
+                self.seek_last()
+            } else {
+                self.seek_first()
+            }
+            return;
+        };
+        let len = self.key.len() - leaf.prefix().len();
+        self.key.truncate(len);
+        self.ascend(back);
+    }
+}
+
+impl<O: Copy, K: Key + ?Sized, V> Clone for RawCursor<O, K, V> {
+    fn clone(&self) -> Self {
+        RawCursor {
+            root: self.root,
+            key: self.key.clone(),
+            stack: self.stack.clone(),
+            leaf: self.leaf,
+        }
+    }
+}
+
+/// A cursor over the entries of an [`Art`].
+///
+/// Created at the ghost position between the last and the first entry, moving with
+/// [`Cursor::next`] from there goes to the first entry and with [`Cursor::prev`] to the last.
+pub struct Cursor<'a, K: Key + ?Sized, V> {
+    raw: RawCursor<Borrow<'a>, K, V>,
+}
+
+impl<'a, K: Key + ?Sized, V> Cursor<'a, K, V> {
+    /// Move to the first entry with a key larger or equal to the given key, or to the ghost
+    /// position if there is no such entry.
+    pub fn seek(&mut self, key: &K) {
+        self.raw.seek(&key_bytes(key))
+    }
+
+    pub fn seek_first(&mut self) {
+        self.raw.seek_first()
+    }
+
+    pub fn seek_last(&mut self) {
+        self.raw.seek_last()
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) {
+        self.raw.next()
+    }
+
+    pub fn prev(&mut self) {
+        self.raw.prev()
+    }
+
+    /// Returns the bytes of the current key.
+    pub fn key_bytes(&self) -> Option<&[u8]> {
+        self.raw.current().map(|x| x.0)
+    }
+
+    pub fn value(&self) -> Option<&'a V> {
+        self.raw.current().map(|x| x.1.into_value_ref())
+    }
+}
+
+impl<K: Key + BorrowedKey + ?Sized, V> Cursor<'_, K, V> {
+    pub fn key(&self) -> Option<&K> {
+        self.key_bytes().map(|x| unsafe { K::from_key_bytes(x) })
+    }
+}
+
+impl<K: Key + ?Sized, V> Clone for Cursor<'_, K, V> {
+    fn clone(&self) -> Self {
+        Cursor {
+            raw: self.raw.clone(),
+        }
+    }
+}
+
+/// A cursor over the entries of an [`Art`] which can modify the tree.
+///
+/// Modifying the structure of the tree invalidates the path of the cursor, so after removing or
+/// inserting an entry the cursor finds its position again from the root.
+pub struct CursorMut<'a, K: Key + ?Sized, V> {
+    tree: &'a mut Art<K, V>,
+    raw: RawCursor<MutValue<'a>, K, V>,
+}
+
+impl<'a, K: Key + ?Sized, V> CursorMut<'a, K, V> {
+    fn root(tree: &Art<K, V>) -> Option<NodePtr<MutValue<'a>, K, V>> {
+        // The cursor holds the only reference to the tree so it may hand out mutable values.
+        tree.tree
+            .root()
+            .map(|x| unsafe { x.as_unknown().assume_ownership() })
+    }
+
+    /// Find the position again after the tree was modified, `key` is the key bytes of the entry
+    /// the cursor should be at, or `None` for the ghost position.
+    fn reposition(&mut self, key: Option<&[u8]>) {
+        self.raw = RawCursor::new(Self::root(self.tree));
+        if let Some(key) = key {
+            self.raw.seek(key);
+        }
+    }
+
+    /// Move to the first entry with a key larger or equal to the given key, or to the ghost
+    /// position if there is no such entry.
+    pub fn seek(&mut self, key: &K) {
+        self.raw.seek(&key_bytes(key))
+    }
+
+    pub fn seek_first(&mut self) {
+        self.raw.seek_first()
+    }
+
+    pub fn seek_last(&mut self) {
+        self.raw.seek_last()
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) {
+        self.raw.next()
+    }
+
+    pub fn prev(&mut self) {
+        self.raw.prev()
+    }
+
+    /// Returns the bytes of the current key.
+    pub fn key_bytes(&self) -> Option<&[u8]> {
+        self.raw.current().map(|x| x.0)
+    }
+
+    pub fn value(&self) -> Option<&V> {
+        self.raw.leaf.as_ref().map(|x| x.as_value())
+    }
+
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.raw.leaf.map(|x| x.into_value_mut())
+    }
+
+    /// Insert an entry before the current entry, or as the last entry if the cursor is at the
+    /// ghost position. The cursor stays at the current entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key does not sort between the previous and the current entry.
+    pub fn insert_before(&mut self, key: &K, value: V) {
+        let bytes = key_bytes(key);
+        let current = self.key_bytes().map(|x| x.to_vec());
+        let mut prev = self.raw.clone();
+        prev.prev();
+        assert!(
+            current.as_ref().is_none_or(|x| bytes < *x)
+                && prev.current().is_none_or(|x| x.0 < bytes.as_slice()),
+            "the key does not sort between the previous and the current entry"
+        );
+        self.tree.insert(key, value);
+        self.reposition(current.as_deref());
+    }
+
+    /// Remove the current entry and move to the next entry, returns the removed value.
+    pub fn remove_current(&mut self) -> Option<V> {
+        let bytes = self.key_bytes()?.to_vec();
+        let res = self.tree.tree.remove_bytes(&bytes);
+        self.tree.len -= res.is_some() as usize;
+        self.reposition(Some(&bytes));
+        res
+    }
+}
+
+impl<K: Key + BorrowedKey + ?Sized, V> CursorMut<'_, K, V> {
+    pub fn key(&self) -> Option<&K> {
+        self.key_bytes().map(|x| unsafe { K::from_key_bytes(x) })
+    }
+}
+
+impl<K: Key + ?Sized, V> Art<K, V> {
+    /// Returns a cursor at the ghost position of the tree.
+    pub fn cursor(&self) -> Cursor<'_, K, V> {
+        Cursor {
+            raw: RawCursor::new(self.tree.root()),
+        }
+    }
+
+    /// Returns a cursor at the ghost position of the tree which can modify the tree.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V> {
+        CursorMut {
+            raw: RawCursor::new(CursorMut::root(self)),
+            tree: self,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Printing trees.
+//!
+//! The [`Display`](fmt::Display) implementation of [`Art`] prints the nodes of the tree, one per
+//! line, with its kind, `len` and prefix. Children are indented below their parent and preceded by
+//! their branch key. [`Art::display`] prints the tree with [`DisplayOptions`] to leave out parts
+//! of large trees.
+//!
+//! The [`Debug`](fmt::Debug) implementation prints the entries of the tree as a map.
+
+use crate::{key::BorrowedKey, key::Key, Art};
+use std::fmt;
+
+/// Options limiting how much of a tree is printed.
+///
+/// Children which are left out are summarized by a line with their amount.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisplayOptions {
+    /// The amount of levels below the root to print the children of, `Some(0)` only prints the
+    /// root.
+    pub max_depth: Option<usize>,
+    /// The amount of children to print per node.
+    pub max_children: Option<usize>,
+}
+
+/// A tree printed with [`DisplayOptions`], returned by [`Art::display`].
+pub struct TreeDisplay<'a, K: Key + ?Sized, V> {
+    tree: &'a Art<K, V>,
+    options: DisplayOptions,
+}
+
+impl<K: Key + ?Sized, V: fmt::Debug> fmt::Display for TreeDisplay<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree.tree.display(f, &self.options)
+    }
+}
+
+impl<K: Key + ?Sized, V: fmt::Debug> Art<K, V> {
+    pub fn display(&self, options: DisplayOptions) -> TreeDisplay<'_, K, V> {
+        TreeDisplay {
+            tree: self,
+            options,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("{self}");
+    }
+}
+
+impl<K: Key + ?Sized, V: fmt::Debug> fmt::Display for Art<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(DisplayOptions::default()).fmt(f)
+    }
+}
+
+impl<K, V> fmt::Debug for Art<K, V>
+where
+    K: Key + BorrowedKey + fmt::Debug + ?Sized,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut iter = self.iter();
+        while let Some((k, v)) = iter.next() {
+            map.entry(&k, v);
+        }
+        map.finish()
+    }
+}
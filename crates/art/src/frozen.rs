@@ -0,0 +1,616 @@
+//! A compact read-only snapshot format for trees.
+//!
+//! A snapshot stores the nodes of a tree without any pointers, nodes are addressed by their offset
+//! into the file and prefixes are stored inline in the node. A snapshot can be opened with
+//! [`FrozenArt::open`] which maps the file into memory, lookups and iteration then read directly
+//! from the mapped file without deserializing anything.
+//!
+//! # Layout
+//!
+//! All integers are stored little endian. The file starts with a header containing the magic
+//! bytes followed by the size and alignment of the value type as two u32's. Then follow the nodes,
+//! each aligned to 8 bytes, children are always written before their parent. The file ends with a
+//! trailer containing the offset of the root node as a u64 (0 if the tree is empty), the amount
+//! of keys as a u64 and the magic bytes again.
+//!
+//! Every node starts with the node kind as a u8, a padding byte, the amount of branches as a u16,
+//! the length of the prefix as a u32 and then the prefix itself. After the prefix, aligned to 8
+//! bytes, follows the body of the node which depends on the kind of the node:
+//!
+//! - Leaf: the value, aligned to the alignment of the value.
+//! - Node4 and Node16: the sorted branch keys, then aligned to 8 bytes the offsets of the branches.
+//! - Node48: 256 bytes containing the index of the branch for each key or `u8::MAX` for none,
+//!   then the offsets of the branches.
+//! - Node256: 256 offsets, one for each key, where 0 means the node does not have the branch.
+
+use crate::{
+    iter::{key_bytes, End},
+    key::{BorrowedKey, Key},
+    raw::{Borrow, LeafNode, NodeKind, NodePtr},
+    Art,
+};
+use bytemuck::Pod;
+use memmap2::Mmap;
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, Write},
+    marker::PhantomData,
+    mem,
+    ops::{Bound, RangeBounds},
+    path::Path,
+};
+
+const MAGIC: [u8; 8] = *b"ARTSNAP1";
+const HEADER_SIZE: usize = 16;
+const TRAILER_SIZE: usize = 24;
+const NODE_ALIGN: u64 = 8;
+const NODE_HEADER_SIZE: usize = 8;
+
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.next_multiple_of(align)
+}
+
+struct SnapshotWriter<W> {
+    writer: W,
+    offset: u64,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn align(&mut self, align: u64) -> io::Result<()> {
+        let mut pad = self.offset.next_multiple_of(align) - self.offset;
+        while pad > 0 {
+            let len = pad.min(64);
+            self.write(&[0u8; 64][..len as usize])?;
+            pad -= len;
+        }
+        Ok(())
+    }
+
+    fn write_header(&mut self, kind: NodeKind, count: usize, prefix: &[u8]) -> io::Result<u64> {
+        self.align(NODE_ALIGN)?;
+        let offset = self.offset;
+        self.write(&[kind as u8, 0])?;
+        self.write(&(count as u16).to_le_bytes())?;
+        self.write(&(prefix.len() as u32).to_le_bytes())?;
+        self.write(prefix)?;
+        self.align(NODE_ALIGN)?;
+        Ok(offset)
+    }
+
+    fn write_node<K: Key + ?Sized, V: Pod>(
+        &mut self,
+        node: NodePtr<Borrow<'_>, K, V>,
+    ) -> io::Result<u64> {
+        let prefix = node.prefix();
+        if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+            let offset = self.write_header(NodeKind::Leaf, 0, prefix)?;
+            self.align(mem::align_of::<V>() as u64)?;
+            self.write(bytemuck::bytes_of(leaf.as_value()))?;
+            return Ok(offset);
+        }
+
+        let mut branches = Vec::new();
+        let mut next = 0u16;
+        while let Some((key, child)) = (next <= u8::MAX as u16)
+            .then(|| node.next_node(next as u8))
+            .flatten()
+        {
+            branches.push((key, self.write_node(child)?));
+            next = key as u16 + 1;
+        }
+
+        let kind = node.kind();
+        let offset = self.write_header(kind, branches.len(), prefix)?;
+        match kind {
+            NodeKind::Leaf => unreachable!(),
+            NodeKind::Node4 | NodeKind::Node16 => {
+                for (key, _) in branches.iter() {
+                    self.write(&[*key])?;
+                }
+                self.align(NODE_ALIGN)?;
+                for (_, ptr) in branches.iter() {
+                    self.write(&ptr.to_le_bytes())?;
+                }
+            }
+            NodeKind::Node48 => {
+                let mut idx = [u8::MAX; 256];
+                for (i, (key, _)) in branches.iter().enumerate() {
+                    idx[*key as usize] = i as u8;
+                }
+                self.write(&idx)?;
+                for (_, ptr) in branches.iter() {
+                    self.write(&ptr.to_le_bytes())?;
+                }
+            }
+            NodeKind::Node256 => {
+                let mut ptrs = [0u64; 256];
+                for (key, ptr) in branches.iter() {
+                    ptrs[*key as usize] = *ptr;
+                }
+                for ptr in ptrs {
+                    self.write(&ptr.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(offset)
+    }
+}
+
+impl<K: Key + ?Sized, V: Pod> Art<K, V> {
+    /// Write a snapshot of the tree which can be opened with [`FrozenArt::open`].
+    pub fn write_frozen<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = SnapshotWriter { writer, offset: 0 };
+        writer.write(&MAGIC)?;
+        writer.write(&(mem::size_of::<V>() as u32).to_le_bytes())?;
+        writer.write(&(mem::align_of::<V>() as u32).to_le_bytes())?;
+
+        let root = match self.tree.root() {
+            Some(x) => writer.write_node(x)?,
+            None => 0,
+        };
+
+        writer.align(NODE_ALIGN)?;
+        writer.write(&root.to_le_bytes())?;
+        writer.write(&(self.len as u64).to_le_bytes())?;
+        writer.write(&MAGIC)?;
+        writer.writer.flush()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FrozenNode<'a> {
+    kind: NodeKind,
+    count: usize,
+    prefix: &'a [u8],
+    body: usize,
+}
+
+impl<'a> FrozenNode<'a> {
+    /// Decode the header of the node at `offset`, returns `None` if the header or prefix of the
+    /// node is not within `map` or the node kind is invalid.
+    fn decode(map: &'a [u8], offset: usize) -> Option<Self> {
+        if !offset.is_multiple_of(NODE_ALIGN as usize) {
+            return None;
+        }
+        let header = map.get(offset..offset.checked_add(NODE_HEADER_SIZE)?)?;
+        let kind = match header[0] {
+            0 => NodeKind::Leaf,
+            1 => NodeKind::Node4,
+            2 => NodeKind::Node16,
+            3 => NodeKind::Node48,
+            4 => NodeKind::Node256,
+            _ => return None,
+        };
+        let count = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let prefix_len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        let prefix_start = offset + NODE_HEADER_SIZE;
+        let prefix = map.get(prefix_start..prefix_start.checked_add(prefix_len)?)?;
+        Some(FrozenNode {
+            kind,
+            count,
+            prefix,
+            body: align_up(prefix_start + prefix_len, NODE_ALIGN as usize),
+        })
+    }
+}
+
+fn read_u64(map: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(map[offset..offset + 8].try_into().unwrap())
+}
+
+/// Check every node below the root, returns the amount of leafs in the tree.
+///
+/// Every node must be within `map`, have a valid kind and a body matching its kind. The key of
+/// every leaf must be valid for the key type. The children of
+/// a node are written before the node, so requiring every child offset to be smaller than the
+/// offset of its parent rules out cycles. Every node takes up at least [`NODE_ALIGN`] bytes which
+/// bounds the amount of nodes a valid snapshot can have, visiting more nodes than that means some
+/// node is the child of multiple parents.
+fn validate<K: Key + ?Sized, V: Pod>(map: &[u8], root: usize) -> Result<usize, &'static str> {
+    let out_of_bounds = "snapshot node out of bounds";
+    let mut remaining = map.len() / NODE_ALIGN as usize;
+    let mut leafs = 0;
+    // The nodes left to visit with the length of the key leading to them and their branch key,
+    // the key of the node on top of the stack is always at the start of `key`.
+    let mut stack = vec![(root, 0, None)];
+    let mut key = Vec::new();
+    while let Some((offset, depth, branch)) = stack.pop() {
+        remaining = remaining
+            .checked_sub(1)
+            .ok_or("snapshot node has multiple parents")?;
+        let node = FrozenNode::decode(map, offset).ok_or(out_of_bounds)?;
+        key.truncate(depth);
+        key.extend(branch);
+        key.extend_from_slice(node.prefix);
+        let mut branch_keys = [0u8; 256];
+        let children_start = match node.kind {
+            NodeKind::Leaf => {
+                let start = align_up(node.body, mem::align_of::<V>());
+                if start + mem::size_of::<V>() > map.len() {
+                    return Err(out_of_bounds);
+                }
+                if !K::is_key_bytes(&key) {
+                    return Err("snapshot key is not valid");
+                }
+                leafs += 1;
+                continue;
+            }
+            NodeKind::Node4 | NodeKind::Node16 => {
+                let max = if node.kind == NodeKind::Node4 { 4 } else { 16 };
+                if node.count > max {
+                    return Err("snapshot node has too many branches");
+                }
+                let keys = map
+                    .get(node.body..node.body + node.count)
+                    .ok_or(out_of_bounds)?;
+                if keys.windows(2).any(|x| x[0] >= x[1]) {
+                    return Err("snapshot node branches are not sorted");
+                }
+                branch_keys[..node.count].copy_from_slice(keys);
+                align_up(node.body + node.count, NODE_ALIGN as usize)
+            }
+            NodeKind::Node48 => {
+                if node.count > 48 {
+                    return Err("snapshot node has too many branches");
+                }
+                let idx = map.get(node.body..node.body + 256).ok_or(out_of_bounds)?;
+                let mut used = [false; 48];
+                for (k, &i) in idx.iter().enumerate().filter(|x| *x.1 != u8::MAX) {
+                    if i as usize >= node.count || mem::replace(&mut used[i as usize], true) {
+                        return Err("snapshot node has an invalid branch index");
+                    }
+                    branch_keys[i as usize] = k as u8;
+                }
+                if used.iter().filter(|x| **x).count() != node.count {
+                    return Err("snapshot node has an invalid branch index");
+                }
+                node.body + 256
+            }
+            NodeKind::Node256 => {
+                branch_keys = std::array::from_fn(|i| i as u8);
+                node.body
+            }
+        };
+
+        let slots = if node.kind == NodeKind::Node256 {
+            256
+        } else {
+            node.count
+        };
+        if children_start + slots * 8 > map.len() {
+            return Err(out_of_bounds);
+        }
+        let mut count = 0;
+        for (i, &branch) in branch_keys[..slots].iter().enumerate() {
+            let child = read_u64(map, children_start + i * 8) as usize;
+            if child == 0 && node.kind == NodeKind::Node256 {
+                continue;
+            }
+            if child == 0 || child >= offset {
+                return Err("snapshot child is not written before its parent");
+            }
+            count += 1;
+            stack.push((child, key.len(), Some(branch)));
+        }
+        if count != node.count {
+            return Err("snapshot node branch count does not match");
+        }
+    }
+    Ok(leafs)
+}
+
+/// A read-only tree backed by a memory mapped snapshot.
+///
+/// Created from a file written by [`Art::write_frozen`]. Every node is checked when the snapshot is
+/// opened, a truncated or otherwise corrupted snapshot is rejected with
+/// [`io::ErrorKind::InvalidData`]. The file is expected to not be modified while it is mapped.
+pub struct FrozenArt<K: Key + ?Sized, V> {
+    map: Mmap,
+    root: Option<usize>,
+    len: usize,
+    _marker: PhantomData<fn(&K) -> V>,
+}
+
+impl<K: Key + ?Sized, V: Pod> FrozenArt<K, V> {
+    /// Open a snapshot file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: The snapshot is only ever read and is required to not be modified while mapped.
+        let map = unsafe { Mmap::map(&file)? };
+        Self::from_map(map)
+    }
+
+    fn from_map(map: Mmap) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if map.len() < HEADER_SIZE + TRAILER_SIZE
+            || map[..8] != MAGIC
+            || map[map.len() - 8..] != MAGIC
+        {
+            return Err(invalid("file is not a tree snapshot"));
+        }
+        let size = u32::from_le_bytes(map[8..12].try_into().unwrap());
+        let align = u32::from_le_bytes(map[12..16].try_into().unwrap());
+        if size as usize != mem::size_of::<V>() || align as usize != mem::align_of::<V>() {
+            return Err(invalid("snapshot value type does not match"));
+        }
+
+        let trailer = map.len() - TRAILER_SIZE;
+        let root = u64::from_le_bytes(map[trailer..trailer + 8].try_into().unwrap()) as usize;
+        let len = u64::from_le_bytes(map[trailer + 8..trailer + 16].try_into().unwrap()) as usize;
+        if root >= trailer {
+            return Err(invalid("snapshot root offset out of bounds"));
+        }
+        let leafs = match root {
+            0 => 0,
+            root => validate::<K, V>(&map[..trailer], root).map_err(invalid)?,
+        };
+        if leafs != len {
+            return Err(invalid("snapshot length does not match"));
+        }
+
+        Ok(FrozenArt {
+            map,
+            root: (root != 0).then_some(root),
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut offset = self.root?;
+        let mut depth = 0;
+        loop {
+            let node = self.node(offset);
+            let end = depth + node.prefix.len();
+            if end > key.len() || (depth..end).any(|i| key.at(i) != node.prefix[i - depth]) {
+                return None;
+            }
+            depth = end;
+
+            if node.kind == NodeKind::Leaf {
+                return (depth == key.len()).then(|| self.value(node));
+            }
+            if depth >= key.len() {
+                return None;
+            }
+            offset = self.branch(node, key.at(depth))?;
+            depth += 1;
+        }
+    }
+
+    /// Returns an iterator over all the entries in the tree in key order.
+    pub fn iter(&self) -> FrozenIter<'_, K, V> {
+        let mut iter = FrozenIter::new(self, End::Unbounded);
+        if let Some(root) = self.root {
+            iter.pending = iter.enter(root);
+        }
+        iter
+    }
+
+    /// Returns an iterator over the entries with keys within the range in key order.
+    pub fn range<'r, R>(&self, range: R) -> FrozenIter<'_, K, V>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let end = End::from_bound(range.end_bound());
+        match range.start_bound() {
+            Bound::Included(x) => FrozenIter::seek(self, &key_bytes(*x), true, end),
+            Bound::Excluded(x) => FrozenIter::seek(self, &key_bytes(*x), false, end),
+            Bound::Unbounded => FrozenIter::seek(self, &[], true, end),
+        }
+    }
+
+    fn read_u64(&self, offset: usize) -> u64 {
+        read_u64(&self.map, offset)
+    }
+
+    fn node(&self, offset: usize) -> FrozenNode<'_> {
+        FrozenNode::decode(&self.map, offset).expect("nodes are validated when opened")
+    }
+
+    fn value(&self, node: FrozenNode) -> &V {
+        let start = align_up(node.body, mem::align_of::<V>());
+        bytemuck::from_bytes(&self.map[start..start + mem::size_of::<V>()])
+    }
+
+    /// Returns the offset of the branch for the given key.
+    fn branch(&self, node: FrozenNode, key: u8) -> Option<usize> {
+        let ptr = match node.kind {
+            NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+            NodeKind::Node4 | NodeKind::Node16 => {
+                let keys = &self.map[node.body..node.body + node.count];
+                let idx = keys.binary_search(&key).ok()?;
+                let ptrs = align_up(node.body + node.count, NODE_ALIGN as usize);
+                self.read_u64(ptrs + idx * 8)
+            }
+            NodeKind::Node48 => {
+                let idx = self.map[node.body + key as usize];
+                if idx == u8::MAX {
+                    return None;
+                }
+                self.read_u64(node.body + 256 + idx as usize * 8)
+            }
+            NodeKind::Node256 => self.read_u64(node.body + key as usize * 8),
+        };
+        (ptr != 0).then_some(ptr as usize)
+    }
+
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    fn next_branch(&self, node: FrozenNode, from: u8) -> Option<(u8, usize)> {
+        match node.kind {
+            NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+            NodeKind::Node4 | NodeKind::Node16 => {
+                let keys = &self.map[node.body..node.body + node.count];
+                let idx = keys.binary_search(&from).unwrap_or_else(|x| x);
+                let key = *keys.get(idx)?;
+                let ptrs = align_up(node.body + node.count, NODE_ALIGN as usize);
+                Some((key, self.read_u64(ptrs + idx * 8) as usize))
+            }
+            NodeKind::Node48 | NodeKind::Node256 => {
+                (from..=u8::MAX).find_map(|k| self.branch(node, k).map(|x| (k, x)))
+            }
+        }
+    }
+}
+
+impl<V: Pod> FrozenArt<str, V> {
+    /// Returns an iterator over the entries with keys starting with the given prefix.
+    pub fn scan_prefix(&self, prefix: &str) -> FrozenIter<'_, str, V> {
+        let prefix = prefix.as_bytes();
+        FrozenIter::seek(self, prefix, true, End::Prefix(prefix.to_vec()))
+    }
+}
+
+/// An iterator over the entries of a [`FrozenArt`].
+pub struct FrozenIter<'a, K: Key + ?Sized, V> {
+    tree: &'a FrozenArt<K, V>,
+    key: Vec<u8>,
+    pending: Option<usize>,
+    stack: Vec<(usize, u16, usize)>,
+    end: End,
+}
+
+impl<'a, K: Key + ?Sized, V: Pod> FrozenIter<'a, K, V> {
+    fn new(tree: &'a FrozenArt<K, V>, end: End) -> Self {
+        FrozenIter {
+            tree,
+            key: Vec::new(),
+            pending: None,
+            stack: Vec::new(),
+            end,
+        }
+    }
+
+    /// Create an iterator positioned at the first key larger than, or if `inclusive` equal to,
+    /// `start`.
+    fn seek(tree: &'a FrozenArt<K, V>, start: &[u8], inclusive: bool, end: End) -> Self {
+        let mut this = Self::new(tree, end);
+        let Some(mut offset) = tree.root else {
+            return this;
+        };
+
+        let mut depth = 0;
+        loop {
+            let node = tree.node(offset);
+            let rest = &start[depth..];
+            let len = node.prefix.len().min(rest.len());
+            match node.prefix[..len].cmp(&rest[..len]) {
+                // every key in the node is smaller than start.
+                Ordering::Less => return this,
+                // every key in the node is larger than start.
+                Ordering::Greater => {
+                    this.pending = this.enter(offset);
+                    return this;
+                }
+                Ordering::Equal if len < node.prefix.len() => {
+                    this.pending = this.enter(offset);
+                    return this;
+                }
+                Ordering::Equal => {}
+            }
+
+            let base = this.key.len();
+            this.key.extend_from_slice(node.prefix);
+            depth += len;
+
+            if node.kind == NodeKind::Leaf {
+                if depth == start.len() && inclusive {
+                    this.pending = Some(offset);
+                }
+                return this;
+            }
+
+            if depth == start.len() {
+                this.stack.push((offset, 0, base));
+                return this;
+            }
+
+            let branch = start[depth];
+            match tree.branch(node, branch) {
+                Some(child) => {
+                    this.stack.push((offset, branch as u16 + 1, base));
+                    this.key.push(branch);
+                    offset = child;
+                    depth += 1;
+                }
+                None => {
+                    this.stack.push((offset, branch as u16, base));
+                    return this;
+                }
+            }
+        }
+    }
+
+    /// Enter a node, returns the node if it is a leaf, otherwise pushes the node onto the stack.
+    fn enter(&mut self, offset: usize) -> Option<usize> {
+        let node = self.tree.node(offset);
+        let base = self.key.len();
+        self.key.extend_from_slice(node.prefix);
+        if node.kind == NodeKind::Leaf {
+            return Some(offset);
+        }
+        self.stack.push((offset, 0, base));
+        None
+    }
+
+    fn next_leaf(&mut self) -> Option<usize> {
+        if let Some(x) = self.pending.take() {
+            return Some(x);
+        }
+
+        loop {
+            let (offset, next, base) = self.stack.last_mut()?;
+            let node = self.tree.node(*offset);
+            let found = if *next > u8::MAX as u16 {
+                None
+            } else {
+                self.tree.next_branch(node, *next as u8)
+            };
+
+            let Some((branch, child)) = found else {
+                self.key.truncate(*base);
+                self.stack.pop();
+                continue;
+            };
+
+            *next = branch as u16 + 1;
+            let len = *base + node.prefix.len();
+            self.key.truncate(len);
+            self.key.push(branch);
+
+            if let Some(leaf) = self.enter(child) {
+                return Some(leaf);
+            }
+        }
+    }
+}
+
+impl<'a, K: Key + BorrowedKey + ?Sized, V: Pod> FrozenIter<'a, K, V> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a V)> {
+        let leaf = self.next_leaf()?;
+        if !self.end.contains(&self.key) {
+            self.stack.clear();
+            return None;
+        }
+        let value = self.tree.value(self.tree.node(leaf));
+        unsafe { Some((K::from_key_bytes(&self.key), value)) }
+    }
+}
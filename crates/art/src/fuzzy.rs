@@ -0,0 +1,24 @@
+//! Searching for keys within an edit distance of a string.
+
+use crate::{key::BorrowedKey, Art};
+use key_automata::Levenshtein;
+
+impl<V> Art<str, V> {
+    /// Returns the keys within `max_edits` insertions, deletions or substitutions of characters
+    /// from the query in key order, together with their values and edit distance.
+    ///
+    /// The tree is walked with a Levenshtein automaton, a subtree is skipped as soon as its prefix
+    /// can no longer be completed into a key within the edit distance.
+    pub fn fuzzy(
+        &self,
+        query: &str,
+        max_edits: usize,
+    ) -> impl Iterator<Item = (String, &V, usize)> {
+        let mut search = self.tree.search(Levenshtein::new(query, max_edits));
+        std::iter::from_fn(move || {
+            let (key, value, distance) = search.next()?;
+            let key = unsafe { <str as BorrowedKey>::from_key_bytes(key) }.to_owned();
+            Some((key, value, distance))
+        })
+    }
+}
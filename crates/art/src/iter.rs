@@ -1,92 +1,164 @@
 use crate::{
     key::Key,
-    raw::{LeafNode, NodePtr, TypedNodePtr},
+    raw::{LeafNode, NodePtr, TypedNodePtr, ValidPtr},
     Art,
 };
-use std::marker::PhantomData;
+use std::{cmp::Ordering, marker::PhantomData, ops::Bound};
 
+/// An iterator over the leafs of tree in key order.
+///
+/// Keeps an explicit stack of the branch nodes currently being iterated, together with the next
+/// key to look at in the node and the length of the key before the prefix of the node.
 pub struct RawIterator<'a, O, K: Key + ?Sized, V> {
-    pub key: Vec<u8>,
-    pub ptr: Option<NodePtr<O, K, V>>,
-    pub _marker: PhantomData<&'a Art<K, V>>,
+    key: Vec<u8>,
+    start: Option<NodePtr<O, K, V>>,
+    stack: Vec<(NodePtr<O, K, V>, u16, usize)>,
+    _marker: PhantomData<&'a Art<K, V>>,
 }
 
-type Item<'a, O, K, V> = (&'a [u8], TypedNodePtr<O, LeafNode<K, V>>);
+pub(crate) type Item<'a, O, K, V> = (&'a [u8], TypedNodePtr<O, LeafNode<K, V>>);
 
-impl<O, K: Key + ?Sized, V> RawIterator<'_, O, K, V> {
-    pub fn next(&mut self) -> Option<Item<'_, O, K, V>> {
-        todo!()
-        /*
-        unsafe {
-            if self.key.is_empty() {
-                // startup
-                let mut ptr = self.ptr?;
-                dbg!(ptr.into_ptr());
-
-                loop {
-                    self.key.extend_from_slice(ptr.header().storage.prefix());
-
-                    if ptr.is::<LeafNode<K, V>>() {
-                        self.ptr = Some(ptr);
-                        return Some((self.key.as_slice(), ptr.into_owned()));
-                    }
-
-                    let (key, new_ptr) = ptr.next_node(0).expect("no leaf nodes in branch node");
-                    dbg!(key);
-                    dbg!(new_ptr.into_ptr());
-                    self.key.push(key);
-                    ptr = new_ptr;
-                }
-            }
-
-            let mut ptr = self.ptr?;
-
-            let new_len = self.key.len() - ptr.header().storage.prefix().len();
-            self.key.truncate(new_len);
-
-            ptr = ptr.header().parent?;
+impl<O: ValidPtr + Copy, K: Key + ?Sized, V> RawIterator<'_, O, K, V> {
+    pub fn new(root: Option<NodePtr<O, K, V>>) -> Self {
+        RawIterator {
+            key: Vec::new(),
+            start: root,
+            stack: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
 
-            loop {
-                let last_key = self.key.last_mut().unwrap();
+    /// Enter a node, returns the node if it is a leaf, otherwise pushes the node onto the stack.
+    fn enter(&mut self, ptr: NodePtr<O, K, V>) -> Option<TypedNodePtr<O, LeafNode<K, V>>> {
+        let base = self.key.len();
+        self.key.extend_from_slice(ptr.prefix());
+        if let Some(leaf) = ptr.cast::<LeafNode<K, V>>() {
+            return Some(leaf);
+        }
+        self.stack.push((ptr, 0, base));
+        None
+    }
 
-                if *last_key == 255 {
-                    self.ptr = ptr.header().parent;
-                    let new_len = self.key.len() - ptr.prefix().len() - 1;
-                    self.key.truncate(new_len);
-                    continue;
+    /// Create an iterator positioned at the first key larger than, or if `inclusive` equal to,
+    /// `start`.
+    pub fn seek(root: Option<NodePtr<O, K, V>>, start: &[u8], inclusive: bool) -> Self {
+        let mut this = Self::new(None);
+        let Some(mut ptr) = root else {
+            return this;
+        };
+
+        let mut depth = 0;
+        loop {
+            let prefix = ptr.prefix();
+            let rest = &start[depth..];
+            let len = prefix.len().min(rest.len());
+            match prefix[..len].cmp(&rest[..len]) {
+                // every key in the node is smaller than start.
+                Ordering::Less => return this,
+                // every key in the node is larger than start.
+                Ordering::Greater => {
+                    this.start = Some(ptr);
+                    return this;
+                }
+                Ordering::Equal if len < prefix.len() => {
+                    this.start = Some(ptr);
+                    return this;
                 }
+                Ordering::Equal => {}
+            }
+            depth += len;
 
-                let Some((found, new_ptr)) = ptr.next_node(*last_key + 1) else {
-                    self.ptr = ptr.header().parent;
-                    let new_len = self.key.len() - ptr.prefix().len() - 1;
-                    self.key.truncate(new_len);
-                    continue;
-                };
+            if ptr.cast::<LeafNode<K, V>>().is_some() {
+                if depth == start.len() && inclusive {
+                    this.start = Some(ptr);
+                }
+                return this;
+            }
 
-                *last_key = found;
+            let base = this.key.len();
+            this.key.extend_from_slice(prefix);
+            if depth == start.len() {
+                this.stack.push((ptr, 0, base));
+                return this;
+            }
 
-                self.key
-                    .extend_from_slice(new_ptr.header().storage.prefix());
+            let branch = start[depth];
+            match ptr.get(branch) {
+                Some(child) => {
+                    this.stack.push((ptr, branch as u16 + 1, base));
+                    this.key.push(branch);
+                    ptr = child;
+                    depth += 1;
+                }
+                None => {
+                    this.stack.push((ptr, branch as u16, base));
+                    return this;
+                }
+            }
+        }
+    }
 
-                ptr = new_ptr;
-                break;
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Item<'_, O, K, V>> {
+        if let Some(root) = self.start.take() {
+            if let Some(leaf) = self.enter(root) {
+                return Some((self.key.as_slice(), leaf));
             }
+        }
 
-            loop {
-                if ptr.is::<LeafNode<K, V>>() {
-                    self.ptr = Some(ptr);
-                    return Some((self.key.as_slice(), ptr.into_owned()));
-                }
+        loop {
+            let (node, next, base) = self.stack.last_mut()?;
+            let found = if *next > u8::MAX as u16 {
+                None
+            } else {
+                node.next_node(*next as u8)
+            };
+
+            let Some((branch, child)) = found else {
+                self.key.truncate(*base);
+                self.stack.pop();
+                continue;
+            };
+
+            *next = branch as u16 + 1;
+            let len = *base + node.prefix().len();
+            self.key.truncate(len);
+            self.key.push(branch);
+
+            if let Some(leaf) = self.enter(child) {
+                return Some((self.key.as_slice(), leaf));
+            }
+        }
+    }
+}
 
-                let (key, new_ptr) = ptr.next_node(0).expect("no leaf nodes in branch node");
+/// The end bound of a range iteration, in key bytes.
+pub(crate) enum End {
+    Unbounded,
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+    Prefix(Vec<u8>),
+}
 
-                self.key.push(key);
-                self.key
-                    .extend_from_slice(new_ptr.header().storage.prefix());
+impl End {
+    pub(crate) fn from_bound<K: Key + ?Sized>(bound: Bound<&&K>) -> Self {
+        match bound {
+            Bound::Included(x) => End::Included(key_bytes(*x)),
+            Bound::Excluded(x) => End::Excluded(key_bytes(*x)),
+            Bound::Unbounded => End::Unbounded,
+        }
+    }
 
-                ptr = new_ptr;
-            }
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        match self {
+            End::Unbounded => true,
+            End::Included(x) => key <= x.as_slice(),
+            End::Excluded(x) => key < x.as_slice(),
+            End::Prefix(x) => key.starts_with(x),
         }
-        */
     }
 }
+
+pub(crate) fn key_bytes<K: Key + ?Sized>(key: &K) -> Vec<u8> {
+    (0..key.len()).map(|i| key.at(i)).collect()
+}
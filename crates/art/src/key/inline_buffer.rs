@@ -135,6 +135,14 @@ unsafe impl<K: Key + ?Sized> KeyStorage<K> for InlineStorage {
         self.key()
     }
 
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self {
+        let mut new = unsafe { InlineStorage::new(prefix.len(), data) };
+        unsafe {
+            std::ptr::copy_nonoverlapping(prefix.as_ptr(), new.buffer_ptr().as_ptr(), prefix.len());
+        }
+        new
+    }
+
     fn drop_prefix(&mut self, offset: usize) {
         let key = self.key();
         let mut new = unsafe { InlineStorage::new(key.len() - offset, self.data) };
@@ -150,12 +158,7 @@ unsafe impl<K: Key + ?Sized> KeyStorage<K> for InlineStorage {
     }
 
     fn prepend_prefix(&mut self, prefix: &[u8], key: u8) {
-        let len = if self.len < INLINE_MAX {
-            self.len as usize
-        } else {
-            unsafe { self.buffer.ptr.as_ref().len }
-        };
-        let new_len = len + prefix.len() + 1;
+        let new_len = self.key().len() + prefix.len() + 1;
         unsafe {
             let mut new = InlineStorage::new(new_len, self.data);
             // copy new prefix.
@@ -1,10 +1,12 @@
 use crate::raw::NodeData;
-use std::ops::RangeBounds;
+use std::ops::Range;
 
 mod inline_buffer;
+mod net;
 mod pod;
 
 use inline_buffer::InlineStorage;
+pub use net::{Ipv4Net, Ipv6Net};
 pub use pod::PodStorageU8;
 
 /// A trait used for the storage of key prefixes.
@@ -14,15 +16,15 @@ pub use pod::PodStorageU8;
 /// rules will result in undefined behaviour.
 ///
 /// - The implementation of this trait must ensure that any call to [`KeyStorage::data`] or
-/// [`KeyStorage::data_mut`] returns an object with the same value as the one given by
-/// [`KeyStorage::store`]. Further more the value object must not changed unless changed externally
-/// by using [`KeyStorage::data_mut`].
+///   [`KeyStorage::data_mut`] returns an object with the same value as the one given by
+///   [`KeyStorage::store`]. Further more the value object must not changed unless changed
+///   externally by using [`KeyStorage::data_mut`].
 ///
 /// In short the caller of this trait must be able to trust that the storage won't suddenly change
 /// the value of NodeData.
 pub unsafe trait KeyStorage<K: Key + ?Sized>: Sized {
     /// Create the storage for a key.
-    fn store<R: RangeBounds<usize>>(key: &K, range: R, data: NodeData) -> Self;
+    fn store(key: &K, range: Range<usize>, data: NodeData) -> Self;
 
     /// Return a reference to NodeData.
     ///
@@ -39,6 +41,9 @@ pub unsafe trait KeyStorage<K: Key + ?Sized>: Sized {
     /// Retrieve the prefix stored in the storage.
     fn prefix(&self) -> &[u8];
 
+    /// Create the storage for a prefix given as bytes.
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self;
+
     /// Drop the start of the key, after calling this the storage should only contain [offset..]
     fn drop_prefix(&mut self, offset: usize);
 
@@ -52,10 +57,23 @@ pub trait Key {
 
     fn len(&self) -> usize;
 
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     fn at(&self, idx: usize) -> u8;
+
+    /// Returns whether the bytes are the full key bytes of a key of this type, used to check the
+    /// keys read from a snapshot.
+    fn is_key_bytes(bytes: &[u8]) -> bool {
+        let _ = bytes;
+        true
+    }
 }
 
 pub trait BorrowedKey {
+    /// # Safety
+    /// The bytes must be the full key bytes of a key of this type.
     unsafe fn from_key_bytes(bytes: &[u8]) -> &Self;
 }
 
@@ -65,11 +83,32 @@ impl BorrowedKey for str {
     }
 }
 
+/// A key which can be a prefix of another key.
+///
+/// The bytes of these keys end with a terminator byte which never directly follows the bytes of a
+/// shorter key within a longer key. The tree thus never contains a key whose bytes are a prefix of
+/// another key, while the key a terminator follows can still be found as the prefix of a longer
+/// key.
+pub trait PrefixKey: Key + ToOwned {
+    const TERMINATOR: u8;
+
+    /// Recreate a key from its full key bytes, including the terminator.
+    fn from_prefix_bytes(bytes: &[u8]) -> Self::Owned;
+}
+
+impl PrefixKey for str {
+    const TERMINATOR: u8 = INVALID_STR_BYTE;
+
+    fn from_prefix_bytes(bytes: &[u8]) -> String {
+        unsafe { Self::from_key_bytes(bytes) }.to_owned()
+    }
+}
+
 // A byte which is not allowed to continue a string in a valid utf-8 string
 // Specifically a byte tagged with a continue bit pattern.
 //
 // This ensures that no string can be a prefix of another.
-const INVALID_STR_BYTE: u8 = 0b1011_1111;
+pub(crate) const INVALID_STR_BYTE: u8 = 0b1011_1111;
 
 impl Key for str {
     type Storage = InlineStorage;
@@ -86,6 +125,10 @@ impl Key for str {
         // +1 for the INVALID_STR_BYTE
         self.len() + 1
     }
+
+    fn is_key_bytes(bytes: &[u8]) -> bool {
+        matches!(bytes.split_last(), Some((&INVALID_STR_BYTE, s)) if std::str::from_utf8(s).is_ok())
+    }
 }
 
 macro_rules! impl_pod {
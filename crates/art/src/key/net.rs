@@ -0,0 +1,117 @@
+//! Network address keys for routing tables.
+//!
+//! A network is stored as one byte for every significant bit of its address followed by a
+//! terminator, so a network is a prefix of all the networks and addresses it contains and
+//! [`Art::longest_prefix_match`](crate::Art::longest_prefix_match) finds the most specific
+//! network containing an address.
+
+use super::{InlineStorage, Key, PrefixKey};
+use core::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The byte following the bits of a network, bits are stored as the bytes 0 and 1.
+const NET_TERMINATOR: u8 = 2;
+
+macro_rules! impl_net {
+    ($name:ident, $addr:ident, $bits:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name {
+            bits: $bits,
+            len: u8,
+        }
+
+        impl $name {
+            const MAX_LEN: u8 = <$bits>::BITS as u8;
+
+            /// Create a network from an address and the amount of significant bits, the other
+            /// bits of the address are cleared.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `len` is larger than the amount of bits in the address.
+            pub fn new(addr: $addr, len: u8) -> Self {
+                assert!(len <= Self::MAX_LEN, "network length out of range");
+                let mask = (<$bits>::MAX)
+                    .checked_shl((Self::MAX_LEN - len) as u32)
+                    .unwrap_or(0);
+                $name {
+                    bits: <$bits>::from(addr) & mask,
+                    len,
+                }
+            }
+
+            pub fn addr(&self) -> $addr {
+                $addr::from(self.bits)
+            }
+
+            /// Returns the amount of significant bits of the address.
+            pub fn prefix_len(&self) -> u8 {
+                self.len
+            }
+
+            /// Returns whether the network contains the given address.
+            pub fn contains(&self, addr: $addr) -> bool {
+                Self::new(addr, self.len) == *self
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}/{}", self.addr(), self.len)
+            }
+        }
+
+        impl From<$addr> for $name {
+            /// Returns the network containing only the given address.
+            fn from(addr: $addr) -> Self {
+                Self::new(addr, Self::MAX_LEN)
+            }
+        }
+
+        impl Key for $name {
+            type Storage = InlineStorage;
+
+            fn len(&self) -> usize {
+                // +1 for the NET_TERMINATOR
+                self.len as usize + 1
+            }
+
+            fn at(&self, idx: usize) -> u8 {
+                if idx >= self.len as usize {
+                    NET_TERMINATOR
+                } else {
+                    (self.bits >> (Self::MAX_LEN as usize - 1 - idx)) as u8 & 1
+                }
+            }
+        }
+
+        impl PrefixKey for $name {
+            const TERMINATOR: u8 = NET_TERMINATOR;
+
+            fn from_prefix_bytes(bytes: &[u8]) -> Self {
+                let len = bytes.len() - 1;
+                let bits = bytes[..len].iter().enumerate().fold(0, |acc, (i, b)| {
+                    acc | (*b as $bits) << (Self::MAX_LEN as usize - 1 - i)
+                });
+                $name {
+                    bits,
+                    len: len as u8,
+                }
+            }
+        }
+    };
+}
+
+impl_net!(
+    Ipv4Net,
+    Ipv4Addr,
+    u32,
+    "An IPv4 network, an address of which only the first bits are significant."
+);
+impl_net!(
+    Ipv6Net,
+    Ipv6Addr,
+    u128,
+    "An IPv6 network, an address of which only the first bits are significant."
+);
@@ -40,24 +40,47 @@ unsafe impl<T: Pod + Key> KeyStorage<T> for PodStorageU8<T> {
         unsafe { std::slice::from_raw_parts(self.value.as_ptr().cast(), self.len as usize) }
     }
 
-    fn drop_prefix(&mut self, offset: usize) {
-        let slice = unsafe {
-            &mut bytemuck::bytes_of_mut(self.value.assume_init_mut())[..self.len as usize]
+    fn from_prefix(prefix: &[u8], data: NodeData) -> Self {
+        assert!(prefix.len() <= std::mem::size_of::<T>());
+
+        let mut value: MaybeUninit<T> = MaybeUninit::uninit();
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                prefix.as_ptr(),
+                value.as_mut_ptr().cast::<u8>(),
+                prefix.len(),
+            )
         };
-        let new_len = slice.len().checked_sub(offset).unwrap();
-        slice.copy_within(offset.., 0);
+        PodStorageU8 {
+            value,
+            len: prefix.len() as u8,
+            data,
+        }
+    }
+
+    fn drop_prefix(&mut self, offset: usize) {
+        let new_len = (self.len as usize).checked_sub(offset).unwrap();
+        unsafe {
+            let ptr = self.value.as_mut_ptr().cast::<u8>();
+            std::ptr::copy(ptr.add(offset), ptr, new_len);
+        }
         self.len = new_len as u8;
     }
 
     fn prepend_prefix(&mut self, prefix: &[u8], key: u8) {
-        let len = self.len as usize + prefix.len() + 1;
-        let old = self.value;
-        let slice = unsafe { &mut bytemuck::bytes_of_mut(self.value.assume_init_mut()) };
-        let old_slice = unsafe { &bytemuck::bytes_of(old.assume_init_ref()) };
-        slice[..prefix.len()].copy_from_slice(prefix);
-        slice[prefix.len()] = key;
-        slice[prefix.len() + 1..len].copy_from_slice(&old_slice[..self.len as usize]);
-        self.len = len as u8
+        let new_len = self.len as usize + prefix.len() + 1;
+        assert!(new_len <= std::mem::size_of::<T>());
+
+        unsafe {
+            let from = self.value.as_mut_ptr().cast::<u8>();
+            // +1 for the key
+            let to = from.add(prefix.len() + 1);
+            std::ptr::copy(from, to, self.len as usize);
+            std::ptr::copy_nonoverlapping(prefix.as_ptr(), from, prefix.len());
+            from.add(prefix.len()).write(key);
+        }
+
+        self.len = new_len as u8
     }
 }
 
@@ -1,11 +1,28 @@
 #![allow(dead_code)]
+#![allow(clippy::missing_safety_doc)]
 
+use iter::{key_bytes, End};
 use key::{BorrowedKey, Key};
-use raw::{BorrowIter, RawArt};
+use raw::{BorrowIter, IterMut, RawArt, ValuesMut};
+use std::ops::{Bound, RangeBounds};
 
+pub mod augmented;
+pub mod cow;
+pub mod cursor;
+pub mod display;
+pub mod export;
+pub mod frozen;
+pub mod fuzzy;
 pub mod iter;
 pub mod key;
+#[cfg(feature = "order-statistics")]
+pub mod order;
+pub mod pattern;
+pub mod prefix;
 pub mod raw;
+pub mod set;
+pub mod split;
+pub mod stats;
 #[cfg(test)]
 mod test;
 
@@ -14,6 +31,17 @@ pub struct Art<K: Key + ?Sized, V> {
     len: usize,
 }
 
+impl<K: Key + ?Sized, V: Clone> Clone for Art<K, V> {
+    /// Returns a deep copy of the tree, every node is allocated at the smallest size which fits
+    /// its branches.
+    fn clone(&self) -> Self {
+        Self {
+            tree: self.tree.clone(),
+            len: self.len,
+        }
+    }
+}
+
 impl<K: Key + ?Sized, V> Default for Art<K, V> {
     fn default() -> Self {
         Self::new()
@@ -28,6 +56,30 @@ impl<K: Key + ?Sized, V> Art<K, V> {
         }
     }
 
+    /// Create a tree from an iterator of unique keys sorted in ascending byte order.
+    ///
+    /// This is considerably faster than inserting the keys one by one as every node is created
+    /// once at the size it needs to be instead of growing as keys are inserted.
+    ///
+    /// Keys must be in the order in which the tree iterates them: integer keys are ordered by their
+    /// little endian byte representation and strings are ordered as if they were terminated by
+    /// the byte `0xBF`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not sorted or contain duplicates.
+    pub fn from_sorted_iter<'a, I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a K, V)>,
+        K: 'a,
+    {
+        let (keys, values): (Vec<&K>, Vec<V>) = iter.into_iter().unzip();
+        Self {
+            tree: RawArt::from_sorted(&keys, &mut values.into_iter()),
+            len: keys.len(),
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
@@ -55,22 +107,54 @@ impl<K: Key + ?Sized, V> Art<K, V> {
         self.len -= res.is_some() as usize;
         res
     }
+
+    /// Returns an iterator over mutable references to the values in key order.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        self.tree.values_mut()
+    }
 }
 
 impl<K: Key + ?Sized + BorrowedKey, V> Art<K, V> {
-    pub fn iter(&self) -> BorrowIter<K, V> {
+    pub fn iter(&self) -> BorrowIter<'_, K, V> {
         self.tree.iter()
     }
-}
 
-impl<K: Key + ?Sized, V: std::fmt::Debug> Art<K, V> {
-    pub fn print(&self) {
-        struct Printer<'a, K: Key + ?Sized, V: std::fmt::Debug>(&'a Art<K, V>);
-        impl<K: Key + ?Sized, V: std::fmt::Debug> std::fmt::Display for Printer<'_, K, V> {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                self.0.tree.display(f)
-            }
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.tree.iter_mut()
+    }
+
+    /// Remove every entry for which `f` returns false.
+    ///
+    /// The tree is walked once, nodes which lose branches are shrunk or merged into their only
+    /// remaining child after all of their branches are visited.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.len -= self
+            .tree
+            .retain(&mut |key, value| f(unsafe { K::from_key_bytes(key) }, value));
+    }
+
+    /// Returns an iterator over the entries with keys within the range in key order.
+    pub fn range<'r, R>(&self, range: R) -> BorrowIter<'_, K, V>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let end = End::from_bound(range.end_bound());
+        match range.start_bound() {
+            Bound::Included(x) => self.tree.seek(&key_bytes(*x), true, end),
+            Bound::Excluded(x) => self.tree.seek(&key_bytes(*x), false, end),
+            Bound::Unbounded => self.tree.seek(&[], true, end),
         }
-        println!("{}", Printer(self));
+    }
+}
+
+impl<V> Art<str, V> {
+    /// Returns an iterator over the entries with keys starting with the given prefix.
+    pub fn scan_prefix(&self, prefix: &str) -> BorrowIter<'_, str, V> {
+        let prefix = prefix.as_bytes();
+        self.tree.seek(prefix, true, End::Prefix(prefix.to_vec()))
     }
 }
@@ -0,0 +1,19 @@
+//! Longest prefix matching for routing tables.
+
+use crate::{iter::key_bytes, key::PrefixKey, Art};
+
+impl<K: PrefixKey + ?Sized, V> Art<K, V> {
+    /// Returns the longest key in the tree which is a prefix of the given key, together with its
+    /// value.
+    ///
+    /// For network keys this is the most specific network which contains the given network.
+    pub fn longest_prefix_match(&self, key: &K) -> Option<(K::Owned, &V)> {
+        let mut bytes = key_bytes(key);
+        // Leave out the terminator of the key itself.
+        bytes.pop();
+        let (len, value) = self.tree.longest_prefix(&bytes, K::TERMINATOR)?;
+        bytes.truncate(len);
+        bytes.push(K::TERMINATOR);
+        Some((K::from_prefix_bytes(&bytes), value))
+    }
+}
@@ -0,0 +1,112 @@
+//! Aggregates of the values kept next to every branch of the nodes, see
+//! [`AugmentedArt`](crate::augmented::AugmentedArt).
+
+use super::{Borrow, LeafNode, NodePtr, Owned, RawArt};
+use crate::{augmented::Aggregate, key::Key};
+use std::{
+    cmp::Ordering,
+    ops::{Bound, RangeBounds},
+};
+
+/// Where a subtree lies relative to a bound.
+///
+/// Returns `None` if the bound starts with the path of the subtree, in which case the subtree can
+/// contain keys on both sides of the bound.
+fn subtree_cmp(path: &[u8], bound: &[u8]) -> Option<Ordering> {
+    (!bound.starts_with(path)).then(|| path.cmp(bound))
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> RawArt<K, V, A> {
+    /// Recompute the aggregates kept next to the branches on the path of the key, after the key
+    /// was inserted or removed.
+    ///
+    /// The walk stops at the first node whose prefix does not match the key, which is the node
+    /// split or merged into its parent by the change. The aggregate next to it is recomputed from
+    /// its branches.
+    pub(crate) fn update_aggregates(&mut self, key: &K) {
+        if let Some(root) = self.root.as_mut() {
+            Self::update_node(root, key, 0);
+        }
+    }
+
+    fn update_node(node: &mut NodePtr<Owned, K, V, A>, key: &K, depth: usize) {
+        let prefix = node.prefix();
+        let matches = prefix
+            .iter()
+            .enumerate()
+            .all(|(i, p)| depth + i < key.len() && key.at(depth + i) == *p);
+        let depth = depth + prefix.len();
+        if !matches || depth >= key.len() || node.is::<LeafNode<K, V, A>>() {
+            return;
+        }
+
+        let branch = key.at(depth);
+        let Some(child) = node.child_mut(branch) else {
+            return;
+        };
+        Self::update_node(child, key, depth + 1);
+        let aggregate = child.aggregate();
+        *node.aggregate_mut(branch).unwrap() = aggregate;
+    }
+
+    /// Returns the aggregate of the values with key bytes within the bounds.
+    pub(crate) fn aggregate(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> A {
+        let Some(root) = self.root() else {
+            return A::identity();
+        };
+        Self::aggregate_node(root, None, &mut Vec::new(), start, end)
+    }
+
+    /// Returns the aggregate of the values below the node within the bounds, `path` contains the
+    /// key bytes before the prefix of the node and `cached` the aggregate kept next to the node.
+    fn aggregate_node(
+        node: NodePtr<Borrow<'_>, K, V, A>,
+        cached: Option<A>,
+        path: &mut Vec<u8>,
+        start: Bound<&[u8]>,
+        end: Bound<&[u8]>,
+    ) -> A {
+        let len = path.len();
+        path.extend_from_slice(node.prefix());
+
+        let after_start = match start {
+            Bound::Included(x) | Bound::Excluded(x) => subtree_cmp(path, x),
+            Bound::Unbounded => Some(Ordering::Greater),
+        };
+        let before_end = match end {
+            Bound::Included(x) | Bound::Excluded(x) => subtree_cmp(path, x),
+            Bound::Unbounded => Some(Ordering::Less),
+        };
+
+        let res = match (after_start, before_end) {
+            (Some(Ordering::Less), _) | (_, Some(Ordering::Greater)) => A::identity(),
+            (Some(Ordering::Greater), Some(Ordering::Less)) => {
+                cached.unwrap_or_else(|| node.aggregate())
+            }
+            _ if node.is::<LeafNode<K, V, A>>() => {
+                if (start, end).contains(path.as_slice()) {
+                    node.aggregate()
+                } else {
+                    A::identity()
+                }
+            }
+            _ => {
+                let mut res = A::identity();
+                let mut next = 0u16;
+                while next <= u8::MAX as u16 {
+                    let Some((key, child)) = node.next_node(next as u8) else {
+                        break;
+                    };
+                    path.push(key);
+                    let cached = node.child_aggregate(key);
+                    res = res.combine(&Self::aggregate_node(child, cached, path, start, end));
+                    path.pop();
+                    next = key as u16 + 1;
+                }
+                res
+            }
+        };
+        path.truncate(len);
+        res
+    }
+}
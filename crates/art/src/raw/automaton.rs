@@ -0,0 +1,85 @@
+//! Walking the tree with an automaton over the characters of string keys.
+
+use super::{Borrow, LeafNode, NodePtr, RawArt};
+use key_automata::{Automaton, Reader};
+
+/// A node to visit, below the first `base` bytes of the key and the branch key.
+struct Pending<'a, V, A: Automaton> {
+    node: NodePtr<Borrow<'a>, str, V>,
+    base: usize,
+    branch: Option<u8>,
+    /// The automaton after reading the first `base` bytes of the key.
+    reader: Reader<A>,
+}
+
+/// An iterator over the keys an automaton matches in key order.
+///
+/// Keeps an explicit stack of the nodes which still have to be visited, the children of a node
+/// are pushed in reverse order so they are visited in key order. A subtree is skipped as soon as
+/// the automaton rejects the key bytes leading to it.
+pub(crate) struct Search<'a, V, A: Automaton> {
+    automaton: A,
+    key: Vec<u8>,
+    stack: Vec<Pending<'a, V, A>>,
+}
+
+impl<V> RawArt<str, V> {
+    pub(crate) fn search<A: Automaton>(&self, automaton: A) -> Search<'_, V, A> {
+        let stack = self
+            .root()
+            .map(|node| Pending {
+                node,
+                base: 0,
+                branch: None,
+                reader: Reader::new(&automaton),
+            })
+            .into_iter()
+            .collect();
+        Search {
+            automaton,
+            key: Vec::new(),
+            stack,
+        }
+    }
+}
+
+impl<'a, V, A: Automaton> Search<'a, V, A> {
+    /// Returns the key bytes, value and match of the next key the automaton matches.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&[u8], &'a V, A::Match)> {
+        'next: loop {
+            let Pending {
+                node,
+                base,
+                branch,
+                mut reader,
+            } = self.stack.pop()?;
+            self.key.truncate(base);
+            for b in branch.iter().chain(node.prefix()) {
+                self.key.push(*b);
+                if !reader.push(&self.automaton, *b) {
+                    continue 'next;
+                }
+            }
+
+            if let Some(leaf) = node.cast::<LeafNode<str, V>>() {
+                if let Some(res) = reader.accept(&self.automaton) {
+                    return Some((&self.key, leaf.into_value_ref(), res));
+                }
+                continue;
+            }
+
+            let base = self.key.len();
+            let mut from = Some(u8::MAX);
+            while let Some((branch, child)) = from.and_then(|x| node.prev_node(x)) {
+                self.stack.push(Pending {
+                    node: child,
+                    base,
+                    branch: Some(branch),
+                    reader: reader.clone(),
+                });
+                from = branch.checked_sub(1);
+            }
+        }
+    }
+}
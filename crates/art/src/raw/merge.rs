@@ -0,0 +1,146 @@
+//! Merging two trees by walking them together.
+//!
+//! Nodes are compared by their prefix, a subtree which is only present in one of the trees is
+//! copied or dropped as a whole without looking at its keys.
+
+use super::{Borrow, LeafNode, NodePtr, OwnedNodePtr, RawArt};
+use crate::key::Key;
+
+/// Which entries are kept when merging two trees.
+pub(crate) struct Merge<F> {
+    /// Keep the entries only present in the left tree.
+    pub left: bool,
+    /// Keep the entries only present in the right tree.
+    pub right: bool,
+    /// Merge the values of a key present in both trees, the entry is dropped if this returns
+    /// `None`.
+    pub both: F,
+}
+
+/// A node with the first `skip` bytes of its prefix already matched.
+struct Side<'a, K: Key + ?Sized, V> {
+    node: NodePtr<Borrow<'a>, K, V>,
+    skip: usize,
+}
+
+impl<'a, K: Key + ?Sized, V> Side<'a, K, V> {
+    fn new(node: NodePtr<Borrow<'a>, K, V>) -> Self {
+        Side { node, skip: 0 }
+    }
+
+    fn prefix(&self) -> &[u8] {
+        &self.node.prefix()[self.skip..]
+    }
+
+    /// Returns the branches of the node as if its prefix ended after `at` bytes.
+    fn children(&self, at: usize) -> Vec<(u8, Side<'a, K, V>)> {
+        let prefix = self.prefix();
+        if at < prefix.len() {
+            let side = Side {
+                node: self.node,
+                skip: self.skip + at + 1,
+            };
+            return vec![(prefix[at], side)];
+        }
+
+        assert!(
+            !self.node.is::<LeafNode<K, V>>(),
+            "{}",
+            RawArt::<K, V>::PREFIX_PANIC
+        );
+        let mut res = Vec::new();
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, child)) = self.node.next_node(next as u8) else {
+                break;
+            };
+            res.push((key, Side::new(child)));
+            next = key as u16 + 1;
+        }
+        res
+    }
+}
+
+impl<K: Key + ?Sized, V: Clone> RawArt<K, V> {
+    /// Merge two trees into a new tree, returns the tree and the amount of entries in it.
+    pub(crate) fn merge<F>(&self, other: &Self, merge: &mut Merge<F>) -> (Self, usize)
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        let mut len = 0;
+        let root = Self::merge_node(
+            self.root().map(Side::new),
+            other.root().map(Side::new),
+            merge,
+            &mut len,
+        );
+        (RawArt { root }, len)
+    }
+
+    fn merge_node<F>(
+        a: Option<Side<'_, K, V>>,
+        b: Option<Side<'_, K, V>>,
+        merge: &mut Merge<F>,
+        len: &mut usize,
+    ) -> Option<OwnedNodePtr<K, V>>
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        let (a, b) = match (a, b) {
+            (None, None) => return None,
+            (Some(a), None) => return merge.left.then(|| Self::copy_side(a, len)),
+            (None, Some(b)) => return merge.right.then(|| Self::copy_side(b, len)),
+            (Some(a), Some(b)) => (a, b),
+        };
+
+        let (pa, pb) = (a.prefix(), b.prefix());
+        let common = pa.iter().zip(pb).take_while(|(x, y)| x == y).count();
+        let prefix = &pa[..common];
+        if common == pa.len() && common == pb.len() {
+            let leafs = (
+                a.node.cast::<LeafNode<K, V>>(),
+                b.node.cast::<LeafNode<K, V>>(),
+            );
+            if let (Some(x), Some(y)) = leafs {
+                let value = (merge.both)(x.into_value_ref(), y.into_value_ref())?;
+                *len += 1;
+                return Some(LeafNode::from_prefix(prefix, value));
+            }
+        }
+
+        // Both nodes now branch at the end of the common prefix, merge the branches in order.
+        let mut ca = a.children(common).into_iter().peekable();
+        let mut cb = b.children(common).into_iter().peekable();
+        let mut children = Vec::new();
+        loop {
+            let (key, a, b) = match (ca.peek(), cb.peek()) {
+                (None, None) => break,
+                (Some(x), Some(y)) if x.0 == y.0 => {
+                    let (key, a) = ca.next().unwrap();
+                    (key, Some(a), cb.next().map(|x| x.1))
+                }
+                (Some(x), y) if y.is_none_or(|y| x.0 < y.0) => {
+                    let (key, a) = ca.next().unwrap();
+                    (key, Some(a), None)
+                }
+                _ => {
+                    let (key, b) = cb.next().unwrap();
+                    (key, None, Some(b))
+                }
+            };
+            if let Some(x) = Self::merge_node(a, b, merge, len) {
+                children.push((key, x));
+            }
+        }
+
+        Self::from_children(prefix, children)
+    }
+
+    fn copy_side(side: Side<'_, K, V>, len: &mut usize) -> OwnedNodePtr<K, V> {
+        let mut node = Self::copy_node(side.node, len);
+        if side.skip > 0 {
+            node.drop_prefix(side.skip);
+        }
+        node
+    }
+}
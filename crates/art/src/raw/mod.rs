@@ -1,20 +1,63 @@
 use crate::{
-    iter::RawIterator,
+    augmented::Aggregate,
+    display::DisplayOptions,
+    iter::{End, RawIterator},
     key::{BorrowedKey, Key},
 };
 use core::fmt;
 
+mod aggregate;
+mod automaton;
+mod merge;
 mod nodes;
+#[cfg(feature = "order-statistics")]
+mod order;
+mod prefix;
 mod ptr;
+mod retain;
+mod split;
 
+pub(crate) use merge::Merge;
 pub use nodes::*;
 pub use ptr::*;
+pub(crate) use split::RawDrain;
 
-pub struct RawArt<K: Key + ?Sized, V> {
-    root: Option<OwnedNodePtr<K, V>>,
+/// The branches of a node moved out of the node, in key order.
+type Children<K, V> = Vec<(u8, OwnedNodePtr<K, V>)>;
+
+/// The bytes of a key to walk the tree with, a key or the full key bytes of one.
+pub(crate) trait KeyPath {
+    fn path_len(&self) -> usize;
+
+    fn path_at(&self, idx: usize) -> u8;
 }
 
-impl<K: Key + ?Sized, V> RawArt<K, V> {
+impl<K: Key + ?Sized> KeyPath for K {
+    fn path_len(&self) -> usize {
+        self.len()
+    }
+
+    fn path_at(&self, idx: usize) -> u8 {
+        self.at(idx)
+    }
+}
+
+impl KeyPath for [u8] {
+    fn path_len(&self) -> usize {
+        self.len()
+    }
+
+    fn path_at(&self, idx: usize) -> u8 {
+        self[idx]
+    }
+}
+
+/// An adaptive radix tree, `A` is the aggregate kept next to every branch of the nodes.
+pub struct RawArt<K: Key + ?Sized, V, A = ()> {
+    root: Option<OwnedNodePtr<K, V, A>>,
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> RawArt<K, V, A> {
     const PREFIX_PANIC: &'static str = "the key was a prefix of an existing key";
 
     pub fn new() -> Self {
@@ -22,42 +65,315 @@ impl<K: Key + ?Sized, V> RawArt<K, V> {
     }
 
     pub fn get(&self, key: &K) -> Option<&V> {
-        todo!()
+        let root = self.root.as_ref()?.as_borrow();
+        Self::find_leaf(root, key).map(|x| x.into_value_ref())
     }
 
     pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        todo!()
+        let root = unsafe { NodePtr::reborrow::<BorrowMut>(&mut **self.root.as_mut()?) };
+        Self::find_leaf(root, key).map(|x| x.into_value_mut())
     }
 
     pub fn insert(&mut self, key: &K, value: V) -> Option<V> {
         if let Some(x) = self.root.as_mut() {
-            todo!()
+            #[cfg(feature = "order-statistics")]
+            if Self::find_leaf(x.as_borrow(), key).is_none() {
+                Self::add_leafs(x, key, 1);
+            }
+            return Self::insert_node(x, key, value);
         }
-        self.root =
-            Some(OwnedTypedNodePtr::new(LeafNode::new(key, 0..key.len(), value)).erase_type());
+        self.root = Some(LeafNode::new_ptr(key, 0..key.len(), value));
+        None
     }
 
     pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove_path(key)
+    }
+
+    /// Remove the entry with the given full key bytes.
+    pub(crate) fn remove_bytes(&mut self, key: &[u8]) -> Option<V> {
+        self.remove_path(key)
+    }
+
+    fn remove_path<Q: KeyPath + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let root = self.root.as_mut()?;
+        #[cfg(feature = "order-statistics")]
+        if Self::find_leaf(root.as_borrow(), key).is_some() {
+            Self::add_leafs(root, key, -1);
+        }
+        if root.is::<LeafNode<K, V, A>>() {
+            let prefix = root.prefix();
+            if Self::match_prefix(key, 0, prefix).is_some() || prefix.len() != key.path_len() {
+                return None;
+            }
+            let leaf = self.root.take().unwrap();
+            return Some(leaf.cast_owned::<LeafNode<K, V, A>>().unwrap().into_value());
+        }
+
+        Self::remove_node(root, key)
+    }
+
+    /// Returns a borrowed pointer to the root node of the tree.
+    pub fn root(&self) -> Option<NodePtr<Borrow<'_>, K, V, A>> {
+        self.root.as_ref().map(|x| x.as_borrow())
+    }
+}
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    pub fn iter(&self) -> BorrowIter<'_, K, V> {
+        BorrowIter {
+            raw: RawIterator::new(self.root()),
+            end: End::Unbounded,
+            done: false,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            raw: RawIterator::new(self.root_mut_value()),
+        }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            raw: RawIterator::new(self.root_mut_value()),
+        }
+    }
+
+    /// Returns a pointer to the root node which allows mutating the values in the tree.
+    fn root_mut_value(&mut self) -> Option<NodePtr<MutValue<'_>, K, V>> {
         let root = self.root.as_mut()?;
-        if root.is::<LeafNode<K, V>>() {
-            todo!()
-            //let leaf = self.root.take().unwrap();
-            //return Some(leaf.cast_owned::<LeafNode<K, V>>().into_value());
+        Some(unsafe { NodePtr::reborrow(&mut **root) })
+    }
+
+    /// Returns an iterator over the leafs starting at the first key larger than, or if `inclusive`
+    /// equal to, `start` and ending at `end`.
+    pub(crate) fn seek(&self, start: &[u8], inclusive: bool, end: End) -> BorrowIter<'_, K, V> {
+        BorrowIter {
+            raw: RawIterator::seek(self.root(), start, inclusive),
+            end,
+            done: false,
+        }
+    }
+
+    /// Build a tree bottom-up from keys sorted in ascending byte order.
+    ///
+    /// `values` must yield the value for each key in the same order as the keys.
+    pub fn from_sorted(keys: &[&K], values: &mut impl Iterator<Item = V>) -> Self {
+        if keys.is_empty() {
+            return Self::new();
+        }
+        Self {
+            root: Some(Self::build_node(keys, values, 0)),
+        }
+    }
+
+    /// Build the node containing all the given keys, all of which share the first `depth` bytes.
+    ///
+    /// The common prefix of the keys is computed once and each branch node is allocated at the
+    /// size required for the amount of branches it has.
+    fn build_node(
+        keys: &[&K],
+        values: &mut impl Iterator<Item = V>,
+        depth: usize,
+    ) -> OwnedNodePtr<K, V> {
+        let first = keys[0];
+        if keys.len() == 1 {
+            let value = values.next().expect("missing value for key");
+            return LeafNode::new_ptr(first, depth..first.len(), value);
+        }
+
+        let mut split = first.len();
+        for k in &keys[1..] {
+            let end = split.min(k.len());
+            split = (depth..end)
+                .find(|&i| first.at(i) != k.at(i))
+                .unwrap_or(end);
+            assert!(
+                split < k.len() && split < first.len(),
+                "sorted input contained a duplicate key"
+            );
         }
 
-        todo!()
+        let range = depth..split;
+        let count = Self::branches(keys, split).count();
+        let mut last = None;
+        let children = Self::branches(keys, split).map(|(branch, run)| {
+            assert!(
+                last.map(|x| x < branch).unwrap_or(true),
+                "input keys were not sorted"
+            );
+            last = Some(branch);
+            (branch, Self::build_node(run, values, split + 1))
+        });
+
+        Self::new_branch(
+            NodeHeader::new::<Node4<K, V>>(first, range),
+            count,
+            children,
+        )
     }
 
-    pub fn iter(&self) -> BorrowIter<K, V> {
-        todo!()
+    /// Create a branch node of the smallest kind which fits `count` branches.
+    ///
+    /// The header must not have any branches, its kind is changed to that of the created node.
+    fn new_branch(
+        mut header: NodeHeader<K, V>,
+        count: usize,
+        mut children: impl Iterator<Item = (u8, OwnedNodePtr<K, V>)>,
+    ) -> OwnedNodePtr<K, V> {
+        #[allow(unused_mut)]
+        let mut node = unsafe {
+            match count {
+                0..=4 => {
+                    header.change_type::<Node4<K, V>>();
+                    let mut node = Node4::new_from_header(header);
+                    children.for_each(|(k, c)| {
+                        node.insert(k, c);
+                    });
+                    OwnedTypedNodePtr::new(node).erase_type()
+                }
+                5..=16 => {
+                    header.change_type::<Node16<K, V>>();
+                    let mut node = Node16::new_from_header(header);
+                    children.for_each(|(k, c)| {
+                        node.insert(k, c);
+                    });
+                    OwnedTypedNodePtr::new(node).erase_type()
+                }
+                17..=48 => {
+                    header.change_type::<Node48<K, V>>();
+                    let mut node = Node48::new_from_header(header);
+                    children.for_each(|(k, c)| {
+                        node.insert(k, c);
+                    });
+                    OwnedTypedNodePtr::new(node).erase_type()
+                }
+                _ => {
+                    header.change_type::<Node256<K, V>>();
+                    let mut node = Node256::new_from_header(header, children.next().unwrap());
+                    children.for_each(|(k, c)| {
+                        node.insert(k, c);
+                    });
+                    OwnedTypedNodePtr::new(node).erase_type()
+                }
+            }
+        };
+        #[cfg(feature = "order-statistics")]
+        {
+            node.header_mut().leafs = Self::child_leafs(node.as_borrow());
+        }
+        node
     }
 
-    fn match_prefix(key: &K, from: usize, to: &[u8]) -> Option<usize> {
+    /// Create the node for a prefix followed by branches sorted by their key.
+    ///
+    /// Returns `None` if there are no branches, a single branch is merged into its node.
+    fn from_children(prefix: &[u8], mut children: Children<K, V>) -> Option<OwnedNodePtr<K, V>> {
+        match children.len() {
+            0 => None,
+            1 => {
+                let (key, mut child) = children.pop().unwrap();
+                child.prepend_prefix(prefix, key);
+                Some(child)
+            }
+            count => Some(Self::new_branch(
+                NodeHeader::from_prefix::<Node4<K, V>>(prefix),
+                count,
+                children.into_iter(),
+            )),
+        }
+    }
+
+    /// Take a branch node apart, returns its header and its branches in key order.
+    fn into_children(mut node: OwnedNodePtr<K, V>) -> (NodeHeader<K, V>, Children<K, V>) {
+        let mut children = Vec::new();
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, _)) = node.as_borrow().next_node(next as u8) else {
+                break;
+            };
+            let child = node.child_mut(key).unwrap();
+            children.push((key, unsafe { child.as_unknown().assume_owned() }));
+            next = key as u16 + 1;
+        }
+        let header = unsafe { Self::dealloc_branch(node.into_unknown()) };
+        (header, children)
+    }
+
+    /// Deallocate a branch node without dropping its children, returns the header of the node.
+    ///
+    /// # Safety
+    /// The children of the node must have been moved out or freed.
+    unsafe fn dealloc_branch(ptr: NodePtr<Unknown, K, V>) -> NodeHeader<K, V> {
+        let kind = ptr.assume_ownership::<Borrow>().header().kind();
+        let header = ptr.take_header();
+        match kind {
+            NodeKind::Leaf => unreachable!("expected a branch node"),
+            NodeKind::Node4 => TypedNodePtr::dealloc(ptr.cast_unchecked::<Node4<K, V>>()),
+            NodeKind::Node16 => TypedNodePtr::dealloc(ptr.cast_unchecked::<Node16<K, V>>()),
+            NodeKind::Node48 => TypedNodePtr::dealloc(ptr.cast_unchecked::<Node48<K, V>>()),
+            NodeKind::Node256 => TypedNodePtr::dealloc(ptr.cast_unchecked::<Node256<K, V>>()),
+        }
+        header
+    }
+
+    /// Copy a node and all its children, every branch node is allocated at the smallest size
+    /// which fits its branches.
+    ///
+    /// The amount of leafs copied is added to `leafs`.
+    fn copy_node(node: NodePtr<Borrow<'_>, K, V>, leafs: &mut usize) -> OwnedNodePtr<K, V>
+    where
+        V: Clone,
+    {
+        if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+            *leafs += 1;
+            return LeafNode::from_prefix(leaf.prefix(), leaf.into_value_ref().clone());
+        }
+
+        let mut children = Vec::new();
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, child)) = node.next_node(next as u8) else {
+                break;
+            };
+            children.push((key, Self::copy_node(child, leafs)));
+            next = key as u16 + 1;
+        }
+        let count = children.len();
+        Self::new_branch(
+            node.header().copy_as::<Node4<K, V>>(),
+            count,
+            children.into_iter(),
+        )
+    }
+
+    /// Returns the runs of keys which have the same byte at the given offset.
+    fn branches<'k, 'a>(
+        keys: &'k [&'a K],
+        at: usize,
+    ) -> impl Iterator<Item = (u8, &'k [&'a K])> + 'k {
+        let mut rest = keys;
+        std::iter::from_fn(move || {
+            let branch = rest.first()?.at(at);
+            let len = rest
+                .iter()
+                .position(|k| k.at(at) != branch)
+                .unwrap_or(rest.len());
+            let (run, tail) = rest.split_at(len);
+            rest = tail;
+            Some((branch, run))
+        })
+    }
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> RawArt<K, V, A> {
+    fn match_prefix<Q: KeyPath + ?Sized>(key: &Q, from: usize, to: &[u8]) -> Option<usize> {
         for (idx, p) in to.iter().copied().enumerate() {
-            if idx + from >= key.len() {
-                panic!("key was a prefix of an existing key");
+            if idx + from >= key.path_len() {
+                panic!("{}", Self::PREFIX_PANIC);
             }
-            let k = key.at(from + idx);
+            let k = key.path_at(from + idx);
             if p != k {
                 return Some(idx);
             }
@@ -65,43 +381,166 @@ impl<K: Key + ?Sized, V> RawArt<K, V> {
         None
     }
 
-    fn insert_node(
-        mut node: NodePtr<BorrowMut, K, V>,
-        key: &K,
-        value: V,
-    ) -> Option<OwnedNodePtr<K, V>> {
-        let mut matched: usize = 0;
+    fn find_leaf<O: ValidPtr, Q: KeyPath + ?Sized>(
+        mut node: NodePtr<O, K, V, A>,
+        key: &Q,
+    ) -> Option<TypedNodePtr<O, LeafNode<K, V, A>>> {
+        let mut depth: usize = 0;
+
+        loop {
+            let prefix = node.prefix();
+            if Self::match_prefix(key, depth, prefix).is_some() {
+                return None;
+            }
+            depth += prefix.len();
+
+            if node.is::<LeafNode<K, V, A>>() {
+                if depth != key.path_len() {
+                    return None;
+                }
+                return Some(unsafe { node.cast_unchecked() });
+            }
+
+            if depth >= key.path_len() {
+                return None;
+            }
+            node = node.get(key.path_at(depth))?;
+            depth += 1;
+        }
+    }
+
+    fn insert_node(mut node: &mut NodePtr<Owned, K, V, A>, key: &K, value: V) -> Option<V> {
+        let mut depth: usize = 0;
 
         loop {
-            let prefix = node.header().prefix();
-            if let Some(x) = Self::match_prefix(key, matched, prefix) {
-                node.new_branch(key, value, matched, range_start, mismatch_index)
+            let prefix = node.prefix();
+            if let Some(mismatch) = Self::match_prefix(key, depth, prefix) {
+                node.new_branch(key, value, depth, mismatch);
+                return None;
             }
+            depth += prefix.len();
+
+            if node.is::<LeafNode<K, V, A>>() {
+                assert_eq!(depth, key.len(), "{}", Self::PREFIX_PANIC);
+                let mut leaf = unsafe { node.cast_mut_unchecked::<LeafNode<K, V, A>>() };
+                return Some(std::mem::replace(leaf.as_value_mut(), value));
+            }
+
+            assert!(depth < key.len(), "{}", Self::PREFIX_PANIC);
+            let branch = key.at(depth);
+            depth += 1;
+
+            if node.as_borrow().get(branch).is_none() {
+                node.insert_grow(branch, LeafNode::new_ptr(key, depth..key.len(), value));
+                return None;
+            }
+            node = node.child_mut(branch).unwrap();
+        }
+    }
+
+    fn remove_node<Q: KeyPath + ?Sized>(
+        mut node: &mut NodePtr<Owned, K, V, A>,
+        key: &Q,
+    ) -> Option<V> {
+        let mut depth: usize = 0;
+
+        loop {
+            let prefix = node.prefix();
+            if Self::match_prefix(key, depth, prefix).is_some() {
+                return None;
+            }
+            depth += prefix.len();
+
+            if depth >= key.path_len() {
+                return None;
+            }
+            let branch = key.path_at(depth);
+            depth += 1;
+
+            let child = node.as_borrow().get(branch)?;
+            if child.is::<LeafNode<K, V, A>>() {
+                let prefix = child.prefix();
+                if Self::match_prefix(key, depth, prefix).is_some()
+                    || depth + prefix.len() != key.path_len()
+                {
+                    return None;
+                }
+                let leaf = node.remove(branch).unwrap();
+                return Some(leaf.cast_owned::<LeafNode<K, V, A>>().unwrap().into_value());
+            }
+
+            node = node.child_mut(branch).unwrap();
         }
     }
 }
 
+impl<K: Key + ?Sized, V: Clone> Clone for RawArt<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root().map(|x| Self::copy_node(x, &mut 0)),
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> Default for RawArt<K, V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct BorrowIter<'a, K: Key + ?Sized, V> {
     raw: RawIterator<'a, Borrow<'a>, K, V>,
+    end: End,
+    done: bool,
 }
 
 impl<'a, K: Key + BorrowedKey + ?Sized, V> BorrowIter<'a, K, V> {
-    pub fn next(&mut self) -> Option<(&K, &V)> {
-        todo!()
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a V)> {
+        if self.done {
+            return None;
+        }
+        let (key, leaf) = self.raw.next()?;
+        if !self.end.contains(key) {
+            self.done = true;
+            return None;
+        }
+        unsafe { Some((K::from_key_bytes(key), leaf.into_value_ref())) }
+    }
+}
+
+pub struct IterMut<'a, K: Key + ?Sized, V> {
+    raw: RawIterator<'a, MutValue<'a>, K, V>,
+}
+
+impl<'a, K: Key + BorrowedKey + ?Sized, V> IterMut<'a, K, V> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &'a mut V)> {
+        let (key, leaf) = self.raw.next()?;
+        unsafe { Some((K::from_key_bytes(key), leaf.into_value_mut())) }
+    }
+}
+
+pub struct ValuesMut<'a, K: Key + ?Sized, V> {
+    raw: RawIterator<'a, MutValue<'a>, K, V>,
+}
+
+impl<'a, K: Key + ?Sized, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.raw.next().map(|(_, leaf)| leaf.into_value_mut())
     }
 }
 
 impl<K: Key + ?Sized, V: fmt::Debug> RawArt<K, V> {
-    pub fn display(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        /*
+    pub fn display(&self, f: &mut fmt::Formatter, options: &DisplayOptions) -> fmt::Result {
         if let Some(x) = self.root.as_ref() {
             write!(f, "TREE = ")?;
-            x.display(f, 1)?;
+            x.display(f, 1, options)?;
         } else {
             writeln!(f, "TREE = EMPTY")?;
         }
         Ok(())
-        */
-        todo!()
     }
 }
@@ -23,6 +23,9 @@ pub struct NodeData {
 pub struct NodeHeader<K: Key + ?Sized, V> {
     //pub(crate) parent: Option<NodePtr<Unknown, K, V>>,
     pub(crate) storage: K::Storage,
+    /// The amount of leafs below the node, only maintained for branch nodes.
+    #[cfg(feature = "order-statistics")]
+    pub(crate) leafs: usize,
     _marker: PhantomData<V>,
 }
 
@@ -49,10 +52,36 @@ impl<K: Key + ?Sized, V> NodeHeader<K, V> {
         NodeHeader {
             //parent: None,
             storage,
+            #[cfg(feature = "order-statistics")]
+            leafs: 0,
             _marker: PhantomData,
         }
     }
 
+    /// Create a header for a node of type `N` with the given bytes as its prefix.
+    pub fn from_prefix<N: NodeType<Key = K>>(prefix: &[u8]) -> Self {
+        let storage = <K::Storage as KeyStorage<K>>::from_prefix(
+            prefix,
+            NodeData {
+                len: 0,
+                kind: N::KIND,
+                free: 0,
+            },
+        );
+        NodeHeader {
+            //parent: None,
+            storage,
+            #[cfg(feature = "order-statistics")]
+            leafs: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create an empty header for a node of type `N` with the same prefix as this header.
+    pub fn copy_as<N: NodeType<Key = K>>(&self) -> Self {
+        Self::from_prefix::<N>(self.prefix())
+    }
+
     pub fn kind(&self) -> NodeKind {
         self.storage.data().kind
     }
@@ -1,33 +1,103 @@
 use super::{NodeHeader, NodeKind, NodeType};
 use crate::{
-    key::{Key, KeyStorage},
-    raw::ptr::{MutValuePtr, OwnedTypedNodePtr, TypedNodePtr, Unknown, ValidPtr},
+    display::DisplayOptions,
+    key::Key,
+    raw::ptr::{
+        Borrow, BorrowMut, MutValue, MutValuePtr, NodePtr, OwnedNodePtr, OwnedTypedNodePtr,
+        TypedNodePtr, Unknown, ValidPtr,
+    },
 };
 use core::fmt;
-use std::{ops::Range, ptr::addr_of_mut};
+use std::{marker::PhantomData, mem, ops::Range, ptr::addr_of_mut};
 
+/// A leaf node, `A` is the aggregate kept by the branches of the tree the leaf belongs to.
 #[repr(C)]
-pub struct LeafNode<K: Key + ?Sized, V> {
+pub struct LeafNode<K: Key + ?Sized, V, A = ()> {
     pub header: NodeHeader<K, V>,
     pub value: V,
+    _aggregate: PhantomData<A>,
 }
 
-unsafe impl<K: Key + ?Sized, V> NodeType for LeafNode<K, V> {
+unsafe impl<K: Key + ?Sized, V, A> NodeType for LeafNode<K, V, A> {
     const KIND: NodeKind = NodeKind::Leaf;
 
     type Key = K;
     type Value = V;
+    type Aggregate = A;
 }
 
-impl<K: Key + ?Sized, V> LeafNode<K, V> {
+impl<K: Key + ?Sized, V, A> LeafNode<K, V, A> {
+    /// Whether leafs are stored inline, without a node header.
+    ///
+    /// Only done with the `inline-leafs` feature. A leaf without a prefix whose value fits, see
+    /// [`Self::IN_SLOT`], is stored in place of the pointer to the leaf. Any other leaf is moved
+    /// into an allocation of only its value followed by its prefix. The pointer is tagged in both
+    /// cases.
+    pub const INLINE: bool = cfg!(feature = "inline-leafs");
+
+    /// Whether the value of an inline leaf without a prefix is stored in the pointer to the leaf.
+    ///
+    /// Only done for values which fit in the half of a pointer without its lowest byte. That byte
+    /// contains the tag which marks the pointer as an inline leaf. The size of the value must also
+    /// equal its alignment, which rules out most values with padding bytes. A value as large as
+    /// the pointer, such as a `u64` on 64 bit targets, leaves no room for the tag.
+    pub const IN_SLOT: bool = Self::INLINE
+        && mem::size_of::<V>() <= mem::size_of::<usize>() / 2
+        && mem::align_of::<V>() <= mem::size_of::<usize>() / 2
+        && (mem::size_of::<V>() == 0 || mem::size_of::<V>() == mem::align_of::<V>());
+
+    /// The offset of the value of an inline leaf in the pointer, see [`Self::IN_SLOT`].
+    pub const VALUE_OFFSET: usize = if cfg!(target_endian = "little") {
+        mem::size_of::<usize>() / 2
+    } else {
+        0
+    };
+
     pub fn new(key: &K, range: Range<usize>, value: V) -> Self {
-        let mut header = NodeHeader::new::<Self>(key, range);
-        LeafNode { header, value }
+        let header = NodeHeader::new::<Self>(key, range);
+        LeafNode {
+            header,
+            value,
+            _aggregate: PhantomData,
+        }
+    }
+
+    /// Create a leaf for the given range of the key, stored inline with the `inline-leafs`
+    /// feature.
+    pub fn new_ptr(key: &K, range: Range<usize>, value: V) -> OwnedNodePtr<K, V, A> {
+        if Self::INLINE {
+            let start = range.start;
+            return unsafe {
+                NodePtr::inline_with(range.len(), value, |dst| {
+                    for (i, x) in dst.iter_mut().enumerate() {
+                        *x = key.at(start + i);
+                    }
+                })
+            };
+        }
+        OwnedTypedNodePtr::new(Self::new(key, range, value)).erase_type()
+    }
+
+    /// Create a leaf with the given prefix, stored inline with the `inline-leafs` feature.
+    pub fn from_prefix(prefix: &[u8], value: V) -> OwnedNodePtr<K, V, A> {
+        if Self::INLINE {
+            return unsafe { NodePtr::inline(prefix, value) };
+        }
+        let header = NodeHeader::from_prefix::<Self>(prefix);
+        OwnedTypedNodePtr::new(LeafNode {
+            header,
+            value,
+            _aggregate: PhantomData,
+        })
+        .erase_type()
     }
 }
 
-impl<K: Key + ?Sized, V> OwnedTypedNodePtr<LeafNode<K, V>> {
+impl<K: Key + ?Sized, V, A> OwnedTypedNodePtr<LeafNode<K, V, A>> {
     pub fn into_value(self) -> V {
+        if self.is_inline() {
+            return unsafe { NodePtr::take_inline(self.into_unknown().erase_type()) };
+        }
         let raw = self.into_unknown();
         let ptr = raw.as_ptr();
         unsafe {
@@ -39,34 +109,97 @@ impl<K: Key + ?Sized, V> OwnedTypedNodePtr<LeafNode<K, V>> {
             // move out the value
             let value = value_ptr.read();
             // all fields dropped, or moved, so deallocated without dropping.
-            TypedNodePtr::<Unknown, LeafNode<K, V>>::dealloc(raw);
+            TypedNodePtr::<Unknown, LeafNode<K, V, A>>::dealloc(raw);
             value
         }
     }
 }
 
-impl<O: ValidPtr, K: Key + ?Sized, V> TypedNodePtr<O, LeafNode<K, V>> {
+impl<O: ValidPtr, K: Key + ?Sized, V, A> TypedNodePtr<O, LeafNode<K, V, A>> {
+    /// Returns a pointer to the value of the leaf.
+    ///
+    /// # Safety
+    /// `this` must point to a valid leaf pointer.
+    unsafe fn value_ptr(this: *mut Self) -> *mut V {
+        if (*this).is_inline() {
+            NodePtr::inline_value(this.cast::<NodePtr<O, K, V, A>>(), O::OWNED)
+        } else {
+            addr_of_mut!((*(*this).as_ptr()).value)
+        }
+    }
+
     pub fn as_value(&self) -> &V {
-        &self.as_ref().value
+        unsafe { &*Self::value_ptr(self as *const Self as *mut Self) }
+    }
+
+    /// Returns the prefix of the leaf.
+    pub fn prefix(&self) -> &[u8] {
+        if self.is_inline() {
+            // A typed pointer has the same layout as the untyped one.
+            return unsafe {
+                (*(self as *const Self).cast::<NodePtr<O, K, V, A>>()).inline_prefix()
+            };
+        }
+        self.header().prefix()
     }
 }
 
-impl<O: MutValuePtr, K: Key + ?Sized, V> TypedNodePtr<O, LeafNode<K, V>> {
+impl<O: MutValuePtr, K: Key + ?Sized, V, A> TypedNodePtr<O, LeafNode<K, V, A>> {
     pub fn as_value_mut(&mut self) -> &mut V {
-        unsafe {
-            let value_ptr = addr_of_mut!((*self.as_unknown().as_ptr()).value);
-            &mut (*value_ptr)
+        unsafe { &mut *Self::value_ptr(self) }
+    }
+}
+
+impl<'a, K: Key + ?Sized, V, A> TypedNodePtr<Borrow<'a>, LeafNode<K, V, A>> {
+    pub fn into_value_ref(mut self) -> &'a V {
+        unsafe { &*Self::value_ptr(&mut self) }
+    }
+}
+
+impl<'a, K: Key + ?Sized, V, A> TypedNodePtr<MutValue<'a>, LeafNode<K, V, A>> {
+    pub fn into_value_mut(mut self) -> &'a mut V {
+        unsafe { &mut *Self::value_ptr(&mut self) }
+    }
+}
+
+impl<'a, K: Key + ?Sized, V, A> TypedNodePtr<BorrowMut<'a>, LeafNode<K, V, A>> {
+    pub fn into_value_mut(mut self) -> &'a mut V {
+        unsafe { &mut *Self::value_ptr(&mut self) }
+    }
+}
+
+impl<O: ValidPtr, K: Key + ?Sized, V: fmt::Debug, A> TypedNodePtr<O, LeafNode<K, V, A>> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        depth: usize,
+        options: &DisplayOptions,
+    ) -> fmt::Result {
+        if self.is_inline() {
+            // Shown like a leaf node, which has no branches either.
+            return writeln!(
+                fmt,
+                "LEAF: len=0 prefix={:?} | {:?}",
+                self.prefix(),
+                self.as_value()
+            );
         }
+        self.as_ref().display(fmt, depth, options)
     }
 }
 
-impl<K: Key + ?Sized, V: fmt::Debug> LeafNode<K, V> {
-    pub fn display(&self, fmt: &mut fmt::Formatter, _depth: usize) -> fmt::Result {
+impl<K: Key + ?Sized, V: fmt::Debug, A> LeafNode<K, V, A> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        _depth: usize,
+        _options: &DisplayOptions,
+    ) -> fmt::Result {
         writeln!(
             fmt,
             "LEAF: len={:?} prefix={:?} | {:?}",
             self.header.data().len,
-            self.header.storage.prefix(),
+            self.header.prefix(),
             self.value
         )
     }
@@ -12,9 +12,15 @@ pub use node256::Node256;
 pub use node4::Node4;
 pub use node48::Node48;
 
-use crate::key::{Key, KeyStorage};
+use crate::{
+    augmented::Aggregate,
+    display::DisplayOptions,
+    key::{Key, KeyStorage},
+};
+use core::fmt;
+use std::mem;
 
-use super::{MutablePtr, NodePtr, OwnedNodePtr, OwnedTypedNodePtr};
+use super::{Borrow, MutablePtr, NodePtr, Owned, OwnedNodePtr, OwnedTypedNodePtr, ValidPtr};
 
 /// # Safety
 /// Implementor must ensure that the associated KIND value is distinct from any other type
@@ -23,31 +29,200 @@ pub unsafe trait NodeType {
     const KIND: NodeKind;
     type Key: Key + ?Sized;
     type Value;
+    /// The aggregate kept next to every branch, see [`NodePtr`].
+    type Aggregate;
 }
 
-impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
-    pub fn insert_grow(&mut self, key: u8, v: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
-        match self.header().kind() {
-            NodeKind::Leaf => panic!(),
+impl<O: ValidPtr, K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<O, K, V, A> {
+    /// Returns the branch for the given key.
+    pub fn get(&self, key: u8) -> Option<NodePtr<O, K, V, A>> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self.cast_ref_unchecked::<Node4<K, V, A>>().get(key),
+                NodeKind::Node16 => self.cast_ref_unchecked::<Node16<K, V, A>>().get(key),
+                NodeKind::Node48 => self.cast_ref_unchecked::<Node48<K, V, A>>().get(key),
+                NodeKind::Node256 => self.cast_ref_unchecked::<Node256<K, V, A>>().get(key),
+            }
+            .map(|x| x.as_unknown().assume_ownership())
+        }
+    }
+
+    /// Returns the branch with the smallest key larger or equal to `from`.
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self.cast_ref_unchecked::<Node4<K, V, A>>().next_node(from),
+                NodeKind::Node16 => self.cast_ref_unchecked::<Node16<K, V, A>>().next_node(from),
+                NodeKind::Node48 => self.cast_ref_unchecked::<Node48<K, V, A>>().next_node(from),
+                NodeKind::Node256 => self
+                    .cast_ref_unchecked::<Node256<K, V, A>>()
+                    .next_node(from),
+            }
+            .map(|(k, x)| (k, x.as_unknown().assume_ownership()))
+        }
+    }
+
+    /// Returns the amount of leafs below the node.
+    #[cfg(feature = "order-statistics")]
+    pub fn leaf_count(&self) -> usize {
+        if self.is::<LeafNode<K, V, A>>() {
+            1
+        } else {
+            self.header().leafs
+        }
+    }
+
+    /// Returns the branch with the largest key smaller or equal to `from`.
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self.cast_ref_unchecked::<Node4<K, V, A>>().prev_node(from),
+                NodeKind::Node16 => self.cast_ref_unchecked::<Node16<K, V, A>>().prev_node(from),
+                NodeKind::Node48 => self.cast_ref_unchecked::<Node48<K, V, A>>().prev_node(from),
+                NodeKind::Node256 => self
+                    .cast_ref_unchecked::<Node256<K, V, A>>()
+                    .prev_node(from),
+            }
+            .map(|(k, x)| (k, x.as_unknown().assume_ownership()))
+        }
+    }
+
+    /// Returns the aggregate of the values below the node, combined from the aggregates kept next
+    /// to its branches.
+    pub fn aggregate(&self) -> A {
+        if mem::size_of::<A>() == 0 {
+            // Nothing to combine, e.g. for a tree without aggregates.
+            return A::identity();
+        }
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => {
+                    A::from_value(self.cast_ref_unchecked::<LeafNode<K, V, A>>().as_value())
+                }
+                NodeKind::Node4 => self.cast_ref_unchecked::<Node4<K, V, A>>().aggregate(),
+                NodeKind::Node16 => self.cast_ref_unchecked::<Node16<K, V, A>>().aggregate(),
+                NodeKind::Node48 => self.cast_ref_unchecked::<Node48<K, V, A>>().aggregate(),
+                NodeKind::Node256 => self.cast_ref_unchecked::<Node256<K, V, A>>().aggregate(),
+            }
+        }
+    }
+
+    /// Returns the aggregate kept next to the branch for the given key.
+    pub fn child_aggregate(&self, key: u8) -> Option<A> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => self
+                    .cast_ref_unchecked::<Node4<K, V, A>>()
+                    .child_aggregate(key),
+                NodeKind::Node16 => self
+                    .cast_ref_unchecked::<Node16<K, V, A>>()
+                    .child_aggregate(key),
+                NodeKind::Node48 => self
+                    .cast_ref_unchecked::<Node48<K, V, A>>()
+                    .child_aggregate(key),
+                NodeKind::Node256 => self
+                    .cast_ref_unchecked::<Node256<K, V, A>>()
+                    .child_aggregate(key),
+            }
+        }
+    }
+}
+
+impl<O: MutablePtr, K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<O, K, V, A> {
+    /// Returns a mutable reference to the slot containing the branch for the given key.
+    pub fn child_mut(&mut self, key: u8) -> Option<&mut NodePtr<Owned, K, V, A>> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node4<K, V, A>>()
+                    .as_ptr())
+                .child_mut(key),
+                NodeKind::Node16 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node16<K, V, A>>()
+                    .as_ptr())
+                .child_mut(key),
+                NodeKind::Node48 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node48<K, V, A>>()
+                    .as_ptr())
+                .child_mut(key),
+                NodeKind::Node256 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node256<K, V, A>>()
+                    .as_ptr())
+                .child_mut(key),
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the aggregate kept next to the branch for the given key.
+    pub fn aggregate_mut(&mut self, key: u8) -> Option<&mut A> {
+        unsafe {
+            match self.kind() {
+                NodeKind::Leaf => panic!("tried to retrieve a branch from a leaf node"),
+                NodeKind::Node4 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node4<K, V, A>>()
+                    .as_ptr())
+                .aggregate_mut(key),
+                NodeKind::Node16 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node16<K, V, A>>()
+                    .as_ptr())
+                .aggregate_mut(key),
+                NodeKind::Node48 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node48<K, V, A>>()
+                    .as_ptr())
+                .aggregate_mut(key),
+                NodeKind::Node256 => (*self
+                    .as_unknown()
+                    .cast_unchecked::<Node256<K, V, A>>()
+                    .as_ptr())
+                .aggregate_mut(key),
+            }
+        }
+    }
+
+    pub fn insert_grow(
+        &mut self,
+        key: u8,
+        v: OwnedNodePtr<K, V, A>,
+    ) -> Option<OwnedNodePtr<K, V, A>> {
+        match self.kind() {
+            NodeKind::Leaf => panic!("tried to insert a branch in a leaf node"),
             NodeKind::Node4 => self.insert_grow_4(key, v),
             NodeKind::Node16 => self.insert_grow_16(key, v),
             NodeKind::Node48 => self.insert_grow_48(key, v),
             NodeKind::Node256 => {
-                unsafe { self.cast_mut_unchecked::<Node256<K, V>>() }.insert(key, v)
+                unsafe { self.cast_mut_unchecked::<Node256<K, V, A>>() }.insert(key, v)
             }
         }
     }
 
-    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V>> {
-        match self.header().kind() {
-            NodeKind::Leaf => panic!(),
+    /// Remove a branch from the node, shrinking the node if required.
+    ///
+    /// A node4 which is left with a single branch is folded into its remaining child.
+    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V, A>> {
+        match self.kind() {
+            NodeKind::Leaf => panic!("tried to remove a branch from a leaf node"),
             NodeKind::Node4 => unsafe {
-                let res = self.cast_mut_unchecked::<Node4<K, V>>().remove(key);
-                // TODO: Fold
+                let mut cast = self.cast_mut_unchecked::<Node4<K, V, A>>();
+                let res = cast.remove(key);
+                if cast.should_shrink() {
+                    self.fold_4()
+                }
                 res
             },
             NodeKind::Node16 => unsafe {
-                let mut cast = self.cast_mut_unchecked::<Node16<K, V>>();
+                let mut cast = self.cast_mut_unchecked::<Node16<K, V, A>>();
                 let res = cast.remove(key);
                 if cast.should_shrink() {
                     self.shrink_16()
@@ -55,7 +230,7 @@ impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
                 res
             },
             NodeKind::Node48 => unsafe {
-                let mut cast = self.cast_mut_unchecked::<Node48<K, V>>();
+                let mut cast = self.cast_mut_unchecked::<Node48<K, V, A>>();
                 let res = cast.remove(key);
                 if cast.should_shrink() {
                     self.shrink_48()
@@ -63,7 +238,7 @@ impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
                 res
             },
             NodeKind::Node256 => unsafe {
-                let mut cast = self.cast_mut_unchecked::<Node256<K, V>>();
+                let mut cast = self.cast_mut_unchecked::<Node256<K, V, A>>();
                 let res = cast.remove(key);
                 if cast.should_shrink() {
                     self.shrink_256()
@@ -73,45 +248,136 @@ impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
         }
     }
 
-    pub fn new_branch(
-        &mut self,
-        key: &K,
-        value: V,
-        at: usize,
-        range_start: usize,
-        mismatch_index: usize,
-    ) {
-        let split_node = OwnedTypedNodePtr::new(Node4::<K, V>::new(key, range_start..at));
-        let leaf_node =
-            OwnedTypedNodePtr::new(LeafNode::<K, V>::new(key, (at + 1)..key.len(), value));
+    /// Split the node at the given offset into its prefix, replacing it with a node4 which
+    /// contains the node and a new leaf for the given key.
+    ///
+    /// `at` is the offset into the key at which the prefix of this node starts and `mismatch` is
+    /// the offset into the prefix of the first byte which differs from the key.
+    pub fn new_branch(&mut self, key: &K, value: V, at: usize, mismatch: usize) {
+        let split_at = at + mismatch;
+        let mut split_node = OwnedTypedNodePtr::new(Node4::<K, V, A>::new(key, at..split_at));
+        #[cfg(feature = "order-statistics")]
+        {
+            split_node.header_mut().leafs = self.as_borrow().leaf_count() + 1;
+        }
+        let leaf_node = LeafNode::<K, V, A>::new_ptr(key, (split_at + 1)..key.len(), value);
 
-        let new_key = key.at(at);
+        let new_key = key.at(split_at);
+        let old_key = self.prefix()[mismatch];
 
-        let prefix_mismatch_offset = mismatch_index - range_start;
-        let old_key = self.header().prefix()[prefix_mismatch_offset];
+        unsafe {
+            let mut old = self.as_unknown().assume_owned();
+            // +1 because also drop the mismatching key.
+            old.drop_prefix(mismatch + 1);
+            let aggregate = old.aggregate();
+            split_node.insert(old_key, old);
+            *split_node.aggregate_mut(old_key).unwrap() = aggregate;
+            split_node.insert(new_key, leaf_node);
+            *self = split_node.erase_type().into_unknown().assume_ownership();
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<Owned, K, V, A> {
+    /// Drop the start of the prefix of the node.
+    pub fn drop_prefix(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+        if !self.is_inline() {
+            self.header_mut().storage.drop_prefix(len);
+            return;
+        }
+        unsafe {
+            let rest = self.prefix().len() - len;
+            *self = NodePtr::replace_inline_prefix(self.as_unknown(), rest, |old, dst| {
+                dst.copy_from_slice(&old[len..])
+            })
+            .into_unknown()
+            .assume_ownership();
+        }
+    }
 
-        // +1 because also drop the mismatching key.
-        self.header_mut().storage.drop_prefix(at + 1);
+    /// Prepend the prefix followed by the key to the prefix of the node.
+    pub fn prepend_prefix(&mut self, prefix: &[u8], key: u8) {
+        if !self.is_inline() {
+            self.header_mut().storage.prepend_prefix(prefix, key);
+            return;
+        }
+        unsafe {
+            let len = prefix.len() + 1 + self.prefix().len();
+            *self = NodePtr::replace_inline_prefix(self.as_unknown(), len, |old, dst| {
+                let (start, end) = dst.split_at_mut(prefix.len());
+                start.copy_from_slice(prefix);
+                end[0] = key;
+                end[1..].copy_from_slice(old);
+            })
+            .into_unknown()
+            .assume_ownership();
+        }
+    }
+}
 
+impl<O: ValidPtr, K: Key + ?Sized, V: fmt::Debug, A> NodePtr<O, K, V, A> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        depth: usize,
+        options: &DisplayOptions,
+    ) -> fmt::Result {
         unsafe {
-            let old = self.as_unknown();
-            *self = split_node.erase_type().as_unknown().assume_ownership();
-
-            let mut this = self.cast_mut_unchecked::<Node4<K, V>>();
-
-            this.header.data_mut().len = 2;
-
-            if new_key < old_key {
-                this.keys[0] = new_key;
-                this.ptr[0] = leaf_node.erase_type().as_unknown();
-                this.keys[1] = old_key;
-                this.ptr[1] = old;
-            } else {
-                this.keys[0] = old_key;
-                this.ptr[0] = old;
-                this.keys[1] = new_key;
-                this.ptr[1] = leaf_node.erase_type().as_unknown();
+            match self.kind() {
+                NodeKind::Leaf => self
+                    .cast_ref_unchecked::<LeafNode<K, V, A>>()
+                    .display(fmt, depth, options),
+                NodeKind::Node4 => self
+                    .cast_ref_unchecked::<Node4<K, V, A>>()
+                    .display(fmt, depth, options),
+                NodeKind::Node16 => self
+                    .cast_ref_unchecked::<Node16<K, V, A>>()
+                    .display(fmt, depth, options),
+                NodeKind::Node48 => self
+                    .cast_ref_unchecked::<Node48<K, V, A>>()
+                    .display(fmt, depth, options),
+                NodeKind::Node256 => self
+                    .cast_ref_unchecked::<Node256<K, V, A>>()
+                    .display(fmt, depth, options),
             }
         }
     }
 }
+
+/// Write the children of a node one per line, indented by `depth`.
+///
+/// Children beyond [`DisplayOptions::max_children`] are summarized in a single line, as are all
+/// children of a node deeper than [`DisplayOptions::max_depth`].
+fn display_children<'a, K, V, A, I>(
+    fmt: &mut fmt::Formatter,
+    depth: usize,
+    options: &DisplayOptions,
+    mut children: I,
+) -> fmt::Result
+where
+    K: Key + ?Sized + 'a,
+    V: fmt::Debug + 'a,
+    A: 'a,
+    I: Iterator<Item = (u8, NodePtr<Borrow<'a>, K, V, A>)>,
+{
+    let indent = |fmt: &mut fmt::Formatter| (0..depth).try_for_each(|_| fmt.write_str("  "));
+    let shown = if options.max_depth.is_some_and(|x| depth > x) {
+        0
+    } else {
+        options.max_children.unwrap_or(usize::MAX)
+    };
+    for (key, child) in children.by_ref().take(shown) {
+        indent(fmt)?;
+        write!(fmt, "[{key}] = ")?;
+        child.display(fmt, depth + 1, options)?;
+    }
+    let rest = children.count();
+    if rest > 0 {
+        indent(fmt)?;
+        writeln!(fmt, "... {rest} more")?;
+    }
+    Ok(())
+}
@@ -1,34 +1,44 @@
 use crate::{
+    augmented::Aggregate,
+    display::DisplayOptions,
     key::Key,
     raw::{
         nodes::Node48,
         ptr::{
-            MutablePtr, NodePtr, OwnedNodePtr, OwnedTypedNodePtr, TypedNodePtr, Unknown, ValidPtr,
+            MutablePtr, NodePtr, Owned, OwnedNodePtr, OwnedTypedNodePtr, TypedNodePtr, Unknown,
+            ValidPtr,
         },
     },
 };
+use core::fmt;
 use std::{
     mem::MaybeUninit,
     ops::Range,
     ptr::{addr_of, addr_of_mut},
 };
 
-use super::{Node4, NodeHeader, NodeKind, NodeType};
+use super::{display_children, Node4, NodeHeader, NodeKind, NodeType};
 
+/// A node with a maximum of 16 branches.
+///
+/// Keys are kept sorted so that branches can be iterated in order. The aggregate of a branch is
+/// kept at the same index as its pointer.
 #[repr(C)]
-pub struct Node16<K: Key + ?Sized, V> {
+pub struct Node16<K: Key + ?Sized, V, A = ()> {
     pub header: NodeHeader<K, V>,
-    pub ptr: [NodePtr<Unknown, K, V>; 16],
+    pub ptr: [NodePtr<Unknown, K, V, A>; 16],
     pub keys: [u8; 16],
+    pub aggs: [A; 16],
 }
 
-unsafe impl<K: Key + ?Sized, V> NodeType for Node16<K, V> {
+unsafe impl<K: Key + ?Sized, V, A> NodeType for Node16<K, V, A> {
     const KIND: NodeKind = NodeKind::Node16;
     type Key = K;
     type Value = V;
+    type Aggregate = A;
 }
 
-impl<K: Key + ?Sized, V> Node16<K, V> {
+impl<K: Key + ?Sized, V, A: Aggregate<V>> Node16<K, V, A> {
     pub fn new(key: &K, range: Range<usize>) -> Self {
         Self::new_from_header(NodeHeader::new::<Self>(key, range))
     }
@@ -37,7 +47,8 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
         Node16 {
             header,
             keys: [0; 16],
-            ptr: [NodePtr::<Unknown, K, V>::dangling(); 16],
+            ptr: [NodePtr::<Unknown, K, V, A>::dangling(); 16],
+            aggs: [A::identity(); 16],
         }
     }
 
@@ -49,28 +60,81 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
         self.header.data().len < 5
     }
 
-    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V>> {
-        let idx = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)?;
+    fn find_key(&self, key: u8) -> Result<usize, usize> {
+        self.keys[..self.header.data().len as usize].binary_search(&key)
+    }
 
-        self.keys.copy_within((idx + 1).min(16).., idx);
+    /// Insert a branch into a node which is not full, returns the existing branch if there was
+    /// one.
+    pub fn insert(&mut self, key: u8, v: OwnedNodePtr<K, V, A>) -> Option<OwnedNodePtr<K, V, A>> {
+        let len = self.header.data().len as usize;
+        match self.find_key(key) {
+            Ok(idx) => {
+                let res = std::mem::replace(&mut self.ptr[idx], v.into_unknown());
+                unsafe { Some(res.assume_owned()) }
+            }
+            Err(idx) => {
+                debug_assert!(!self.is_full());
+                self.keys.copy_within(idx..len, idx + 1);
+                self.ptr.copy_within(idx..len, idx + 1);
+                self.aggs.copy_within(idx..len, idx + 1);
+                self.keys[idx] = key;
+                self.ptr[idx] = v.into_unknown();
+                self.aggs[idx] = A::identity();
+                self.header.data_mut().len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V, A>> {
+        let len = self.header.data().len as usize;
+        let idx = self.find_key(key).ok()?;
+
+        let res = self.ptr[idx];
+        self.keys.copy_within(idx + 1..len, idx);
+        self.ptr.copy_within(idx + 1..len, idx);
+        self.aggs.copy_within(idx + 1..len, idx);
         self.header.data_mut().len -= 1;
 
-        unsafe { Some(self.ptr[idx].assume_owned()) }
+        unsafe { Some(res.assume_owned()) }
+    }
+
+    pub fn child_mut(&mut self, key: u8) -> Option<&mut NodePtr<Owned, K, V, A>> {
+        let idx = self.find_key(key).ok()?;
+        unsafe { Some(self.ptr[idx].assume_owned_mut()) }
+    }
+
+    /// Returns the aggregate of the branch for the given key.
+    pub fn child_aggregate(&self, key: u8) -> Option<A> {
+        let idx = self.find_key(key).ok()?;
+        Some(self.aggs[idx])
+    }
+
+    /// Returns the slot containing the aggregate of the branch for the given key.
+    pub fn aggregate_mut(&mut self, key: u8) -> Option<&mut A> {
+        let idx = self.find_key(key).ok()?;
+        Some(&mut self.aggs[idx])
     }
 
-    /// Copy over from node 16 into an uninitalized node48.
+    /// Returns the aggregates of all branches combined in key order.
+    pub fn aggregate(&self) -> A {
+        let len = self.header.data().len as usize;
+        self.aggs[..len]
+            .iter()
+            .fold(A::identity(), |acc, x| acc.combine(x))
+    }
+
+    /// Copy over from node 4 into an uninitalized node16.
     ///
     /// This function is designed to avoid unnessacery copying
     pub unsafe fn copy_from_node4(
-        node: OwnedTypedNodePtr<Node4<K, V>>,
+        node: OwnedTypedNodePtr<Node4<K, V, A>>,
         place: &mut MaybeUninit<Self>,
     ) {
         debug_assert!(node.is_full());
 
-        let node = node.as_unknown();
+        let node = node.into_unknown();
         let dst_ptr = place.as_mut_ptr();
 
         // copy over pointers into the array.
@@ -84,6 +148,11 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
 
         std::ptr::copy_nonoverlapping(src, dst, 4);
 
+        let src = addr_of!((*node.as_ptr()).aggs[0]);
+        let dst = addr_of_mut!((*dst_ptr).aggs[0]);
+
+        std::ptr::copy_nonoverlapping(src, dst, 4);
+
         // copy over header.
         let dst = addr_of_mut!((*dst_ptr).header);
         let mut header = node.erase_type().take_header();
@@ -95,18 +164,20 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
     }
 
     pub unsafe fn copy_from_node48(
-        node: OwnedTypedNodePtr<Node48<K, V>>,
+        node: OwnedTypedNodePtr<Node48<K, V, A>>,
         place: &mut MaybeUninit<Self>,
     ) {
-        debug_assert!(node.should_shrink());
+        debug_assert!(node.header.data().len <= 16);
 
-        let node = node.as_unknown();
+        let node = node.into_unknown();
         let dst_ptr = place.as_mut_ptr();
 
         let ptr_src = addr_of!((*node.as_ptr()).ptr[0]);
         let idx_src = addr_of!((*node.as_ptr()).idx[0]);
+        let agg_src = addr_of!((*node.as_ptr()).aggs[0]);
         let ptr_dst = addr_of_mut!((*dst_ptr).ptr[0]);
         let key_dst = addr_of_mut!((*dst_ptr).keys[0]);
+        let agg_dst = addr_of_mut!((*dst_ptr).aggs[0]);
 
         let mut insert_at = 0u8;
         for i in 0..=255u8 {
@@ -115,6 +186,8 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
                 key_dst.add(insert_at as usize).write(i);
                 let ptr = ptr_src.add(idx as usize).read();
                 ptr_dst.add(insert_at as usize).write(ptr.ptr);
+                let agg = agg_src.add(idx as usize).read();
+                agg_dst.add(insert_at as usize).write(agg);
                 insert_at += 1;
             }
         }
@@ -123,220 +196,107 @@ impl<K: Key + ?Sized, V> Node16<K, V> {
         let mut header = node.erase_type().take_header();
         header.change_type::<Self>();
         dst.write(header);
+
+        // everthing copied over, delete node since it is unused.
+        TypedNodePtr::dealloc(node);
     }
 }
 
-impl<O: ValidPtr, K: Key + ?Sized, V> TypedNodePtr<O, Node16<K, V>> {
-    pub fn get(&self, key: u8) -> Option<NodePtr<O, K, V>> {
-        let idx = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)?;
-        unsafe { Some(self.ptr[idx].assume_ownership::<O>()) }
+impl<O: ValidPtr, K: Key + ?Sized, V, A: Aggregate<V>> TypedNodePtr<O, Node16<K, V, A>> {
+    /// Returns a pointer borrowing the branch at the given index.
+    unsafe fn child(&self, idx: usize) -> NodePtr<O, K, V, A> {
+        NodePtr::borrow_slot(addr_of_mut!((*self.as_ptr()).ptr[idx]))
     }
 
-    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V>)> {
-        let (ptr_idx, next_key) = self
-            .keys
-            .iter()
-            .copied()
-            .enumerate()
-            .filter(|(_, x)| *x >= from)
-            .min_by_key(|(_, x)| *x)?;
-        Some((next_key, unsafe {
-            self.ptr[ptr_idx].assume_ownership::<O>()
-        }))
+    pub fn get(&self, key: u8) -> Option<NodePtr<O, K, V, A>> {
+        let idx = self.find_key(key).ok()?;
+        unsafe { Some(self.child(idx)) }
     }
-}
-
-impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
-    pub fn insert_grow_16(&mut self, key: u8, v: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
-        unsafe {
-            debug_assert!(self.is::<Node16<K, V>>());
 
-            let mut cast_ptr = self.cast_mut_unchecked::<Node16<K, V>>();
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        let idx = self.find_key(from).unwrap_or_else(|x| x);
+        if idx >= self.header.data().len as usize {
+            return None;
+        }
+        Some((self.keys[idx], unsafe { self.child(idx) }))
+    }
 
-            let len = cast_ptr.header().data().len;
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        let idx = match self.find_key(from) {
+            Ok(x) => x,
+            Err(0) => return None,
+            Err(x) => x - 1,
+        };
+        Some((self.keys[idx], unsafe { self.child(idx) }))
+    }
+}
 
-            if let Some(x) = cast_ptr.keys[..len as usize]
-                .iter()
-                .copied()
-                .position(|x| x == key)
-            {
-                let this = cast_ptr.as_mut();
-                let res = std::mem::replace(&mut this.ptr[x], v.into_unknown());
-                return unsafe { Some(res.assume_owned()) };
-            }
+impl<O: MutablePtr, K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<O, K, V, A> {
+    pub fn insert_grow_16(
+        &mut self,
+        key: u8,
+        v: OwnedNodePtr<K, V, A>,
+    ) -> Option<OwnedNodePtr<K, V, A>> {
+        unsafe {
+            debug_assert!(self.is::<Node16<K, V, A>>());
 
-            if !cast_ptr.is_full() {
-                cast_ptr.header_mut().data_mut().len += 1;
-                cast_ptr.as_mut().ptr[len as usize] = v.into_unknown();
-                cast_ptr.as_mut().keys[len as usize] = key;
+            let mut cast_ptr = self.cast_mut_unchecked::<Node16<K, V, A>>();
 
-                return None;
+            if !cast_ptr.is_full() || cast_ptr.find_key(key).is_ok() {
+                return cast_ptr.insert(key, v);
             }
 
             let this = self
                 .as_unknown()
-                .cast_unchecked::<Node16<K, V>>()
+                .cast_unchecked::<Node16<K, V, A>>()
                 .assume_owned();
 
-            let ptr = TypedNodePtr::<Unknown, Node48<K, V>>::alloc();
+            let ptr = TypedNodePtr::<Unknown, Node48<K, V, A>>::alloc();
             Node48::copy_from_node16(this, ptr.as_nonnull().cast().as_mut());
             *self = ptr.erase_type().assume_ownership();
 
-            None
+            self.cast_mut_unchecked::<Node48<K, V, A>>().insert(key, v)
         }
     }
 
     pub unsafe fn shrink_16(&mut self) {
         let this = self
             .as_unknown()
-            .cast_unchecked::<Node16<K, V>>()
+            .cast_unchecked::<Node16<K, V, A>>()
             .assume_owned();
 
-        let ptr = TypedNodePtr::<Unknown, Node4<K, V>>::alloc();
+        let ptr = TypedNodePtr::<Unknown, Node4<K, V, A>>::alloc();
         Node4::copy_from_node16(this, ptr.as_nonnull().cast().as_mut());
         *self = ptr.erase_type().assume_ownership();
     }
 }
-/*
-    pub fn insert(&mut self, key: u8, ptr: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
-        assert!(!self.is_full());
-        if let Some(x) = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)
-        {
-            let res = std::mem::replace(&mut self.ptr[x], ptr.into_unknown());
-            return unsafe { Some(res.assume_owned()) };
-        }
-
-        let idx = self.header.data().len;
-        self.header.data_mut().len += 1;
-        self.ptr[idx as usize] = ptr.into_unknown();
-        self.keys[idx as usize] = key;
-        None
-    }
-
-    /// # Safety
-    /// Caller must ensure that the given `NodePtr` is a pointer to `Node4`.
-    pub unsafe fn insert_grow(
-        this: &mut RawBoxedNode<K, V>,
-        key: u8,
-        v: OwnedNodePtr<K, V>,
-    ) -> Option<OwnedNodePtr<K, V>> {
-        debug_assert!(this.is::<Self>());
-
-        if let Some(x) = this.as_ref::<Self>().keys
-            [..this.as_ref::<Self>().header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)
-        {
-            let res = std::mem::replace(&mut this.as_mut::<Self>().ptr[x], v.into_unknown());
-            return Some(res.assume_owned());
-        }
-
-        if this.as_ref::<Self>().is_full() {
-            let mut ptr = Self::grow(this.into_owned());
-            ptr.as_mut().insert(key, v);
-            *this = ptr.into_boxed();
-            return None;
-        }
-
-        let idx = this.as_ref::<Self>().header.data().len;
-        this.as_mut::<Self>().header.data_mut().len += 1;
-        this.as_mut::<Self>().ptr[idx as usize].write(v.into_raw());
-        this.as_mut::<Self>().keys[idx as usize] = key;
-        None
-    }
-
-    pub fn remove(&mut self, key: u8) -> Option<BoxedNode<K, V>> {
-        let len = self.header.data().len;
-        for i in 0..len {
-            if self.keys[i as usize] == key {
-                let ptr = unsafe { self.ptr.get_unchecked(i as usize).assume_init() };
-                self.keys.swap(i as usize, (len - 1) as usize);
-                self.ptr.swap(i as usize, (len - 1) as usize);
-                self.header.data_mut().len -= 1;
-                return unsafe { Some(BoxedNode::from_raw(ptr)) };
-            }
-        }
-        None
-    }
 
-    pub unsafe fn shrink(this: RawOwnedNode<Self>) -> RawOwnedNode<Node4<K, V>> {
-        // ensure there is enough space for all the nodes.
-        assert!(this.as_ref().should_shrink());
-        // copy over the keys to be later copied back.
-        let keys = <[u8; 4]>::try_from(&this.as_ref().keys[0..4]).unwrap();
-
-        let mut ptr = this.realloc::<Node4<K, V>>();
-        ptr.as_mut().keys = keys;
-        ptr
-    }
-
-    unsafe fn grow(this: &mut OwnedNodePtr<Self>) {
-        assert!(this.as_ref().is_full());
-
-        let old_ptr = this.into_unknown();
-        let mut new_ptr = TypedNodePtr::<Unknown, Node48<K, V>>::alloc();
-        new_ptr.take_header(this.as_unknown());
-
-        let src_ptr = new_ptr.as_nonnull().cast::<Self>();
-
-        // set all the idx to max
-        let idx_ptr = addr_of_mut!(*((*new_ptr.as_ptr()).idx.as_mut_ptr())).cast::<u8>();
-        std::ptr::write_bytes(idx_ptr, u8::MAX, 256);
-
-        // write in the proper idx's
-        for (idx, k) in src_ptr.as_ref().keys.iter().copied().enumerate() {
-            idx_ptr.add(k as usize).write(idx as u8);
-        }
-
-        // intialize free list
-        let ptr_ptr = addr_of_mut!((*new_ptr.as_ptr()).ptr).cast::<PtrUnion<K, V>>();
-        for i in 16..47u8 {
-            ptr_ptr.add(i as usize).write(PtrUnion { free: i + 1 })
-        }
-
-        ptr_ptr.add(47).write(PtrUnion { free: u8::MAX });
-
-        let mut new_ptr = new_ptr.assume_owned();
-        new_ptr.header_mut().data_mut().free = 16;
-        new_ptr
-    }
-}
-
-impl<K: Key + ?Sized, V: fmt::Debug> Node16<K, V> {
-    pub fn display(&self, fmt: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+impl<K: Key + ?Sized, V: fmt::Debug, A> Node16<K, V, A> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        depth: usize,
+        options: &DisplayOptions,
+    ) -> fmt::Result {
         writeln!(
             fmt,
             "NODE16: len={},prefix={:?}",
-            self.header.storage.data().len,
-            self.header.storage.prefix()
+            self.header.data().len,
+            self.header.prefix()
         )?;
-        for i in 0..self.header.storage.data().len {
-            for _ in 0..depth {
-                fmt.write_str("  ")?;
-            }
-            write!(fmt, "[{}] = ", self.keys[i as usize])?;
-            unsafe {
-                self.ptr[i as usize]
-                    .assume_init_ref()
-                    .display(fmt, depth + 1)?;
-            }
-        }
-        Ok(())
+        let children = (0..self.header.data().len as usize).map(|i| {
+            (self.keys[i], unsafe {
+                NodePtr::borrow_slot(&self.ptr[i] as *const _ as *mut NodePtr<Unknown, K, V, A>)
+            })
+        });
+        display_children(fmt, depth, options, children)
     }
 }
 
-impl<K: Key + ?Sized, V> Drop for Node16<K, V> {
+impl<K: Key + ?Sized, V, A> Drop for Node16<K, V, A> {
     fn drop(&mut self) {
         for i in 0..self.header.data().len {
-            unsafe { self.ptr[i as usize].assume_init().drop_in_place() }
+            unsafe { NodePtr::free(self.ptr[i as usize]) }
         }
     }
 }
-*/
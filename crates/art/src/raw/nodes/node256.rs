@@ -1,31 +1,58 @@
+use core::fmt;
 use std::{
     mem::MaybeUninit,
+    ops::Range,
     ptr::{addr_of, addr_of_mut},
 };
 
 use crate::{
+    augmented::Aggregate,
+    display::DisplayOptions,
     key::Key,
     raw::{
-        ptr::{OwnedNodePtr, OwnedTypedNodePtr, TypedNodePtr},
+        ptr::{Owned, OwnedNodePtr, OwnedTypedNodePtr, TypedNodePtr, ValidPtr},
         MutablePtr, NodePtr, Unknown,
     },
 };
 
-use super::{Node48, NodeHeader, NodeKind, NodeType};
+use super::{display_children, Node48, NodeHeader, NodeKind, NodeType};
 
+/// A node with a branch for every key.
+///
+/// The aggregate of a branch is kept at the same index as its pointer.
 #[repr(C)]
-pub struct Node256<K: Key + ?Sized, V> {
+pub struct Node256<K: Key + ?Sized, V, A = ()> {
     pub header: NodeHeader<K, V>,
-    pub ptr: [Option<OwnedNodePtr<K, V>>; 256],
+    pub ptr: [Option<OwnedNodePtr<K, V, A>>; 256],
+    pub aggs: [A; 256],
 }
 
-unsafe impl<K: Key + ?Sized, V> NodeType for Node256<K, V> {
+unsafe impl<K: Key + ?Sized, V, A> NodeType for Node256<K, V, A> {
     const KIND: NodeKind = NodeKind::Node256;
     type Key = K;
     type Value = V;
+    type Aggregate = A;
 }
 
-impl<K: Key + ?Sized, V> Node256<K, V> {
+impl<K: Key + ?Sized, V, A: Aggregate<V>> Node256<K, V, A> {
+    /// Create a new node with the given first branch.
+    ///
+    /// A node256 can't be empty as the length is stored one less then the actual amount of
+    /// branches.
+    pub fn new(key: &K, range: Range<usize>, first: (u8, OwnedNodePtr<K, V, A>)) -> Self {
+        Self::new_from_header(NodeHeader::new::<Self>(key, range), first)
+    }
+
+    pub fn new_from_header(header: NodeHeader<K, V>, first: (u8, OwnedNodePtr<K, V, A>)) -> Self {
+        let mut ptr = [const { None }; 256];
+        ptr[first.0 as usize] = Some(first.1);
+        Node256 {
+            header,
+            ptr,
+            aggs: [A::identity(); 256],
+        }
+    }
+
     pub fn is_full(&self) -> bool {
         // HACK: slight quirk with len being only u8
         // Can't fit full length so actuall length is self.header.data().len + 1
@@ -38,40 +65,76 @@ impl<K: Key + ?Sized, V> Node256<K, V> {
         self.header.data().len < 48
     }
 
-    pub fn insert(&mut self, key: u8, v: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
+    pub fn insert(&mut self, key: u8, v: OwnedNodePtr<K, V, A>) -> Option<OwnedNodePtr<K, V, A>> {
         let res = self.ptr[key as usize].replace(v);
-        self.header.data_mut().len += res.is_none() as u8;
+        if res.is_none() {
+            self.aggs[key as usize] = A::identity();
+            self.header.data_mut().len += 1;
+        }
         res
     }
 
-    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V>> {
+    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V, A>> {
         let res = self.ptr[key as usize].take();
         self.header.data_mut().len -= res.is_some() as u8;
         res
     }
 
+    pub fn child_mut(&mut self, key: u8) -> Option<&mut NodePtr<Owned, K, V, A>> {
+        self.ptr[key as usize].as_deref_mut()
+    }
+
+    /// Returns the aggregate of the branch for the given key.
+    pub fn child_aggregate(&self, key: u8) -> Option<A> {
+        self.ptr[key as usize].as_ref()?;
+        Some(self.aggs[key as usize])
+    }
+
+    /// Returns the slot containing the aggregate of the branch for the given key.
+    pub fn aggregate_mut(&mut self, key: u8) -> Option<&mut A> {
+        self.ptr[key as usize].as_ref()?;
+        Some(&mut self.aggs[key as usize])
+    }
+
+    /// Returns the aggregates of all branches combined in key order.
+    pub fn aggregate(&self) -> A {
+        self.ptr
+            .iter()
+            .zip(&self.aggs)
+            .filter(|(ptr, _)| ptr.is_some())
+            .fold(A::identity(), |acc, (_, x)| acc.combine(x))
+    }
+
     pub unsafe fn copy_from_node48(
-        node: OwnedTypedNodePtr<Node48<K, V>>,
+        node: OwnedTypedNodePtr<Node48<K, V, A>>,
         place: &mut MaybeUninit<Self>,
     ) {
         debug_assert!(node.is_full());
 
-        let node = node.as_unknown();
+        let node = node.into_unknown();
         let dst_ptr = place.as_mut_ptr();
 
         // copy over pointers into the array.
         let src_ptr = addr_of!((*node.as_ptr()).ptr[0]);
         let src_idx = addr_of!((*node.as_ptr()).idx[0]);
+        let src_agg = addr_of!((*node.as_ptr()).aggs[0]);
         let dst = addr_of_mut!((*dst_ptr).ptr[0]);
+        let dst_agg = addr_of_mut!((*dst_ptr).aggs[0]);
 
         std::ptr::write_bytes(dst, 0, 256);
+        for i in 0..256 {
+            dst_agg.add(i).write(A::identity());
+        }
 
         for i in 0..256 {
             let idx = src_idx.add(i).read();
-            debug_assert_ne!(idx, u8::MAX);
+            if idx == u8::MAX {
+                continue;
+            }
 
-            let ptr = src_ptr.add(i).read();
-            dst.add(idx as usize).write(Some(ptr.ptr.assume_owned()));
+            let ptr = src_ptr.add(idx as usize).read();
+            dst.add(i).write(Some(ptr.ptr.assume_owned()));
+            dst_agg.add(i).write(src_agg.add(idx as usize).read());
         }
 
         // copy over header.
@@ -86,94 +149,62 @@ impl<K: Key + ?Sized, V> Node256<K, V> {
     }
 }
 
-impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
-    pub unsafe fn shrink_256(&mut self) {
-        let this = self
-            .as_unknown()
-            .cast_unchecked::<Node256<K, V>>()
-            .assume_owned();
-
-        let ptr = TypedNodePtr::<Unknown, Node48<K, V>>::alloc();
-        Node48::copy_from_node256(this, ptr.as_nonnull().cast().as_mut());
-        *self = ptr.erase_type().assume_ownership();
+impl<O: ValidPtr, K: Key + ?Sized, V, A: Aggregate<V>> TypedNodePtr<O, Node256<K, V, A>> {
+    /// Returns a pointer borrowing the branch for the given key, if there is one.
+    fn child(&self, key: u8) -> Option<NodePtr<O, K, V, A>> {
+        self.ptr[key as usize].as_ref()?;
+        // A filled slot has the same layout as the pointer it contains.
+        let slot = unsafe { addr_of_mut!((*self.as_ptr()).ptr[key as usize]) };
+        Some(unsafe { NodePtr::borrow_slot(slot.cast::<NodePtr<Owned, K, V, A>>()) })
     }
-}
 
-/*
-    pub fn get(&self, key: u8) -> Option<&BoxedNode<K, V>> {
-        self.ptr[key as usize].as_ref()
+    pub fn get(&self, key: u8) -> Option<NodePtr<O, K, V, A>> {
+        self.child(key)
     }
 
-    pub fn get_mut(&mut self, key: u8) -> Option<&mut BoxedNode<K, V>> {
-        self.ptr[key as usize].as_mut()
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        (from..=u8::MAX).find_map(|key| Some((key, self.child(key)?)))
     }
 
-    pub fn next_node(&mut self, from: u8) -> Option<(u8, RawBoxedNode<K, V>)> {
-        self.ptr[from as usize..]
-            .iter()
-            .enumerate()
-            .find_map(|(idx, x)| x.as_ref().map(|x| (idx as u8, x.as_raw())))
-    }
-
-    pub fn insert(&mut self, key: u8, ptr: BoxedNode<K, V>) -> Option<BoxedNode<K, V>> {
-        let res = self.ptr[key as usize].replace(ptr);
-        self.header.data_mut().len += res.is_none() as u8;
-        res
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        (0..=from)
+            .rev()
+            .find_map(|key| Some((key, self.child(key)?)))
     }
+}
 
-    pub fn remove(&mut self, key: u8) -> Option<BoxedNode<K, V>> {
-        let res = self.ptr[key as usize].take();
-        self.header.data_mut().len -= res.is_some() as u8;
-        res
-    }
-
-    pub unsafe fn shrink(mut this: RawOwnedNode<Self>) -> RawOwnedNode<Node48<K, V>> {
-        assert!(this.as_ref().should_shrink());
-        let mut new_node = RawOwnedNode::<Node48<K, V>>::alloc();
-
-        let key_ptr = addr_of_mut!((*new_node.as_ptr()).idx[0]);
-        let ptr_ptr = addr_of_mut!((*new_node.as_ptr()).ptr[0]);
-        let mut written = 0;
-
-        std::ptr::write_bytes(key_ptr, u8::MAX, 256);
-
-        for (idx, p) in this.as_mut().ptr.iter_mut().enumerate() {
-            if let Some(x) = p.take() {
-                ptr_ptr.add(written).write(PtrUnion { ptr: x.into_raw() });
-                key_ptr.add(idx).write(written as u8);
-                written += 1;
-            }
-        }
-        debug_assert_eq!(written, 48);
-
-        new_node.copy_header_from(this);
-        // HACK: undo storage quirk.
-        new_node.header_mut().data_mut().len += 1;
-        new_node.header_mut().data_mut().free = u8::MAX;
-
-        RawOwnedNode::dealloc(this);
+impl<O: MutablePtr, K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<O, K, V, A> {
+    pub unsafe fn shrink_256(&mut self) {
+        let this = self
+            .as_unknown()
+            .cast_unchecked::<Node256<K, V, A>>()
+            .assume_owned();
 
-        new_node
+        let ptr = TypedNodePtr::<Unknown, Node48<K, V, A>>::alloc();
+        Node48::copy_from_node256(this, ptr.as_nonnull().cast().as_mut());
+        *self = ptr.erase_type().assume_ownership();
     }
 }
 
-impl<K: Key + ?Sized, V: fmt::Debug> Node256<K, V> {
-    pub fn display(&self, fmt: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+impl<K: Key + ?Sized, V: fmt::Debug, A> Node256<K, V, A> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        depth: usize,
+        options: &DisplayOptions,
+    ) -> fmt::Result {
         writeln!(
             fmt,
             "NODE256: len={},prefix={:?}",
-            self.header.storage.data().len,
-            self.header.storage.prefix()
+            self.header.data().len,
+            self.header.prefix()
         )?;
-        for (idx, p) in self.ptr.iter().enumerate() {
-            let Some(p) = p else { break };
-            for _ in 0..depth {
-                fmt.write_str("  ")?;
-            }
-            write!(fmt, "[{}] = ", idx)?;
-            p.display(fmt, depth + 1)?;
-        }
-        Ok(())
+        let children = self.ptr.iter().enumerate().filter_map(|(i, p)| {
+            let p = p.as_ref()?;
+            Some((i as u8, unsafe {
+                NodePtr::borrow_slot(&**p as *const _ as *mut NodePtr<Owned, K, V, A>)
+            }))
+        });
+        display_children(fmt, depth, options, children)
     }
 }
-*/
@@ -1,36 +1,50 @@
-use super::{Node16, NodeHeader, NodeKind, NodeType};
+use super::{display_children, Node16, NodeHeader, NodeKind, NodeType};
 use crate::{
+    augmented::Aggregate,
+    display::DisplayOptions,
     key::Key,
     raw::{
-        ptr::{NodePtr, OwnedTypedNodePtr, TypedNodePtr, Unknown},
-        MutablePtr, OwnedNodePtr,
+        ptr::{NodePtr, OwnedTypedNodePtr, TypedNodePtr, Unknown, ValidPtr},
+        MutablePtr, Owned, OwnedNodePtr,
     },
 };
+use core::fmt;
 use std::{
     mem::MaybeUninit,
     ops::Range,
     ptr::{addr_of, addr_of_mut},
 };
 
+/// A node with a maximum of 4 branches.
+///
+/// Keys are kept sorted so that branches can be iterated in order. The aggregate of a branch is
+/// kept at the same index as its pointer.
 #[repr(C)]
-pub struct Node4<K: Key + ?Sized, V> {
+pub struct Node4<K: Key + ?Sized, V, A = ()> {
     pub header: NodeHeader<K, V>,
-    pub ptr: [NodePtr<Unknown, K, V>; 4],
+    pub ptr: [NodePtr<Unknown, K, V, A>; 4],
     pub keys: [u8; 4],
+    pub aggs: [A; 4],
 }
 
-unsafe impl<K: Key + ?Sized, V> NodeType for Node4<K, V> {
+unsafe impl<K: Key + ?Sized, V, A> NodeType for Node4<K, V, A> {
     const KIND: NodeKind = NodeKind::Node4;
     type Key = K;
     type Value = V;
+    type Aggregate = A;
 }
 
-impl<K: Key + ?Sized, V> Node4<K, V> {
+impl<K: Key + ?Sized, V, A: Aggregate<V>> Node4<K, V, A> {
     pub fn new(key: &K, range: Range<usize>) -> Self {
+        Self::new_from_header(NodeHeader::new::<Self>(key, range))
+    }
+
+    pub fn new_from_header(header: NodeHeader<K, V>) -> Self {
         Node4 {
-            header: NodeHeader::new::<Self>(key, range),
+            header,
             keys: [0; 4],
             ptr: [NodePtr::dangling(); 4],
+            aggs: [A::identity(); 4],
         }
     }
 
@@ -42,25 +56,78 @@ impl<K: Key + ?Sized, V> Node4<K, V> {
         self.header.data().len == 1
     }
 
-    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V>> {
-        let idx = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)?;
+    fn find_key(&self, key: u8) -> Result<usize, usize> {
+        self.keys[..self.header.data().len as usize].binary_search(&key)
+    }
+
+    /// Insert a branch into a node which is not full, returns the existing branch if there was
+    /// one.
+    pub fn insert(&mut self, key: u8, v: OwnedNodePtr<K, V, A>) -> Option<OwnedNodePtr<K, V, A>> {
+        let len = self.header.data().len as usize;
+        match self.find_key(key) {
+            Ok(idx) => {
+                let res = std::mem::replace(&mut self.ptr[idx], v.into_unknown());
+                unsafe { Some(res.assume_owned()) }
+            }
+            Err(idx) => {
+                debug_assert!(!self.is_full());
+                self.keys.copy_within(idx..len, idx + 1);
+                self.ptr.copy_within(idx..len, idx + 1);
+                self.aggs.copy_within(idx..len, idx + 1);
+                self.keys[idx] = key;
+                self.ptr[idx] = v.into_unknown();
+                self.aggs[idx] = A::identity();
+                self.header.data_mut().len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: u8) -> Option<OwnedNodePtr<K, V, A>> {
+        let len = self.header.data().len as usize;
+        let idx = self.find_key(key).ok()?;
 
-        self.keys.copy_within((idx + 1).min(4).., idx);
+        let res = self.ptr[idx];
+        self.keys.copy_within(idx + 1..len, idx);
+        self.ptr.copy_within(idx + 1..len, idx);
+        self.aggs.copy_within(idx + 1..len, idx);
         self.header.data_mut().len -= 1;
 
-        unsafe { Some(self.ptr[idx].assume_owned()) }
+        unsafe { Some(res.assume_owned()) }
+    }
+
+    pub fn child_mut(&mut self, key: u8) -> Option<&mut NodePtr<Owned, K, V, A>> {
+        let idx = self.find_key(key).ok()?;
+        unsafe { Some(self.ptr[idx].assume_owned_mut()) }
+    }
+
+    /// Returns the aggregate of the branch for the given key.
+    pub fn child_aggregate(&self, key: u8) -> Option<A> {
+        let idx = self.find_key(key).ok()?;
+        Some(self.aggs[idx])
+    }
+
+    /// Returns the slot containing the aggregate of the branch for the given key.
+    pub fn aggregate_mut(&mut self, key: u8) -> Option<&mut A> {
+        let idx = self.find_key(key).ok()?;
+        Some(&mut self.aggs[idx])
+    }
+
+    /// Returns the aggregates of all branches combined in key order.
+    pub fn aggregate(&self) -> A {
+        let len = self.header.data().len as usize;
+        self.aggs[..len]
+            .iter()
+            .fold(A::identity(), |acc, x| acc.combine(x))
     }
 
     pub unsafe fn copy_from_node16(
-        node: OwnedTypedNodePtr<Node16<K, V>>,
+        node: OwnedTypedNodePtr<Node16<K, V, A>>,
         place: &mut MaybeUninit<Self>,
     ) {
-        debug_assert!(node.should_shrink());
+        debug_assert!(node.header.data().len <= 4);
 
-        let node = node.as_unknown();
+        let node = node.into_unknown();
         let dst_ptr = place.as_mut_ptr();
 
         // copy over pointers into the array.
@@ -73,6 +140,10 @@ impl<K: Key + ?Sized, V> Node4<K, V> {
         let dst = addr_of_mut!((*dst_ptr).keys[0]);
         std::ptr::copy_nonoverlapping(src, dst, 4);
 
+        let src = addr_of!((*node.as_ptr()).aggs[0]);
+        let dst = addr_of_mut!((*dst_ptr).aggs[0]);
+        std::ptr::copy_nonoverlapping(src, dst, 4);
+
         // copy over header.
         let dst = addr_of_mut!((*dst_ptr).header);
         let mut header = node.erase_type().take_header();
@@ -84,208 +155,106 @@ impl<K: Key + ?Sized, V> Node4<K, V> {
     }
 }
 
-impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
-    pub fn insert_grow_4(&mut self, key: u8, v: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
-        unsafe {
-            debug_assert!(self.is::<Node4<K, V>>());
-
-            let mut cast_ptr = self.cast_mut_unchecked::<Node4<K, V>>();
-
-            let len = cast_ptr.header().data().len;
-
-            if let Some(x) = cast_ptr.keys[..len as usize]
-                .iter()
-                .copied()
-                .position(|x| x == key)
-            {
-                let this = cast_ptr.as_mut();
-                let res = std::mem::replace(&mut this.ptr[x], v.into_unknown());
-                return Some(res.assume_owned());
-            }
-
-            if !cast_ptr.is_full() {
-                cast_ptr.header_mut().data_mut().len += 1;
-                cast_ptr.as_mut().ptr[len as usize] = v.into_unknown();
-                cast_ptr.as_mut().keys[len as usize] = key;
-
-                return None;
-            }
-
-            let this = self
-                .as_unknown()
-                .cast_unchecked::<Node4<K, V>>()
-                .assume_owned();
-
-            let ptr = TypedNodePtr::<Unknown, Node4<K, V>>::alloc();
-            Node16::copy_from_node4(this, ptr.as_nonnull().cast().as_mut());
-            *self = ptr.erase_type().assume_ownership();
-
-            None
-        }
-    }
-}
-
-/*
-
-    pub fn get(&self, key: u8) -> Option<NodePtr<Borrow, K, V>> {
-        let idx = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)?;
-        unsafe { Some(self.ptr[idx].assume_borrow()) }
+impl<O: ValidPtr, K: Key + ?Sized, V, A: Aggregate<V>> TypedNodePtr<O, Node4<K, V, A>> {
+    /// Returns a pointer borrowing the branch at the given index.
+    unsafe fn child(&self, idx: usize) -> NodePtr<O, K, V, A> {
+        NodePtr::borrow_slot(addr_of_mut!((*self.as_ptr()).ptr[idx]))
     }
 
-    pub fn get_mut(&mut self, key: u8) -> Option<NodePtr<BorrowMut, K, V>> {
-        let idx = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)?;
-        unsafe { Some(self.ptr[idx].assume_borrow_mut()) }
-    }
-
-    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<Unknown, K, V>)> {
-        let (ptr_idx, next_key) = self
-            .keys
-            .iter()
-            .copied()
-            .enumerate()
-            .filter(|(_, x)| *x >= from)
-            .min_by_key(|(_, x)| *x)?;
-        Some((next_key, unsafe { self.ptr[ptr_idx] }))
+    pub fn get(&self, key: u8) -> Option<NodePtr<O, K, V, A>> {
+        let idx = self.find_key(key).ok()?;
+        unsafe { Some(self.child(idx)) }
     }
 
-    pub fn insert(&mut self, key: u8, ptr: OwnedNodePtr<K, V>) -> Option<OwnedNodePtr<K, V>> {
-        assert!(!self.is_full());
-        if let Some(x) = self.keys[..self.header.data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)
-        {
-            let res = std::mem::replace(&mut self.ptr[x], ptr.into_unknown());
-            return unsafe { Some(res.assume_owned()) };
-        }
-        let idx = self.header.data().len;
-        self.header.data_mut().len += 1;
-        self.ptr[idx as usize] = ptr.into_unknown();
-        self.keys[idx as usize] = key;
-        None
-    }
-
-    pub unsafe fn insert_grow(
-        this: &mut OwnedNodePtr<K, V>,
-        key: u8,
-        v: OwnedNodePtr<K, V>,
-    ) -> Option<OwnedNodePtr<K, V>> {
-        debug_assert!(this.is::<Self>());
-
-        if let Some(x) = this.cast_ref::<Self>().keys[..this.header().data().len as usize]
-            .iter()
-            .copied()
-            .position(|x| x == key)
-        {
-            let res = std::mem::replace(
-                &mut this.as_unknown().cast_mut_unchecked::<Self>().ptr[x],
-                v.into_unknown(),
-            );
-            return Some(res.assume_owned());
-        }
-
-        if this.cast_ref::<Self>().is_full() {
-            Self::grow(this)
-            ptr.as_mut().insert(key, v);
-            *this = ptr.into_boxed();
+    pub fn next_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        let idx = self.find_key(from).unwrap_or_else(|x| x);
+        if idx >= self.header.data().len as usize {
             return None;
         }
+        Some((self.keys[idx], unsafe { self.child(idx) }))
+    }
 
-        let idx = this.as_ref::<Self>().header.data().len;
-        this.as_mut::<Self>().header.data_mut().len += 1;
-        this.as_mut::<Self>().ptr[idx as usize].write(v.into_raw());
-        this.as_mut::<Self>().keys[idx as usize] = key;
-        None
+    pub fn prev_node(&self, from: u8) -> Option<(u8, NodePtr<O, K, V, A>)> {
+        let idx = match self.find_key(from) {
+            Ok(x) => x,
+            Err(0) => return None,
+            Err(x) => x - 1,
+        };
+        Some((self.keys[idx], unsafe { self.child(idx) }))
     }
+}
 
-    pub fn remove(&mut self, key: u8) -> Option<BoxedNode<K, V>> {
-        let len = self.header.data().len;
-        for i in 0..len {
-            if self.keys[i as usize] == key {
-                let ptr = unsafe { self.ptr.get_unchecked(i as usize).assume_init() };
+impl<O: MutablePtr, K: Key + ?Sized, V, A: Aggregate<V>> NodePtr<O, K, V, A> {
+    pub fn insert_grow_4(
+        &mut self,
+        key: u8,
+        v: OwnedNodePtr<K, V, A>,
+    ) -> Option<OwnedNodePtr<K, V, A>> {
+        unsafe {
+            debug_assert!(self.is::<Node4<K, V, A>>());
 
-                // swap remove
-                self.keys.swap(i as usize, (len - 1) as usize);
-                self.ptr.swap(i as usize, (len - 1) as usize);
+            let mut cast_ptr = self.cast_mut_unchecked::<Node4<K, V, A>>();
 
-                self.header.data_mut().len -= 1;
-                return unsafe { Some(BoxedNode::from_raw(ptr)) };
+            if !cast_ptr.is_full() || cast_ptr.find_key(key).is_ok() {
+                return cast_ptr.insert(key, v);
             }
-        }
-        None
-    }
-
-    pub unsafe fn fold(mut this: RawOwnedNode<Self>) -> RawBoxedNode<K, V> {
-        debug_assert_eq!(this.as_ref().header.data().len, 1);
-        // move out the child
-        let mut child = this.as_mut().ptr[0].assume_init();
 
-        // append the current prefix and the key of the child to the prefix of the child.
-        child
-            .header_mut()
-            .storage
-            .prepend_prefix(this.as_ref().header.storage.prefix(), this.as_ref().keys[0]);
-
-        child.header_mut().parent = this.header().parent;
+            let this = self
+                .as_unknown()
+                .cast_unchecked::<Node4<K, V, A>>()
+                .assume_owned();
 
-        this.drop_in_place();
+            let ptr = TypedNodePtr::<Unknown, Node16<K, V, A>>::alloc();
+            Node16::copy_from_node4(this, ptr.as_nonnull().cast().as_mut());
+            *self = ptr.erase_type().assume_ownership();
 
-        child
+            self.cast_mut_unchecked::<Node16<K, V, A>>().insert(key, v)
+        }
     }
 
-    unsafe fn grow(this: &mut NodePtr<Owned,K,V>) {
-        let old = this.cast_ref_unchecked::<Self>().as_unknown();
-        debug_assert!(old.is_full());
+    /// Fold a node4 with a single branch into its child, joining the prefixes.
+    pub unsafe fn fold_4(&mut self) {
+        let this = self.as_unknown().cast_unchecked::<Node4<K, V, A>>();
+        let node = &*this.as_ptr();
+        debug_assert!(node.should_shrink());
 
-        let new_node = Node16::new(key, range)
+        let mut child = node.ptr[0].assume_ownership::<Owned>();
+        child.prepend_prefix(node.header.prefix(), node.keys[0]);
 
-        let new_ptr = TypedNodePtr::<Unknown,Node16<K, V>>::alloc();
-        new_ptr.take_header(old);
+        // child moved out, only the header remains to be dropped.
+        std::ptr::drop_in_place(addr_of_mut!((*this.as_ptr()).header));
+        TypedNodePtr::dealloc(this);
 
-        let src_ptr = new_ptr.as_ptr().cast::<Self>();
-        std::ptr::copy(
-            addr_of_mut!((*src_ptr).keys).cast::<u8>(),
-            addr_of_mut!((*new_ptr.as_ptr()).keys).cast::<u8>(),
-            4,
-        );
-        new_ptr
+        *self = child.as_unknown().assume_ownership();
     }
 }
 
-impl<K: Key + ?Sized, V: fmt::Debug> Node4<K, V> {
-    pub fn display(&self, fmt: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+impl<K: Key + ?Sized, V: fmt::Debug, A> Node4<K, V, A> {
+    pub fn display(
+        &self,
+        fmt: &mut fmt::Formatter,
+        depth: usize,
+        options: &DisplayOptions,
+    ) -> fmt::Result {
         writeln!(
             fmt,
             "NODE4: len={},prefix={:?}",
-            self.header.storage.data().len,
-            self.header.storage.prefix()
+            self.header.data().len,
+            self.header.prefix()
         )?;
-        for i in 0..self.header.storage.data().len {
-            for _ in 0..depth {
-                fmt.write_str("  ")?;
-            }
-            write!(fmt, "[{}] = ", self.keys[i as usize])?;
-            unsafe {
-                self.ptr[i as usize]
-                    .assume_init_ref()
-                    .display(fmt, depth + 1)?;
-            }
-        }
-        Ok(())
+        let children = (0..self.header.data().len as usize).map(|i| {
+            (self.keys[i], unsafe {
+                NodePtr::borrow_slot(&self.ptr[i] as *const _ as *mut NodePtr<Unknown, K, V, A>)
+            })
+        });
+        display_children(fmt, depth, options, children)
     }
 }
 
-impl<K: Key + ?Sized, V> Drop for Node4<K, V> {
+impl<K: Key + ?Sized, V, A> Drop for Node4<K, V, A> {
     fn drop(&mut self) {
         for i in 0..self.header.data().len {
-            unsafe { self.ptr[i as usize].assume_init().drop_in_place() }
+            unsafe { NodePtr::free(self.ptr[i as usize]) }
         }
     }
 }
-*/
@@ -0,0 +1,132 @@
+//! Order statistics using the amount of leafs kept in every branch node.
+
+use super::{Borrow, KeyPath, LeafNode, NodePtr, Owned, RawArt};
+use crate::{augmented::Aggregate, key::Key};
+
+impl<K: Key + ?Sized, V, A: Aggregate<V>> RawArt<K, V, A> {
+    /// Returns the sum of the amount of leafs below the children of a branch node.
+    pub(super) fn child_leafs(node: NodePtr<Borrow<'_>, K, V, A>) -> usize {
+        let mut leafs = 0;
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((key, child)) = node.next_node(next as u8) else {
+                break;
+            };
+            leafs += child.leaf_count();
+            next = key as u16 + 1;
+        }
+        leafs
+    }
+
+    /// Add `delta` to the leaf count of every branch node on the path of the key.
+    ///
+    /// The walk stops at the first node whose prefix does not match the key, which is the node
+    /// split when the key is inserted.
+    pub(super) fn add_leafs<Q: KeyPath + ?Sized>(
+        mut node: &mut NodePtr<Owned, K, V, A>,
+        key: &Q,
+        delta: isize,
+    ) {
+        let mut depth = 0;
+        loop {
+            let prefix = node.prefix();
+            if node.is::<LeafNode<K, V, A>>() || Self::match_prefix(key, depth, prefix).is_some() {
+                return;
+            }
+            depth += prefix.len();
+
+            let header = node.header_mut();
+            header.leafs = header.leafs.checked_add_signed(delta).unwrap();
+            let Some(next) = node.child_mut(key.path_at(depth)) else {
+                return;
+            };
+            node = next;
+            depth += 1;
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    /// Returns the key bytes and value of the entry at the given position in key order.
+    pub fn nth(&self, mut idx: usize) -> Option<(Vec<u8>, &V)> {
+        let mut node = self.root()?;
+        if idx >= node.leaf_count() {
+            return None;
+        }
+
+        let mut key = Vec::new();
+        'outer: loop {
+            key.extend_from_slice(node.prefix());
+            if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+                return Some((key, leaf.into_value_ref()));
+            }
+
+            let mut next = 0u16;
+            while next <= u8::MAX as u16 {
+                let Some((branch, child)) = node.next_node(next as u8) else {
+                    break;
+                };
+                let leafs = child.leaf_count();
+                if idx < leafs {
+                    key.push(branch);
+                    node = child;
+                    continue 'outer;
+                }
+                idx -= leafs;
+                next = branch as u16 + 1;
+            }
+            unreachable!("leaf count of a node did not match its children");
+        }
+    }
+
+    /// Returns the amount of keys smaller than, or if `inclusive` equal to, the given key bytes.
+    pub fn rank(&self, key: &[u8], inclusive: bool) -> usize {
+        let Some(mut node) = self.root() else {
+            return 0;
+        };
+
+        let mut depth = 0;
+        let mut rank = 0;
+        loop {
+            let prefix = node.prefix();
+            let rest = &key[depth..];
+            if let Some((p, k)) = prefix.iter().zip(rest).find(|(p, k)| p != k) {
+                // The whole subtree is either smaller or larger than the key.
+                if p < k {
+                    rank += node.leaf_count();
+                }
+                return rank;
+            }
+            if rest.len() < prefix.len() {
+                // All keys in the subtree start with the key and are thus larger.
+                return rank;
+            }
+            depth += prefix.len();
+
+            if node.is::<LeafNode<K, V>>() {
+                if depth < key.len() || inclusive {
+                    rank += 1;
+                }
+                return rank;
+            }
+            if depth == key.len() {
+                return rank;
+            }
+
+            let branch = key[depth];
+            let mut next = 0u16;
+            while let Some((x, child)) = node.next_node(next as u8) {
+                if x >= branch {
+                    break;
+                }
+                rank += child.leaf_count();
+                next = x as u16 + 1;
+            }
+            let Some(child) = node.get(branch) else {
+                return rank;
+            };
+            node = child;
+            depth += 1;
+        }
+    }
+}
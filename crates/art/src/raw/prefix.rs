@@ -0,0 +1,46 @@
+//! Finding the longest stored key which is a prefix of a key.
+
+use super::{LeafNode, RawArt};
+use crate::key::Key;
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    /// Returns the longest stored key consisting of a prefix of `key` followed by `terminator`,
+    /// as the length of that prefix and the value of the key.
+    pub(crate) fn longest_prefix(&self, key: &[u8], terminator: u8) -> Option<(usize, &V)> {
+        let mut node = self.root()?;
+        let mut depth = 0;
+        let mut best = None;
+        loop {
+            let prefix = node.prefix();
+            let rest = &key[depth..];
+            if let Some(leaf) = node.cast::<LeafNode<K, V>>() {
+                if let Some((last, prefix)) = prefix.split_last() {
+                    if *last == terminator && rest.starts_with(prefix) {
+                        best = Some((depth + prefix.len(), leaf.into_value_ref()));
+                    }
+                }
+                return best;
+            }
+
+            if !rest.starts_with(prefix) {
+                return best;
+            }
+            depth += prefix.len();
+
+            // A key ending here is stored as a leaf below the terminator branch.
+            if let Some(leaf) = node
+                .get(terminator)
+                .and_then(|x| x.cast::<LeafNode<K, V>>())
+                .filter(|x| x.prefix().is_empty())
+            {
+                best = Some((depth, leaf.into_value_ref()));
+            }
+
+            let Some(next) = key.get(depth).and_then(|x| node.get(*x)) else {
+                return best;
+            };
+            node = next;
+            depth += 1;
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     alloc::Layout,
     marker::PhantomData,
+    mem,
     ops::{Deref, DerefMut},
     ptr::NonNull,
 };
@@ -9,9 +10,26 @@ use crate::key::Key;
 
 use super::nodes::{LeafNode, Node16, Node256, Node4, Node48, NodeHeader, NodeKind, NodeType};
 
+/// The bit set in a pointer to an inline leaf, see [`LeafNode::INLINE`].
+///
+/// Nodes and the allocations of inline leafs are aligned to at least two bytes so this bit is
+/// never set in the pointer to either.
+const INLINE_TAG: usize = 1;
+
+/// The bit set next to [`INLINE_TAG`] in a pointer to an inline leaf with a prefix, whose
+/// allocation contains the prefix after the value.
+///
+/// The allocations of inline leafs are aligned to at least four bytes so this bit is never set in
+/// the pointer to one.
+const PREFIX_TAG: usize = 2;
+
 /// # Safety
 /// These traits are only for internal uses and should be implemented outside of this crate.
-pub unsafe trait ValidPtr {}
+pub unsafe trait ValidPtr {
+    /// Whether the pointer owns the node, an owned pointer to an inline leaf contains the value of
+    /// the leaf while a borrowed pointer refers to the slot containing the owned pointer.
+    const OWNED: bool = false;
+}
 
 /// # Safety
 /// These traits are only for internal uses and should be implemented outside of this crate.
@@ -28,7 +46,9 @@ pub unsafe trait OwnedPtr: MutablePtr {}
 unsafe impl<'a> ValidPtr for Borrow<'a> {}
 unsafe impl<'a> ValidPtr for MutValue<'a> {}
 unsafe impl<'a> ValidPtr for BorrowMut<'a> {}
-unsafe impl ValidPtr for Owned {}
+unsafe impl ValidPtr for Owned {
+    const OWNED: bool = true;
+}
 
 unsafe impl<'a> MutValuePtr for MutValue<'a> {}
 unsafe impl<'a> MutValuePtr for BorrowMut<'a> {}
@@ -57,6 +77,7 @@ pub struct BorrowMut<'a>(PhantomData<&'a mut ()>);
 /// A marker type signifying that pointer has ownership of the node
 pub enum Owned {}
 
+#[repr(transparent)]
 pub struct TypedNodePtr<Owner, N: NodeType> {
     owner: PhantomData<Owner>,
     ptr: NonNull<N>,
@@ -100,12 +121,19 @@ impl<O, N: NodeType> TypedNodePtr<O, N> {
         TypedNodePtr::<Unknown, _>::unknown_from_nonnull(self.as_nonnull())
     }
 
-    pub fn erase_type(self) -> NodePtr<O, N::Key, N::Value> {
+    pub fn erase_type(self) -> NodePtr<O, N::Key, N::Value, N::Aggregate> {
         NodePtr {
             ptr: self.ptr.cast(),
             owner: PhantomData,
         }
     }
+
+    /// Returns whether the pointer is an inline leaf which has no node to point to.
+    pub fn is_inline(&self) -> bool {
+        N::KIND == NodeKind::Leaf
+            && LeafNode::<N::Key, N::Value, N::Aggregate>::INLINE
+            && self.ptr.as_ptr().addr() & INLINE_TAG != 0
+    }
 }
 
 impl<N: NodeType> TypedNodePtr<Unknown, N> {
@@ -172,30 +200,36 @@ impl<N: NodeType> TypedNodePtr<Unknown, N> {
 
 impl<O: ValidPtr, N: NodeType> TypedNodePtr<O, N> {
     pub fn header(&self) -> &NodeHeader<N::Key, N::Value> {
+        debug_assert!(!self.is_inline(), "an inline leaf has no header");
         unsafe { self.ptr.cast().as_ref() }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn as_ref(&self) -> &N {
+        debug_assert!(!self.is_inline(), "an inline leaf has no node");
         unsafe { self.ptr.as_ref() }
     }
 }
 
 impl<O: MutablePtr, N: NodeType> TypedNodePtr<O, N> {
     pub fn header_mut(&mut self) -> &mut NodeHeader<N::Key, N::Value> {
+        debug_assert!(!self.is_inline(), "an inline leaf has no header");
         unsafe { self.ptr.cast().as_mut() }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn as_mut(&mut self) -> &mut N {
+        debug_assert!(!self.is_inline(), "an inline leaf has no node");
         unsafe { self.ptr.as_mut() }
     }
 }
 
 impl<N: NodeType> TypedNodePtr<Owned, N> {
-    pub fn borrow(&self) -> TypedNodePtr<Borrow, N> {
+    pub fn borrow(&self) -> TypedNodePtr<Borrow<'_>, N> {
         unsafe { TypedNodePtr::from_nonnull(self.ptr) }
     }
 
-    pub fn borrow_mut(&self) -> TypedNodePtr<BorrowMut, N> {
+    pub fn borrow_mut(&self) -> TypedNodePtr<BorrowMut<'_>, N> {
         unsafe { TypedNodePtr::from_nonnull(self.ptr) }
     }
 }
@@ -217,7 +251,7 @@ impl<N: NodeType> OwnedTypedNodePtr<N> {
         res
     }
 
-    pub fn erase_type(self) -> OwnedNodePtr<N::Key, N::Value> {
+    pub fn erase_type(self) -> OwnedNodePtr<N::Key, N::Value, N::Aggregate> {
         unsafe { self.into_unknown().erase_type().assume_owned() }
     }
 }
@@ -239,6 +273,9 @@ impl<N: NodeType> Deref for OwnedTypedNodePtr<N> {
 impl<N: NodeType> Drop for OwnedTypedNodePtr<N> {
     fn drop(&mut self) {
         unsafe {
+            if self.is_inline() {
+                return NodePtr::free(self.ptr.as_unknown().erase_type());
+            }
             let p = self.ptr.as_unknown();
             p.drop_in_place();
             TypedNodePtr::<Unknown, N>::dealloc(p);
@@ -260,12 +297,17 @@ impl<O: MutablePtr, N: NodeType> DerefMut for TypedNodePtr<O, N> {
     }
 }
 
-pub struct NodePtr<Owner, K: Key + ?Sized, V> {
-    owner: PhantomData<Owner>,
+/// A pointer to a node of any kind.
+///
+/// `A` is the aggregate kept next to every child pointer in the branch nodes, see
+/// [`AugmentedArt`](crate::augmented::AugmentedArt). It is `()` for trees which don't keep one.
+#[repr(transparent)]
+pub struct NodePtr<Owner, K: Key + ?Sized, V, A = ()> {
+    owner: PhantomData<(Owner, A)>,
     pub(crate) ptr: NonNull<NodeHeader<K, V>>,
 }
 
-impl<O: Clone, K: Key + ?Sized, V> Clone for NodePtr<O, K, V> {
+impl<O: Clone, K: Key + ?Sized, V, A> Clone for NodePtr<O, K, V, A> {
     fn clone(&self) -> Self {
         NodePtr {
             owner: PhantomData,
@@ -274,9 +316,9 @@ impl<O: Clone, K: Key + ?Sized, V> Clone for NodePtr<O, K, V> {
     }
 }
 
-impl<O: Copy, K: Key + ?Sized, V> Copy for NodePtr<O, K, V> {}
+impl<O: Copy, K: Key + ?Sized, V, A> Copy for NodePtr<O, K, V, A> {}
 
-impl<O, K: Key + ?Sized, V> NodePtr<O, K, V> {
+impl<O, K: Key + ?Sized, V, A> NodePtr<O, K, V, A> {
     pub fn as_ptr(&self) -> *mut NodeHeader<K, V> {
         self.ptr.as_ptr()
     }
@@ -285,7 +327,7 @@ impl<O, K: Key + ?Sized, V> NodePtr<O, K, V> {
         self.ptr
     }
 
-    pub fn as_unknown(&self) -> NodePtr<Unknown, K, V> {
+    pub fn as_unknown(&self) -> NodePtr<Unknown, K, V, A> {
         NodePtr {
             owner: PhantomData,
             ptr: self.ptr,
@@ -294,7 +336,7 @@ impl<O, K: Key + ?Sized, V> NodePtr<O, K, V> {
 
     pub unsafe fn cast_unchecked<N>(self) -> TypedNodePtr<O, N>
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
         TypedNodePtr {
             owner: PhantomData,
@@ -302,28 +344,122 @@ impl<O, K: Key + ?Sized, V> NodePtr<O, K, V> {
         }
     }
 
-    pub unsafe fn cast_ref_unchecked<N>(&self) -> TypedNodePtr<Borrow, N>
+    pub unsafe fn cast_ref_unchecked<N>(&self) -> TypedNodePtr<Borrow<'_>, N>
     where
-        N: NodeType<Key = K, Value = V>,
+        O: ValidPtr,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
-        TypedNodePtr {
+        Self::reborrow(self as *const Self as *mut Self).cast_unchecked()
+    }
+
+    pub unsafe fn cast_mut_unchecked<N>(&mut self) -> TypedNodePtr<BorrowMut<'_>, N>
+    where
+        O: ValidPtr,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
+    {
+        Self::reborrow(self as *mut Self).cast_unchecked()
+    }
+
+    /// Returns whether the pointer is an inline leaf, see [`LeafNode::INLINE`].
+    pub fn is_inline(&self) -> bool {
+        LeafNode::<K, V, A>::INLINE && self.ptr.as_ptr().addr() & INLINE_TAG != 0
+    }
+
+    /// Returns whether an inline leaf has a prefix, see [`PREFIX_TAG`].
+    fn has_inline_prefix(&self) -> bool {
+        debug_assert!(self.is_inline());
+        self.ptr.as_ptr().addr() & PREFIX_TAG != 0
+    }
+
+    /// Returns whether the value of an inline leaf is stored in the slot, see
+    /// [`LeafNode::IN_SLOT`].
+    fn in_slot(&self) -> bool {
+        LeafNode::<K, V, A>::IN_SLOT && !self.has_inline_prefix()
+    }
+
+    /// Returns the allocation of an inline leaf which isn't stored in the slot.
+    fn inline_alloc(&self) -> *mut u8 {
+        debug_assert!(!self.in_slot());
+        self.ptr
+            .as_ptr()
+            .map_addr(|x| x & !(INLINE_TAG | PREFIX_TAG))
+            .cast()
+    }
+
+    /// Returns the prefix of an inline leaf.
+    ///
+    /// # Safety
+    /// The pointer must be an inline leaf.
+    pub unsafe fn inline_prefix(&self) -> &[u8] {
+        if !self.has_inline_prefix() {
+            return &[];
+        }
+        let alloc = self.inline_alloc();
+        let (len, offset) = NodePtr::<Unknown, K, V, A>::read_prefix_len(alloc);
+        std::slice::from_raw_parts(alloc.add(offset), len)
+    }
+
+    /// Returns a pointer borrowing the node owned by the pointer in the slot.
+    ///
+    /// An inline leaf with its value in the slot lives in the slot itself, the returned pointer
+    /// then refers to the slot.
+    ///
+    /// # Safety
+    /// The slot must contain a pointer which owns its node, or in the case of an inline leaf its
+    /// value.
+    pub unsafe fn borrow_slot<B>(slot: *mut Self) -> NodePtr<B, K, V, A> {
+        let ptr = if (*slot).is_inline() && (*slot).in_slot() {
+            NonNull::new_unchecked(slot.cast::<NodeHeader<K, V>>().map_addr(|x| x | INLINE_TAG))
+        } else {
+            (*slot).ptr
+        };
+        NodePtr {
             owner: PhantomData,
-            ptr: self.ptr.cast(),
+            ptr,
         }
     }
 
-    pub unsafe fn cast_mut_unchecked<N>(&mut self) -> TypedNodePtr<BorrowMut, N>
+    /// Returns a pointer with a different ownership to the node of the pointer.
+    ///
+    /// # Safety
+    /// The new ownership must not outlive the ownership of the pointer.
+    pub unsafe fn reborrow<B>(this: *mut Self) -> NodePtr<B, K, V, A>
     where
-        N: NodeType<Key = K, Value = V>,
+        O: ValidPtr,
     {
-        TypedNodePtr {
-            owner: PhantomData,
-            ptr: self.ptr.cast(),
+        if O::OWNED {
+            Self::borrow_slot(this)
+        } else {
+            (*this).as_unknown().assume_ownership()
+        }
+    }
+
+    /// Returns a pointer to the value of an inline leaf.
+    ///
+    /// `owned` is whether the pointer owns the leaf, in which case a value stored in the slot is
+    /// stored in the pointer `this` points to, otherwise the pointer refers to the slot which
+    /// contains it. A value which isn't stored in the slot is pointed to by either.
+    ///
+    /// # Safety
+    /// The pointer must be an inline leaf.
+    pub unsafe fn inline_value(this: *mut Self, owned: bool) -> *mut V {
+        if !(*this).in_slot() {
+            return (*this).inline_alloc().cast();
         }
+        let slot = if owned {
+            this.cast::<u8>()
+        } else {
+            (*this)
+                .ptr
+                .as_ptr()
+                .map_addr(|x| x & !INLINE_TAG)
+                .cast::<u8>()
+        };
+        slot.add(LeafNode::<K, V, A>::VALUE_OFFSET).cast()
     }
 }
 
-impl<K: Key + ?Sized, V> NodePtr<Unknown, K, V> {
+impl<K: Key + ?Sized, V, A> NodePtr<Unknown, K, V, A> {
     pub fn dangling() -> Self {
         NodePtr {
             owner: PhantomData,
@@ -331,25 +467,153 @@ impl<K: Key + ?Sized, V> NodePtr<Unknown, K, V> {
         }
     }
 
+    /// The layout of the allocation of an inline leaf which isn't stored in the slot, with a
+    /// prefix of the given length.
+    ///
+    /// The value comes first. A leaf with a prefix follows it with the length of the prefix and
+    /// the prefix itself. The length takes a single byte if it is shorter than `u8::MAX`, a longer
+    /// length is marked by `u8::MAX` followed by the length as an unaligned `u32`.
+    fn inline_layout(len: usize) -> Layout {
+        let value = Layout::new::<V>().align_to(4).unwrap();
+        if len == 0 {
+            return Layout::from_size_align(value.size().max(1), value.align()).unwrap();
+        }
+        let extra = if len < u8::MAX as usize { 1 } else { 5 };
+        let (layout, _) = value
+            .extend(Layout::array::<u8>(extra + len).unwrap())
+            .unwrap();
+        layout.pad_to_align()
+    }
+
+    /// Returns the length of the prefix of an inline leaf with a prefix, and the offset of the
+    /// prefix in its allocation, see [`Self::inline_layout`].
+    unsafe fn read_prefix_len(alloc: *const u8) -> (usize, usize) {
+        let offset = mem::size_of::<V>();
+        match alloc.add(offset).read() {
+            u8::MAX => {
+                let len = alloc.add(offset + 1).cast::<u32>().read_unaligned();
+                (len as usize, offset + 5)
+            }
+            len => (len as usize, offset + 1),
+        }
+    }
+
+    /// Create an owned pointer to an inline leaf containing the value, with a prefix of the given
+    /// length written by `init`.
+    ///
+    /// # Safety
+    /// Leafs with values of type `V` must be stored inline, see [`LeafNode::INLINE`].
+    pub unsafe fn inline_with(
+        len: usize,
+        value: V,
+        init: impl FnOnce(&mut [u8]),
+    ) -> OwnedNodePtr<K, V, A> {
+        if LeafNode::<K, V, A>::IN_SLOT && len == 0 {
+            let mut ptr = NodePtr::<Unknown, K, V, A> {
+                owner: PhantomData,
+                ptr: NonNull::new_unchecked(std::ptr::without_provenance_mut(INLINE_TAG)),
+            };
+            Self::inline_value(&mut ptr, true).write(value);
+            return ptr.assume_owned();
+        }
+
+        assert!(len <= u32::MAX as usize, "key too long");
+        let layout = Self::inline_layout(len);
+        let alloc = std::alloc::alloc(layout);
+        if alloc.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        alloc.cast::<V>().write(value);
+        let mut tag = INLINE_TAG;
+        if len > 0 {
+            let at = alloc.add(mem::size_of::<V>());
+            let offset = if len < u8::MAX as usize {
+                at.write(len as u8);
+                1
+            } else {
+                at.write(u8::MAX);
+                at.add(1).cast::<u32>().write_unaligned(len as u32);
+                5
+            };
+            init(std::slice::from_raw_parts_mut(at.add(offset), len));
+            tag |= PREFIX_TAG;
+        }
+        NodePtr::<Unknown, K, V, A> {
+            owner: PhantomData,
+            ptr: NonNull::new_unchecked(alloc.map_addr(|x| x | tag).cast()),
+        }
+        .assume_owned()
+    }
+
+    /// Create an owned pointer to an inline leaf containing the value with the given prefix.
+    ///
+    /// # Safety
+    /// Leafs with values of type `V` must be stored inline, see [`LeafNode::INLINE`].
+    pub unsafe fn inline(prefix: &[u8], value: V) -> OwnedNodePtr<K, V, A> {
+        Self::inline_with(prefix.len(), value, |dst| dst.copy_from_slice(prefix))
+    }
+
+    /// Move the value of an owned inline leaf into a new inline leaf with a prefix of the given
+    /// length, freeing the old leaf.
+    ///
+    /// `init` is called with the prefix of the old leaf and the prefix of the new leaf to write.
+    ///
+    /// # Safety
+    /// The pointer must be an inline leaf which owns its value.
+    pub unsafe fn replace_inline_prefix(
+        mut ptr: Self,
+        len: usize,
+        init: impl FnOnce(&[u8], &mut [u8]),
+    ) -> OwnedNodePtr<K, V, A> {
+        let value = Self::inline_value(&mut ptr, true).read();
+        let new = Self::inline_with(len, value, |dst| init(ptr.inline_prefix(), dst));
+        Self::dealloc_inline(ptr);
+        new
+    }
+
+    /// Free an owned inline leaf whose value was moved out.
+    unsafe fn dealloc_inline(ptr: Self) {
+        if !ptr.in_slot() {
+            let layout = Self::inline_layout(ptr.inline_prefix().len());
+            std::alloc::dealloc(ptr.inline_alloc(), layout);
+        }
+    }
+
+    /// Move the value out of an owned inline leaf, freeing the leaf.
+    ///
+    /// # Safety
+    /// The pointer must be an inline leaf which owns its value.
+    pub unsafe fn take_inline(mut ptr: Self) -> V {
+        let res = Self::inline_value(&mut ptr, true).read();
+        Self::dealloc_inline(ptr);
+        res
+    }
+
     pub unsafe fn free(ptr: Self) {
+        if ptr.is_inline() {
+            drop(Self::take_inline(ptr));
+            return;
+        }
         match ptr.assume_ownership::<Borrow>().header().kind() {
             NodeKind::Leaf => {
-                TypedNodePtr::free(ptr.cast_unchecked::<LeafNode<K, V>>().as_unknown())
+                TypedNodePtr::free(ptr.cast_unchecked::<LeafNode<K, V, A>>().as_unknown())
+            }
+            NodeKind::Node4 => {
+                TypedNodePtr::free(ptr.cast_unchecked::<Node4<K, V, A>>().as_unknown())
             }
-            NodeKind::Node4 => TypedNodePtr::free(ptr.cast_unchecked::<Node4<K, V>>().as_unknown()),
             NodeKind::Node16 => {
-                TypedNodePtr::free(ptr.cast_unchecked::<Node16<K, V>>().as_unknown())
+                TypedNodePtr::free(ptr.cast_unchecked::<Node16<K, V, A>>().as_unknown())
             }
             NodeKind::Node48 => {
-                TypedNodePtr::free(ptr.cast_unchecked::<Node48<K, V>>().as_unknown())
+                TypedNodePtr::free(ptr.cast_unchecked::<Node48<K, V, A>>().as_unknown())
             }
             NodeKind::Node256 => {
-                TypedNodePtr::free(ptr.cast_unchecked::<Node256<K, V>>().as_unknown())
+                TypedNodePtr::free(ptr.cast_unchecked::<Node256<K, V, A>>().as_unknown())
             }
         }
     }
 
-    pub unsafe fn assume_owned(self) -> OwnedNodePtr<K, V> {
+    pub unsafe fn assume_owned(self) -> OwnedNodePtr<K, V, A> {
         OwnedNodePtr {
             ptr: NodePtr {
                 owner: PhantomData,
@@ -358,7 +622,7 @@ impl<K: Key + ?Sized, V> NodePtr<Unknown, K, V> {
         }
     }
 
-    pub unsafe fn assume_ownership<O>(self) -> NodePtr<O, K, V> {
+    pub unsafe fn assume_ownership<O>(self) -> NodePtr<O, K, V, A> {
         NodePtr {
             owner: PhantomData,
             ptr: self.ptr,
@@ -368,58 +632,84 @@ impl<K: Key + ?Sized, V> NodePtr<Unknown, K, V> {
     pub unsafe fn take_header(self) -> NodeHeader<K, V> {
         self.ptr.as_ptr().read()
     }
+
+    /// Reinterpret a reference to a child slot as a reference to an owned pointer.
+    ///
+    /// # Safety
+    /// The slot must contain a valid pointer which is owned by the node containing the slot.
+    pub unsafe fn assume_owned_mut(&mut self) -> &mut NodePtr<Owned, K, V, A> {
+        &mut *(self as *mut Self).cast::<NodePtr<Owned, K, V, A>>()
+    }
 }
 
-impl<O: ValidPtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
+impl<O: ValidPtr, K: Key + ?Sized, V, A> NodePtr<O, K, V, A> {
     pub fn header(&self) -> &NodeHeader<K, V> {
+        debug_assert!(!self.is_inline(), "an inline leaf has no header");
         unsafe { self.ptr.as_ref() }
     }
 
+    pub fn kind(&self) -> NodeKind {
+        if self.is_inline() {
+            return NodeKind::Leaf;
+        }
+        self.header().kind()
+    }
+
+    /// Returns the prefix of the node.
+    pub fn prefix(&self) -> &[u8] {
+        if self.is_inline() {
+            return unsafe { self.inline_prefix() };
+        }
+        self.header().prefix()
+    }
+
     pub fn is<N>(&self) -> bool
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
-        self.header().is::<N>()
+        self.kind() == N::KIND
     }
 
     pub fn cast<N>(self) -> Option<TypedNodePtr<O, N>>
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
         self.is::<N>().then(|| unsafe { self.cast_unchecked() })
     }
 
-    pub fn cast_ref<N>(&self) -> Option<TypedNodePtr<Borrow, N>>
+    pub fn cast_ref<N>(&self) -> Option<TypedNodePtr<Borrow<'_>, N>>
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
         self.is::<N>().then(|| unsafe { self.cast_ref_unchecked() })
     }
 }
 
-impl<O: MutablePtr, K: Key + ?Sized, V> NodePtr<O, K, V> {
+impl<O: MutablePtr, K: Key + ?Sized, V, A> NodePtr<O, K, V, A> {
     pub fn header_mut(&mut self) -> &mut NodeHeader<K, V> {
+        debug_assert!(!self.is_inline(), "an inline leaf has no header");
         unsafe { self.ptr.as_mut() }
     }
 
-    pub fn as_borrow(&self) -> NodePtr<Borrow, K, V> {
-        unsafe { self.as_unknown().assume_ownership() }
+    pub fn as_borrow(&self) -> NodePtr<Borrow<'_>, K, V, A> {
+        unsafe { Self::reborrow(self as *const Self as *mut Self) }
     }
 
-    pub fn cast_mut<N>(&mut self) -> Option<TypedNodePtr<BorrowMut, N>>
+    pub fn cast_mut<N>(&mut self) -> Option<TypedNodePtr<BorrowMut<'_>, N>>
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
         self.is::<N>().then(|| unsafe { self.cast_mut_unchecked() })
     }
 }
 
-pub struct OwnedNodePtr<K: Key + ?Sized, V> {
-    ptr: NodePtr<Owned, K, V>,
+#[repr(transparent)]
+pub struct OwnedNodePtr<K: Key + ?Sized, V, A = ()> {
+    ptr: NodePtr<Owned, K, V, A>,
 }
 
-impl<K: Key + ?Sized, V> OwnedNodePtr<K, V> {
-    pub fn into_unknown(self) -> NodePtr<Unknown, K, V> {
+impl<K: Key + ?Sized, V, A> OwnedNodePtr<K, V, A> {
+    pub fn into_unknown(self) -> NodePtr<Unknown, K, V, A> {
         let res = self.ptr.as_unknown();
         std::mem::forget(self);
         res
@@ -427,29 +717,29 @@ impl<K: Key + ?Sized, V> OwnedNodePtr<K, V> {
 
     pub fn cast_owned<N>(self) -> Option<OwnedTypedNodePtr<N>>
     where
-        N: NodeType<Key = K, Value = V>,
+        N: NodeType<Key = K, Value = V, Aggregate = A>,
     {
         self.is::<N>()
             .then(|| unsafe { self.into_unknown().cast_unchecked().assume_owned() })
     }
 }
 
-impl<K: Key + ?Sized, V> DerefMut for OwnedNodePtr<K, V> {
+impl<K: Key + ?Sized, V, A> DerefMut for OwnedNodePtr<K, V, A> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.ptr
     }
 }
 
-impl<K: Key + ?Sized, V> Deref for OwnedNodePtr<K, V> {
-    type Target = NodePtr<Owned, K, V>;
+impl<K: Key + ?Sized, V, A> Deref for OwnedNodePtr<K, V, A> {
+    type Target = NodePtr<Owned, K, V, A>;
 
     fn deref(&self) -> &Self::Target {
         &self.ptr
     }
 }
 
-impl<K: Key + ?Sized, V> Drop for OwnedNodePtr<K, V> {
+impl<K: Key + ?Sized, V, A> Drop for OwnedNodePtr<K, V, A> {
     fn drop(&mut self) {
-        unsafe { NodePtr::<Unknown, K, V>::free(self.ptr.as_unknown()) }
+        unsafe { NodePtr::<Unknown, K, V, A>::free(self.ptr.as_unknown()) }
     }
 }
@@ -0,0 +1,96 @@
+//! Removing every entry which does not satisfy a predicate in a single walk over the tree.
+
+use super::{LeafNode, NodePtr, Owned, OwnedNodePtr, RawArt};
+use crate::key::Key;
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    /// Remove the entries for which `f` returns false, returns the amount of entries removed.
+    ///
+    /// `f` is called in key order with the key bytes and value of every entry.
+    pub(crate) fn retain<F>(&mut self, f: &mut F) -> usize
+    where
+        F: FnMut(&[u8], &mut V) -> bool,
+    {
+        let mut removed = 0;
+        let Some(root) = self.root.as_mut() else {
+            return 0;
+        };
+        if !Self::retain_node(root, &mut Vec::new(), f, &mut removed) {
+            // The root was already freed.
+            self.root.take().map(OwnedNodePtr::into_unknown);
+        }
+        removed
+    }
+
+    /// Retain the entries below a node, returns false if no entries were left in which case the
+    /// node was freed.
+    ///
+    /// Branches are only removed from a node after all of its children are visited, the node is
+    /// then replaced once by a node of the size which fits the remaining branches.
+    fn retain_node<F>(
+        node: &mut NodePtr<Owned, K, V>,
+        key: &mut Vec<u8>,
+        f: &mut F,
+        removed: &mut usize,
+    ) -> bool
+    where
+        F: FnMut(&[u8], &mut V) -> bool,
+    {
+        let len = key.len();
+        key.extend_from_slice(node.prefix());
+
+        if let Some(mut leaf) = node.cast_mut::<LeafNode<K, V>>() {
+            let keep = f(key, leaf.as_value_mut());
+            key.truncate(len);
+            if !keep {
+                *removed += 1;
+                unsafe { NodePtr::free(node.as_unknown()) };
+            }
+            return keep;
+        }
+
+        let mut branches = Vec::new();
+        let mut dropped = Vec::new();
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((branch, _)) = node.as_borrow().next_node(next as u8) else {
+                break;
+            };
+            key.push(branch);
+            if Self::retain_node(node.child_mut(branch).unwrap(), key, f, removed) {
+                branches.push(branch);
+            } else {
+                dropped.push(branch);
+            }
+            key.pop();
+            next = branch as u16 + 1;
+        }
+        key.truncate(len);
+
+        if dropped.is_empty() {
+            #[cfg(feature = "order-statistics")]
+            {
+                node.header_mut().leafs = Self::child_leafs(node.as_borrow());
+            }
+            return true;
+        }
+
+        // Move the remaining children out of the node, the dropped children were already freed.
+        let children: Vec<_> = branches
+            .into_iter()
+            .map(|x| {
+                (x, unsafe {
+                    node.child_mut(x).unwrap().as_unknown().assume_owned()
+                })
+            })
+            .collect();
+        let header = unsafe { Self::dealloc_branch(node.as_unknown()) };
+        match Self::from_children(header.prefix(), children) {
+            Some(new) => {
+                *node = unsafe { new.into_unknown().assume_ownership() };
+                true
+            }
+            None => false,
+        }
+    }
+}
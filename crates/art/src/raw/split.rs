@@ -0,0 +1,273 @@
+//! Cutting a tree in two along the path to a key and joining trees with disjoint key ranges.
+//!
+//! Only the nodes on the path to the key where the trees are cut or joined are changed, the
+//! subtrees on either side of the path are moved as a whole.
+
+use super::{Borrow, LeafNode, Node4, NodeHeader, NodePtr, Owned, OwnedNodePtr, RawArt};
+use crate::key::Key;
+use std::{cmp::Ordering, collections::VecDeque};
+
+/// The parts of a node on either side of the key it is split at.
+type Halves<K, V> = (Option<OwnedNodePtr<K, V>>, Option<OwnedNodePtr<K, V>>);
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    /// Returns the amount of leafs below a node.
+    fn leaf_count(node: NodePtr<Borrow<'_>, K, V>) -> usize {
+        #[cfg(feature = "order-statistics")]
+        return node.leaf_count();
+
+        // Without the counts kept in the branch nodes only the branch nodes have to be visited,
+        // the leafs are counted from the kinds of the children.
+        #[cfg(not(feature = "order-statistics"))]
+        {
+            if node.is::<LeafNode<K, V>>() {
+                return 1;
+            }
+            let mut count = 0;
+            let mut stack = vec![node];
+            while let Some(node) = stack.pop() {
+                let mut next = 0u16;
+                while next <= u8::MAX as u16 {
+                    let Some((key, child)) = node.next_node(next as u8) else {
+                        break;
+                    };
+                    if child.is::<LeafNode<K, V>>() {
+                        count += 1;
+                    } else {
+                        stack.push(child);
+                    }
+                    next = key as u16 + 1;
+                }
+            }
+            count
+        }
+    }
+
+    /// Move the entries with key bytes larger than, or if `inclusive` equal to, `key` into a new
+    /// tree, returns the new tree and the amount of entries moved.
+    pub(crate) fn split_off(&mut self, key: &[u8], inclusive: bool) -> (Self, usize) {
+        let Some(root) = self.root.take() else {
+            return (Self::new(), 0);
+        };
+        let mut moved = 0;
+        let (left, right) = Self::split_node(root, key, 0, inclusive, &mut moved);
+        self.root = left;
+        (RawArt { root: right }, moved)
+    }
+
+    fn split_node(
+        node: OwnedNodePtr<K, V>,
+        key: &[u8],
+        mut depth: usize,
+        inclusive: bool,
+        moved: &mut usize,
+    ) -> Halves<K, V> {
+        let mut move_right = |node: OwnedNodePtr<K, V>| {
+            *moved += Self::leaf_count(node.as_borrow());
+            (None, Some(node))
+        };
+        let prefix = node.prefix();
+        let rest = &key[depth..];
+        let len = prefix.len().min(rest.len());
+        match prefix[..len].cmp(&rest[..len]) {
+            // Every key in the node is smaller than the key.
+            Ordering::Less => return (Some(node), None),
+            // Every key in the node is larger than the key.
+            Ordering::Greater => return move_right(node),
+            Ordering::Equal if len < prefix.len() => return move_right(node),
+            Ordering::Equal => {}
+        }
+        depth += len;
+
+        if node.is::<LeafNode<K, V>>() {
+            return if depth == key.len() && inclusive {
+                move_right(node)
+            } else {
+                (Some(node), None)
+            };
+        }
+        if depth == key.len() {
+            return move_right(node);
+        }
+
+        let branch = key[depth];
+        if node.as_borrow().next_node(branch).is_none() {
+            return (Some(node), None);
+        }
+        if node.as_borrow().prev_node(branch).is_none() {
+            return move_right(node);
+        }
+
+        let (header, children) = Self::into_children(node);
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        for (x, child) in children {
+            match x.cmp(&branch) {
+                Ordering::Less => left.push((x, child)),
+                Ordering::Greater => {
+                    *moved += Self::leaf_count(child.as_borrow());
+                    right.push((x, child));
+                }
+                Ordering::Equal => {
+                    let (l, r) = Self::split_node(child, key, depth + 1, inclusive, moved);
+                    left.extend(l.map(|c| (x, c)));
+                    right.extend(r.map(|c| (x, c)));
+                }
+            }
+        }
+        let prefix = header.prefix();
+        (
+            Self::from_children(prefix, left),
+            Self::from_children(prefix, right),
+        )
+    }
+
+    /// Move every entry of `other` into this tree, the keys of `other` must all be larger than
+    /// the keys in this tree.
+    pub(crate) fn append(&mut self, other: &mut Self) {
+        let Some(right) = other.root.take() else {
+            return;
+        };
+        match self.root.as_mut() {
+            Some(left) => Self::join(left, right),
+            None => self.root = Some(right),
+        }
+    }
+
+    /// Join a node containing larger keys into a node.
+    fn join(left: &mut NodePtr<Owned, K, V>, mut right: OwnedNodePtr<K, V>) {
+        let (lp, rp) = (left.prefix(), right.prefix());
+        let common = lp.iter().zip(rp).take_while(|(a, b)| a == b).count();
+
+        if common < lp.len() && common < rp.len() {
+            // The nodes differ within their prefixes, they become the branches of a new node.
+            let prefix = NodeHeader::from_prefix::<Node4<K, V>>(&lp[..common]);
+            let (lb, rb) = (lp[common], rp[common]);
+            let mut old = unsafe { left.as_unknown().assume_owned() };
+            old.drop_prefix(common + 1);
+            right.drop_prefix(common + 1);
+            let new = Self::new_branch(prefix, 2, [(lb, old), (rb, right)].into_iter());
+            *left = unsafe { new.into_unknown().assume_ownership() };
+            return;
+        }
+
+        // A node whose prefix ends within the prefix of the other node must branch there.
+        assert!(
+            !(common == lp.len() && left.is::<LeafNode<K, V>>()
+                || common == rp.len() && right.is::<LeafNode<K, V>>()),
+            "{}",
+            Self::PREFIX_PANIC
+        );
+        match lp.len().cmp(&rp.len()) {
+            // The right node continues below the last branch of the left node.
+            Ordering::Less => {
+                let branch = rp[common];
+                right.drop_prefix(common + 1);
+                Self::join_branch(left, branch, right);
+            }
+            // The left node continues below the first branch of the right node.
+            Ordering::Greater => {
+                let branch = lp[common];
+                let mut old = unsafe { left.as_unknown().assume_owned() };
+                old.drop_prefix(common + 1);
+                match right.child_mut(branch) {
+                    Some(slot) => {
+                        let child = unsafe { slot.as_unknown().assume_owned() };
+                        *slot = unsafe { old.into_unknown().assume_ownership() };
+                        Self::join(slot, child);
+                    }
+                    None => {
+                        right.insert_grow(branch, old);
+                    }
+                }
+                #[cfg(feature = "order-statistics")]
+                {
+                    right.header_mut().leafs = Self::child_leafs(right.as_borrow());
+                }
+                *left = unsafe { right.into_unknown().assume_ownership() };
+            }
+            Ordering::Equal => {
+                let (_, children) = Self::into_children(right);
+                for (branch, child) in children {
+                    Self::join_branch(left, branch, child);
+                }
+            }
+        }
+    }
+
+    /// Join a node into the branch of a node.
+    fn join_branch(node: &mut NodePtr<Owned, K, V>, branch: u8, child: OwnedNodePtr<K, V>) {
+        match node.child_mut(branch) {
+            Some(slot) => Self::join(slot, child),
+            None => {
+                node.insert_grow(branch, child);
+            }
+        }
+        #[cfg(feature = "order-statistics")]
+        {
+            node.header_mut().leafs = Self::child_leafs(node.as_borrow());
+        }
+    }
+}
+
+/// An iterator taking a tree apart into its key bytes and values in key order.
+///
+/// Keeps the subtrees which were not taken apart yet in key order together with the key bytes
+/// leading to them. Only the subtree at the front or back is taken apart, so every node is freed
+/// as soon as its branches are moved out.
+pub(crate) struct RawDrain<K: Key + ?Sized, V> {
+    nodes: VecDeque<(Vec<u8>, OwnedNodePtr<K, V>)>,
+}
+
+impl<K: Key + ?Sized, V> RawArt<K, V> {
+    pub(crate) fn into_drain(mut self) -> RawDrain<K, V> {
+        RawDrain {
+            nodes: self
+                .root
+                .take()
+                .map(|x| (Vec::new(), x))
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> RawDrain<K, V> {
+    /// Take a subtree apart until it is a leaf, the other branches are kept in key order at the
+    /// front or, if not `front`, the back of the subtrees.
+    fn take(&mut self, front: bool) -> Option<(Vec<u8>, V)> {
+        loop {
+            let (mut key, node) = match front {
+                true => self.nodes.pop_front()?,
+                false => self.nodes.pop_back()?,
+            };
+            key.extend_from_slice(node.prefix());
+            if node.is::<LeafNode<K, V>>() {
+                let leaf = node.cast_owned::<LeafNode<K, V>>().unwrap();
+                return Some((key, leaf.into_value()));
+            }
+
+            let (_, children) = RawArt::into_children(node);
+            let children = children.into_iter().map(|(branch, child)| {
+                let mut key = key.clone();
+                key.push(branch);
+                (key, child)
+            });
+            if front {
+                for x in children.rev() {
+                    self.nodes.push_front(x);
+                }
+            } else {
+                self.nodes.extend(children);
+            }
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(Vec<u8>, V)> {
+        self.take(true)
+    }
+
+    pub fn next_back(&mut self) -> Option<(Vec<u8>, V)> {
+        self.take(false)
+    }
+}
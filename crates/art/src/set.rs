@@ -0,0 +1,58 @@
+//! Set operations on trees.
+//!
+//! The operations walk both trees together instead of inserting the entries of one tree into the
+//! other. Subtrees which are only present in one of the trees are kept or dropped as a whole, so
+//! disjoint parts of the trees are never searched key by key.
+//!
+//! All operations return a new tree and take a closure which decides the value of keys present in
+//! both trees, it is called with the value from `self` first.
+
+use crate::{key::Key, raw::Merge, Art};
+
+impl<K: Key + ?Sized, V: Clone> Art<K, V> {
+    /// Returns a tree with the entries of both trees, the values of keys present in both trees
+    /// are merged with `f`.
+    pub fn union_with<F>(&self, other: &Self, mut f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> V,
+    {
+        self.merge(other, true, true, |a, b| Some(f(a, b)))
+    }
+
+    /// Returns a tree with the keys present in both trees, their values are merged with `f`.
+    pub fn intersection<F>(&self, other: &Self, mut f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> V,
+    {
+        self.merge(other, false, false, |a, b| Some(f(a, b)))
+    }
+
+    /// Returns a tree with the entries of `self` which are not present in `other`.
+    ///
+    /// Keys present in both trees are kept with the value returned by `f`, if it returns one.
+    pub fn difference<F>(&self, other: &Self, f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        self.merge(other, true, false, f)
+    }
+
+    /// Returns a tree with the entries which are present in only one of the trees.
+    ///
+    /// Keys present in both trees are kept with the value returned by `f`, if it returns one.
+    pub fn symmetric_difference<F>(&self, other: &Self, f: F) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        self.merge(other, true, true, f)
+    }
+
+    fn merge<F>(&self, other: &Self, left: bool, right: bool, both: F) -> Self
+    where
+        F: FnMut(&V, &V) -> Option<V>,
+    {
+        let mut merge = Merge { left, right, both };
+        let (tree, len) = self.tree.merge(&other.tree, &mut merge);
+        Art { tree, len }
+    }
+}
@@ -0,0 +1,123 @@
+//! Splitting a tree in two and joining trees with disjoint key ranges.
+//!
+//! Only the nodes on the path to the key where a tree is split or joined are rebuilt, the
+//! subtrees on either side of the path move as a whole.
+
+use crate::{
+    cursor::RawCursor,
+    iter::key_bytes,
+    key::{BorrowedKey, Key},
+    raw::{RawArt, RawDrain},
+    Art,
+};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+};
+
+impl<K: Key + ?Sized, V> Art<K, V> {
+    /// Move every entry with a key larger or equal to the given key into a new tree.
+    pub fn split_off(&mut self, key: &K) -> Self {
+        let (tree, len) = self.tree.split_off(&key_bytes(key), true);
+        self.len -= len;
+        Art { tree, len }
+    }
+
+    /// Move every entry of `other` into this tree, leaving `other` empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the first key of `other` is not larger than the last key of this tree.
+    pub fn append(&mut self, other: &mut Self) {
+        let mut last = RawCursor::new(self.tree.root());
+        last.seek_last();
+        let mut first = RawCursor::new(other.tree.root());
+        first.seek_first();
+        if let (Some(last), Some(first)) = (last.current(), first.current()) {
+            assert!(
+                last.0 < first.0,
+                "the keys of the appended tree are not larger than the keys of the tree"
+            );
+        }
+        self.tree.append(&mut other.tree);
+        self.len += std::mem::take(&mut other.len);
+    }
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V> Art<K, V> {
+    /// Remove every entry from the tree, returns the entries in key order.
+    pub fn drain(&mut self) -> Drain<K, V> {
+        let len = std::mem::take(&mut self.len);
+        Drain::new(std::mem::take(&mut self.tree), len)
+    }
+
+    /// Remove the entries with keys within the range, returns the removed entries in key order.
+    ///
+    /// The range is split off from the tree after which the entries above the range are joined
+    /// back in.
+    pub fn drain_range<'r, R>(&mut self, range: R) -> Drain<K, V>
+    where
+        R: RangeBounds<&'r K>,
+        K: 'r,
+    {
+        let (mut tree, moved) = match range.start_bound() {
+            Bound::Included(x) => self.tree.split_off(&key_bytes(*x), true),
+            Bound::Excluded(x) => self.tree.split_off(&key_bytes(*x), false),
+            Bound::Unbounded => (std::mem::take(&mut self.tree), self.len),
+        };
+        let (mut rest, kept) = match range.end_bound() {
+            Bound::Included(x) => tree.split_off(&key_bytes(*x), false),
+            Bound::Excluded(x) => tree.split_off(&key_bytes(*x), true),
+            Bound::Unbounded => (RawArt::new(), 0),
+        };
+        self.tree.append(&mut rest);
+        self.len -= moved - kept;
+        Drain::new(tree, moved - kept)
+    }
+}
+
+/// An iterator over the entries removed from a tree.
+///
+/// The removed entries stay in the detached tree, which is taken apart as the iterator advances.
+pub struct Drain<K: Key + ToOwned + ?Sized, V> {
+    raw: RawDrain<K, V>,
+    len: usize,
+    marker: PhantomData<fn() -> K::Owned>,
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V> Drain<K, V> {
+    fn new(tree: RawArt<K, V>, len: usize) -> Self {
+        Drain {
+            raw: tree.into_drain(),
+            len,
+            marker: PhantomData,
+        }
+    }
+
+    fn entry(&mut self, (key, value): (Vec<u8>, V)) -> (K::Owned, V) {
+        self.len -= 1;
+        (unsafe { K::from_key_bytes(&key) }.to_owned(), value)
+    }
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V> Iterator for Drain<K, V> {
+    type Item = (K::Owned, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.raw.next()?;
+        Some(self.entry(entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V> DoubleEndedIterator for Drain<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.raw.next_back()?;
+        Some(self.entry(entry))
+    }
+}
+
+impl<K: Key + BorrowedKey + ToOwned + ?Sized, V> ExactSizeIterator for Drain<K, V> {}
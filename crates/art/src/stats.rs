@@ -0,0 +1,69 @@
+//! Statistics about the shape of a tree.
+
+use crate::{
+    key::Key,
+    raw::{Borrow, NodeKind, NodePtr},
+    Art,
+};
+
+/// Counts of the nodes in a tree, useful for inspecting how well a set of keys compresses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub leafs: usize,
+    /// The amount of leafs stored without a leaf node, in the pointer to the leaf or in an
+    /// allocation of only the value and the prefix. Every leaf is stored this way with the
+    /// `inline-leafs` feature.
+    pub inline_leafs: usize,
+    pub node4: usize,
+    pub node16: usize,
+    pub node48: usize,
+    pub node256: usize,
+    /// The total length of all the prefixes stored in the nodes, including the key suffixes
+    /// stored in leafs.
+    pub prefix_bytes: usize,
+    /// The largest amount of nodes on a path from the root to a leaf.
+    pub max_depth: usize,
+}
+
+impl Stats {
+    /// Returns the amount of branch nodes.
+    pub fn branches(&self) -> usize {
+        self.node4 + self.node16 + self.node48 + self.node256
+    }
+
+    fn visit<K: Key + ?Sized, V>(&mut self, node: NodePtr<Borrow<'_>, K, V>, depth: usize) {
+        self.prefix_bytes += node.prefix().len();
+        match node.kind() {
+            NodeKind::Leaf => {
+                self.leafs += 1;
+                self.inline_leafs += node.is_inline() as usize;
+                self.max_depth = self.max_depth.max(depth);
+                return;
+            }
+            NodeKind::Node4 => self.node4 += 1,
+            NodeKind::Node16 => self.node16 += 1,
+            NodeKind::Node48 => self.node48 += 1,
+            NodeKind::Node256 => self.node256 += 1,
+        }
+
+        let mut next = 0u16;
+        while next <= u8::MAX as u16 {
+            let Some((branch, child)) = node.next_node(next as u8) else {
+                break;
+            };
+            self.visit(child, depth + 1);
+            next = branch as u16 + 1;
+        }
+    }
+}
+
+impl<K: Key + ?Sized, V> Art<K, V> {
+    /// Walks the tree and returns statistics about its nodes.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        if let Some(root) = self.tree.root() {
+            stats.visit(root, 1);
+        }
+        stats
+    }
+}
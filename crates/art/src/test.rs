@@ -1,4 +1,12 @@
-use crate::Art;
+use crate::{
+    augmented::{AugmentedArt, Count, Max, Min, Sum},
+    cow::CowArt,
+    display::DisplayOptions,
+    frozen::FrozenArt,
+    key::INVALID_STR_BYTE,
+    Art,
+};
+use std::ops::Bound;
 
 #[test]
 fn test_string() {
@@ -98,7 +106,7 @@ fn test_string() {
 const XOR_SHIFT_INIT: u64 = 384931938475643;
 
 fn rol64(x: u64, by: u64) -> u64 {
-    (x << by) | (x >> (64 - by))
+    x.rotate_left(by as u32)
 }
 
 struct XorState([u64; 4]);
@@ -300,3 +308,1398 @@ fn test_grow_shrink() {
         assert_eq!(tree.remove(&k), Some(k));
     }
 }
+
+/// Sort strings in the order of the tree, which includes the terminating byte of str keys.
+fn sort_str<S: AsRef<str>>(keys: &mut [S]) {
+    keys.sort_by(|a, b| {
+        let a = a.as_ref().bytes().chain([INVALID_STR_BYTE]);
+        let b = b.as_ref().bytes().chain([INVALID_STR_BYTE]);
+        a.cmp(b)
+    });
+}
+
+#[test]
+fn iter_str() {
+    let mut tree = Art::<str, usize>::new();
+    let mut keys = vec!["b", "a", "hello", "hello world", "hellp", "ab", "", "z"];
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+    sort_str(&mut keys);
+
+    let mut iter = tree.iter();
+    for k in keys {
+        let (key, _) = iter.next().unwrap();
+        assert_eq!(key, k);
+    }
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn from_sorted_iter() {
+    let mut state = XorState::new();
+    let mut keys: Vec<String> = (0..10_000)
+        .map(|_| {
+            let k = xorshift(&mut state);
+            // vary the length so that there are nodes of every size.
+            format!("{:x}", k >> (k % 48))
+        })
+        .collect();
+    sort_str(&mut keys);
+    keys.dedup();
+
+    let tree = Art::<str, usize>::from_sorted_iter(keys.iter().map(|x| x.as_str()).zip(0..));
+    assert_eq!(tree.len(), keys.len());
+
+    let mut iter = tree.iter();
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k).copied(), Some(i));
+        assert_eq!(iter.next(), Some((k.as_str(), &i)));
+    }
+    assert!(iter.next().is_none());
+
+    // integer keys are ordered by their little endian bytes.
+    let keys: Vec<u64> = (0..=u16::MAX).map(|x| x.swap_bytes() as u64).collect();
+    let mut tree = Art::<u64, u64>::from_sorted_iter(keys.iter().map(|x| (x, *x)));
+    let stats = tree.stats();
+    assert_eq!(stats.node256, 257);
+    assert_eq!(stats.branches(), 257);
+    assert_eq!(stats.leafs, keys.len());
+    assert_eq!(stats.max_depth, 3);
+    // leafs store the remaining 6 bytes of the key.
+    assert_eq!(stats.prefix_bytes, keys.len() * 6);
+    for i in 0..=u16::MAX as u64 {
+        assert_eq!(tree.remove(&i), Some(i));
+    }
+    assert!(tree.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn from_sorted_iter_unsorted() {
+    Art::<str, usize>::from_sorted_iter([("b", 0), ("a", 1)]);
+}
+
+#[test]
+fn range_str() {
+    let mut state = XorState::new();
+    let mut tree = Art::<str, u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..10_000 {
+        let k = xorshift(&mut state);
+        let key = format!("{:x}", k >> (k % 48));
+        tree.insert(&key, k);
+        keys.push(key);
+    }
+    sort_str(&mut keys);
+    keys.dedup();
+
+    let (from, to) = (keys[100].as_str(), keys[200].as_str());
+    let mut iter = tree.range(from..to);
+    for k in keys[100..200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = tree.range((Bound::Excluded(from), Bound::Included(to)));
+    for k in keys[101..=200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    // Bounds which are not keys in the tree.
+    let mut iter = tree.range("g"..);
+    assert_eq!(iter.next(), None);
+    let mut iter = tree.range(.."0");
+    assert_eq!(iter.next(), None);
+
+    for prefix in ["", "ab", "1f", "fff", "not hex"] {
+        let mut iter = tree.scan_prefix(prefix);
+        for k in keys.iter().filter(|x| x.starts_with(prefix)) {
+            assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+        }
+        assert_eq!(iter.next(), None);
+    }
+}
+
+#[test]
+fn frozen_str() {
+    let mut state = XorState::new();
+    let mut tree = Art::<str, u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..10_000 {
+        let k = xorshift(&mut state);
+        let key = format!("{:x}", k >> (k % 48));
+        tree.insert(&key, k);
+        keys.push(key);
+    }
+    sort_str(&mut keys);
+    keys.dedup();
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    tree.write_frozen(&mut file).unwrap();
+    let frozen = FrozenArt::<str, u64>::open(file.path()).unwrap();
+
+    assert_eq!(frozen.len(), tree.len());
+    assert_eq!(frozen.get("not a hex key"), None);
+
+    let mut iter = frozen.iter();
+    for k in keys.iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let (from, to) = (keys[100].as_str(), keys[200].as_str());
+    let mut iter = frozen.range(from..to);
+    for k in keys[100..200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = frozen.range((Bound::Excluded(from), Bound::Included(to)));
+    for k in keys[101..=200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = frozen.scan_prefix("ab");
+    for k in keys.iter().filter(|x| x.starts_with("ab")) {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn frozen_u64() {
+    let mut tree = Art::<u64, [u8; 8]>::new();
+    let mut state = XorState::new();
+    let mut keys = Vec::new();
+    for _ in 0..100_000 {
+        let k = xorshift(&mut state);
+        tree.insert(&k, k.to_le_bytes());
+        keys.push(k);
+    }
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    tree.write_frozen(&mut file).unwrap();
+    let frozen = FrozenArt::<u64, [u8; 8]>::open(file.path()).unwrap();
+    for k in keys {
+        assert_eq!(frozen.get(&k), Some(&k.to_le_bytes()));
+    }
+    assert!(FrozenArt::<u64, u32>::open(file.path()).is_err());
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    Art::<u64, u64>::new().write_frozen(&mut file).unwrap();
+    let frozen = FrozenArt::<u64, u64>::open(file.path()).unwrap();
+    assert!(frozen.is_empty());
+    assert_eq!(frozen.get(&0), None);
+}
+
+#[test]
+fn frozen_corrupt() {
+    let mut tree = Art::<str, u64>::new();
+    for (i, k) in ["apple", "apricot", "banana", "blueberry", "cherry"]
+        .iter()
+        .enumerate()
+    {
+        tree.insert(k, i as u64);
+    }
+    let mut bytes = Vec::new();
+    tree.write_frozen(&mut bytes).unwrap();
+
+    let open = |bytes: &[u8]| {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, bytes).unwrap();
+        FrozenArt::<str, u64>::open(file.path()).map(|x| x.len())
+    };
+    assert_eq!(open(&bytes).unwrap(), 5);
+
+    let trailer = bytes.len() - 24;
+    let root = u64::from_le_bytes(bytes[trailer..trailer + 8].try_into().unwrap()) as usize;
+    let count = u16::from_le_bytes([bytes[root + 2], bytes[root + 3]]) as usize;
+    let prefix_len = u32::from_le_bytes(bytes[root + 4..root + 8].try_into().unwrap()) as usize;
+    let body = (root + 8 + prefix_len).next_multiple_of(8);
+    let first_child = (body + count).next_multiple_of(8);
+
+    let assert_invalid = |corrupt: &dyn Fn(&mut Vec<u8>)| {
+        let mut bytes = bytes.clone();
+        corrupt(&mut bytes);
+        let err = open(&bytes).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    };
+    // invalid node kind.
+    assert_invalid(&|b| b[root] = 9);
+    // prefix running past the end of the nodes.
+    assert_invalid(&|b| b[root + 4..root + 8].copy_from_slice(&u32::MAX.to_le_bytes()));
+    // child pointing at its parent.
+    assert_invalid(&|b| {
+        b[first_child..first_child + 8].copy_from_slice(&(root as u64).to_le_bytes())
+    });
+    // children cut out of the file, leaving offsets past the end of the nodes.
+    assert_invalid(&|b| {
+        b.drain(16..root);
+        let trailer = b.len() - 24;
+        b[trailer..trailer + 8].copy_from_slice(&16u64.to_le_bytes());
+    });
+    // wrong length.
+    assert_invalid(&|b| b[trailer + 8] = 6);
+    // a key which isn't utf-8, or lost its terminator.
+    let leaf = bytes.windows(5).position(|x| x == b"herry").unwrap();
+    assert_invalid(&|b| b[leaf] = 0xff);
+    assert_invalid(&|b| b[leaf + 5] = b'y');
+}
+
+#[test]
+fn export() {
+    let mut tree = Art::<str, usize>::new();
+    assert_eq!(tree.to_json(), r#"{"len":0,"root":null}"#);
+    tree.insert("a", 1);
+    tree.insert("b\"", 2);
+    assert_eq!(
+        tree.to_json(),
+        r#"{"len":2,"root":{"kind":"Node4","prefix":"","children":[{"key":97,"node":{"kind":"Leaf","prefix":"\\xbf","value":"1"}},{"key":98,"node":{"kind":"Leaf","prefix":"\"\\xbf","value":"2"}}]}}"#
+    );
+
+    let dot = tree.to_dot();
+    assert!(dot.starts_with("digraph art {\n"));
+    assert!(dot.contains("n0 [label=\"Node4\\nprefix: \\nchildren: 2\"];"));
+    assert!(dot.contains("n1 [label=\"Leaf\\nprefix: \\\\xbf\\nvalue: 1\"];"));
+    assert!(dot.contains("n0 -> n1 [label=\"a\"];"));
+    assert!(dot.contains("n2 [label=\"Leaf\\nprefix: \\\"\\\\xbf\\nvalue: 2\"];"));
+    assert!(dot.ends_with("}\n"));
+}
+
+#[test]
+fn display() {
+    let mut tree = Art::<str, usize>::new();
+    assert_eq!(tree.to_string(), "TREE = EMPTY\n");
+    assert_eq!(format!("{tree:?}"), "{}");
+    for (idx, key) in ["aa", "ab", "ac", "b"].iter().enumerate() {
+        tree.insert(key, idx);
+    }
+    assert_eq!(
+        tree.to_string(),
+        "TREE = NODE4: len=2,prefix=[]
+  [97] = NODE4: len=3,prefix=[]
+    [97] = LEAF: len=0 prefix=[191] | 0
+    [98] = LEAF: len=0 prefix=[191] | 1
+    [99] = LEAF: len=0 prefix=[191] | 2
+  [98] = LEAF: len=0 prefix=[191] | 3
+"
+    );
+    let options = DisplayOptions {
+        max_depth: Some(0),
+        max_children: None,
+    };
+    assert_eq!(
+        tree.display(options).to_string(),
+        "TREE = NODE4: len=2,prefix=[]
+  ... 2 more
+"
+    );
+    let options = DisplayOptions {
+        max_depth: None,
+        max_children: Some(2),
+    };
+    assert_eq!(
+        tree.display(options).to_string(),
+        "TREE = NODE4: len=2,prefix=[]
+  [97] = NODE4: len=3,prefix=[]
+    [97] = LEAF: len=0 prefix=[191] | 0
+    [98] = LEAF: len=0 prefix=[191] | 1
+    ... 1 more
+  [98] = LEAF: len=0 prefix=[191] | 3
+"
+    );
+    assert_eq!(
+        format!("{tree:?}"),
+        r#"{"aa": 0, "ab": 1, "ac": 2, "b": 3}"#
+    );
+}
+
+#[test]
+fn clone() {
+    let mut tree = Art::<str, usize>::new();
+    let keys: Vec<String> = (0..17u8)
+        .map(|x| format!("key{}", (b'a' + x) as char))
+        .collect();
+    for (idx, key) in keys.iter().enumerate() {
+        tree.insert(key, idx);
+    }
+    // The branch stays a node48 after shrinking to 16 branches.
+    tree.remove(&keys[16]);
+    assert_eq!(tree.stats().node48, 1);
+
+    let mut copy = tree.clone();
+    assert_eq!(copy.len(), 16);
+    assert_eq!(copy.stats().node48, 0);
+    assert_eq!(copy.stats().node16, 1);
+    // Apart from the kind of the branch the trees are identical.
+    assert_eq!(copy.to_json(), tree.to_json().replace("Node48", "Node16"));
+
+    copy.insert("keya", 100);
+    copy.remove("keyb");
+    *copy.get_mut("keyc").unwrap() = 200;
+    assert_eq!(tree.get("keya"), Some(&0));
+    assert_eq!(tree.get("keyb"), Some(&1));
+    assert_eq!(tree.get("keyc"), Some(&2));
+    assert_eq!(copy.get("keya"), Some(&100));
+    assert_eq!(copy.get("keyb"), None);
+    assert_eq!(copy.get("keyc"), Some(&200));
+}
+
+#[test]
+fn cow() {
+    let mut state = XorState::new();
+    let mut tree = CowArt::<u64, u64>::new();
+    let mut expect = std::collections::BTreeMap::new();
+    for _ in 0..10_000 {
+        // Limit the range of keys so some keys are inserted twice.
+        let k = xorshift(&mut state) % (1 << 20);
+        assert_eq!(tree.insert(&k, k), expect.insert(k, k));
+    }
+    assert_eq!(tree.len(), expect.len());
+
+    let snapshot = tree.clone();
+    let snapshot_expect = expect.clone();
+    for _ in 0..10_000 {
+        let k = xorshift(&mut state) % (1 << 20);
+        match k % 3 {
+            0 => assert_eq!(tree.insert(&k, k + 1), expect.insert(k, k + 1)),
+            1 => assert_eq!(tree.remove(&k), expect.remove(&k)),
+            _ => {
+                if let Some(x) = tree.get_mut(&k) {
+                    *x += 2;
+                }
+                if let Some(x) = expect.get_mut(&k) {
+                    *x += 2;
+                }
+            }
+        }
+    }
+    for k in expect.keys().copied().collect::<Vec<_>>() {
+        assert_eq!(tree.remove(&k), expect.remove(&k));
+    }
+    assert!(tree.is_empty());
+    assert_eq!(tree.get(&0), None);
+
+    assert_eq!(snapshot.len(), snapshot_expect.len());
+    for (k, v) in snapshot_expect.iter() {
+        assert_eq!(snapshot.get(k), Some(v));
+    }
+}
+
+#[test]
+fn cow_iter() {
+    let mut state = XorState::new();
+    let mut tree = CowArt::<str, u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..10_000 {
+        let k = xorshift(&mut state) % (1 << 16);
+        let key = format!("{k:x}");
+        if tree.insert(&key, k).is_none() {
+            keys.push(key);
+        }
+    }
+    let snapshot = tree.clone();
+    // Remove some keys so the nodes shrink while the snapshot still shares them.
+    keys.retain(|k| !k.ends_with('0') || tree.remove(k).is_none());
+    sort_str(&mut keys);
+    assert_eq!(tree.len(), keys.len());
+
+    let mut iter = tree.iter();
+    for k in keys.iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let (from, to) = (keys[100].as_str(), keys[200].as_str());
+    let mut iter = tree.range(from..to);
+    for k in keys[100..200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = tree.range((Bound::Excluded(from), Bound::Included(to)));
+    for k in keys[101..=200].iter() {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut iter = tree.scan_prefix("ab");
+    for k in keys.iter().filter(|x| x.starts_with("ab")) {
+        assert_eq!(iter.next(), Some((k.as_str(), tree.get(k).unwrap())));
+    }
+    assert_eq!(iter.next(), None);
+
+    let mut count = 0;
+    let mut iter = snapshot.iter();
+    while let Some((k, v)) = iter.next() {
+        assert_eq!(u64::from_str_radix(k, 16).unwrap(), *v);
+        count += 1;
+    }
+    assert_eq!(count, snapshot.len());
+}
+
+#[test]
+fn set_operations() {
+    use std::collections::BTreeMap;
+
+    fn check(tree: &Art<u64, u64>, expect: &BTreeMap<u64, u64>, keys: &[u64]) {
+        assert_eq!(tree.len(), expect.len());
+        for k in keys {
+            assert_eq!(tree.get(k), expect.get(k), "key {k:x}");
+        }
+    }
+
+    let mut state = XorState::new();
+    let (mut a, mut b) = (Art::<u64, u64>::new(), Art::<u64, u64>::new());
+    let (mut expect_a, mut expect_b) = (BTreeMap::new(), BTreeMap::new());
+    let mut keys = Vec::new();
+    for i in 0..20_000 {
+        // Mix dense and sparse keys so the trees share some subtrees and not others.
+        let k = match i % 3 {
+            0 => xorshift(&mut state) % 4096,
+            _ => xorshift(&mut state),
+        };
+        keys.push(k);
+        if i % 2 == 0 {
+            a.insert(&k, k);
+            expect_a.insert(k, k);
+        } else {
+            b.insert(&k, k / 2);
+            expect_b.insert(k, k / 2);
+        }
+    }
+
+    let mut expect = expect_a.clone();
+    for (k, v) in expect_b.iter() {
+        expect.entry(*k).and_modify(|x| *x ^= v).or_insert(*v);
+    }
+    check(&a.union_with(&b, |x, y| x ^ y), &expect, &keys);
+
+    let expect = expect_a
+        .iter()
+        .filter_map(|(k, v)| Some((*k, v ^ expect_b.get(k)?)))
+        .collect();
+    check(&a.intersection(&b, |x, y| x ^ y), &expect, &keys);
+
+    let expect = expect_a
+        .iter()
+        .filter(|(k, _)| !expect_b.contains_key(k))
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    check(&a.difference(&b, |_, _| None), &expect, &keys);
+
+    let mut expect: BTreeMap<_, _> = expect_a
+        .iter()
+        .chain(expect_b.iter())
+        .filter(|(k, _)| expect_a.contains_key(k) != expect_b.contains_key(k))
+        .map(|(k, v)| (*k, *v))
+        .collect();
+    // Keep the even keys which are in both trees.
+    for (k, v) in expect_a.iter() {
+        if expect_b.contains_key(k) && k % 2 == 0 {
+            expect.insert(*k, *v);
+        }
+    }
+    let sym = a.symmetric_difference(&b, |x, _| (x % 2 == 0).then_some(*x));
+    check(&sym, &expect, &keys);
+
+    // Merging with an empty tree copies the other tree.
+    let empty = Art::new();
+    check(&a.union_with(&empty, |x, _| *x), &expect_a, &keys);
+    check(&empty.union_with(&b, |x, _| *x), &expect_b, &keys);
+    assert!(a.intersection(&empty, |x, _| *x).is_empty());
+}
+
+#[test]
+fn set_operations_str() {
+    let mut a = Art::<str, usize>::new();
+    let mut b = Art::<str, usize>::new();
+    for (idx, key) in ["apple", "applesauce", "banana", "band", "cherry"]
+        .iter()
+        .enumerate()
+    {
+        a.insert(key, idx);
+    }
+    for (idx, key) in ["apple", "bandana", "band", "cherries", "date"]
+        .iter()
+        .enumerate()
+    {
+        b.insert(key, 10 + idx);
+    }
+
+    let entries = |tree: &Art<str, usize>| {
+        let mut res = Vec::new();
+        let mut iter = tree.iter();
+        while let Some((k, v)) = iter.next() {
+            res.push((k.to_owned(), *v));
+        }
+        res
+    };
+    // Entries are given in the order of the tree, in which a key sorts after its extensions.
+    let expect = |entries: &[(&str, usize)]| {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        entries(&a.union_with(&b, |x, y| x + y)),
+        expect(&[
+            ("applesauce", 1),
+            ("apple", 10),
+            ("banana", 2),
+            ("bandana", 11),
+            ("band", 15),
+            ("cherries", 13),
+            ("cherry", 4),
+            ("date", 14),
+        ])
+    );
+    assert_eq!(
+        entries(&a.intersection(&b, |x, y| x * y)),
+        expect(&[("apple", 0), ("band", 36)])
+    );
+    assert_eq!(
+        entries(&a.difference(&b, |_, _| None)),
+        expect(&[("applesauce", 1), ("banana", 2), ("cherry", 4)])
+    );
+    assert_eq!(
+        entries(&b.symmetric_difference(&a, |_, _| None)),
+        expect(&[
+            ("applesauce", 1),
+            ("banana", 2),
+            ("bandana", 11),
+            ("cherries", 13),
+            ("cherry", 4),
+            ("date", 14),
+        ])
+    );
+}
+
+#[cfg(feature = "order-statistics")]
+#[test]
+fn order_statistics() {
+    use std::collections::BTreeSet;
+
+    // The tree orders integer keys by their little endian bytes.
+    fn check(tree: &Art<u64, u64>, expect: &BTreeSet<[u8; 8]>, keys: &[u64]) {
+        for k in keys {
+            let bytes = k.to_le_bytes();
+            let below = expect.range(..bytes).count();
+            assert_eq!(tree.rank(k), below, "key {k:x}");
+            let within = expect.range(bytes..).take(100).count();
+            let end = expect
+                .range(bytes..)
+                .nth(99)
+                .map(|x| u64::from_le_bytes(*x));
+            match end {
+                Some(end) => assert_eq!(tree.range_count(k..=&end), within),
+                None => assert_eq!(tree.range_count(k..), within),
+            }
+        }
+        assert_eq!(tree.range_count(..), expect.len());
+    }
+
+    let mut state = XorState::new();
+    let mut tree = Art::<u64, u64>::new();
+    let mut expect = BTreeSet::new();
+    let mut keys = Vec::new();
+    for i in 0..20_000 {
+        let k = match i % 3 {
+            0 => xorshift(&mut state) % 4096,
+            _ => xorshift(&mut state),
+        };
+        keys.push(k);
+        tree.insert(&k, k);
+        expect.insert(k.to_le_bytes());
+    }
+    check(&tree, &expect, &keys[..1000]);
+
+    for k in keys.iter().step_by(3) {
+        tree.remove(k);
+        expect.remove(&k.to_le_bytes());
+    }
+    check(&tree, &expect, &keys[..1000]);
+
+    let copy = tree.clone();
+    check(&copy, &expect, &keys[..1000]);
+    let union = tree.union_with(&Art::new(), |x, _| *x);
+    check(&union, &expect, &keys[..1000]);
+
+    let mut tree = Art::<str, usize>::new();
+    for (i, k) in ["apple", "apricot", "banana", "band", "ban", "cherry"]
+        .iter()
+        .enumerate()
+    {
+        tree.insert(k, i);
+    }
+    let mut i = 0;
+    let mut iter = tree.iter();
+    while let Some((k, v)) = iter.next() {
+        assert_eq!(tree.nth(i), Some((k.to_owned(), v)));
+        assert_eq!(tree.rank(k), i);
+        i += 1;
+    }
+    assert_eq!(tree.nth(i), None);
+    // "b" sorts after all keys starting with it, only "cherry" lies before "c".
+    assert_eq!(tree.range_count(&"b"..&"c"), 1);
+    assert_eq!(tree.range_count(&"apricot"..=&"ban"), 4);
+}
+
+#[test]
+fn augmented() {
+    use std::collections::BTreeMap;
+
+    // The tree orders integer keys by their little endian bytes.
+    fn check(tree: &AugmentedArt<u64, u64, Sum<u64>>, expect: &BTreeMap<[u8; 8], u64>, k: u64) {
+        let bytes = k.to_le_bytes();
+        let end = expect
+            .range(bytes..)
+            .nth(50)
+            .map(|x| u64::from_le_bytes(*x.0));
+        let end = end.unwrap_or(u64::MAX);
+        let (lo, hi) = (bytes, end.to_le_bytes());
+        let sum = |range: (Bound<[u8; 8]>, Bound<[u8; 8]>)| expect.range(range).map(|x| x.1).sum();
+
+        let range = (Bound::Included(&k), Bound::Excluded(&end));
+        let bounds = (Bound::Included(lo), Bound::Excluded(hi));
+        assert_eq!(tree.aggregate(range), Sum(sum(bounds)), "key {k:x}");
+        let range = (Bound::Excluded(&k), Bound::Included(&end));
+        let bounds = (Bound::Excluded(lo), Bound::Included(hi));
+        assert_eq!(tree.aggregate(range), Sum(sum(bounds)), "key {k:x}");
+        let range = (Bound::Unbounded, Bound::Included(&k));
+        let bounds = (Bound::Unbounded, Bound::Included(lo));
+        assert_eq!(tree.aggregate(range), Sum(sum(bounds)), "key {k:x}");
+    }
+
+    let mut state = XorState::new();
+    let mut tree = AugmentedArt::<u64, u64, Sum<u64>>::new();
+    let mut expect = BTreeMap::new();
+    let mut keys = Vec::new();
+    for i in 0..10_000 {
+        let k = match i % 3 {
+            0 => xorshift(&mut state) % 4096,
+            _ => xorshift(&mut state),
+        };
+        let v = k % 1000;
+        keys.push(k);
+        assert_eq!(tree.insert(&k, v), expect.insert(k.to_le_bytes(), v));
+    }
+    assert_eq!(tree.len(), expect.len());
+    assert_eq!(tree.aggregate(..), Sum(expect.values().sum()));
+    keys.iter()
+        .take(500)
+        .for_each(|k| check(&tree, &expect, *k));
+
+    for (i, k) in keys.iter().enumerate().step_by(3) {
+        if i % 2 == 0 {
+            assert_eq!(tree.remove(k), expect.remove(&k.to_le_bytes()));
+        } else {
+            assert_eq!(tree.insert(k, 7), expect.insert(k.to_le_bytes(), 7));
+        }
+    }
+    assert_eq!(tree.len(), expect.len());
+    assert_eq!(tree.aggregate(..), Sum(expect.values().sum()));
+    keys.iter()
+        .take(500)
+        .for_each(|k| check(&tree, &expect, *k));
+
+    let mut tree = AugmentedArt::<str, u32, Min<u32>>::new();
+    let mut max = AugmentedArt::<str, u32, Max<u32>>::new();
+    let mut count = AugmentedArt::<str, u32, Count>::new();
+    for (k, v) in [
+        ("apple", 3),
+        ("apricot", 8),
+        ("banana", 1),
+        ("band", 6),
+        ("cherry", 4),
+    ] {
+        tree.insert(k, v);
+        max.insert(k, v);
+        count.insert(k, v);
+    }
+    assert_eq!(tree.aggregate(..), Min(Some(1)));
+    assert_eq!(tree.aggregate(&"apple"..=&"apricot"), Min(Some(3)));
+    assert_eq!(tree.aggregate(&"apricot"..&"cherry"), Min(Some(1)));
+    assert_eq!(tree.aggregate(&"cherry"..&"cherry"), Min(None));
+    assert_eq!(max.aggregate(&"band"..), Max(Some(6)));
+    assert_eq!(count.aggregate(&"apricot"..=&"cherry"), Count(4));
+    tree.remove("banana");
+    assert_eq!(tree.aggregate(&"apricot"..&"cherry"), Min(Some(6)));
+}
+
+#[test]
+fn longest_prefix_match() {
+    use crate::key::{Ipv4Net, Ipv6Net};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut routes = Art::<Ipv4Net, &str>::new();
+    let net = |a, b, c, d, len| Ipv4Net::new(Ipv4Addr::new(a, b, c, d), len);
+    routes.insert(&net(0, 0, 0, 0, 0), "default");
+    routes.insert(&net(10, 0, 0, 0, 8), "ten");
+    routes.insert(&net(10, 1, 0, 0, 16), "ten-one");
+    routes.insert(&net(10, 1, 2, 0, 24), "ten-one-two");
+    routes.insert(&net(10, 1, 2, 3, 32), "host");
+    routes.insert(&net(192, 168, 0, 0, 16), "private");
+
+    let lookup = |routes: &Art<Ipv4Net, &'static str>, a, b, c, d| {
+        let addr = Ipv4Net::from(Ipv4Addr::new(a, b, c, d));
+        routes.longest_prefix_match(&addr).map(|(k, v)| (k, *v))
+    };
+    assert_eq!(
+        lookup(&routes, 10, 1, 2, 3),
+        Some((net(10, 1, 2, 3, 32), "host"))
+    );
+    assert_eq!(
+        lookup(&routes, 10, 1, 2, 4),
+        Some((net(10, 1, 2, 0, 24), "ten-one-two"))
+    );
+    assert_eq!(
+        lookup(&routes, 10, 1, 3, 4),
+        Some((net(10, 1, 0, 0, 16), "ten-one"))
+    );
+    assert_eq!(
+        lookup(&routes, 10, 2, 3, 4),
+        Some((net(10, 0, 0, 0, 8), "ten"))
+    );
+    assert_eq!(
+        lookup(&routes, 192, 168, 9, 9),
+        Some((net(192, 168, 0, 0, 16), "private"))
+    );
+    assert_eq!(
+        lookup(&routes, 8, 8, 8, 8),
+        Some((net(0, 0, 0, 0, 0), "default"))
+    );
+    // Networks can be looked up as well, and are a prefix of themselves.
+    let found = routes.longest_prefix_match(&net(10, 1, 128, 0, 17));
+    assert_eq!(found, Some((net(10, 1, 0, 0, 16), &"ten-one")));
+    let found = routes.longest_prefix_match(&net(10, 1, 0, 0, 16));
+    assert_eq!(found, Some((net(10, 1, 0, 0, 16), &"ten-one")));
+
+    routes.remove(&net(0, 0, 0, 0, 0));
+    assert_eq!(lookup(&routes, 8, 8, 8, 8), None);
+    assert!(net(10, 0, 0, 0, 8).contains(Ipv4Addr::new(10, 200, 0, 1)));
+    assert_eq!(net(10, 1, 2, 3, 12), net(10, 0, 0, 0, 12));
+
+    let mut routes = Art::<Ipv6Net, u32>::new();
+    let v6 = |s: &str, len| Ipv6Net::new(s.parse::<Ipv6Addr>().unwrap(), len);
+    routes.insert(&v6("2001:db8::", 32), 1);
+    routes.insert(&v6("2001:db8:1::", 48), 2);
+    let addr = Ipv6Net::from("2001:db8:1::7".parse::<Ipv6Addr>().unwrap());
+    assert_eq!(
+        routes.longest_prefix_match(&addr),
+        Some((v6("2001:db8:1::", 48), &2))
+    );
+    let addr = Ipv6Net::from("2001:db8:2::7".parse::<Ipv6Addr>().unwrap());
+    assert_eq!(
+        routes.longest_prefix_match(&addr),
+        Some((v6("2001:db8::", 32), &1))
+    );
+
+    let mut urls = Art::<str, u32>::new();
+    urls.insert("/", 0);
+    urls.insert("/api", 1);
+    urls.insert("/api/users", 2);
+    urls.insert("/static", 3);
+    let lookup = |x| urls.longest_prefix_match(x).map(|(k, v)| (k, *v));
+    assert_eq!(lookup("/api/users/7"), Some(("/api/users".to_owned(), 2)));
+    assert_eq!(lookup("/api/user"), Some(("/api".to_owned(), 1)));
+    assert_eq!(lookup("/index.html"), Some(("/".to_owned(), 0)));
+    assert_eq!(lookup("/api"), Some(("/api".to_owned(), 1)));
+    assert_eq!(lookup("api"), None);
+}
+
+#[test]
+fn fuzzy() {
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, x) in a.chars().enumerate() {
+            let mut next = vec![i + 1];
+            for (j, y) in b.iter().enumerate() {
+                next.push(
+                    (row[j] + (x != *y) as usize)
+                        .min(row[j + 1] + 1)
+                        .min(next[j] + 1),
+                );
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    let words = [
+        "apple", "apply", "ample", "maple", "applet", "app", "banana", "bandana", "cafe", "café",
+        "caffe", "naïve", "native", "", "a",
+    ];
+    let mut tree = Art::<str, usize>::new();
+    for (i, w) in words.iter().enumerate() {
+        tree.insert(w, i);
+    }
+
+    for query in ["apple", "aple", "cafe", "naive", "banan", "", "zzzz", "ä"] {
+        for max in 0..3 {
+            let found: Vec<_> = tree.fuzzy(query, max).map(|(k, v, d)| (k, *v, d)).collect();
+            // Matches are returned in the order of the tree.
+            let mut expect = Vec::new();
+            let mut iter = tree.iter();
+            while let Some((k, v)) = iter.next() {
+                let d = distance(k, query);
+                if d <= max {
+                    expect.push((k.to_owned(), *v, d));
+                }
+            }
+            assert_eq!(found, expect, "query {query:?} max {max}");
+        }
+    }
+}
+
+#[test]
+fn scan_glob() {
+    let keys = [
+        "user:1:session",
+        "user:2:session",
+        "user:2:profile",
+        "user:12:session",
+        "user::session",
+        "user:é:session",
+        "admin:1:session",
+        "user",
+        "[user]",
+        "a*b",
+        "",
+    ];
+    let mut tree = Art::<str, usize>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+
+    let scan = |tree: &Art<str, usize>, pattern| {
+        let mut found: Vec<_> = tree.scan_glob(pattern).map(|(k, _)| k).collect();
+        found.sort();
+        found
+    };
+    let expect = |x: &[&str]| {
+        let mut x: Vec<_> = x.iter().map(|x| x.to_string()).collect();
+        x.sort();
+        x
+    };
+
+    assert_eq!(
+        scan(&tree, "user:*:session"),
+        expect(&[
+            "user:1:session",
+            "user:2:session",
+            "user:12:session",
+            "user::session",
+            "user:é:session"
+        ])
+    );
+    assert_eq!(
+        scan(&tree, "user:?:session"),
+        expect(&["user:1:session", "user:2:session", "user:é:session"])
+    );
+    assert_eq!(
+        scan(&tree, "user:[1-2]:*"),
+        expect(&["user:1:session", "user:2:session", "user:2:profile"])
+    );
+    assert_eq!(
+        scan(&tree, "user:[^1]:s*"),
+        expect(&["user:2:session", "user:é:session"])
+    );
+    assert_eq!(
+        scan(&tree, "*:1:*"),
+        expect(&["user:1:session", "admin:1:session"])
+    );
+    assert_eq!(scan(&tree, "user"), expect(&["user"]));
+    assert_eq!(scan(&tree, "a\\*b"), expect(&["a*b"]));
+    assert_eq!(scan(&tree, "\\[user]"), expect(&["[user]"]));
+    assert_eq!(scan(&tree, "[user"), expect(&[]));
+    assert_eq!(scan(&tree, ""), expect(&[""]));
+    assert_eq!(scan(&tree, "*").len(), keys.len());
+
+    // Matches are returned in key order.
+    let found: Vec<_> = tree.scan_glob("*").map(|(k, v)| (k, *v)).collect();
+    let mut iter = tree.iter();
+    let mut all = Vec::new();
+    while let Some((k, v)) = iter.next() {
+        all.push((k.to_owned(), *v));
+    }
+    assert_eq!(found, all);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn scan_regex() {
+    let keys = [
+        "user:1:session",
+        "user:2:session",
+        "user:12:session",
+        "user:é:session",
+        "admin:1:session",
+        "user",
+        "",
+    ];
+    let mut tree = Art::<str, usize>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i);
+    }
+
+    let scan = |tree: &Art<str, usize>, pattern| {
+        let mut found: Vec<_> = tree.scan_regex(pattern).unwrap().map(|(k, _)| k).collect();
+        found.sort();
+        found
+    };
+    assert_eq!(
+        scan(&tree, r"user:\d+:session"),
+        ["user:12:session", "user:1:session", "user:2:session"]
+    );
+    assert_eq!(scan(&tree, r"user:.:session").len(), 3);
+    // The expression has to match the whole key.
+    assert_eq!(scan(&tree, "user"), ["user"]);
+    assert_eq!(scan(&tree, "use"), Vec::<String>::new());
+    assert_eq!(scan(&tree, "user|use|u"), ["user"]);
+    assert_eq!(
+        scan(&tree, "(admin|user):1:.*"),
+        ["admin:1:session", "user:1:session"]
+    );
+    assert_eq!(scan(&tree, ".*").len(), keys.len());
+    assert!(tree.scan_regex("(").is_err());
+}
+
+#[test]
+fn cursor() {
+    let mut state = XorState::new();
+    let mut tree = Art::<str, u64>::new();
+    let mut keys = Vec::new();
+    for _ in 0..2_000 {
+        let k = xorshift(&mut state);
+        let key = format!("{:x}", k >> (k % 48));
+        tree.insert(&key, k);
+        keys.push(key);
+    }
+    sort_str(&mut keys);
+    keys.dedup();
+
+    let mut cursor = tree.cursor();
+    assert_eq!(cursor.key(), None);
+    cursor.next();
+    for k in keys.iter() {
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        assert_eq!(cursor.value(), tree.get(k));
+        cursor.next();
+    }
+    // Moving past the end goes to the ghost position and from there around to the start.
+    assert_eq!(cursor.key(), None);
+    cursor.next();
+    assert_eq!(cursor.key(), Some(keys[0].as_str()));
+    cursor.prev();
+    assert_eq!(cursor.key(), None);
+    cursor.prev();
+    for k in keys.iter().rev() {
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        cursor.prev();
+    }
+    assert_eq!(cursor.key(), None);
+
+    let bytes = |x: &str| x.bytes().chain([INVALID_STR_BYTE]).collect::<Vec<_>>();
+    for _ in 0..1_000 {
+        let k = xorshift(&mut state);
+        let probe = format!("{:x}", k >> (k % 64));
+        let expect = keys.iter().find(|x| bytes(x) >= bytes(&probe));
+        cursor.seek(&probe);
+        assert_eq!(cursor.key(), expect.map(|x| x.as_str()), "seek {probe:?}");
+    }
+    for (i, k) in keys.iter().enumerate().step_by(7) {
+        cursor.seek(k);
+        assert_eq!(cursor.key(), Some(k.as_str()));
+        cursor.prev();
+        assert_eq!(cursor.key(), i.checked_sub(1).map(|x| keys[x].as_str()));
+    }
+    cursor.seek_last();
+    assert_eq!(cursor.key(), keys.last().map(|x| x.as_str()));
+    cursor.seek_first();
+    assert_eq!(cursor.key(), Some(keys[0].as_str()));
+
+    let mut cursor = tree.cursor_mut();
+    cursor.seek_first();
+    let mut i = 0;
+    while let Some(k) = cursor.key() {
+        assert_eq!(k, keys[i]);
+        if i % 2 == 0 {
+            assert!(cursor.remove_current().is_some());
+        } else {
+            *cursor.value_mut().unwrap() = i as u64;
+            cursor.next();
+        }
+        i += 1;
+    }
+    assert_eq!(tree.len(), keys.len() / 2);
+    for (i, k) in keys.iter().enumerate() {
+        let expect = (i % 2 == 1).then_some(i as u64);
+        assert_eq!(tree.get(k).copied(), expect);
+    }
+
+    // Put the removed keys back in front of their successors.
+    let mut cursor = tree.cursor_mut();
+    for (i, k) in keys.iter().enumerate().step_by(2) {
+        match keys.get(i + 1) {
+            Some(next) => cursor.seek(next),
+            None => cursor.seek_last(),
+        }
+        if keys.get(i + 1).is_none() {
+            cursor.next();
+        }
+        cursor.insert_before(k, 0);
+        assert_eq!(cursor.key(), keys.get(i + 1).map(|x| x.as_str()));
+        cursor.prev();
+        assert_eq!(cursor.key(), Some(k.as_str()));
+    }
+    assert_eq!(tree.len(), keys.len());
+
+    // Integer keys are ordered by their little endian bytes.
+    let mut tree = Art::<u64, u64>::new();
+    for i in 0..1_000u64 {
+        tree.insert(&(i * 3), i);
+    }
+    let mut cursor = tree.cursor();
+    for probe in [0, 1, 301, 2_000, 2_998, u64::MAX] {
+        let expect = (0..1_000u64)
+            .filter(|x| (x * 3).to_le_bytes() >= probe.to_le_bytes())
+            .min_by_key(|x| (x * 3).to_le_bytes());
+        cursor.seek(&probe);
+        assert_eq!(cursor.value(), expect.as_ref());
+        let bytes = expect.map(|x| (x * 3).to_le_bytes());
+        assert_eq!(cursor.key_bytes(), bytes.as_ref().map(|x| &x[..]));
+    }
+
+    let mut cursor = tree.cursor_mut();
+    cursor.seek(&300);
+    while cursor.value().is_some_and(|x| x % 2 == 0) {
+        assert!(cursor.remove_current().is_some());
+    }
+    assert_eq!(tree.get(&300), None);
+    let left = (0..1_000u64)
+        .filter(|x| tree.get(&(x * 3)).is_some())
+        .count();
+    assert!(left < 1_000);
+    assert_eq!(tree.len(), left);
+}
+
+#[test]
+#[should_panic]
+fn cursor_insert_before_unordered() {
+    let mut tree = Art::<str, u64>::new();
+    tree.insert("a", 0);
+    tree.insert("c", 1);
+    let mut cursor = tree.cursor_mut();
+    cursor.seek("c");
+    cursor.insert_before("d", 2);
+}
+
+#[test]
+fn iter_mut_retain() {
+    let mut state = XorState::new();
+    let mut keys: Vec<String> = (0..10_000)
+        .map(|_| {
+            let k = xorshift(&mut state);
+            format!("{:x}", k >> (k % 48))
+        })
+        .collect();
+    sort_str(&mut keys);
+    keys.dedup();
+    let mut tree = Art::<str, usize>::from_sorted_iter(keys.iter().map(|x| x.as_str()).zip(0..));
+
+    let mut iter = tree.iter_mut();
+    for (i, k) in keys.iter().enumerate() {
+        let (key, value) = iter.next().unwrap();
+        assert_eq!(key, k);
+        *value += i;
+    }
+    assert!(iter.next().is_none());
+    tree.values_mut().for_each(|x| *x /= 2);
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k).copied(), Some(i));
+    }
+
+    let mut visited = Vec::new();
+    tree.retain(|k, v| {
+        visited.push(k.to_owned());
+        *v += 1;
+        *v % 3 != 1
+    });
+    assert_eq!(visited, keys);
+    let kept: Vec<_> = (0..keys.len()).filter(|i| i % 3 != 0).collect();
+    assert_eq!(tree.len(), kept.len());
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k).copied(), (i % 3 != 0).then_some(i + 1));
+    }
+    // Nodes which lost branches are shrunk to the size a tree built from the remaining keys has.
+    let expect =
+        Art::<str, usize>::from_sorted_iter(kept.iter().map(|i| (keys[*i].as_str(), i + 1)));
+    assert_eq!(tree.stats(), expect.stats());
+    #[cfg(feature = "order-statistics")]
+    for (i, k) in kept.iter().enumerate().step_by(97) {
+        assert_eq!(tree.rank(&keys[*k]), i);
+    }
+
+    tree.retain(|k, _| !k.starts_with('1'));
+    for (i, k) in keys.iter().enumerate() {
+        let expect = (i % 3 != 0 && !k.starts_with('1')).then_some(i + 1);
+        assert_eq!(tree.get(k).copied(), expect);
+    }
+    tree.retain(|_, _| false);
+    assert!(tree.is_empty());
+    assert!(tree.iter().next().is_none());
+
+    // Every ascii character after every ascii character, so the nodes start out as node256.
+    let mut keys: Vec<String> = (0..128u8)
+        .flat_map(|a| (0..128u8).map(move |b| [a as char, b as char].iter().collect()))
+        .collect();
+    sort_str(&mut keys);
+    let mut tree = Art::<str, usize>::from_sorted_iter(keys.iter().map(|x| x.as_str()).zip(0..));
+    tree.retain(|_, v| *v % 5 == 0);
+    let expect = Art::<str, usize>::from_sorted_iter(
+        keys.iter()
+            .map(|x| x.as_str())
+            .zip(0..)
+            .filter(|x| x.1 % 5 == 0),
+    );
+    assert_eq!(tree.len(), expect.len());
+    assert_eq!(tree.stats(), expect.stats());
+    // A single remaining key collapses the tree into a leaf.
+    tree.retain(|_, v| *v == 5);
+    assert_eq!(tree.stats().branches(), 0);
+    assert_eq!(tree.get(&keys[5]), Some(&5));
+}
+
+#[test]
+fn split_append_drain() {
+    let mut state = XorState::new();
+    let mut keys: Vec<String> = (0..10_000)
+        .map(|_| {
+            let k = xorshift(&mut state);
+            format!("{:x}", k >> (k % 48))
+        })
+        .collect();
+    sort_str(&mut keys);
+    keys.dedup();
+    let build = |keys: &[String], offset: usize| {
+        Art::<str, usize>::from_sorted_iter(keys.iter().map(|x| x.as_str()).zip(offset..))
+    };
+    let keys_of = |tree: &Art<str, usize>| {
+        let mut res = Vec::new();
+        let mut iter = tree.iter();
+        while let Some((k, _)) = iter.next() {
+            res.push(k.to_owned());
+        }
+        res
+    };
+    let mut tree = build(&keys, 0);
+    let full = tree.stats();
+
+    for _ in 0..50 {
+        let probe = format!("{:x}", xorshift(&mut state) >> (xorshift(&mut state) % 60));
+        let bytes = |x: &str| [x.as_bytes(), &[INVALID_STR_BYTE]].concat();
+        let at = keys.partition_point(|x| bytes(x) < bytes(&probe));
+
+        let mut right = tree.split_off(&probe);
+        assert_eq!(tree.len(), at);
+        assert_eq!(right.len(), keys.len() - at);
+        // Nodes on the path are rebuilt at the size a tree built from their keys has.
+        assert_eq!(tree.stats(), build(&keys[..at], 0).stats());
+        assert_eq!(right.stats(), build(&keys[at..], at).stats());
+        assert_eq!(keys_of(&tree), keys[..at]);
+        assert_eq!(keys_of(&right), keys[at..]);
+        #[cfg(feature = "order-statistics")]
+        if let Some(k) = keys.get(at + 1) {
+            assert_eq!(right.rank(k), 1);
+        }
+
+        tree.append(&mut right);
+        assert!(right.is_empty());
+        assert_eq!(tree.len(), keys.len());
+        assert_eq!(tree.stats(), full);
+        for (i, k) in keys.iter().enumerate().step_by(31) {
+            assert_eq!(tree.get(k).copied(), Some(i));
+            #[cfg(feature = "order-statistics")]
+            assert_eq!(tree.rank(k), i);
+        }
+    }
+
+    // Joining trees built separately.
+    let mut tree = build(&keys[..keys.len() / 3], 0);
+    let mut right = build(&keys[keys.len() / 3..], keys.len() / 3);
+    tree.append(&mut right);
+    assert_eq!(tree.stats(), full);
+    let mut empty = Art::new();
+    tree.append(&mut empty);
+    empty.append(&mut tree);
+    assert!(tree.is_empty());
+    let mut tree = empty;
+    assert_eq!(tree.stats(), full);
+
+    let (a, b) = (keys[1000].as_str(), keys[2000].as_str());
+    let drained: Vec<_> = tree
+        .drain_range((Bound::Excluded(a), Bound::Included(b)))
+        .collect();
+    assert!(drained
+        .iter()
+        .map(|(k, v)| (k.as_str(), *v))
+        .eq(keys[1001..=2000].iter().map(|x| x.as_str()).zip(1001..)));
+    assert_eq!(tree.len(), keys.len() - 1000);
+    assert!(tree.get(a).is_some() && tree.get(b).is_none());
+    assert!(tree.get(&keys[2001]).is_some());
+    assert!(tree.drain_range(b..a).next().is_none());
+    assert_eq!(tree.drain_range(..a).count(), 1000);
+    let mut drain = tree.drain_range(&*keys[3000]..);
+    assert_eq!(drain.len(), keys.len() - 3000);
+    // The drain takes the tree apart from both ends.
+    for i in 0..100 {
+        let (k, v) = drain.next().unwrap();
+        assert_eq!((k.as_str(), v), (keys[3000 + i].as_str(), 3000 + i));
+        let (k, v) = drain.next_back().unwrap();
+        let back = keys.len() - 1 - i;
+        assert_eq!((k.as_str(), v), (keys[back].as_str(), back));
+    }
+    assert_eq!(drain.len(), keys.len() - 3200);
+    assert!(drain.map(|x| x.1).eq(3100..keys.len() - 100));
+    let rest: Vec<_> = tree.drain().map(|x| x.1).collect();
+    assert_eq!(
+        rest,
+        [1000].into_iter().chain(2001..3000).collect::<Vec<_>>()
+    );
+    assert!(tree.is_empty());
+    assert!(keys_of(&tree).is_empty());
+
+    let mut tree = Art::<u64, u64>::new();
+    for i in 0..1000 {
+        tree.insert(&(i * 7), i);
+    }
+    let right = tree.split_off(&3500);
+    assert_eq!(tree.len() + right.len(), 1000);
+    assert!((0..1000).all(|i| tree.get(&(i * 7)).is_some() != right.get(&(i * 7)).is_some()));
+}
+
+#[test]
+#[should_panic]
+fn append_unordered() {
+    let mut tree = Art::<str, ()>::new();
+    tree.insert("b", ());
+    let mut other = Art::<str, ()>::new();
+    other.insert("a", ());
+    tree.append(&mut other);
+}
+
+#[cfg(feature = "inline-leafs")]
+#[test]
+fn inline_leafs() {
+    // Big endian keys which differ in their last byte end at a branch, so their leafs have no
+    // prefix left.
+    let keys: Vec<u64> = (0..2000u64).map(u64::to_be).collect();
+    let mut tree = Art::<u64, u32>::new();
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.insert(k, i as u32), None);
+    }
+    let stats = tree.stats();
+    assert_eq!(stats.leafs, keys.len());
+    assert_eq!(stats.inline_leafs, keys.len());
+    assert_eq!(tree.insert(&keys[7], 70), Some(7));
+    *tree.get_mut(&keys[7]).unwrap() = 7;
+    tree.values_mut().for_each(|x| *x += 1);
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(tree.get(k).copied(), Some(i as u32 + 1));
+    }
+
+    // The last remaining leaf of a node takes over the prefix of the node and stays inline, it
+    // gives the prefix back once the keys around it return.
+    for k in &keys[1..256] {
+        assert!(tree.remove(k).is_some());
+    }
+    let stats = tree.stats();
+    assert_eq!(stats.inline_leafs, tree.len());
+    // The 6 bytes shared by all keys and the last byte of the remaining key.
+    assert_eq!(stats.prefix_bytes, 7);
+    assert_eq!(tree.get(&keys[0]).copied(), Some(1));
+    let clone = tree.clone();
+    for (i, k) in keys[..1000].iter().enumerate() {
+        tree.insert(k, i as u32 + 1);
+    }
+    for k in keys.iter().step_by(2) {
+        assert!(tree.remove(k).is_some());
+    }
+    let mut right = tree.split_off(&keys[500]);
+    tree.append(&mut right);
+    for (i, k) in keys.iter().enumerate() {
+        let expect = (i % 2 == 1).then_some(i as u32 + 1);
+        assert_eq!(tree.get(k).copied(), expect);
+        let expect = (i == 0 || i >= 256).then_some(i as u32 + 1);
+        assert_eq!(clone.get(k).copied(), expect);
+    }
+    assert_eq!(tree.stats().inline_leafs, tree.len());
+
+    // Values which don't fit in the pointer are moved into an allocation of their own, as are
+    // leafs with a prefix.
+    let mut tree = Art::<u64, u64>::new();
+    for (i, k) in keys.iter().enumerate() {
+        tree.insert(k, i as u64);
+    }
+    assert_eq!(tree.stats().inline_leafs, keys.len());
+    for k in &keys[1..256] {
+        assert!(tree.remove(k).is_some());
+    }
+    assert_eq!(tree.stats().inline_leafs, tree.len());
+    let random: Vec<u64> = (1..2000u64)
+        .map(|x| x.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .collect();
+    for k in &random {
+        tree.insert(k, *k);
+    }
+    assert_eq!(tree.stats().inline_leafs, tree.len());
+    for k in &random {
+        assert_eq!(tree.remove(k), Some(*k));
+    }
+    assert_eq!(tree.get(&keys[0]).copied(), Some(0));
+    let mut strings = Art::<u64, String>::new();
+    for (i, k) in keys.iter().enumerate() {
+        strings.insert(k, i.to_string());
+    }
+    let right = strings.split_off(&keys[1000]);
+    assert_eq!(strings.remove(&keys[3]).as_deref(), Some("3"));
+    assert_eq!(right.get(&keys[1500]).map(String::as_str), Some("1500"));
+    assert_eq!(strings.stats().inline_leafs, strings.len());
+
+    // Prefixes too long for their length to fit a single byte.
+    let long = "x".repeat(300);
+    let longer = format!("{long}y");
+    let mut tree = Art::<str, u64>::new();
+    tree.insert("a", 1);
+    tree.insert(&long, 2);
+    assert!(tree.stats().prefix_bytes > 300);
+    tree.insert(&longer, 3);
+    assert_eq!(tree.remove("a"), Some(1));
+    assert_eq!(tree.get(&long), Some(&2));
+    assert_eq!(tree.get(&longer), Some(&3));
+    assert_eq!(tree.remove(&longer), Some(3));
+    assert_eq!(tree.stats().prefix_bytes, long.len() + 1);
+    assert_eq!(tree.get(&long), Some(&2));
+
+    // Values without a size are stored in the pointer unless the leaf has a prefix.
+    let mut set = Art::<str, ()>::new();
+    for k in ["a", "ab", "abc", "b"] {
+        set.insert(k, ());
+    }
+    assert_eq!(set.stats().inline_leafs, 4);
+    let union = set.union_with(&set.clone(), |_, _| ());
+    assert_eq!(union.stats(), set.stats());
+    set.retain(|k, _| k != "b");
+    assert_eq!(set.remove("a"), Some(()));
+    assert_eq!(set.remove("ab"), Some(()));
+    assert_eq!(set.get("abc"), Some(&()));
+    assert_eq!(set.len(), 1);
+    assert_eq!(set.stats().inline_leafs, 1);
+}
@@ -0,0 +1,45 @@
+//! Finding the keys within an edit distance of a string.
+
+use crate::Automaton;
+
+/// A Levenshtein automaton, its states are the rows of the edit distance matrix between the query
+/// and the characters of a key read so far.
+pub struct Levenshtein {
+    query: Vec<char>,
+    max_edits: usize,
+}
+
+impl Levenshtein {
+    pub fn new(query: &str, max_edits: usize) -> Self {
+        Levenshtein {
+            query: query.chars().collect(),
+            max_edits,
+        }
+    }
+}
+
+impl Automaton for Levenshtein {
+    type State = Vec<usize>;
+    /// The edit distance between the query and the key.
+    type Match = usize;
+
+    fn start(&self) -> Vec<usize> {
+        (0..=self.query.len()).collect()
+    }
+
+    fn step(&self, row: &Vec<usize>, c: char) -> Option<Vec<usize>> {
+        let mut next = Vec::with_capacity(row.len());
+        next.push(row[0] + 1);
+        for (i, q) in self.query.iter().enumerate() {
+            let substitute = row[i] + (*q != c) as usize;
+            next.push(substitute.min(row[i + 1] + 1).min(next[i] + 1));
+        }
+        // A key starting with the characters read can still be within the maximum edit distance.
+        next.iter().any(|x| *x <= self.max_edits).then_some(next)
+    }
+
+    fn accept(&self, row: &Vec<usize>) -> Option<usize> {
+        let distance = *row.last().unwrap();
+        (distance <= self.max_edits).then_some(distance)
+    }
+}
@@ -0,0 +1,108 @@
+//! Automata over the characters of string keys, shared by the `art` and `aart` trees.
+//!
+//! The trees walk their nodes with an automaton and skip a subtree as soon as the automaton
+//! rejects the key bytes leading to it.
+
+mod fuzzy;
+mod pattern;
+
+pub use fuzzy::Levenshtein;
+pub use pattern::Glob;
+#[cfg(feature = "regex")]
+pub use pattern::Regex;
+#[cfg(feature = "regex")]
+pub use regex_automata::dfa::dense::BuildError;
+
+/// An automaton reading the characters of a key.
+pub trait Automaton {
+    type State: Clone;
+
+    /// What is reported for a matching key, e.g. its edit distance to a query.
+    type Match;
+
+    /// The state before reading any characters.
+    fn start(&self) -> Self::State;
+
+    /// Returns the state after reading a character, or `None` if no key continuing with the
+    /// characters read can match.
+    fn step(&self, state: &Self::State, c: char) -> Option<Self::State>;
+
+    /// Returns the match if a key consisting of the characters read matches.
+    fn accept(&self, state: &Self::State) -> Option<Self::Match>;
+
+    /// Read all the characters of a key, returns the match if the key matches.
+    fn run(&self, key: &str) -> Option<Self::Match> {
+        let state = key
+            .chars()
+            .try_fold(self.start(), |state, c| self.step(&state, c))?;
+        self.accept(&state)
+    }
+}
+
+/// An automaton state fed with the bytes of a key, which are decoded into characters.
+pub struct Reader<A: Automaton> {
+    state: A::State,
+    /// The bytes read of the character which is not yet complete.
+    pending: [u8; 4],
+    len: u8,
+}
+
+impl<A: Automaton> Clone for Reader<A> {
+    fn clone(&self) -> Self {
+        Reader {
+            state: self.state.clone(),
+            pending: self.pending,
+            len: self.len,
+        }
+    }
+}
+
+impl<A: Automaton> Reader<A> {
+    pub fn new(automaton: &A) -> Self {
+        Reader {
+            state: automaton.start(),
+            pending: [0; 4],
+            len: 0,
+        }
+    }
+
+    /// Feed the next byte of the key, returns `false` if no key starting with the bytes read can
+    /// match.
+    ///
+    /// Bytes which can't start a character, like the terminator of string keys, are skipped.
+    pub fn push(&mut self, automaton: &A, byte: u8) -> bool {
+        if self.len == 0 && matches!(byte, 0x80..=0xBF | 0xF8..=0xFF) {
+            return true;
+        }
+        self.pending[self.len as usize] = byte;
+        self.len += 1;
+
+        let width = match self.pending[0] {
+            0..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        if (self.len as usize) < width {
+            return true;
+        }
+        self.len = 0;
+        let c = std::str::from_utf8(&self.pending[..width])
+            .expect("string keys are valid UTF-8")
+            .chars()
+            .next()
+            .unwrap();
+        match automaton.step(&self.state, c) {
+            Some(next) => {
+                self.state = next;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the match if the key read so far matches.
+    pub fn accept(&self, automaton: &A) -> Option<A::Match> {
+        automaton.accept(&self.state)
+    }
+}
@@ -0,0 +1,209 @@
+//! Automata matching keys against glob patterns and regular expressions.
+
+use crate::Automaton;
+
+enum Token {
+    Char(char),
+    /// `?`, matches any single character.
+    Any,
+    /// `*`, matches any sequence of characters.
+    Star,
+    /// `[...]`, matches a single character in, or if negated not in, one of the ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(x) => *x == c,
+            Token::Any => true,
+            Token::Star => false,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+            }
+        }
+    }
+}
+
+/// An automaton for a glob pattern, its states are the positions in the pattern the characters
+/// read so far can end at.
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    /// Parse a glob pattern with the syntax of the redis `KEYS` command.
+    ///
+    /// `\` escapes the next character. A `[` without a closing `]` and a trailing `\` match
+    /// themselves.
+    pub fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                '*' => {
+                    if matches!(tokens.last(), Some(Token::Star)) {
+                        i += 1;
+                        continue;
+                    }
+                    Token::Star
+                }
+                '?' => Token::Any,
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    Token::Char(chars[i])
+                }
+                '[' => match Self::parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        i += len;
+                        token
+                    }
+                    None => Token::Char('['),
+                },
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Glob { tokens }
+    }
+
+    /// Returns the literal characters at the start of the pattern, every key matching the pattern
+    /// starts with them.
+    pub fn literal_prefix(&self) -> String {
+        self.tokens
+            .iter()
+            .map_while(|x| match x {
+                Token::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parse the class after a `[`, returns the class and the amount of characters up to and
+    /// including the closing `]`.
+    fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+        let negated = chars.first() == Some(&'^');
+        let mut i = negated as usize;
+        let mut ranges = Vec::new();
+        loop {
+            let mut lo = *chars.get(i)?;
+            match lo {
+                ']' => return Some((Token::Class { negated, ranges }, i + 1)),
+                '\\' => {
+                    i += 1;
+                    lo = *chars.get(i)?;
+                }
+                _ => {}
+            }
+            let mut hi = lo;
+            if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|x| *x != ']') {
+                hi = chars[i + 2];
+                i += 2;
+            }
+            ranges.push((lo.min(hi), lo.max(hi)));
+            i += 1;
+        }
+    }
+
+    /// Add a position to the state together with the positions after any stars at it.
+    fn add(&self, state: &mut Vec<usize>, mut pos: usize) {
+        loop {
+            if !state.contains(&pos) {
+                state.push(pos);
+            }
+            if !matches!(self.tokens.get(pos), Some(Token::Star)) {
+                return;
+            }
+            pos += 1;
+        }
+    }
+}
+
+impl Automaton for Glob {
+    type State = Vec<usize>;
+    type Match = ();
+
+    fn start(&self) -> Vec<usize> {
+        let mut state = Vec::new();
+        self.add(&mut state, 0);
+        state
+    }
+
+    fn step(&self, state: &Vec<usize>, c: char) -> Option<Vec<usize>> {
+        let mut next = Vec::new();
+        for pos in state {
+            match self.tokens.get(*pos) {
+                Some(Token::Star) => self.add(&mut next, *pos),
+                Some(x) if x.matches(c) => self.add(&mut next, pos + 1),
+                _ => {}
+            }
+        }
+        (!next.is_empty()).then_some(next)
+    }
+
+    fn accept(&self, state: &Vec<usize>) -> Option<()> {
+        state.contains(&self.tokens.len()).then_some(())
+    }
+}
+
+#[cfg(feature = "regex")]
+pub use self::regex::Regex;
+
+#[cfg(feature = "regex")]
+mod regex {
+    use super::Automaton;
+    use regex_automata::{
+        dfa::{dense, Automaton as _},
+        util::{primitives::StateID, start},
+        Anchored, MatchKind,
+    };
+
+    /// An automaton for a regular expression which has to match the whole key.
+    pub struct Regex {
+        dfa: dense::DFA<Vec<u32>>,
+        start: StateID,
+    }
+
+    impl Regex {
+        pub fn new(pattern: &str) -> Result<Self, Box<dense::BuildError>> {
+            let dfa = dense::Builder::new()
+                .configure(dense::Config::new().match_kind(MatchKind::All))
+                .build(&format!("(?:{pattern})$"))
+                .map_err(Box::new)?;
+            let start = dfa
+                .start_state(&start::Config::new().anchored(Anchored::Yes))
+                .expect("the dfa has anchored start states");
+            Ok(Regex { dfa, start })
+        }
+    }
+
+    impl Automaton for Regex {
+        type State = StateID;
+        type Match = ();
+
+        fn start(&self) -> StateID {
+            self.start
+        }
+
+        fn step(&self, state: &StateID, c: char) -> Option<StateID> {
+            let mut state = *state;
+            for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                state = self.dfa.next_state(state, b);
+                if self.dfa.is_dead_state(state) {
+                    return None;
+                }
+            }
+            Some(state)
+        }
+
+        fn accept(&self, state: &StateID) -> Option<()> {
+            let eoi = self.dfa.next_eoi_state(*state);
+            self.dfa.is_match_state(eoi).then_some(())
+        }
+    }
+}
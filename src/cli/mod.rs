@@ -0,0 +1,347 @@
+//! The interactive command interpreter.
+//!
+//! Every line of input is a single command. Arguments are separated by whitespace, an argument
+//! containing whitespace can be written between double quotes in which `\"`, `\\`, `\n` and `\t`
+//! are recognized as escapes. The output of `dump` is itself a valid script which recreates the
+//! dumped entries.
+//!
+//! Scans return entries in the order of the tree, in which a key sorts after every key it is a
+//! prefix of. `ab` sorts before `a`, so `scan a b` includes `a` and `ba` but not `ab`. Use
+//! `prefix` to find all keys starting with a string.
+
+use crate::store::Store;
+use art::{raw::BorrowIter, Art};
+use std::{
+    io::{self, BufRead, Write},
+    ops::Bound,
+};
+
+#[cfg(test)]
+mod test;
+
+pub const HELP: &str = "\
+commands:
+  put <key> <value>   insert or replace an entry
+  get <key>           print the value of an entry
+  del <key>           remove an entry
+  scan <from> [to]    print the entries with keys from `from` up to, but excluding, `to`
+  prefix <prefix>     print the entries with keys starting with `prefix`
+  stats               print statistics about the tree
+  dump                print all entries as a script of put commands
+  checkpoint          write a snapshot and clear the log, only with a data directory
+  help                print this message
+  quit                exit
+";
+
+/// The tree the commands operate on.
+pub enum Backend {
+    Memory(Art<str, String>),
+    Store(Store),
+}
+
+impl Backend {
+    fn tree(&self) -> &Art<str, String> {
+        match self {
+            Backend::Memory(x) => x,
+            Backend::Store(x) => x.tree(),
+        }
+    }
+
+    fn put(&mut self, key: &str, value: String) -> io::Result<Option<String>> {
+        match self {
+            Backend::Memory(x) => Ok(x.insert(key, value)),
+            Backend::Store(x) => x.put(key, value),
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> io::Result<Option<String>> {
+        match self {
+            Backend::Memory(x) => Ok(x.remove(key)),
+            Backend::Store(x) => x.delete(key),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Put { key: String, value: String },
+    Get { key: String },
+    Del { key: String },
+    Scan { from: String, to: Option<String> },
+    Prefix { prefix: String },
+    Stats,
+    Dump,
+    Checkpoint,
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// Parse a line of input, returns `None` for an empty line.
+    pub fn parse(line: &str) -> Result<Option<Command>, String> {
+        let args = split_args(line)?;
+        let Some((name, args)) = args.split_first() else {
+            return Ok(None);
+        };
+
+        let expect = |min: usize, max: usize, usage: &str| {
+            if args.len() < min || args.len() > max {
+                Err(format!("usage: {usage}"))
+            } else {
+                Ok(())
+            }
+        };
+
+        let command = match name.as_str() {
+            "put" => {
+                expect(2, 2, "put <key> <value>")?;
+                Command::Put {
+                    key: args[0].clone(),
+                    value: args[1].clone(),
+                }
+            }
+            "get" => {
+                expect(1, 1, "get <key>")?;
+                Command::Get {
+                    key: args[0].clone(),
+                }
+            }
+            "del" => {
+                expect(1, 1, "del <key>")?;
+                Command::Del {
+                    key: args[0].clone(),
+                }
+            }
+            "scan" => {
+                expect(1, 2, "scan <from> [to]")?;
+                Command::Scan {
+                    from: args[0].clone(),
+                    to: args.get(1).cloned(),
+                }
+            }
+            "prefix" => {
+                expect(1, 1, "prefix <prefix>")?;
+                Command::Prefix {
+                    prefix: args[0].clone(),
+                }
+            }
+            "stats" => {
+                expect(0, 0, "stats")?;
+                Command::Stats
+            }
+            "dump" => {
+                expect(0, 0, "dump")?;
+                Command::Dump
+            }
+            "checkpoint" => {
+                expect(0, 0, "checkpoint")?;
+                Command::Checkpoint
+            }
+            "help" => Command::Help,
+            "quit" | "exit" => Command::Quit,
+            x => return Err(format!("unknown command `{x}`, try `help`")),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|x| x.is_whitespace()).is_some() {}
+        let Some(c) = chars.next() else {
+            return Ok(args);
+        };
+
+        let mut arg = String::new();
+        if c != '"' {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|x| !x.is_whitespace()) {
+                arg.push(c);
+            }
+            args.push(arg);
+            continue;
+        }
+
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('"') => arg.push('"'),
+                    Some('\\') => arg.push('\\'),
+                    Some('n') => arg.push('\n'),
+                    Some('t') => arg.push('\t'),
+                    Some(x) => return Err(format!("unknown escape `\\{x}`")),
+                    None => return Err("unterminated string".to_owned()),
+                },
+                Some(x) => arg.push(x),
+                None => return Err("unterminated string".to_owned()),
+            }
+        }
+        if chars.peek().is_some_and(|x| !x.is_whitespace()) {
+            return Err("expected whitespace after string".to_owned());
+        }
+        args.push(arg);
+    }
+}
+
+/// Quote a string if it can't be parsed back as a single argument as is.
+pub fn quote(s: &str) -> String {
+    if !s.is_empty() && !s.starts_with('"') && !s.contains(char::is_whitespace) {
+        return s.to_owned();
+    }
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            x => res.push(x),
+        }
+    }
+    res.push('"');
+    res
+}
+
+pub struct Repl {
+    backend: Backend,
+}
+
+impl Repl {
+    pub fn new(backend: Backend) -> Self {
+        Repl { backend }
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    /// Run commands read from `input` until the input ends or a `quit` command.
+    ///
+    /// Errors from commands are written to `out` and don't stop the loop, only errors from
+    /// reading the input or writing the output are returned.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        out: &mut W,
+        prompt: bool,
+    ) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            if prompt {
+                write!(out, "> ")?;
+                out.flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            if !self.execute(&line, out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Execute a single line, returns false if the line asked to quit.
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let command = match Command::parse(line) {
+            Ok(Some(x)) => x,
+            Ok(None) => return Ok(true),
+            Err(e) => {
+                writeln!(out, "error: {e}")?;
+                return Ok(true);
+            }
+        };
+        if command == Command::Quit {
+            return Ok(false);
+        }
+        self.apply(command, out)?;
+        Ok(true)
+    }
+
+    /// Apply a command, writing its output to `out`.
+    ///
+    /// Errors from the command are written to `out` as well, only errors from writing the output
+    /// are returned.
+    fn apply<W: Write>(&mut self, command: Command, out: &mut W) -> io::Result<()> {
+        match command {
+            Command::Put { key, value } => match self.backend.put(&key, value) {
+                Ok(_) => writeln!(out, "OK"),
+                Err(e) => writeln!(out, "error: {e}"),
+            },
+            Command::Get { key } => match self.backend.tree().get(&key) {
+                Some(x) => writeln!(out, "{}", quote(x)),
+                None => writeln!(out, "(not found)"),
+            },
+            Command::Del { key } => match self.backend.delete(&key) {
+                Ok(Some(_)) => writeln!(out, "OK"),
+                Ok(None) => writeln!(out, "(not found)"),
+                Err(e) => writeln!(out, "error: {e}"),
+            },
+            Command::Scan { from, to } => {
+                let tree = self.backend.tree();
+                let end = match to.as_deref() {
+                    Some(x) => Bound::Excluded(x),
+                    None => Bound::Unbounded,
+                };
+                write_entries(out, tree.range((Bound::Included(from.as_str()), end)))
+            }
+            Command::Prefix { prefix } => {
+                write_entries(out, self.backend.tree().scan_prefix(&prefix))
+            }
+            Command::Stats => self.write_stats(out),
+            Command::Dump => {
+                let mut iter = self.backend.tree().iter();
+                while let Some((k, v)) = iter.next() {
+                    writeln!(out, "put {} {}", quote(k), quote(v))?;
+                }
+                Ok(())
+            }
+            Command::Checkpoint => match &mut self.backend {
+                Backend::Memory(_) => writeln!(out, "error: no data directory to checkpoint to"),
+                Backend::Store(x) => match x.checkpoint() {
+                    Ok(()) => writeln!(out, "OK"),
+                    Err(e) => writeln!(out, "error: {e}"),
+                },
+            },
+            Command::Help => write!(out, "{HELP}"),
+            Command::Quit => Ok(()),
+        }
+    }
+
+    fn write_stats<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let tree = self.backend.tree();
+        let stats = tree.stats();
+        let (mut key_bytes, mut value_bytes) = (0, 0);
+        let mut iter = tree.iter();
+        while let Some((k, v)) = iter.next() {
+            key_bytes += k.len();
+            value_bytes += v.len();
+        }
+
+        writeln!(out, "entries:      {}", tree.len())?;
+        writeln!(out, "key bytes:    {key_bytes}")?;
+        writeln!(out, "value bytes:  {value_bytes}")?;
+        writeln!(out, "node4:        {}", stats.node4)?;
+        writeln!(out, "node16:       {}", stats.node16)?;
+        writeln!(out, "node48:       {}", stats.node48)?;
+        writeln!(out, "node256:      {}", stats.node256)?;
+        writeln!(out, "prefix bytes: {}", stats.prefix_bytes)?;
+        writeln!(out, "max depth:    {}", stats.max_depth)?;
+        if let Backend::Store(x) = &self.backend {
+            writeln!(out, "wal bytes:    {}", x.wal_len())?;
+        }
+        Ok(())
+    }
+}
+
+fn write_entries<W: Write>(out: &mut W, mut iter: BorrowIter<'_, str, String>) -> io::Result<()> {
+    let mut count = 0;
+    while let Some((k, v)) = iter.next() {
+        writeln!(out, "{} {}", quote(k), quote(v))?;
+        count += 1;
+    }
+    writeln!(out, "({count} entries)")
+}
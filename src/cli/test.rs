@@ -0,0 +1,131 @@
+use super::{quote, Backend, Command, Repl};
+use crate::store::Store;
+use art::Art;
+
+fn run(repl: &mut Repl, script: &str) -> String {
+    let mut out = Vec::new();
+    repl.run(script.as_bytes(), &mut out, false).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn parse() {
+    assert_eq!(Command::parse("  ").unwrap(), None);
+    assert_eq!(
+        Command::parse(r#"put "hello world" "a \"quoted\"\n\\ value""#).unwrap(),
+        Some(Command::Put {
+            key: "hello world".to_owned(),
+            value: "a \"quoted\"\n\\ value".to_owned(),
+        })
+    );
+    assert_eq!(
+        Command::parse("scan a").unwrap(),
+        Some(Command::Scan {
+            from: "a".to_owned(),
+            to: None
+        })
+    );
+    assert_eq!(
+        Command::parse(r#"get """#).unwrap(),
+        Some(Command::Get { key: String::new() })
+    );
+    assert!(Command::parse("get").is_err());
+    assert!(Command::parse("put a b c").is_err());
+    assert!(Command::parse(r#"get "unterminated"#).is_err());
+    assert!(Command::parse(r#"get "a"b"#).is_err());
+    assert!(Command::parse("frobnicate").is_err());
+
+    for s in [
+        "",
+        "plain",
+        "with space",
+        "\"quote",
+        "tab\tnew\nline",
+        "back\\slash",
+    ] {
+        assert_eq!(
+            Command::parse(&format!("get {}", quote(s))).unwrap(),
+            Some(Command::Get { key: s.to_owned() })
+        );
+    }
+}
+
+#[test]
+fn commands() {
+    let mut repl = Repl::new(Backend::Memory(Art::new()));
+    let out = run(
+        &mut repl,
+        "put apple red
+put banana yellow
+put apricot orange
+put \"blue berry\" blue
+get apple
+get cherry
+del banana
+del banana
+scan apricot blue
+prefix ap
+bogus
+get
+",
+    );
+    // `blue berry` sorts before `blue` in the order of the tree.
+    assert_eq!(
+        out,
+        "OK
+OK
+OK
+OK
+red
+(not found)
+OK
+(not found)
+apricot orange
+\"blue berry\" blue
+(2 entries)
+apple red
+apricot orange
+(2 entries)
+error: unknown command `bogus`, try `help`
+error: usage: get <key>
+"
+    );
+
+    let mut ordered = Repl::new(Backend::Memory(Art::new()));
+    let out = run(
+        &mut ordered,
+        "put a 1\nput ab 2\nput ba 3\nput b 4\nscan a b\n",
+    );
+    assert_eq!(out, "OK\nOK\nOK\nOK\na 1\nba 3\n(2 entries)\n");
+
+    let out = run(&mut repl, "stats\nquit\nput after quit\n");
+    assert!(out.starts_with("entries:      3\n"));
+    assert_eq!(run(&mut repl, "get after"), "(not found)\n");
+    assert!(run(&mut repl, "checkpoint").starts_with("error:"));
+}
+
+#[test]
+fn dump_roundtrip() {
+    let mut repl = Repl::new(Backend::Memory(Art::new()));
+    run(
+        &mut repl,
+        "put a 1\nput \"with space\" \"\"\nput \"esc\\\\\" \"line\\nbreak\"\nput b \"\\\"\"\n",
+    );
+    let dump = run(&mut repl, "dump");
+    assert_eq!(dump.lines().count(), 4);
+
+    let mut copy = Repl::new(Backend::Memory(Art::new()));
+    run(&mut copy, &dump);
+    assert_eq!(run(&mut copy, "dump"), dump);
+}
+
+#[test]
+fn data_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut repl = Repl::new(Backend::Store(Store::open(dir.path()).unwrap()));
+    run(&mut repl, "put a 1\nput b 2\ncheckpoint\nput c 3\ndel a\n");
+    drop(repl);
+
+    let mut repl = Repl::new(Backend::Store(Store::open(dir.path()).unwrap()));
+    assert_eq!(run(&mut repl, "dump"), "put b 2\nput c 3\n");
+}
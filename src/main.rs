@@ -1,99 +1,108 @@
-fn main() {
-    let mut tree = art::Art::<str, usize>::new();
-    tree.print();
-    tree.insert("hello world", 1);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    tree.print();
-    tree.insert("hello moon ", 2);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-
-    tree.insert("h", 3);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-
-    tree.insert("hello foo", 4);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-
-    tree.insert("hello boo", 5);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-    assert_eq!(tree.get("hello boo").copied(), Some(5));
-
-    tree.insert("hello voo", 6);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-    assert_eq!(tree.get("hello boo").copied(), Some(5));
-    assert_eq!(tree.get("hello voo").copied(), Some(6));
-
-    tree.insert("hello voa", 7);
-
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-    assert_eq!(tree.get("hello boo").copied(), Some(5));
-    assert_eq!(tree.get("hello voo").copied(), Some(6));
-    assert_eq!(tree.get("hello voa").copied(), Some(7));
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+};
+use toydb::{
+    cli::{Backend, Repl},
+    server::Server,
+    store::Store,
+};
+
+const USAGE: &str = "\
+usage: toydb [--data-dir <dir>]
+       toydb serve [--listen <addr>]
+
+Without a subcommand reads commands from stdin, run `help` for a list of commands. Without a data
+directory the tree only lives in memory.
+
+`serve` listens for connections speaking the Redis protocol, by default on 127.0.0.1:6379. The
+server only keeps its entries in memory.
+";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(|x| x.as_str()) == Some("serve") {
+        args.next();
+        return serve(args);
+    }
 
-    tree.insert(
-        "hello a very very long prefix that doesn't fit into an inlined buffer.",
-        8,
-    );
+    let mut data_dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data-dir" => {
+                let Some(dir) = args.next() else {
+                    eprint!("missing argument to --data-dir\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                };
+                data_dir = Some(PathBuf::from(dir));
+            }
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            x => {
+                eprint!("unknown argument `{x}`\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
 
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-    assert_eq!(tree.get("hello boo").copied(), Some(5));
-    assert_eq!(tree.get("hello voo").copied(), Some(6));
-    assert_eq!(tree.get("hello voa").copied(), Some(7));
-    assert_eq!(
-        tree.get("hello a very very long prefix that doesn't fit into an inlined buffer.")
-            .copied(),
-        Some(8)
-    );
+    let backend = match data_dir {
+        Some(dir) => match Store::open(&dir) {
+            Ok(x) => Backend::Store(x),
+            Err(e) => {
+                eprintln!("failed to open store at `{}`: {e}", dir.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Backend::Memory(Default::default()),
+    };
+
+    let stdin = io::stdin();
+    let prompt = stdin.is_terminal();
+    if let Err(e) = Repl::new(backend).run(stdin.lock(), &mut io::stdout().lock(), prompt) {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
 
-    tree.insert("hello world\0 null byte", 9);
-    tree.print();
-    assert_eq!(tree.get("hello world").copied(), Some(1));
-    assert_eq!(tree.get("hello moon ").copied(), Some(2));
-    assert_eq!(tree.get("h").copied(), Some(3));
-    assert_eq!(tree.get("hello foo").copied(), Some(4));
-    assert_eq!(tree.get("hello boo").copied(), Some(5));
-    assert_eq!(tree.get("hello voo").copied(), Some(6));
-    assert_eq!(tree.get("hello voa").copied(), Some(7));
-    assert_eq!(
-        tree.get("hello a very very long prefix that doesn't fit into an inlined buffer.")
-            .copied(),
-        Some(8)
-    );
-    assert_eq!(tree.get("hello world\0 null byte").copied(), Some(9));
+fn serve(mut args: impl Iterator<Item = String>) -> ExitCode {
+    let mut listen = "127.0.0.1:6379".to_owned();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                let Some(addr) = args.next() else {
+                    eprint!("missing argument to --listen\n\n{USAGE}");
+                    return ExitCode::FAILURE;
+                };
+                listen = addr;
+            }
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            x => {
+                eprint!("unknown argument `{x}`\n\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
 
-    let mut iter = tree.iter();
-    while let Some((k, v)) = iter.next() {
-        dbg!(k, v);
+    let server = match Server::bind(&listen) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("failed to listen on `{listen}`: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Ok(addr) = server.local_addr() {
+        eprintln!("listening on {addr}");
+    }
+    if let Err(e) = server.run() {
+        eprintln!("error: {e}");
+        return ExitCode::FAILURE;
     }
+    ExitCode::SUCCESS
 }
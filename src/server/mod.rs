@@ -0,0 +1,230 @@
+//! A server speaking a subset of the Redis protocol.
+//!
+//! The server keeps its entries in memory in a [`ConcurrentAart`], every connection is handled by
+//! its own thread. Reads never take a lock, writes are serialized.
+//!
+//! Supported commands are `GET`, `SET`, `DEL`, `EXISTS`, `DBSIZE`, `SCAN` and `PING`. Keys and
+//! values must be valid utf-8.
+//!
+//! `SCAN` returns keys in the order of the tree. Its cursor refers to the last key returned and
+//! is only valid on the connection which received it.
+
+use aart::ConcurrentAart;
+use key_automata::{Automaton, Glob};
+use resp::Value;
+use std::{
+    collections::BTreeMap,
+    io::{self, BufReader, BufWriter, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    ops::Bound,
+    sync::Arc,
+    thread,
+};
+
+pub mod resp;
+
+#[cfg(test)]
+mod test;
+
+/// The maximum amount of `SCAN` cursors kept per connection, older cursors are forgotten.
+const MAX_CURSORS: usize = 1024;
+/// The amount of entries a `SCAN` looks at if no `COUNT` is given.
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+pub type Map = ConcurrentAart<str, String>;
+
+pub struct Server {
+    listener: TcpListener,
+    map: Arc<Map>,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            map: Arc::new(Map::new()),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn map(&self) -> &Arc<Map> {
+        &self.map
+    }
+
+    /// Accept connections until accepting fails.
+    pub fn run(&self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let map = self.map.clone();
+            thread::spawn(move || {
+                // Errors only end the connection.
+                let _ = Connection::new(map).handle(stream);
+            });
+        }
+    }
+}
+
+struct Connection {
+    map: Arc<Map>,
+    cursors: BTreeMap<u64, String>,
+    next_cursor: u64,
+}
+
+impl Connection {
+    fn new(map: Arc<Map>) -> Self {
+        Connection {
+            map,
+            cursors: BTreeMap::new(),
+            next_cursor: 1,
+        }
+    }
+
+    fn handle(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        loop {
+            let args = match resp::read_command(&mut reader) {
+                Ok(Some(x)) => x,
+                Ok(None) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    // The stream can't be trusted anymore after a malformed request.
+                    Value::error(format!("ERR {e}")).write(&mut writer)?;
+                    return writer.flush();
+                }
+                Err(e) => return Err(e),
+            };
+            self.execute(&args).write(&mut writer)?;
+            // Only flush once all pipelined requests have been handled.
+            if reader.buffer().is_empty() {
+                writer.flush()?;
+            }
+        }
+    }
+
+    fn execute(&mut self, args: &[Vec<u8>]) -> Value {
+        let Some((name, args)) = args.split_first() else {
+            return Value::error("ERR empty command");
+        };
+        let name = String::from_utf8_lossy(name).to_ascii_uppercase();
+        let args = match args
+            .iter()
+            .map(|x| std::str::from_utf8(x))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(x) => x,
+            Err(_) => return Value::error("ERR arguments must be valid utf-8"),
+        };
+
+        let arity = |min: usize, max: usize| {
+            (args.len() < min || args.len() > max).then(|| {
+                Value::error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    name.to_ascii_lowercase()
+                ))
+            })
+        };
+
+        match name.as_str() {
+            "PING" => arity(0, 1).unwrap_or_else(|| match args.first() {
+                Some(x) => Value::bulk(*x),
+                None => Value::Simple("PONG".to_owned()),
+            }),
+            "GET" => arity(1, 1).unwrap_or_else(|| {
+                Value::Bulk(self.map.get(args[0]).map(|x| x.as_bytes().to_vec()))
+            }),
+            "SET" => arity(2, 2).unwrap_or_else(|| {
+                self.map.insert(args[0], args[1].to_owned());
+                Value::ok()
+            }),
+            "DEL" => arity(1, usize::MAX).unwrap_or_else(|| {
+                let removed = args.iter().filter(|x| self.map.remove(x).is_some());
+                Value::Integer(removed.count() as i64)
+            }),
+            "EXISTS" => arity(1, usize::MAX).unwrap_or_else(|| {
+                let found = args.iter().filter(|x| self.map.contains_key(x));
+                Value::Integer(found.count() as i64)
+            }),
+            "DBSIZE" => arity(0, 0).unwrap_or_else(|| Value::Integer(self.map.len() as i64)),
+            "SCAN" => arity(1, 5).unwrap_or_else(|| self.scan(&args)),
+            _ => Value::error(format!("ERR unknown command '{name}'")),
+        }
+    }
+
+    fn scan(&mut self, args: &[&str]) -> Value {
+        let mut pattern = None;
+        let mut count = DEFAULT_SCAN_COUNT;
+        for option in args[1..].chunks(2) {
+            match (option[0].to_ascii_uppercase().as_str(), option.get(1)) {
+                ("MATCH", Some(x)) => pattern = Some(*x),
+                ("COUNT", Some(x)) => match x.parse() {
+                    Ok(x) if x > 0 => count = x,
+                    _ => return Value::error("ERR value is not an integer or out of range"),
+                },
+                _ => return Value::error("ERR syntax error"),
+            }
+        }
+
+        let after = match args[0].parse::<u64>() {
+            Ok(0) => None,
+            Ok(x) => match self.cursors.get(&x) {
+                Some(x) => Some(x.clone()),
+                None => return Value::error("ERR invalid cursor"),
+            },
+            Err(_) => return Value::error("ERR invalid cursor"),
+        };
+
+        // Keys matching the pattern all start with its literal prefix, so only that part of the
+        // tree has to be searched.
+        let pattern = pattern.map(Glob::new);
+        let prefix = pattern
+            .as_ref()
+            .map(Glob::literal_prefix)
+            .unwrap_or_default();
+        let snapshot = self.map.snapshot();
+        let mut iter = match after.as_deref() {
+            None => snapshot.scan_prefix(&prefix),
+            Some(x) => snapshot.range((Bound::Excluded(x), Bound::Unbounded)),
+        };
+
+        let mut keys = Vec::new();
+        let mut last = None;
+        for _ in 0..count {
+            let Some((key, _)) = iter.next() else {
+                last = None;
+                break;
+            };
+            if !key.starts_with(&prefix) {
+                last = None;
+                break;
+            }
+            if pattern.as_ref().is_none_or(|x| x.run(key).is_some()) {
+                keys.push(Value::bulk(key));
+            }
+            last = Some(key.to_owned());
+        }
+        // Only return a cursor if there are more keys.
+        if !matches!(iter.next(), Some((key, _)) if key.starts_with(&prefix)) {
+            last = None;
+        }
+
+        let cursor = match last {
+            Some(key) => {
+                let id = self.next_cursor;
+                self.next_cursor += 1;
+                if self.cursors.len() == MAX_CURSORS {
+                    self.cursors.pop_first();
+                }
+                self.cursors.insert(id, key);
+                id
+            }
+            None => 0,
+        };
+        Value::Array(Some(vec![
+            Value::bulk(cursor.to_string()),
+            Value::Array(Some(keys)),
+        ]))
+    }
+}
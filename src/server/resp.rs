@@ -0,0 +1,186 @@
+//! The Redis serialization protocol.
+//!
+//! Only the parts of RESP2 needed to talk to Redis clients are implemented. Requests are either an
+//! array of bulk strings or an inline command, a single line of whitespace separated arguments as
+//! typed into a terminal.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// The largest bulk string accepted, the same limit Redis uses.
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// The largest amount of elements accepted in an array.
+const MAX_ARRAY_LEN: usize = 1024 * 1024;
+/// The deepest nesting of arrays accepted by [`Value::read`].
+const MAX_DEPTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Value>>),
+}
+
+impl Value {
+    pub fn ok() -> Self {
+        Value::Simple("OK".to_owned())
+    }
+
+    pub fn error<S: Into<String>>(msg: S) -> Self {
+        Value::Error(msg.into())
+    }
+
+    pub fn bulk<B: Into<Vec<u8>>>(bytes: B) -> Self {
+        Value::Bulk(Some(bytes.into()))
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Value::Simple(x) => write!(w, "+{x}\r\n"),
+            Value::Error(x) => write!(w, "-{x}\r\n"),
+            Value::Integer(x) => write!(w, ":{x}\r\n"),
+            Value::Bulk(None) => write!(w, "$-1\r\n"),
+            Value::Bulk(Some(x)) => {
+                write!(w, "${}\r\n", x.len())?;
+                w.write_all(x)?;
+                w.write_all(b"\r\n")
+            }
+            Value::Array(None) => write!(w, "*-1\r\n"),
+            Value::Array(Some(x)) => {
+                write!(w, "*{}\r\n", x.len())?;
+                x.iter().try_for_each(|x| x.write(w))
+            }
+        }
+    }
+
+    /// Read a value, returns `None` if the reader is at the end of its input.
+    pub fn read<R: BufRead>(r: &mut R) -> io::Result<Option<Self>> {
+        Self::read_nested(r, 0)
+    }
+
+    /// Read a value nested in `depth` arrays.
+    fn read_nested<R: BufRead>(r: &mut R, depth: usize) -> io::Result<Option<Self>> {
+        let Some(line) = read_line(r)? else {
+            return Ok(None);
+        };
+        let (kind, rest) = line.split_first().ok_or_else(|| invalid("empty line"))?;
+        let text = || String::from_utf8(rest.to_vec()).map_err(|_| invalid("invalid utf-8"));
+        let value = match kind {
+            b'+' => Value::Simple(text()?),
+            b'-' => Value::Error(text()?),
+            b':' => Value::Integer(parse_int(rest)?),
+            b'$' => Value::Bulk(read_bulk(r, rest)?),
+            b'*' => match parse_len(rest, MAX_ARRAY_LEN)? {
+                None => Value::Array(None),
+                Some(_) if depth == MAX_DEPTH => return Err(invalid("arrays nested too deep")),
+                Some(len) => {
+                    // Don't trust the length for the allocation, the values might never arrive.
+                    let mut values = Vec::new();
+                    for _ in 0..len {
+                        let value = Value::read_nested(r, depth + 1)?;
+                        values.push(value.ok_or_else(unexpected_eof)?);
+                    }
+                    Value::Array(Some(values))
+                }
+            },
+            _ => return Err(invalid("unknown value type")),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Read a command, returns `None` if the reader is at the end of its input.
+pub fn read_command<R: BufRead>(r: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    loop {
+        let Some(&first) = r.fill_buf()?.first() else {
+            return Ok(None);
+        };
+        if first == b'*' {
+            break;
+        }
+
+        let line = read_line(r)?.ok_or_else(unexpected_eof)?;
+        let args: Vec<Vec<u8>> = line
+            .split(|x| x.is_ascii_whitespace())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_vec())
+            .collect();
+        // Empty lines are ignored like Redis does.
+        if !args.is_empty() {
+            return Ok(Some(args));
+        }
+    }
+
+    // Commands are a flat array of bulk strings, anything else is rejected before it is read.
+    let expected = || invalid("expected an array of bulk strings");
+    let line = read_line(r)?.ok_or_else(unexpected_eof)?;
+    let len = parse_len(&line[1..], MAX_ARRAY_LEN)?.ok_or_else(expected)?;
+    let mut args = Vec::new();
+    for _ in 0..len {
+        let line = read_line(r)?.ok_or_else(unexpected_eof)?;
+        let Some((b'$', len)) = line.split_first() else {
+            return Err(expected());
+        };
+        args.push(read_bulk(r, len)?.ok_or_else(expected)?);
+    }
+    Ok(Some(args))
+}
+
+fn read_bulk<R: BufRead>(r: &mut R, len: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let Some(len) = parse_len(len, MAX_BULK_LEN)? else {
+        return Ok(None);
+    };
+    // Don't trust the length for the allocation, the data might never arrive.
+    let mut data = Vec::new();
+    r.take(len as u64 + 2).read_to_end(&mut data)?;
+    if data.len() != len + 2 {
+        return Err(unexpected_eof());
+    }
+    if !data.ends_with(b"\r\n") {
+        return Err(invalid("bulk string not terminated by CRLF"));
+    }
+    data.truncate(len);
+    Ok(Some(data))
+}
+
+/// Read a line terminated by CRLF, or only LF, without the terminator.
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    // Lines only hold short headers or inline commands.
+    r.take(MAX_ARRAY_LEN as u64).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(unexpected_eof());
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Parse a length, returns `None` for the length -1 which is used for null values.
+fn parse_len(bytes: &[u8], max: usize) -> io::Result<Option<usize>> {
+    match parse_int(bytes)? {
+        -1 => Ok(None),
+        x if x >= 0 && x as u64 <= max as u64 => Ok(Some(x as usize)),
+        _ => Err(invalid("invalid length")),
+    }
+}
+
+fn parse_int(bytes: &[u8]) -> io::Result<i64> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(|| invalid("invalid integer"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Protocol error: {msg}"))
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::from(io::ErrorKind::UnexpectedEof)
+}
@@ -0,0 +1,248 @@
+use super::{
+    resp::{self, Value},
+    Server,
+};
+use key_automata::{Automaton, Glob};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    sync::Arc,
+    thread,
+};
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn connect(server: &Server) -> Self {
+        let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+        }
+    }
+
+    fn send(&mut self, args: &[&str]) {
+        let request = args.iter().map(|x| Value::bulk(*x)).collect();
+        let mut buffer = Vec::new();
+        Value::Array(Some(request)).write(&mut buffer).unwrap();
+        self.writer.write_all(&buffer).unwrap();
+    }
+
+    fn read(&mut self) -> Value {
+        Value::read(&mut self.reader).unwrap().unwrap()
+    }
+
+    fn cmd(&mut self, args: &[&str]) -> Value {
+        self.send(args);
+        self.read()
+    }
+}
+
+fn start() -> Arc<Server> {
+    let server = Arc::new(Server::bind("127.0.0.1:0").unwrap());
+    let s = server.clone();
+    thread::spawn(move || s.run());
+    server
+}
+
+fn bulk(s: &str) -> Value {
+    Value::bulk(s)
+}
+
+/// Run a complete scan, returns the keys.
+fn scan_all(client: &mut Client, options: &[&str]) -> Vec<String> {
+    let mut cursor = "0".to_owned();
+    let mut keys = Vec::new();
+    loop {
+        let mut args = vec!["SCAN", cursor.as_str()];
+        args.extend_from_slice(options);
+        let Value::Array(Some(res)) = client.cmd(&args) else {
+            panic!("invalid scan response");
+        };
+        let [Value::Bulk(Some(next)), Value::Array(Some(batch))] = res.as_slice() else {
+            panic!("invalid scan response");
+        };
+        for k in batch {
+            let Value::Bulk(Some(k)) = k else {
+                panic!("invalid scan response");
+            };
+            keys.push(String::from_utf8(k.clone()).unwrap());
+        }
+        cursor = String::from_utf8(next.clone()).unwrap();
+        if cursor == "0" {
+            return keys;
+        }
+    }
+}
+
+#[test]
+fn commands() {
+    let server = start();
+    let mut client = Client::connect(&server);
+
+    assert_eq!(client.cmd(&["PING"]), Value::Simple("PONG".to_owned()));
+    assert_eq!(client.cmd(&["GET", "a"]), Value::Bulk(None));
+    assert_eq!(client.cmd(&["SET", "a", "1"]), Value::ok());
+    assert_eq!(client.cmd(&["set", "b", "two words"]), Value::ok());
+    assert_eq!(client.cmd(&["GET", "a"]), bulk("1"));
+    assert_eq!(client.cmd(&["GET", "b"]), bulk("two words"));
+    assert_eq!(
+        client.cmd(&["EXISTS", "a", "b", "c", "a"]),
+        Value::Integer(3)
+    );
+    assert_eq!(client.cmd(&["DBSIZE"]), Value::Integer(2));
+    assert_eq!(client.cmd(&["DEL", "a", "c"]), Value::Integer(1));
+    assert_eq!(client.cmd(&["DBSIZE"]), Value::Integer(1));
+    assert_eq!(client.cmd(&["SET", "b", ""]), Value::ok());
+    assert_eq!(client.cmd(&["GET", "b"]), bulk(""));
+
+    assert!(matches!(client.cmd(&["GET"]), Value::Error(_)));
+    assert!(matches!(client.cmd(&["SET", "a"]), Value::Error(_)));
+    assert!(matches!(client.cmd(&["FLUSHALL"]), Value::Error(_)));
+    assert!(matches!(client.cmd(&["SCAN", "12345"]), Value::Error(_)));
+
+    // Writes are visible to other connections.
+    let mut other = Client::connect(&server);
+    assert_eq!(other.cmd(&["GET", "b"]), bulk(""));
+}
+
+#[test]
+fn inline_and_pipelined() {
+    let server = start();
+    let mut client = Client::connect(&server);
+
+    client.writer.write_all(b"SET x 1\r\n\r\nGET x\n").unwrap();
+    assert_eq!(client.read(), Value::ok());
+    assert_eq!(client.read(), bulk("1"));
+
+    for i in 0..100 {
+        client.send(&["SET", &i.to_string(), "v"]);
+    }
+    for _ in 0..100 {
+        assert_eq!(client.read(), Value::ok());
+    }
+    assert_eq!(client.cmd(&["DBSIZE"]), Value::Integer(101));
+}
+
+#[test]
+fn protocol_error() {
+    let server = start();
+    let mut client = Client::connect(&server);
+    client.writer.write_all(b"*1\r\n:12\r\n").unwrap();
+    assert!(matches!(client.read(), Value::Error(_)));
+    // The server closes the connection.
+    assert_eq!(Value::read(&mut client.reader).unwrap(), None);
+}
+
+#[test]
+fn nested_array() {
+    let server = start();
+    let mut client = Client::connect(&server);
+    client.writer.write_all(&b"*1\r\n".repeat(200_000)).unwrap();
+    let Value::Error(e) = client.read() else {
+        panic!("expected a protocol error");
+    };
+    assert!(e.starts_with("ERR Protocol error"));
+
+    // The server is still up.
+    let mut other = Client::connect(&server);
+    assert_eq!(other.cmd(&["PING"]), Value::Simple("PONG".to_owned()));
+
+    // Replies read by clients may nest, but only so deep.
+    let mut input: &[u8] = b"*1\r\n*1\r\n*0\r\n";
+    let nested = Value::Array(Some(vec![Value::Array(Some(vec![Value::Array(Some(
+        vec![],
+    ))]))]));
+    assert_eq!(Value::read(&mut input).unwrap(), Some(nested));
+    let input = b"*1\r\n".repeat(100);
+    assert!(Value::read(&mut input.as_slice()).is_err());
+}
+
+#[test]
+fn scan() {
+    let server = start();
+    let mut client = Client::connect(&server);
+    let mut expected = Vec::new();
+    for i in 0..500 {
+        let key = format!("user:{i}");
+        client.send(&["SET", &key, "v"]);
+        client.read();
+        expected.push(key);
+        client.send(&["SET", &format!("order:{i}"), "v"]);
+        client.read();
+    }
+    expected.sort();
+
+    let mut keys = scan_all(&mut client, &["MATCH", "user:*"]);
+    keys.sort();
+    assert_eq!(keys, expected);
+
+    let mut keys = scan_all(&mut client, &["COUNT", "7"]);
+    assert_eq!(keys.len(), 1000);
+    keys.dedup();
+    assert_eq!(keys.len(), 1000);
+
+    let keys = scan_all(&mut client, &["MATCH", "*:1?", "COUNT", "100"]);
+    assert_eq!(keys.len(), 20);
+    assert!(scan_all(&mut client, &["MATCH", "none*"]).is_empty());
+}
+
+#[test]
+fn concurrent_clients() {
+    let server = start();
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let server = server.clone();
+            thread::spawn(move || {
+                let mut client = Client::connect(&server);
+                for i in 0..200 {
+                    let key = format!("{t}:{i}");
+                    assert_eq!(client.cmd(&["SET", &key, &i.to_string()]), Value::ok());
+                    assert_eq!(client.cmd(&["GET", &key]), bulk(&i.to_string()));
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    let mut client = Client::connect(&server);
+    assert_eq!(client.cmd(&["DBSIZE"]), Value::Integer(1600));
+}
+
+#[test]
+fn read_command() {
+    let mut input: &[u8] =
+        b"*2\r\n$3\r\nGET\r\n$1\r\na\r\n  \r\nDEL  a b\r\n*1\r\n$10\r\nshort\r\n";
+    assert_eq!(
+        resp::read_command(&mut input).unwrap(),
+        Some(vec![b"GET".to_vec(), b"a".to_vec()])
+    );
+    assert_eq!(
+        resp::read_command(&mut input).unwrap(),
+        Some(vec![b"DEL".to_vec(), b"a".to_vec(), b"b".to_vec()])
+    );
+    assert!(resp::read_command(&mut input).is_err());
+    assert!(input.fill_buf().unwrap().is_empty());
+}
+
+#[test]
+fn glob() {
+    assert!(Glob::new("*").run("").is_some());
+    assert!(Glob::new("h?llo").run("hello").is_some());
+    assert!(Glob::new("h*llo").run("heeeello").is_some());
+    assert!(Glob::new("h[ae]llo").run("hallo").is_some());
+    assert!(Glob::new("h[ae]llo").run("hillo").is_none());
+    assert!(Glob::new("h[^e]llo").run("hallo").is_some());
+    assert!(Glob::new("h[^e]llo").run("hello").is_none());
+    assert!(Glob::new("h[a-b]llo").run("hbllo").is_some());
+    assert!(Glob::new("a*b*c").run("aXXbYYbZc").is_some());
+    assert!(Glob::new("a*b*c").run("aXXbYYbZ").is_none());
+    assert!(Glob::new("\\*x").run("*x").is_some());
+    assert!(Glob::new("\\*x").run("ax").is_none());
+    assert_eq!(Glob::new("user:*").literal_prefix(), "user:");
+    assert_eq!(Glob::new("a\\*b?").literal_prefix(), "a*b");
+}
@@ -0,0 +1,137 @@
+//! A durable key-value store.
+//!
+//! Every modification is appended to a write-ahead log before it is applied to the in-memory
+//! tree. When the log grows too large the store writes a full snapshot of the tree, after which
+//! the log is cleared. Opening a store reads the latest snapshot and then replays the log on top
+//! of it.
+//!
+//! Replaying the log is idempotent, so a crash after a snapshot is written but before the log is
+//! cleared results in the same state.
+
+use art::Art;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub mod snapshot;
+pub mod wal;
+
+#[cfg(test)]
+mod test;
+
+use wal::{Record, Wal};
+
+const WAL_FILE: &str = "wal.log";
+const SNAPSHOT_FILE: &str = "snapshot";
+
+#[derive(Clone, Copy, Debug)]
+pub struct StoreOptions {
+    /// Flush every write to disk before returning.
+    pub sync: bool,
+    /// Size of the log in bytes after which a checkpoint is taken, `None` to only checkpoint when
+    /// [`Store::checkpoint`] is called.
+    pub checkpoint_size: Option<u64>,
+}
+
+impl Default for StoreOptions {
+    fn default() -> Self {
+        StoreOptions {
+            sync: true,
+            checkpoint_size: Some(64 * 1024 * 1024),
+        }
+    }
+}
+
+pub struct Store {
+    dir: PathBuf,
+    tree: Art<str, String>,
+    wal: Wal,
+    options: StoreOptions,
+}
+
+impl Store {
+    /// Open the store in the given directory with the default options, creating it if it does not
+    /// exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        Self::open_with(dir, StoreOptions::default())
+    }
+
+    pub fn open_with<P: AsRef<Path>>(dir: P, options: StoreOptions) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut tree = snapshot::read(&dir.join(SNAPSHOT_FILE))?;
+        let wal = Wal::open(dir.join(WAL_FILE), options.sync, |record| match record {
+            Record::Put { key, value } => {
+                tree.insert(key, value.to_owned());
+            }
+            Record::Delete { key } => {
+                tree.remove(key);
+            }
+        })?;
+
+        Ok(Store {
+            dir,
+            tree,
+            wal,
+            options,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the in-memory tree containing the current state of the store.
+    pub fn tree(&self) -> &Art<str, String> {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the current size of the write-ahead log in bytes.
+    pub fn wal_len(&self) -> u64 {
+        self.wal.len()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.tree.get(key).map(|x| x.as_str())
+    }
+
+    pub fn put(&mut self, key: &str, value: String) -> io::Result<Option<String>> {
+        self.wal.append(Record::Put { key, value: &value })?;
+        let res = self.tree.insert(key, value);
+        self.maybe_checkpoint()?;
+        Ok(res)
+    }
+
+    pub fn delete(&mut self, key: &str) -> io::Result<Option<String>> {
+        if self.tree.get(key).is_none() {
+            return Ok(None);
+        }
+        self.wal.append(Record::Delete { key })?;
+        let res = self.tree.remove(key);
+        self.maybe_checkpoint()?;
+        Ok(res)
+    }
+
+    /// Write a snapshot of the current state and clear the write-ahead log.
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        snapshot::write(&self.dir.join(SNAPSHOT_FILE), &self.tree)?;
+        self.wal.clear()
+    }
+
+    fn maybe_checkpoint(&mut self) -> io::Result<()> {
+        match self.options.checkpoint_size {
+            Some(x) if self.wal.len() >= x => self.checkpoint(),
+            _ => Ok(()),
+        }
+    }
+}
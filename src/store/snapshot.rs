@@ -0,0 +1,108 @@
+//! Full snapshots of the store.
+//!
+//! A snapshot starts with the magic bytes followed by the amount of entries as a u64. Then follow
+//! the entries, each as a length prefixed key and a length prefixed value, lengths are stored as a
+//! u32. The snapshot ends with the crc32 checksum of all the preceding bytes. All integers are
+//! stored little endian.
+//!
+//! Snapshots are first written to a temporary file which is then renamed over the previous
+//! snapshot, so a crash during a checkpoint leaves either the old or the new snapshot in place.
+
+use art::Art;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+const MAGIC: [u8; 8] = *b"TOYSNAP1";
+
+struct ChecksumWriter<W> {
+    writer: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hasher.update(bytes);
+        self.writer.write_all(bytes)
+    }
+
+    fn write_str(&mut self, s: &str) -> io::Result<()> {
+        self.write(&(s.len() as u32).to_le_bytes())?;
+        self.write(s.as_bytes())
+    }
+}
+
+/// Atomically replace the snapshot at `path` with the contents of the tree.
+pub fn write(path: &Path, tree: &Art<str, String>) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let file = File::create(&tmp_path)?;
+
+    let mut writer = ChecksumWriter {
+        writer: BufWriter::new(file),
+        hasher: crc32fast::Hasher::new(),
+    };
+    writer.write(&MAGIC)?;
+    writer.write(&(tree.len() as u64).to_le_bytes())?;
+    let mut iter = tree.iter();
+    while let Some((key, value)) = iter.next() {
+        writer.write_str(key)?;
+        writer.write_str(value)?;
+    }
+    let checksum = writer.hasher.finalize();
+    writer.writer.write_all(&checksum.to_le_bytes())?;
+    let file = writer.writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    // Make sure the rename itself is durable.
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Read the snapshot at `path`, returns an empty tree if there is no snapshot.
+pub fn read(path: &Path) -> io::Result<Art<str, String>> {
+    let bytes = match fs::read(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Art::new()),
+        Err(e) => return Err(e),
+    };
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "corrupt snapshot");
+
+    if bytes.len() < MAGIC.len() + 12 || bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid());
+    }
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    if crc32fast::hash(data) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(invalid());
+    }
+
+    let mut rest = &data[MAGIC.len()..];
+    let count = u64::from_le_bytes(rest[..8].try_into().unwrap());
+    rest = &rest[8..];
+
+    // Every entry takes at least the 8 bytes of its lengths, so a count which doesn't fit into the
+    // rest of the snapshot can't cause a large allocation.
+    let mut entries = Vec::with_capacity((count as usize).min(rest.len() / 8));
+    for _ in 0..count {
+        let key = read_str(&mut rest).ok_or_else(invalid)?;
+        let value = read_str(&mut rest).ok_or_else(invalid)?;
+        entries.push((key, value.to_owned()));
+    }
+    if !rest.is_empty() {
+        return Err(invalid());
+    }
+    // Entries are written in the order of the tree so the tree can be bulk loaded.
+    Ok(Art::from_sorted_iter(entries))
+}
+
+fn read_str<'a>(bytes: &mut &'a [u8]) -> Option<&'a str> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+    let s = bytes.get(4..4 + len)?;
+    *bytes = &bytes[4 + len..];
+    std::str::from_utf8(s).ok()
+}
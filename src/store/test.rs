@@ -0,0 +1,177 @@
+use super::{Store, StoreOptions, SNAPSHOT_FILE, WAL_FILE};
+use std::{collections::BTreeMap, fs, path::Path};
+
+const OPTIONS: StoreOptions = StoreOptions {
+    sync: false,
+    checkpoint_size: None,
+};
+
+fn assert_state(store: &Store, expected: &BTreeMap<String, String>) {
+    assert_eq!(store.len(), expected.len());
+    for (k, v) in expected {
+        assert_eq!(store.get(k), Some(v.as_str()));
+    }
+}
+
+/// Apply a deterministic sequence of operations, returning the expected state after each
+/// operation together with the length of the log at that point.
+fn run_ops(store: &mut Store, count: usize) -> Vec<(u64, BTreeMap<String, String>)> {
+    let mut model = BTreeMap::new();
+    let mut states = vec![(store.wal_len(), model.clone())];
+    for i in 0..count {
+        let key = format!("key{}", (i * 7) % 13);
+        if i % 5 == 4 {
+            assert_eq!(store.delete(&key).unwrap(), model.remove(&key));
+        } else {
+            let value = format!("value {i}");
+            assert_eq!(
+                store.put(&key, value.clone()).unwrap(),
+                model.insert(key, value)
+            );
+        }
+        states.push((store.wal_len(), model.clone()));
+    }
+    states
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+    }
+}
+
+#[test]
+fn reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    let states = run_ops(&mut store, 100);
+    drop(store);
+
+    let store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    assert_state(&store, &states.last().unwrap().1);
+}
+
+#[test]
+fn truncated_wal() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    let states = run_ops(&mut store, 50);
+    drop(store);
+
+    let wal = fs::read(dir.path().join(WAL_FILE)).unwrap();
+    for offset in 0..=wal.len() {
+        let crash_dir = tempfile::tempdir().unwrap();
+        fs::write(crash_dir.path().join(WAL_FILE), &wal[..offset]).unwrap();
+
+        // The state after the last operation which was completely written to the log.
+        let (len, expected) = states
+            .iter()
+            .rev()
+            .find(|(len, _)| *len <= offset as u64)
+            .unwrap();
+
+        let mut store = Store::open_with(crash_dir.path(), OPTIONS).unwrap();
+        assert_state(&store, expected);
+        assert_eq!(store.wal_len(), *len);
+
+        // New writes must survive after recovering from a torn record.
+        store.put("after crash", "value".to_owned()).unwrap();
+        drop(store);
+        let store = Store::open_with(crash_dir.path(), OPTIONS).unwrap();
+        assert_eq!(store.get("after crash"), Some("value"));
+        assert_eq!(store.len(), expected.len() + 1);
+    }
+}
+
+#[test]
+fn corrupt_wal() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    let states = run_ops(&mut store, 20);
+    drop(store);
+
+    // Flip a byte in the payload of the 10th record, replay should stop before it.
+    let path = dir.path().join(WAL_FILE);
+    let mut wal = fs::read(&path).unwrap();
+    let (len, _) = states[9];
+    wal[len as usize + 10] ^= 0xff;
+    fs::write(&path, wal).unwrap();
+
+    let store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    assert_state(&store, &states[9].1);
+}
+
+#[test]
+fn checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    let states = run_ops(&mut store, 100);
+
+    // Simulate a crash after the snapshot is written but before the log is cleared.
+    let crash_dir = tempfile::tempdir().unwrap();
+    copy_dir(dir.path(), crash_dir.path());
+
+    store.checkpoint().unwrap();
+    assert_eq!(store.wal_len(), 0);
+    fs::copy(
+        dir.path().join(SNAPSHOT_FILE),
+        crash_dir.path().join(SNAPSHOT_FILE),
+    )
+    .unwrap();
+
+    store.put("new", "value".to_owned()).unwrap();
+    drop(store);
+
+    let store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    let mut expected = states.last().unwrap().1.clone();
+    expected.insert("new".to_owned(), "value".to_owned());
+    assert_state(&store, &expected);
+
+    let store = Store::open_with(crash_dir.path(), OPTIONS).unwrap();
+    assert_state(&store, &states.last().unwrap().1);
+}
+
+#[test]
+fn automatic_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let options = StoreOptions {
+        sync: false,
+        checkpoint_size: Some(256),
+    };
+    let mut store = Store::open_with(dir.path(), options).unwrap();
+    let states = run_ops(&mut store, 100);
+    assert!(store.wal_len() < 256);
+    assert!(dir.path().join(SNAPSHOT_FILE).exists());
+    drop(store);
+
+    let store = Store::open_with(dir.path(), options).unwrap();
+    assert_state(&store, &states.last().unwrap().1);
+}
+
+#[test]
+fn corrupt_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut store = Store::open_with(dir.path(), OPTIONS).unwrap();
+    run_ops(&mut store, 10);
+    store.checkpoint().unwrap();
+    drop(store);
+
+    let path = dir.path().join(SNAPSHOT_FILE);
+    let mut snapshot = fs::read(&path).unwrap();
+    snapshot[20] ^= 0xff;
+    fs::write(&path, snapshot).unwrap();
+
+    assert!(Store::open_with(dir.path(), OPTIONS).is_err());
+}
+
+#[test]
+fn snapshot_count_too_large() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut snapshot = b"TOYSNAP1".to_vec();
+    snapshot.extend_from_slice(&u64::MAX.to_le_bytes());
+    snapshot.extend_from_slice(&crc32fast::hash(&snapshot).to_le_bytes());
+    fs::write(dir.path().join(SNAPSHOT_FILE), snapshot).unwrap();
+
+    assert!(Store::open_with(dir.path(), OPTIONS).is_err());
+}
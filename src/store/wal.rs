@@ -0,0 +1,171 @@
+//! The write-ahead log.
+//!
+//! The log is a sequence of records, each record is stored as the length of the payload as a u32,
+//! the crc32 checksum of the payload as a u32 and then the payload itself. A payload starts with
+//! the kind of operation as a u8 followed by the length prefixed key and, for puts, the length
+//! prefixed value. All integers are stored little endian.
+//!
+//! A crash can leave a partially written record at the end of the log. When the log is opened
+//! records are replayed until the first incomplete or corrupt record, after which the log is
+//! truncated so that new records are appended after the last valid one.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const PUT: u8 = 1;
+const DELETE: u8 = 2;
+const RECORD_HEADER_SIZE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Record<'a> {
+    Put { key: &'a str, value: &'a str },
+    Delete { key: &'a str },
+}
+
+impl<'a> Record<'a> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(&[0; RECORD_HEADER_SIZE]);
+        match self {
+            Record::Put { key, value } => {
+                buffer.push(PUT);
+                encode_str(buffer, key);
+                encode_str(buffer, value);
+            }
+            Record::Delete { key } => {
+                buffer.push(DELETE);
+                encode_str(buffer, key);
+            }
+        }
+        let payload = &buffer[RECORD_HEADER_SIZE..];
+        let len = (payload.len() as u32).to_le_bytes();
+        let checksum = crc32fast::hash(payload).to_le_bytes();
+        buffer[..4].copy_from_slice(&len);
+        buffer[4..8].copy_from_slice(&checksum);
+    }
+
+    /// Decode a record from the start of the bytes, returning the record and its encoded length.
+    ///
+    /// Returns `None` if the bytes don't start with a complete and valid record.
+    fn decode(bytes: &'a [u8]) -> Option<(Self, usize)> {
+        let header = bytes.get(..RECORD_HEADER_SIZE)?;
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let payload = bytes.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + len)?;
+        if crc32fast::hash(payload) != checksum {
+            return None;
+        }
+
+        let (kind, mut rest) = payload.split_first()?;
+        let key = decode_str(&mut rest)?;
+        let record = match *kind {
+            PUT => Record::Put {
+                key,
+                value: decode_str(&mut rest)?,
+            },
+            DELETE => Record::Delete { key },
+            _ => return None,
+        };
+        if !rest.is_empty() {
+            return None;
+        }
+        Some((record, RECORD_HEADER_SIZE + len))
+    }
+}
+
+fn encode_str(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+fn decode_str<'a>(bytes: &mut &'a [u8]) -> Option<&'a str> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().unwrap()) as usize;
+    let s = bytes.get(4..4 + len)?;
+    *bytes = &bytes[4 + len..];
+    std::str::from_utf8(s).ok()
+}
+
+pub struct Wal {
+    file: File,
+    len: u64,
+    sync: bool,
+    buffer: Vec<u8>,
+}
+
+impl Wal {
+    /// Open the log at the given path, creating it if it does not exist, and replay all the valid
+    /// records in it.
+    ///
+    /// If `sync` is set every appended record is flushed to disk before returning.
+    pub fn open<P, F>(path: P, sync: bool, mut replay: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(Record),
+    {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut offset = 0;
+        while let Some((record, len)) = Record::decode(&bytes[offset..]) {
+            replay(record);
+            offset += len;
+        }
+
+        if offset != bytes.len() {
+            // Torn write at the end of the log, drop it.
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        Ok(Wal {
+            file,
+            len: offset as u64,
+            sync,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Returns the length of the log in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn append(&mut self, record: Record) -> io::Result<()> {
+        record.encode(&mut self.buffer);
+        if let Err(e) = self.file.write_all(&self.buffer) {
+            // Don't leave a partial record behind which would hide any later records.
+            self.file.set_len(self.len)?;
+            self.file.seek(SeekFrom::Start(self.len))?;
+            return Err(e);
+        }
+        self.len += self.buffer.len() as u64;
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Remove all records from the log.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_all()?;
+        self.len = 0;
+        Ok(())
+    }
+}